@@ -36,6 +36,7 @@ pub fn large_dense_heap_mock() -> Arc<dyn Module> {
         guard_size: 4 * 1024 * 1024,
         initial_size: HEAP_LEN as u64,
         max_size: None,
+        shared: false,
     };
 
     let mut heap = vec![0x00; HEAP_LEN];
@@ -59,6 +60,7 @@ pub fn large_sparse_heap_mock() -> Arc<dyn Module> {
         guard_size: 4 * 1024 * 1024,
         initial_size: HEAP_LEN as u64,
         max_size: None,
+        shared: false,
     };
 
     let mut heap = vec![0x00; HEAP_LEN];
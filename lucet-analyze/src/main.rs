@@ -1,6 +1,7 @@
 use byteorder::{LittleEndian, ReadBytesExt};
 use colored::Colorize;
 use goblin::{elf, Object};
+use lucet_module_data::{Global, ModuleData};
 use std::env;
 use std::fs::File;
 use std::io::Cursor;
@@ -17,6 +18,7 @@ struct ArtifactSummary<'a> {
     data_segments: Option<DataSegments>,
     sparse_page_data: Option<SparsePageData>,
     trap_manifest: Option<TrapManifest>,
+    module_data: Option<Vec<u8>>,
     exported_functions: Vec<&'a str>,
     imported_symbols: Vec<&'a str>,
 }
@@ -30,6 +32,9 @@ struct StandardSymbols {
     lucet_heap_spec: Option<elf::sym::Sym>,
     lucet_globals_spec: Option<elf::sym::Sym>,
     guest_sparse_page_data: Option<elf::sym::Sym>,
+    lucet_module_data: Option<elf::sym::Sym>,
+    lucet_module_data_len: Option<elf::sym::Sym>,
+    guest_start: Option<elf::sym::Sym>,
 }
 
 #[derive(Debug)]
@@ -95,12 +100,16 @@ impl<'a> ArtifactSummary<'a> {
                 lucet_heap_spec: None,
                 lucet_globals_spec: None,
                 guest_sparse_page_data: None,
+                lucet_module_data: None,
+                lucet_module_data_len: None,
+                guest_start: None,
             },
             heap_spec: None,
             globals_spec: None,
             data_segments: None,
             sparse_page_data: None,
             trap_manifest: None,
+            module_data: None,
             exported_functions: Vec::new(),
             imported_symbols: Vec::new(),
         }
@@ -154,6 +163,9 @@ impl<'a> ArtifactSummary<'a> {
                 "lucet_heap_spec" => self.symbols.lucet_heap_spec = Some(sym.clone()),
                 "lucet_globals_spec" => self.symbols.lucet_globals_spec = Some(sym.clone()),
                 "guest_sparse_page_data" => self.symbols.guest_sparse_page_data = Some(sym.clone()),
+                "lucet_module_data" => self.symbols.lucet_module_data = Some(sym.clone()),
+                "lucet_module_data_len" => self.symbols.lucet_module_data_len = Some(sym.clone()),
+                "guest_start" => self.symbols.guest_start = Some(sym.clone()),
                 _ => {
                     if sym.st_bind() == elf::sym::STB_GLOBAL {
                         if sym.is_function() {
@@ -171,6 +183,24 @@ impl<'a> ArtifactSummary<'a> {
         self.data_segments = self.parse_data_segments();
         self.trap_manifest = self.parse_trap_manifest();
         self.sparse_page_data = self.parse_sparse_page_data();
+        self.module_data = self.read_module_data();
+    }
+
+    /// Read the serialized module data; it is deserialized when printed, since it borrows from the
+    /// returned buffer.
+    fn read_module_data(&self) -> Option<Vec<u8>> {
+        let data_sym = self.symbols.lucet_module_data.as_ref()?;
+        let data_len_sym = self.symbols.lucet_module_data_len.as_ref()?;
+
+        let buffer = self.read_memory(data_len_sym.st_value, data_len_sym.st_size)?;
+        let data_len = Cursor::new(buffer).read_u32::<LittleEndian>().ok()?;
+        let mut buffer = self.read_memory(data_sym.st_value, data_sym.st_size)?;
+        if data_len as usize > buffer.len() {
+            eprintln!("size of module data doesn't match its length symbol");
+            return None;
+        }
+        buffer.truncate(data_len as usize);
+        Some(buffer)
     }
 
     fn parse_heap_spec(&self) -> Option<HeapSpec> {
@@ -508,6 +538,27 @@ fn print_summary(summary: ArtifactSummary) {
         println!("  {}", "MISSING!".red().bold());
     }
 
+    println!("");
+    println!("Module Data:");
+    let module_data = summary
+        .module_data
+        .as_ref()
+        .map(|buf| ModuleData::deserialize(buf));
+    match module_data {
+        Some(Ok(module_data)) => print_module_data(&module_data),
+        Some(Err(e)) => println!("  {}: {}", "INVALID!".red().bold(), e),
+        None => println!("  {}", "MISSING!".red().bold()),
+    }
+    println!(
+        "  {:17}: {}",
+        "Start function",
+        if summary.symbols.guest_start.is_some() {
+            "yes"
+        } else {
+            "no"
+        }
+    );
+
     println!("");
     println!("Trap Manifest:");
     if let Some(trap_manifest) = summary.trap_manifest {
@@ -519,6 +570,82 @@ fn print_summary(summary: ArtifactSummary) {
     }
 }
 
+fn print_module_data(module_data: &ModuleData) {
+    let globals = module_data.globals_spec();
+    let imported_globals = globals
+        .iter()
+        .filter(|g| match g.global() {
+            Global::Import { .. } => true,
+            Global::Def { .. } => false,
+        })
+        .count();
+    println!(
+        "  {:17}: {} ({} imported)",
+        "Globals",
+        globals.len(),
+        imported_globals
+    );
+    for (idx, global) in globals.iter().enumerate() {
+        if let Some(name) = global.export() {
+            println!("    global {} (index {}): {:?}", name, idx, global.ty());
+        }
+    }
+
+    println!("  {:17}: {}", "Tables", module_data.tables_spec().len());
+    for (idx, table) in module_data.tables_spec().iter().enumerate() {
+        let max_size = table
+            .max_size()
+            .map(|max| max.to_string())
+            .unwrap_or_else(|| "unbounded".to_owned());
+        println!(
+            "    table {}: {:?}, min {}, max {}",
+            idx,
+            table.elem_type(),
+            table.min_size(),
+            max_size
+        );
+    }
+
+    println!(
+        "  {:17}: {}",
+        "Linked imports",
+        module_data.linked_imports().len()
+    );
+    for import in module_data.linked_imports() {
+        println!(
+            "    {}::{}: {:?}",
+            import.module(),
+            import.field(),
+            import.signature()
+        );
+    }
+
+    println!(
+        "  {:17}: {}",
+        "Function exports",
+        module_data.function_exports().len()
+    );
+    for export in module_data.function_exports() {
+        println!("    {}: {:?}", export.name(), export.signature());
+    }
+
+    println!(
+        "  {:17}: {}",
+        "Memory exports",
+        module_data.memory_exports().len()
+    );
+    for name in module_data.memory_exports() {
+        println!("    {}", name);
+    }
+
+    println!(
+        "  {:17}: {} data, {} element",
+        "Passive segments",
+        module_data.passive_data().len(),
+        module_data.passive_elements().len()
+    );
+}
+
 fn exists_to_str<T>(p: &Option<T>) -> colored::ColoredString {
     return match p {
         Some(_) => "exists".green(),
@@ -0,0 +1,87 @@
+use crate::types::{Signature, ValueType};
+use serde::{Deserialize, Serialize};
+
+/// A function exported by a WebAssembly module, along with its signature.
+///
+/// Exported globals and tables are described by the `export` fields of their own specifications;
+/// exported memories by the names in
+/// [`ModuleData::memory_exports()`](../struct.ModuleData.html#method.memory_exports).
+///
+/// The lifetime parameter exists to support zero-copy deserialization for the `&str` fields at the
+/// leaves of the structure. For a variant with owned types at the leaves, see
+/// [`OwnedFunctionExport`](owned/struct.OwnedFunctionExport.html).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionExport<'a> {
+    name: &'a str,
+    signature: Signature,
+    result_globals: Vec<(ValueType, u32)>,
+}
+
+impl<'a> FunctionExport<'a> {
+    pub fn new(name: &'a str, signature: Signature) -> Self {
+        Self {
+            name,
+            signature,
+            result_globals: vec![],
+        }
+    }
+
+    /// Set the globals that the results of the function are passed through.
+    ///
+    /// `lucetc` compiles functions with several results into functions with none, which store
+    /// their results into globals before returning.
+    pub fn with_result_globals(mut self, result_globals: Vec<(ValueType, u32)>) -> Self {
+        self.result_globals = result_globals;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// The type of each result of the function and the index of the global it is passed through,
+    /// if the function has several results.
+    ///
+    /// This is empty for functions with at most one result, which is returned as described by the
+    /// signature.
+    pub fn result_globals(&self) -> &[(ValueType, u32)] {
+        &self.result_globals
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A variant of [`FunctionExport`](../struct.FunctionExport.html) with owned strings throughout.
+///
+/// This type is useful when directly building up a value to be serialized.
+pub struct OwnedFunctionExport {
+    name: String,
+    signature: Signature,
+    result_globals: Vec<(ValueType, u32)>,
+}
+
+impl OwnedFunctionExport {
+    pub fn new(name: String, signature: Signature) -> Self {
+        Self {
+            name,
+            signature,
+            result_globals: vec![],
+        }
+    }
+
+    pub fn with_result_globals(mut self, result_globals: Vec<(ValueType, u32)>) -> Self {
+        self.result_globals = result_globals;
+        self
+    }
+
+    /// Create a [`FunctionExport`](../struct.FunctionExport.html) backed by the values in this
+    /// `OwnedFunctionExport`.
+    pub fn to_ref<'a>(&'a self) -> FunctionExport<'a> {
+        FunctionExport::new(self.name.as_str(), self.signature.clone())
+            .with_result_globals(self.result_globals.clone())
+    }
+}
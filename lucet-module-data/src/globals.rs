@@ -1,6 +1,7 @@
+use crate::types::ValueType;
 use serde::{Deserialize, Serialize};
 
-/// A WebAssembly global along with its export specification.
+/// A WebAssembly global along with its type and export specification.
 ///
 /// The lifetime parameter exists to support zero-copy deserialization for the `&str` fields at the
 /// leaves of the structure. For a variant with owned types at the leaves, see
@@ -9,34 +10,44 @@ use serde::{Deserialize, Serialize};
 pub struct GlobalSpec<'a> {
     #[serde(borrow)]
     global: Global<'a>,
+    ty: ValueType,
     export: Option<&'a str>,
 }
 
 impl<'a> GlobalSpec<'a> {
-    pub fn new(global: Global<'a>, export: Option<&'a str>) -> Self {
-        Self { global, export }
+    pub fn new(global: Global<'a>, ty: ValueType, export: Option<&'a str>) -> Self {
+        Self { global, ty, export }
     }
 
-    /// Create a new global definition with an initial value and an optional export name.
+    /// Create a new `i64` global definition with an initial value and an optional export name.
     pub fn new_def(init_val: i64, export: Option<&'a str>) -> Self {
         Self::new(
             Global::Def {
                 def: GlobalDef::new(init_val),
             },
+            ValueType::I64,
             export,
         )
     }
 
-    /// Create a new global import definition with a module and field name, and an optional export
-    /// name.
+    /// Create a new `i64` global import definition with a module and field name, and an optional
+    /// export name.
     pub fn new_import(module: &'a str, field: &'a str, export: Option<&'a str>) -> Self {
-        Self::new(Global::Import { module, field }, export)
+        Self::new(Global::Import { module, field }, ValueType::I64, export)
     }
 
     pub fn global(&self) -> &Global {
         &self.global
     }
 
+    /// The WebAssembly type of the global.
+    ///
+    /// Every global occupies an `i64` slot; `i32` globals are stored sign-extended, and `f32` and
+    /// `f64` globals are stored as their bit patterns in the low bits of the slot.
+    pub fn ty(&self) -> ValueType {
+        self.ty
+    }
+
     pub fn export(&self) -> Option<&str> {
         self.export
     }
@@ -59,8 +70,8 @@ pub enum Global<'a> {
 
 /// A global definition.
 ///
-/// Currently we cast everything to an `i64`, but in the future this may have explicit variants for
-/// the different WebAssembly scalar types.
+/// The initial value is stored as the contents of the global's `i64` slot; see
+/// [`GlobalSpec::ty()`](struct.GlobalSpec.html#method.ty) for how each type is represented.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GlobalDef {
     init_val: i64,
@@ -83,28 +94,34 @@ impl GlobalDef {
 /// This type is useful when directly building up a value to be serialized.
 pub struct OwnedGlobalSpec {
     global: OwnedGlobal,
+    ty: ValueType,
     export: Option<String>,
 }
 
 impl OwnedGlobalSpec {
-    pub fn new(global: OwnedGlobal, export: Option<String>) -> Self {
-        Self { global, export }
+    pub fn new(global: OwnedGlobal, ty: ValueType, export: Option<String>) -> Self {
+        Self { global, ty, export }
     }
 
-    /// Create a new global definition with an initial value and an optional export name.
+    /// Create a new `i64` global definition with an initial value and an optional export name.
     pub fn new_def(init_val: i64, export: Option<String>) -> Self {
         Self::new(
             OwnedGlobal::Def {
                 def: GlobalDef::new(init_val),
             },
+            ValueType::I64,
             export,
         )
     }
 
-    /// Create a new global import definition with a module and field name, and an optional export
-    /// name.
+    /// Create a new `i64` global import definition with a module and field name, and an optional
+    /// export name.
     pub fn new_import(module: String, field: String, export: Option<String>) -> Self {
-        Self::new(OwnedGlobal::Import { module, field }, export)
+        Self::new(
+            OwnedGlobal::Import { module, field },
+            ValueType::I64,
+            export,
+        )
     }
 
     /// Create a [`GlobalSpec`](../struct.GlobalSpec.html) backed by the values in this
@@ -114,7 +131,7 @@ impl OwnedGlobalSpec {
             Some(e) => Some(e.as_str()),
             None => None,
         };
-        GlobalSpec::new(self.global.to_ref(), export)
+        GlobalSpec::new(self.global.to_ref(), self.ty, export)
     }
 }
 
@@ -4,18 +4,32 @@
 //! [`bincode`](https://github.com/TyOverby/bincode) format to the compiled Lucet modules.
 
 mod error;
+mod exports;
 mod globals;
 mod linear_memory;
+mod linked_imports;
 mod module_data;
+mod segments;
+mod tables;
+mod types;
 
 pub use crate::error::Error;
+pub use crate::exports::FunctionExport;
 pub use crate::globals::{Global, GlobalDef, GlobalSpec};
 pub use crate::linear_memory::{HeapSpec, SparseData};
+pub use crate::linked_imports::LinkedImport;
 pub use crate::module_data::ModuleData;
+pub use crate::segments::{PassiveDataSegment, PassiveElemSegment};
+pub use crate::tables::TableSpec;
+pub use crate::types::{RefType, Signature, ValueType};
 
 /// Owned variants of the module data types, useful for serialization and testing.
 pub mod owned {
-    pub use crate::globals::OwnedGlobalSpec;
+    pub use crate::exports::OwnedFunctionExport;
+    pub use crate::globals::{OwnedGlobal, OwnedGlobalSpec};
     pub use crate::linear_memory::OwnedSparseData;
+    pub use crate::linked_imports::OwnedLinkedImport;
     pub use crate::module_data::OwnedModuleData;
+    pub use crate::segments::OwnedPassiveDataSegment;
+    pub use crate::tables::OwnedTableSpec;
 }
@@ -42,6 +42,10 @@ pub struct HeapSpec {
     /// `reserved_size`. If it does not, the max size is left up to the runtime, and is allowed to
     /// be less than `reserved_size`.
     pub max_size: Option<u64>,
+
+    /// Whether the WebAssembly program declared its linear memory as shared, so that several
+    /// instances may operate on the same heap at once.
+    pub shared: bool,
 }
 
 impl HeapSpec {
@@ -56,6 +60,7 @@ impl HeapSpec {
             guard_size,
             initial_size,
             max_size,
+            shared: false,
         }
     }
 
@@ -66,6 +71,7 @@ impl HeapSpec {
             guard_size: 0,
             initial_size: 0,
             max_size: None,
+            shared: false,
         }
     }
 }
@@ -0,0 +1,77 @@
+use crate::types::Signature;
+use serde::{Deserialize, Serialize};
+
+/// A function import that is satisfied by an export of another Lucet module, rather than by a
+/// hostcall.
+///
+/// `lucetc` compiles a call to a linked import as a call through a per-instance table of functions
+/// and `vmctx` pointers. The index of a `LinkedImport` in
+/// [`ModuleData::linked_imports()`](../struct.ModuleData.html#method.linked_imports) is its index
+/// into that table; the runtime fills in the entry when the instance is linked to an instance of
+/// the module named by `module`, provided that the export has the same `signature`.
+///
+/// The lifetime parameter exists to support zero-copy deserialization for the `&str` fields at the
+/// leaves of the structure. For a variant with owned types at the leaves, see
+/// [`OwnedLinkedImport`](owned/struct.OwnedLinkedImport.html).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkedImport<'a> {
+    module: &'a str,
+    field: &'a str,
+    signature: Signature,
+}
+
+impl<'a> LinkedImport<'a> {
+    pub fn new(module: &'a str, field: &'a str, signature: Signature) -> Self {
+        Self {
+            module,
+            field,
+            signature,
+        }
+    }
+
+    /// The name of the WebAssembly module that provides this import.
+    pub fn module(&self) -> &str {
+        self.module
+    }
+
+    /// The name of the export in the providing module.
+    pub fn field(&self) -> &str {
+        self.field
+    }
+
+    /// The signature the importing module expects the export to have.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A variant of [`LinkedImport`](../struct.LinkedImport.html) with owned strings throughout.
+///
+/// This type is useful when directly building up a value to be serialized.
+pub struct OwnedLinkedImport {
+    module: String,
+    field: String,
+    signature: Signature,
+}
+
+impl OwnedLinkedImport {
+    pub fn new(module: String, field: String, signature: Signature) -> Self {
+        Self {
+            module,
+            field,
+            signature,
+        }
+    }
+
+    /// Create a [`LinkedImport`](../struct.LinkedImport.html) backed by the values in this
+    /// `OwnedLinkedImport`.
+    pub fn to_ref<'a>(&'a self) -> LinkedImport<'a> {
+        LinkedImport::new(
+            self.module.as_str(),
+            self.field.as_str(),
+            self.signature.clone(),
+        )
+    }
+}
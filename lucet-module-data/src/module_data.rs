@@ -1,6 +1,10 @@
 use crate::{
+    exports::FunctionExport,
     globals::GlobalSpec,
     linear_memory::{HeapSpec, SparseData},
+    linked_imports::LinkedImport,
+    segments::{PassiveDataSegment, PassiveElemSegment},
+    tables::TableSpec,
     Error,
 };
 use serde::{Deserialize, Serialize};
@@ -20,6 +24,17 @@ pub struct ModuleData<'a> {
     sparse_data: SparseData<'a>,
     #[serde(borrow)]
     globals_spec: Vec<GlobalSpec<'a>>,
+    #[serde(borrow)]
+    linked_imports: Vec<LinkedImport<'a>>,
+    #[serde(borrow)]
+    tables_spec: Vec<TableSpec<'a>>,
+    #[serde(borrow)]
+    function_exports: Vec<FunctionExport<'a>>,
+    #[serde(borrow)]
+    memory_exports: Vec<&'a str>,
+    #[serde(borrow)]
+    passive_data: Vec<PassiveDataSegment<'a>>,
+    passive_elements: Vec<PassiveElemSegment>,
 }
 
 impl<'a> ModuleData<'a> {
@@ -27,11 +42,23 @@ impl<'a> ModuleData<'a> {
         heap_spec: HeapSpec,
         sparse_data: SparseData<'a>,
         globals_spec: Vec<GlobalSpec<'a>>,
+        linked_imports: Vec<LinkedImport<'a>>,
+        tables_spec: Vec<TableSpec<'a>>,
+        function_exports: Vec<FunctionExport<'a>>,
+        memory_exports: Vec<&'a str>,
+        passive_data: Vec<PassiveDataSegment<'a>>,
+        passive_elements: Vec<PassiveElemSegment>,
     ) -> Self {
         Self {
             heap_spec,
             sparse_data,
             globals_spec,
+            linked_imports,
+            tables_spec,
+            function_exports,
+            memory_exports,
+            passive_data,
+            passive_elements,
         }
     }
 
@@ -47,6 +74,37 @@ impl<'a> ModuleData<'a> {
         &self.globals_spec
     }
 
+    /// The function imports that are satisfied by exports of other Lucet modules, in the order of
+    /// their slots in the instance's linked function table.
+    pub fn linked_imports(&self) -> &[LinkedImport<'a>] {
+        &self.linked_imports
+    }
+
+    /// The tables of the module, indexed by their WebAssembly table indices.
+    pub fn tables_spec(&self) -> &[TableSpec<'a>] {
+        &self.tables_spec
+    }
+
+    /// The functions exported by the module, with their signatures.
+    pub fn function_exports(&self) -> &[FunctionExport<'a>] {
+        &self.function_exports
+    }
+
+    /// The names under which the module exports its linear memory.
+    pub fn memory_exports(&self) -> &[&'a str] {
+        &self.memory_exports
+    }
+
+    /// The passive data segments of the module, in the order of their segment indices.
+    pub fn passive_data(&self) -> &[PassiveDataSegment<'a>] {
+        &self.passive_data
+    }
+
+    /// The passive element segments of the module, in the order of their segment indices.
+    pub fn passive_elements(&self) -> &[PassiveElemSegment] {
+        &self.passive_elements
+    }
+
     /// Serialize to (https://github.com/TyOverby/bincode).
     pub fn serialize(&self) -> Result<Vec<u8>, Error> {
         bincode::serialize(self).map_err(Error::SerializationError)
@@ -58,7 +116,10 @@ impl<'a> ModuleData<'a> {
     }
 }
 
-use crate::{globals::OwnedGlobalSpec, linear_memory::OwnedSparseData};
+use crate::{
+    exports::OwnedFunctionExport, globals::OwnedGlobalSpec, linear_memory::OwnedSparseData,
+    linked_imports::OwnedLinkedImport, segments::OwnedPassiveDataSegment, tables::OwnedTableSpec,
+};
 
 /// The metadata (and some data) for a Lucet module.
 ///
@@ -69,6 +130,12 @@ pub struct OwnedModuleData {
     heap_spec: HeapSpec,
     sparse_data: OwnedSparseData,
     globals_spec: Vec<OwnedGlobalSpec>,
+    linked_imports: Vec<OwnedLinkedImport>,
+    tables_spec: Vec<OwnedTableSpec>,
+    function_exports: Vec<OwnedFunctionExport>,
+    memory_exports: Vec<String>,
+    passive_data: Vec<OwnedPassiveDataSegment>,
+    passive_elements: Vec<PassiveElemSegment>,
 }
 
 impl OwnedModuleData {
@@ -76,11 +143,23 @@ impl OwnedModuleData {
         heap_spec: HeapSpec,
         sparse_data: OwnedSparseData,
         globals_spec: Vec<OwnedGlobalSpec>,
+        linked_imports: Vec<OwnedLinkedImport>,
+        tables_spec: Vec<OwnedTableSpec>,
+        function_exports: Vec<OwnedFunctionExport>,
+        memory_exports: Vec<String>,
+        passive_data: Vec<OwnedPassiveDataSegment>,
+        passive_elements: Vec<PassiveElemSegment>,
     ) -> Self {
         Self {
             heap_spec,
             sparse_data,
             globals_spec,
+            linked_imports,
+            tables_spec,
+            function_exports,
+            memory_exports,
+            passive_data,
+            passive_elements,
         }
     }
 
@@ -91,6 +170,12 @@ impl OwnedModuleData {
             self.heap_spec.clone(),
             self.sparse_data.to_ref(),
             self.globals_spec.iter().map(|gs| gs.to_ref()).collect(),
+            self.linked_imports.iter().map(|li| li.to_ref()).collect(),
+            self.tables_spec.iter().map(|ts| ts.to_ref()).collect(),
+            self.function_exports.iter().map(|fe| fe.to_ref()).collect(),
+            self.memory_exports.iter().map(|me| me.as_str()).collect(),
+            self.passive_data.iter().map(|pd| pd.to_ref()).collect(),
+            self.passive_elements.clone(),
         )
     }
 
@@ -99,6 +184,12 @@ impl OwnedModuleData {
             HeapSpec::new(0, 0, 0, None),
             OwnedSparseData::new(vec![]).unwrap(),
             vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
         )
     }
 
@@ -106,6 +197,11 @@ impl OwnedModuleData {
         self.heap_spec = heap_spec;
         self
     }
+
+    pub fn with_passive_data(mut self, passive_data: Vec<OwnedPassiveDataSegment>) -> Self {
+        self.passive_data = passive_data;
+        self
+    }
 }
 
 impl Default for OwnedModuleData {
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+/// A passive data segment, which is only copied into the heap by `memory.init`.
+///
+/// Active data segments are not part of this specification; they are already applied to the
+/// initial heap in [`SparseData`](struct.SparseData.html).
+///
+/// The lifetime parameter exists to support zero-copy deserialization for the `&[u8]` contents of
+/// the segment. For a variant with an owned `Vec<u8>`, see
+/// [`OwnedPassiveDataSegment`](owned/struct.OwnedPassiveDataSegment.html).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassiveDataSegment<'a> {
+    index: u32,
+    #[serde(borrow)]
+    data: &'a [u8],
+}
+
+impl<'a> PassiveDataSegment<'a> {
+    pub fn new(index: u32, data: &'a [u8]) -> Self {
+        Self { index, data }
+    }
+
+    /// The index of the segment in the module's data section.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+/// A passive element segment, which is only copied into a table by `table.init`.
+///
+/// The elements themselves are stored in the compiled module as the `len` entries starting at
+/// `start` of the `guest_passive_elements` symbol, which has the same layout as
+/// `guest_table_<index>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassiveElemSegment {
+    index: u32,
+    start: u32,
+    len: u32,
+}
+
+impl PassiveElemSegment {
+    pub fn new(index: u32, start: u32, len: u32) -> Self {
+        Self { index, start, len }
+    }
+
+    /// The index of the segment in the module's element section.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The position of the segment's first element in `guest_passive_elements`.
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A variant of [`PassiveDataSegment`](../struct.PassiveDataSegment.html) with an owned
+/// `Vec<u8>`.
+///
+/// This type is useful when directly building up a value to be serialized.
+pub struct OwnedPassiveDataSegment {
+    index: u32,
+    data: Vec<u8>,
+}
+
+impl OwnedPassiveDataSegment {
+    pub fn new(index: u32, data: Vec<u8>) -> Self {
+        Self { index, data }
+    }
+
+    /// Create a [`PassiveDataSegment`](../struct.PassiveDataSegment.html) backed by the values in
+    /// this `OwnedPassiveDataSegment`.
+    pub fn to_ref<'a>(&'a self) -> PassiveDataSegment<'a> {
+        PassiveDataSegment::new(self.index, &self.data)
+    }
+}
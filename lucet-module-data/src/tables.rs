@@ -0,0 +1,107 @@
+use crate::types::RefType;
+use serde::{Deserialize, Serialize};
+
+/// A WebAssembly table along with its import and export specification.
+///
+/// The initial elements of the table are not part of this specification; they are stored in the
+/// compiled module as the `guest_table_<index>` symbol.
+///
+/// The lifetime parameter exists to support zero-copy deserialization for the `&str` fields at the
+/// leaves of the structure. For a variant with owned types at the leaves, see
+/// [`OwnedTableSpec`](owned/struct.OwnedTableSpec.html).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableSpec<'a> {
+    elem_type: RefType,
+    min_size: u32,
+    max_size: Option<u32>,
+    #[serde(borrow)]
+    import: Option<(&'a str, &'a str)>,
+    export: Option<&'a str>,
+}
+
+impl<'a> TableSpec<'a> {
+    pub fn new(
+        elem_type: RefType,
+        min_size: u32,
+        max_size: Option<u32>,
+        import: Option<(&'a str, &'a str)>,
+        export: Option<&'a str>,
+    ) -> Self {
+        Self {
+            elem_type,
+            min_size,
+            max_size,
+            import,
+            export,
+        }
+    }
+
+    /// The type of the elements of the table.
+    pub fn elem_type(&self) -> RefType {
+        self.elem_type
+    }
+
+    /// The initial number of elements in the table.
+    pub fn min_size(&self) -> u32 {
+        self.min_size
+    }
+
+    /// The maximum number of elements in the table, if one was declared.
+    pub fn max_size(&self) -> Option<u32> {
+        self.max_size
+    }
+
+    /// The module and field names of the table, if it is imported.
+    pub fn import(&self) -> Option<(&str, &str)> {
+        self.import
+    }
+
+    pub fn export(&self) -> Option<&str> {
+        self.export
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A variant of [`TableSpec`](../struct.TableSpec.html) with owned strings throughout.
+///
+/// This type is useful when directly building up a value to be serialized.
+pub struct OwnedTableSpec {
+    elem_type: RefType,
+    min_size: u32,
+    max_size: Option<u32>,
+    import: Option<(String, String)>,
+    export: Option<String>,
+}
+
+impl OwnedTableSpec {
+    pub fn new(
+        elem_type: RefType,
+        min_size: u32,
+        max_size: Option<u32>,
+        import: Option<(String, String)>,
+        export: Option<String>,
+    ) -> Self {
+        Self {
+            elem_type,
+            min_size,
+            max_size,
+            import,
+            export,
+        }
+    }
+
+    /// Create a [`TableSpec`](../struct.TableSpec.html) backed by the values in this
+    /// `OwnedTableSpec`.
+    pub fn to_ref<'a>(&'a self) -> TableSpec<'a> {
+        TableSpec::new(
+            self.elem_type,
+            self.min_size,
+            self.max_size,
+            self.import
+                .as_ref()
+                .map(|(module, field)| (module.as_str(), field.as_str())),
+            self.export.as_ref().map(|e| e.as_str()),
+        )
+    }
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+/// A WebAssembly value type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ValueType {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+}
+
+/// The type of the elements of a WebAssembly table.
+///
+/// Guest code handles both kinds of reference as 64-bit values, with zero as the null reference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RefType {
+    /// A reference to a function, which the table elements of indirect calls are.
+    FuncRef,
+    /// An opaque reference provided by the host.
+    ExternRef,
+}
+
+/// The signature of a WebAssembly function, not including the `vmctx` argument that Lucet passes
+/// to every guest function.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Signature {
+    pub params: Vec<ValueType>,
+    pub ret_ty: Option<ValueType>,
+}
+
+impl Signature {
+    pub fn new(params: Vec<ValueType>, ret_ty: Option<ValueType>) -> Self {
+        Self { params, ret_ty }
+    }
+}
@@ -0,0 +1,38 @@
+/// Regenerate the C headers in `include/` from the Rust sources.
+///
+/// The headers are checked in, so this only runs with the `generate-headers` feature; otherwise
+/// building the crate leaves the source tree untouched. Run `make headers` after changing the C
+/// API; CI runs `make headers-check`, which fails if the checked-in headers are out of date.
+#[cfg(feature = "generate-headers")]
+fn main() {
+    use std::env;
+    use std::path::PathBuf;
+
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let include_dir = crate_dir.join("include");
+
+    // `lucet.h` declares the embedding API, along with the types it shares with
+    // `lucet-runtime-internals`. The functions available to hostcalls are generated separately into
+    // `lucet_vmctx.h`, which `lucet.h` includes.
+    cbindgen::generate(&crate_dir)
+        .expect("can generate lucet.h")
+        .write_to_file(include_dir.join("lucet.h"));
+
+    let vmctx_config_path = crate_dir.join("cbindgen-vmctx.toml");
+    let vmctx_config = cbindgen::Config::from_file(vmctx_config_path.to_str().unwrap())
+        .expect("can read cbindgen-vmctx.toml");
+    cbindgen::Builder::new()
+        .with_config(vmctx_config)
+        .with_src(crate_dir.join("src/c_api/vmctx.rs"))
+        .generate()
+        .expect("can generate lucet_vmctx.h")
+        .write_to_file(include_dir.join("lucet_vmctx.h"));
+
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=cbindgen-vmctx.toml");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=lucet-runtime-internals/src/c_api.rs");
+}
+
+#[cfg(not(feature = "generate-headers"))]
+fn main() {}
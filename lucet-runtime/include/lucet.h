@@ -1,3 +1,5 @@
+/* Generated from the lucet-runtime C API by cbindgen. Do not edit by hand. */
+
 #ifndef LUCET_H
 #define LUCET_H
 
@@ -7,70 +9,317 @@
 #include <stdint.h>
 #include <stdlib.h>
 #include <ucontext.h>
-
-#include "lucet_types.h"
 #include "lucet_val.h"
 #include "lucet_vmctx.h"
 
-#define LUCET_WASM_PAGE_SIZE (64 * 1024)
+enum lucet_error {
+    lucet_error_ok,
+    lucet_error_invalid_argument,
+    lucet_error_region_full,
+    lucet_error_module,
+    lucet_error_limits_exceeded,
+    lucet_error_symbol_not_found,
+    lucet_error_func_not_found,
+    lucet_error_runtime_fault,
+    lucet_error_runtime_terminated,
+    lucet_error_dl,
+    lucet_error_internal,
+    lucet_error_unsupported,
+};
+
+enum lucet_signal_behavior {
+    lucet_signal_behavior_default,
+    lucet_signal_behavior_continue,
+    lucet_signal_behavior_terminate,
+};
+
+enum lucet_state_tag {
+    lucet_state_tag_returned,
+    lucet_state_tag_running,
+    lucet_state_tag_fault,
+    lucet_state_tag_terminated,
+};
+
+enum lucet_terminated_reason {
+    lucet_terminated_reason_signal,
+    lucet_terminated_reason_get_embed_ctx,
+    lucet_terminated_reason_provided,
+    lucet_terminated_reason_panic,
+    lucet_terminated_reason_unlinked_import,
+};
+
+enum lucet_trapcode_type {
+    lucet_trapcode_type_stack_overflow,
+    lucet_trapcode_type_heap_out_of_bounds,
+    lucet_trapcode_type_out_of_bounds,
+    lucet_trapcode_type_indirect_call_to_null,
+    lucet_trapcode_type_bad_signature,
+    lucet_trapcode_type_integer_overflow,
+    lucet_trapcode_type_integer_div_by_zero,
+    lucet_trapcode_type_bad_conversion_to_integer,
+    lucet_trapcode_type_interrupt,
+    lucet_trapcode_type_table_out_of_bounds,
+    lucet_trapcode_type_user,
+    lucet_trapcode_type_unknown,
+};
+
+enum lucet_val_type {
+    lucet_val_type_c_ptr,
+    lucet_val_type_guest_ptr,
+    lucet_val_type_u8,
+    lucet_val_type_u16,
+    lucet_val_type_u32,
+    lucet_val_type_u64,
+    lucet_val_type_i8,
+    lucet_val_type_i16,
+    lucet_val_type_i32,
+    lucet_val_type_i64,
+    lucet_val_type_usize,
+    lucet_val_type_isize,
+    lucet_val_type_bool,
+    lucet_val_type_f32,
+    lucet_val_type_f64,
+    lucet_val_type_v128,
+};
+
+struct lucet_dl_module;
+
+struct lucet_instance;
+
+struct lucet_region;
+
+/**
+ * Runtime limits for the various memories that back a Lucet instance.
+ *
+ * Each value other than `table_elements` is specified in bytes, and must be evenly divisible by the
+ * host page size (4K).
+ */
+struct lucet_alloc_limits {
+    /**
+     * Max size of the heap, which can be backed by real memory. (default 1M)
+     */
+    uint64_t heap_memory_size;
+    /**
+     * Size of total virtual memory. (default 8G)
+     */
+    uint64_t heap_address_space_size;
+    /**
+     * Size of the guest stack. (default 128K)
+     */
+    uint64_t stack_size;
+    /**
+     * Size of the globals region in bytes; each global uses 8 bytes. (default 4K)
+     */
+    uint64_t globals_size;
+    /**
+     * Max number of elements in each table. (default 1M)
+     */
+    uint64_t table_elements;
+};
+
+union lucet_val_inner_val {
+    void *as_c_ptr;
+    uint64_t as_u64;
+    int64_t as_i64;
+    float as_f32;
+    double as_f64;
+    uint64_t as_v128[2];
+};
+
+struct lucet_val {
+    enum lucet_val_type ty;
+    union lucet_val_inner_val inner_val;
+};
+
+typedef void (*lucet_fatal_handler)(struct lucet_instance *inst);
+
+struct lucet_trapcode {
+    enum lucet_trapcode_type code;
+    uint16_t tag;
+};
+
+typedef enum lucet_signal_behavior (*lucet_signal_handler)(struct lucet_instance *inst, const struct lucet_trapcode *trap, int signum, const siginfo_t *siginfo, const void *context);
+
+struct lucet_untyped_retval {
+    char fp[16];
+    char gp[8];
+};
+
+struct lucet_module_addr_details {
+    bool module_code_resolvable;
+    bool in_module_code;
+    const char *file_name;
+    const char *sym_name;
+};
+
+struct lucet_runtime_fault {
+    bool fatal;
+    struct lucet_trapcode trapcode;
+    uintptr_t rip_addr;
+    struct lucet_module_addr_details rip_addr_details;
+    siginfo_t signal_info;
+    ucontext_t context;
+};
+
+struct lucet_terminated {
+    enum lucet_terminated_reason reason;
+    void *provided;
+    /**
+     * The message of the panic that terminated the instance, if the payload was a string.
+     */
+    const char *panic_message;
+};
+
+union lucet_state_val {
+    struct lucet_untyped_retval returned;
+    bool running;
+    struct lucet_runtime_fault fault;
+    struct lucet_terminated terminated;
+};
+
+struct lucet_state {
+    enum lucet_state_tag tag;
+    union lucet_state_val val;
+};
+
+union lucet_retval_gp {
+    char as_untyped[8];
+    void *as_c_ptr;
+    uint64_t as_u64;
+    int64_t as_i64;
+};
+
+/**
+ * A description of a runtime fault that does not depend on the layout of `siginfo_t` or
+ * `ucontext_t`.
+ *
+ * The names in `rip_addr_details` are owned by the `lucet_state` these details were read from,
+ * and remain valid until it is passed to `lucet_state_release()`.
+ */
+struct lucet_fault_details {
+    bool fatal;
+    struct lucet_trapcode trapcode;
+    uintptr_t rip_addr;
+    struct lucet_module_addr_details rip_addr_details;
+    int signal_number;
+    uintptr_t fault_addr;
+};
+
+/**
+ * Prevent Lucet from installing its own handlers for `SIGBUS`, `SIGFPE`, `SIGILL`, and `SIGSEGV`.
+ *
+ * Embedders that call this must call `lucet_handle_signal()` first from their own handlers for
+ * those signals, which must be installed with `SA_SIGINFO` and `SA_ONSTACK`. This must be called
+ * before any instance runs; it returns `lucet_error_invalid_argument` if Lucet's handlers have
+ * already been installed.
+ */
+enum lucet_error lucet_disable_signal_handler_installation(void);
+
+enum lucet_error lucet_dl_module_get_export_func(const struct lucet_dl_module *module,
+                                                 const char *sym,
+                                                 const void **func_out);
 
 enum lucet_error lucet_dl_module_load(const char *path, struct lucet_dl_module **mod_out);
 
 void lucet_dl_module_release(const struct lucet_dl_module *module);
 
-const char *lucet_error_name(enum lucet_error e);
+/**
+ * Check that instances of the module can be created in a region with the given limits, without
+ * creating an instance.
+ *
+ * If `limits` is `NULL`, the default limits are used.
+ */
+enum lucet_error lucet_dl_module_validate(const struct lucet_dl_module *module,
+                                          const struct lucet_alloc_limits *limits);
+
+const char *lucet_error_name(int e);
+
+/**
+ * Handle a signal on behalf of the instance running on this thread, if there is one.
+ *
+ * Returns `false` if the signal did not arise from a running Lucet instance, in which case the
+ * embedder's signal handler should handle it as it would have otherwise. Returns `true` if the
+ * signal was handled and the interrupted code can continue. If the signal ends the instance's run,
+ * this function does not return.
+ */
+bool lucet_handle_signal(int signum, siginfo_t *siginfo, void *ucontext);
 
 bool lucet_instance_check_heap(const struct lucet_instance *inst, const void *ptr, uintptr_t len);
 
 void *lucet_instance_embed_ctx(struct lucet_instance *inst);
 
+enum lucet_error lucet_instance_get_export_table(const struct lucet_instance *inst,
+                                                 const char *name,
+                                                 uint32_t *table_idx_out);
+
+enum lucet_error lucet_instance_get_global(const struct lucet_instance *inst,
+                                           uint32_t global_idx,
+                                           int64_t *value_out);
+
+uint32_t lucet_instance_global_count(const struct lucet_instance *inst);
+
 enum lucet_error lucet_instance_grow_heap(struct lucet_instance *inst,
-                                          uint32_t               additional_pages,
-                                          uint32_t *             previous_pages_out);
+                                          uint32_t additional_pages,
+                                          uint32_t *previous_pages_out);
 
 uint8_t *lucet_instance_heap(struct lucet_instance *inst);
 
 uint32_t lucet_instance_heap_len(const struct lucet_instance *inst);
 
+/**
+ * Set the embedder context of an instance, returning the previous context or `NULL` if there was
+ * none.
+ */
+void *lucet_instance_insert_embed_ctx(struct lucet_instance *inst, void *embed_ctx);
+
 void lucet_instance_release(struct lucet_instance *inst);
 
+/**
+ * Remove the embedder context of an instance, returning it or `NULL` if there was none.
+ */
+void *lucet_instance_remove_embed_ctx(struct lucet_instance *inst);
+
 enum lucet_error lucet_instance_reset(struct lucet_instance *inst);
 
-enum lucet_error lucet_instance_run(struct lucet_instance * inst,
-                                    const char *            entrypoint,
-                                    uintptr_t               argc,
+enum lucet_error lucet_instance_run(struct lucet_instance *inst,
+                                    const char *entrypoint,
+                                    uintptr_t argc,
                                     const struct lucet_val *argv);
 
-enum lucet_error lucet_instance_run_func_idx(struct lucet_instance * inst,
-                                             uint32_t                table_idx,
-                                             uint32_t                func_idx,
-                                             uintptr_t               argc,
+enum lucet_error lucet_instance_run_func_idx(struct lucet_instance *inst,
+                                             uint32_t table_idx,
+                                             uint32_t func_idx,
+                                             uintptr_t argc,
                                              const struct lucet_val *argv);
 
 enum lucet_error lucet_instance_set_fatal_handler(struct lucet_instance *inst,
-                                                  lucet_fatal_handler    fatal_handler);
+                                                  lucet_fatal_handler fatal_handler);
+
+enum lucet_error lucet_instance_set_global(struct lucet_instance *inst,
+                                           uint32_t global_idx,
+                                           int64_t value);
 
 /**
  * Release or run* must not be called in the body of this function!
  */
 enum lucet_error lucet_instance_set_signal_handler(struct lucet_instance *inst,
-                                                   lucet_signal_handler   signal_handler);
+                                                   lucet_signal_handler signal_handler);
 
 enum lucet_error lucet_instance_state(const struct lucet_instance *inst,
-                                      struct lucet_state *         state_out);
+                                      struct lucet_state *state_out);
 
-enum lucet_error lucet_mmap_region_create(uint64_t                         instance_capacity,
+enum lucet_error lucet_mmap_region_create(uint64_t instance_capacity,
                                           const struct lucet_alloc_limits *limits,
-                                          struct lucet_region **           region_out);
+                                          struct lucet_region **region_out);
 
-enum lucet_error lucet_region_new_instance(const struct lucet_region *   region,
+enum lucet_error lucet_region_new_instance(const struct lucet_region *region,
                                            const struct lucet_dl_module *module,
-                                           struct lucet_instance **      inst_out);
+                                           struct lucet_instance **inst_out);
 
-enum lucet_error lucet_region_new_instance_with_ctx(const struct lucet_region *   region,
+enum lucet_error lucet_region_new_instance_with_ctx(const struct lucet_region *region,
                                                     const struct lucet_dl_module *module,
-                                                    void *                        embed_ctx,
-                                                    struct lucet_instance **      inst_out);
+                                                    void *embed_ctx,
+                                                    struct lucet_instance **inst_out);
 
 void lucet_region_release(const struct lucet_region *region);
 
@@ -80,8 +329,29 @@ double lucet_retval_f64(const struct lucet_untyped_retval *retval);
 
 union lucet_retval_gp lucet_retval_gp(const struct lucet_untyped_retval *retval);
 
+/**
+ * Get a description of the fault in a state with the `lucet_state_tag_fault` tag.
+ *
+ * The strings in the details are owned by `state`.
+ */
+enum lucet_error lucet_state_fault_details(const struct lucet_state *state,
+                                           struct lucet_fault_details *details_out);
+
 void lucet_state_release(struct lucet_state *state);
 
-const char *lucet_state_tag_name(enum lucet_state_tag tag);
+const char *lucet_state_tag_name(int tag);
+
+/**
+ * Get the pointer passed to `lucet_vmctx_terminate()` in a state with the
+ * `lucet_state_tag_terminated` tag.
+ *
+ * The pointer is `NULL` if the instance was terminated for any other reason.
+ */
+enum lucet_error lucet_state_terminated_provided(const struct lucet_state *state,
+                                                 void **provided_out);
+
+const char *lucet_terminated_reason_name(int reason);
+
+const char *lucet_trapcode_type_name(int ty);
 
 #endif /* LUCET_H */
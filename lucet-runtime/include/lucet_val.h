@@ -16,6 +16,10 @@
  * lucet_instance_run(inst, "add_2", 2, (struct lucet_val[]){ LUCET_VAL_U64(123), LUCET_VAL_U64(456)
  * }); lucet_instance_state(inst, &state); uint64_t res =
  * LUCET_UNTYPED_RETVAL_TO_U64(state.val.returned);
+ *
+ * Unlike `lucet.h` and `lucet_vmctx.h`, this header is written by hand. It
+ * also declares the types that those generated headers use but cannot
+ * generate.
  */
 
 #include <sys/types.h>
@@ -24,6 +28,17 @@
 #include <stddef.h>
 #include <stdint.h>
 
+#define LUCET_WASM_PAGE_SIZE (64 * 1024)
+
+// Typedefs for use in hostcall signatures (host functions called from the
+// guest). Instances always have a 32-bit pointer size into their heap, and
+// 32-bit size_t type.
+typedef uint32_t guest_int;
+typedef uint32_t guest_ptr_t;
+typedef uint32_t guest_size_t;
+
+struct lucet_vmctx;
+
 // Creates a lucet_val value from the given type
 
 #define LUCET_VAL_T(T, C, X) \
@@ -50,6 +65,9 @@
 #define LUCET_VAL_F32(X) LUCET_VAL_T(f32, as_f32, X)
 #define LUCET_VAL_F64(X) LUCET_VAL_T(f64, as_f64, X)
 
+#define LUCET_VAL_V128(LO, HI) \
+    ((struct lucet_val[1]){ { .ty = lucet_val_type_v128, .inner_val.as_v128 = { (LO), (HI) } } }[0])
+
 // Converts a lucet_val value to the given type
 
 #define LUCET_VAL_TO_T(T, C, V) ((T)((V).inner_val.C))
@@ -1,43 +1,289 @@
+/* Generated from the lucet-runtime C API by cbindgen. Do not edit by hand. */
+
 #ifndef LUCET_VMCTX_H
 #define LUCET_VMCTX_H
 
 #include <stdbool.h>
 #include <stddef.h>
 #include <stdint.h>
+#include "lucet_val.h"
+
+/**
+ * Replace the `width` bytes at `addr` with `replacement` if they equal `expected`, returning the
+ * old value.
+ */
+uint32_t lucet_vmctx_atomic_cmpxchg_i32(const struct lucet_vmctx *vmctx,
+                                        uint32_t addr,
+                                        uint32_t expected,
+                                        uint32_t replacement,
+                                        uint32_t width);
+
+/**
+ * Replace the `width` bytes at `addr` with `replacement` if they equal `expected`, returning the
+ * old value.
+ */
+uint64_t lucet_vmctx_atomic_cmpxchg_i64(const struct lucet_vmctx *vmctx,
+                                        uint32_t addr,
+                                        uint64_t expected,
+                                        uint64_t replacement,
+                                        uint32_t width);
+
+/**
+ * Order the memory accesses before the call with those after it.
+ */
+void lucet_vmctx_atomic_fence(const struct lucet_vmctx *_vmctx);
+
+/**
+ * Load `width` bytes at `addr`, zero-extended.
+ */
+uint32_t lucet_vmctx_atomic_load_i32(const struct lucet_vmctx *vmctx,
+                                     uint32_t addr,
+                                     uint32_t width);
+
+/**
+ * Load `width` bytes at `addr`, zero-extended.
+ */
+uint64_t lucet_vmctx_atomic_load_i64(const struct lucet_vmctx *vmctx,
+                                     uint32_t addr,
+                                     uint32_t width);
+
+/**
+ * Wake up to `count` threads waiting on `addr`, returning the number woken.
+ */
+uint32_t lucet_vmctx_atomic_notify(const struct lucet_vmctx *vmctx, uint32_t addr, uint32_t count);
+
+/**
+ * Apply the read-modify-write operation `op` to the `width` bytes at `addr`, returning the old
+ * value.
+ *
+ * The operations are numbered 0 to 5, in the order of the `AtomicRmwOp` variants.
+ */
+uint32_t lucet_vmctx_atomic_rmw_i32(const struct lucet_vmctx *vmctx,
+                                    uint32_t addr,
+                                    uint32_t value,
+                                    uint32_t width,
+                                    uint32_t op);
+
+/**
+ * Apply the read-modify-write operation `op` to the `width` bytes at `addr`, returning the old
+ * value.
+ *
+ * The operations are numbered 0 to 5, in the order of the `AtomicRmwOp` variants.
+ */
+uint64_t lucet_vmctx_atomic_rmw_i64(const struct lucet_vmctx *vmctx,
+                                    uint32_t addr,
+                                    uint64_t value,
+                                    uint32_t width,
+                                    uint32_t op);
+
+/**
+ * Store the low `width` bytes of `value` at `addr`.
+ */
+void lucet_vmctx_atomic_store_i32(const struct lucet_vmctx *vmctx,
+                                  uint32_t addr,
+                                  uint32_t value,
+                                  uint32_t width);
+
+/**
+ * Store the low `width` bytes of `value` at `addr`.
+ */
+void lucet_vmctx_atomic_store_i64(const struct lucet_vmctx *vmctx,
+                                  uint32_t addr,
+                                  uint64_t value,
+                                  uint32_t width);
+
+/**
+ * Wait for a notification on `addr` if the 32-bit value there equals `expected`.
+ *
+ * Returns 0 if woken, 1 if the value was not equal, and 2 if `timeout` nanoseconds passed.
+ */
+uint32_t lucet_vmctx_atomic_wait32(const struct lucet_vmctx *vmctx,
+                                   uint32_t addr,
+                                   uint32_t expected,
+                                   int64_t timeout);
+
+/**
+ * Wait for a notification on `addr` if the 64-bit value there equals `expected`.
+ *
+ * Returns 0 if woken, 1 if the value was not equal, and 2 if `timeout` nanoseconds passed.
+ */
+uint32_t lucet_vmctx_atomic_wait64(const struct lucet_vmctx *vmctx,
+                                   uint32_t addr,
+                                   uint64_t expected,
+                                   int64_t timeout);
+
+/**
+ * Check if a memory region is inside the instance heap.
+ */
+bool lucet_vmctx_check_heap(const struct lucet_vmctx *vmctx, void *ptr, size_t len);
+
+/**
+ * Get the number of WebAssembly pages currently in the heap.
+ */
+uint32_t lucet_vmctx_current_memory(const struct lucet_vmctx *vmctx);
+
+/**
+ * Discard the passive data segment `segment`.
+ */
+void lucet_vmctx_data_drop(const struct lucet_vmctx *vmctx, uint32_t segment);
+
+/**
+ * Discard the passive element segment `segment`.
+ */
+void lucet_vmctx_elem_drop(const struct lucet_vmctx *vmctx, uint32_t segment);
+
+/**
+ * Get the delegate object for the current instance.
+ *
+ * TODO: rename
+ */
+void *lucet_vmctx_get_delegate(const struct lucet_vmctx *vmctx);
+
+/**
+ * Get the address of a function by WebAssembly table and function index.
+ *
+ * Returns `NULL` if there is no such function.
+ */
+void *lucet_vmctx_get_func_from_idx(const struct lucet_vmctx *vmctx,
+                                    uint32_t table_idx,
+                                    uint32_t func_idx);
+
+/**
+ * Get a pointer to the instance globals.
+ *
+ * This is mostly for tests, as `lucetc` compiles global accesses directly.
+ */
+int64_t *lucet_vmctx_get_globals(const struct lucet_vmctx *vmctx);
+
+/**
+ * Get a pointer to the instance heap.
+ */
+char *lucet_vmctx_get_heap(const struct lucet_vmctx *vmctx);
+
+/**
+ * Grows the guest heap by the given number of WebAssembly pages.
+ *
+ * On success, returns the number of pages that existed before the call. On failure, returns `-1`.
+ */
+int32_t lucet_vmctx_grow_memory(const struct lucet_vmctx *vmctx, uint32_t additional_pages);
+
+/**
+ * Copy `len` bytes of the heap from `src` to `dst`; the ranges may overlap.
+ *
+ * Returns nonzero, having copied nothing, if either range is out of bounds.
+ */
+uint32_t lucet_vmctx_memory_copy(const struct lucet_vmctx *vmctx,
+                                 uint32_t dst,
+                                 uint32_t src,
+                                 uint32_t len);
+
+/**
+ * Set `len` bytes of the heap starting at `dst` to the low byte of `val`.
+ *
+ * Returns nonzero, having written nothing, if the range is out of bounds.
+ */
+uint32_t lucet_vmctx_memory_fill(const struct lucet_vmctx *vmctx,
+                                 uint32_t dst,
+                                 uint32_t val,
+                                 uint32_t len);
+
+/**
+ * Copy `len` bytes from `src` in the passive data segment `segment` to `dst` in the heap.
+ *
+ * Returns nonzero, having copied nothing, if either range is out of bounds.
+ */
+uint32_t lucet_vmctx_memory_init(const struct lucet_vmctx *vmctx,
+                                 uint32_t dst,
+                                 uint32_t src,
+                                 uint32_t len,
+                                 uint32_t segment);
 
-// Typedefs for use in hostcall signatures (host functions called from the
-// guest). Instances always have a 32-bit pointer size into their heap, and
-// 32-bit size_t type.
-typedef uint32_t guest_int;
-typedef uint32_t guest_ptr_t;
-typedef uint32_t guest_size_t;
+/**
+ * Get a reference to the function that `ref.func` names with the operand `func`.
+ */
+uint64_t lucet_vmctx_ref_func(const struct lucet_vmctx *vmctx, uint32_t func);
 
-struct lucet_vmctx;
+/**
+ * Check that `len` elements starting at `index` are within the table `table`.
+ *
+ * Returns nonzero if the range is out of bounds.
+ */
+uint32_t lucet_vmctx_table_check(const struct lucet_vmctx *vmctx,
+                                 uint32_t index,
+                                 uint32_t len,
+                                 uint32_t table);
 
-// Get a pointer to the instance heap.
-char *lucet_vmctx_get_heap(struct lucet_vmctx const *);
+/**
+ * Copy `len` elements from `src` in the table `src_table` to `dst` in the table `dst_table`; the
+ * ranges may overlap.
+ *
+ * Returns nonzero, having copied nothing, if either range is out of bounds.
+ */
+uint32_t lucet_vmctx_table_copy(const struct lucet_vmctx *vmctx,
+                                uint32_t dst,
+                                uint32_t src,
+                                uint32_t len,
+                                uint32_t dst_table,
+                                uint32_t src_table);
 
-// Check if a memory region is inside the instance heap.
-bool lucet_vmctx_check_heap(struct lucet_vmctx const *, void *ptr, size_t len);
+/**
+ * Set `len` elements of the table `table` starting at `dst` to the reference `val`.
+ *
+ * Returns nonzero, having written nothing, if the range is out of bounds.
+ */
+uint32_t lucet_vmctx_table_fill(const struct lucet_vmctx *vmctx,
+                                uint32_t dst,
+                                uint64_t val,
+                                uint32_t len,
+                                uint32_t table);
 
-// Get the embedding context for a given instance
-// TODO: rename
-void *lucet_vmctx_get_delegate(struct lucet_vmctx const *);
+/**
+ * Get the reference at `index` in the table `table`.
+ */
+uint64_t lucet_vmctx_table_get(const struct lucet_vmctx *vmctx, uint32_t index, uint32_t table);
 
-void lucet_vmctx_terminate(struct lucet_vmctx const *, void *info);
+/**
+ * Grow the table `table` by `delta` elements set to the reference `init`.
+ *
+ * On success, returns the number of elements that existed before the call. On failure, returns
+ * `-1`.
+ */
+uint32_t lucet_vmctx_table_grow(const struct lucet_vmctx *vmctx,
+                                uint64_t init,
+                                uint32_t delta,
+                                uint32_t table);
 
-// returns the current number of wasm pages
-uint32_t lucet_vmctx_current_memory(struct lucet_vmctx const *);
+/**
+ * Copy `len` elements from `src` in the passive element segment `segment` to `dst` in the table
+ * `table`.
+ *
+ * Returns nonzero, having copied nothing, if either range is out of bounds.
+ */
+uint32_t lucet_vmctx_table_init(const struct lucet_vmctx *vmctx,
+                                uint32_t dst,
+                                uint32_t src,
+                                uint32_t len,
+                                uint32_t segment,
+                                uint32_t table);
 
-// takes the number of wasm pages to grow by. returns the number of pages before
-// the call on success, or -1 on failure.
-int32_t lucet_vmctx_grow_memory(struct lucet_vmctx const *, uint32_t additional_pages);
+/**
+ * Store the reference `val` at `index` in the table `table`.
+ *
+ * Returns nonzero, having written nothing, if `index` is out of bounds.
+ */
+uint32_t lucet_vmctx_table_set(const struct lucet_vmctx *vmctx,
+                               uint32_t index,
+                               uint64_t val,
+                               uint32_t table);
 
-// returns the address of a function given its ID
-void *lucet_vmctx_get_func_from_idx(struct lucet_vmctx const *ctx, uint32_t table_id,
-                                    uint32_t func_id);
+/**
+ * Get the number of elements in the table `table`.
+ */
+uint32_t lucet_vmctx_table_size(const struct lucet_vmctx *vmctx, uint32_t table);
 
-// Mostly for tests - this conversion is builtin to lucetc
-int64_t *lucet_vmctx_get_globals(struct lucet_vmctx const *ctx);
+/**
+ * Terminate the instance, making `info` available to the host through the instance state.
+ */
+void lucet_vmctx_terminate(const struct lucet_vmctx *vmctx, void *info);
 
-#endif // LUCET_VMCTX_H
+#endif /* LUCET_VMCTX_H */
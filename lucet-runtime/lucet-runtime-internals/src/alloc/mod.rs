@@ -5,6 +5,10 @@ use libc::{c_void, SIGSTKSZ};
 use nix::unistd::{sysconf, SysconfVar};
 use std::sync::{Arc, Once, Weak};
 
+pub mod shared;
+
+pub use crate::alloc::shared::SharedHeap;
+
 pub const HOST_PAGE_SIZE_EXPECTED: usize = 4096;
 static mut HOST_PAGE_SIZE: usize = 0;
 static HOST_PAGE_SIZE_INIT: Once = Once::new();
@@ -102,6 +106,8 @@ pub struct Alloc {
     pub heap_inaccessible_size: usize,
     pub slot: Option<Slot>,
     pub region: Arc<dyn RegionInternal>,
+    /// The heap shared with other instances, which is mapped over the heap of the slot.
+    pub shared_heap: Option<Arc<SharedHeap>>,
 }
 
 impl Drop for Alloc {
@@ -123,6 +129,26 @@ impl Alloc {
         (addr as usize >= guard_start) && ((addr as usize) < guard_end)
     }
 
+    /// Whether an address lies in the part of the stack that `Alloc::limit_stack()` makes
+    /// inaccessible when given `usable_size`.
+    pub fn addr_in_unusable_stack(&self, addr: *const c_void, usable_size: usize) -> bool {
+        let stack = self.slot().stack as usize;
+        let unusable_size = self.slot().limits.stack_size.saturating_sub(usable_size);
+        (addr as usize >= stack) && ((addr as usize) < stack + unusable_size)
+    }
+
+    /// Leave only the top `usable_size` bytes of the stack accessible, so that a guest that uses
+    /// more faults in the same way as if it had overflowed a stack of that size.
+    ///
+    /// Passing `Limits::stack_size` makes the whole stack accessible again.
+    pub fn limit_stack(&mut self, usable_size: usize) -> Result<(), Error> {
+        let slot = self.slot();
+        if usable_size > slot.limits.stack_size || usable_size % host_page_size() != 0 {
+            lucet_bail!("usable stack size is invalid; this is a bug");
+        }
+        self.region.clone().limit_stack(slot, usable_size)
+    }
+
     pub fn expand_heap(&mut self, expand_bytes: u32, module: &dyn Module) -> Result<u32, Error> {
         let slot = self.slot();
 
@@ -131,6 +157,10 @@ impl Alloc {
             return Ok(self.heap_accessible_size as u32);
         }
 
+        if self.shared_heap.is_some() {
+            bail_limits_exceeded!("shared heaps cannot be expanded");
+        }
+
         let host_page_size = host_page_size() as u32;
 
         if self.heap_accessible_size as u32 % host_page_size != 0 {
@@ -326,7 +356,8 @@ impl Alloc {
 
 /// Runtime limits for the various memories that back a Lucet instance.
 ///
-/// Each value is specified in bytes, and must be evenly divisible by the host page size (4K).
+/// Each value other than `table_elements` is specified in bytes, and must be evenly divisible by the
+/// host page size (4K).
 #[derive(Clone, Debug)]
 #[repr(C)]
 pub struct Limits {
@@ -338,6 +369,8 @@ pub struct Limits {
     pub stack_size: usize,
     /// Size of the globals region in bytes; each global uses 8 bytes. (default 4K)
     pub globals_size: usize,
+    /// Max number of elements in each table. (default 1M)
+    pub table_elements: usize,
 }
 
 impl Default for Limits {
@@ -347,6 +380,7 @@ impl Default for Limits {
             heap_address_space_size: 0x200000000,
             stack_size: 128 * 1024,
             globals_size: 4096,
+            table_elements: 1024 * 1024,
         }
     }
 }
@@ -0,0 +1,200 @@
+//! Heaps shared between instances, for modules whose memory is declared `shared`.
+//!
+//! A shared heap is backed by a memory file that is mapped over the heap of each instance using it,
+//! so the instances see each other's writes while keeping their own stacks and globals. Instances
+//! using the same shared heap can run on different threads at the same time.
+//!
+//! Shared heaps have a fixed size: they are created at the initial heap size of the module, and
+//! growing the heap by any nonzero number of pages fails.
+//!
+//! A shared heap is initialized once: its data segments are copied in when it is created, and the
+//! `start` section of the module only runs for the first instance using it.
+
+use crate::alloc::host_page_size;
+use crate::error::Error;
+use crate::module::Module;
+use libc::c_void;
+use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use nix::unistd::{close, ftruncate};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The memory of a shared heap, and the threads waiting on addresses within it.
+pub struct SharedHeap {
+    fd: RawFd,
+    size: usize,
+    started: AtomicBool,
+    waiters: Mutex<HashMap<u32, VecDeque<Arc<Waiter>>>>,
+}
+
+struct Waiter {
+    woken: Mutex<bool>,
+    cond: Condvar,
+}
+
+impl SharedHeap {
+    /// Create a shared heap for instances of `module`, initialized from its data segments.
+    pub fn new(module: &dyn Module) -> Result<Arc<SharedHeap>, Error> {
+        let spec = module.heap_spec();
+        if !spec.shared {
+            return Err(Error::InvalidArgument("module memory is not shared"));
+        }
+        let size = spec.initial_size as usize;
+        if module.sparse_page_data_len() * host_page_size() > size {
+            return Err(lucet_incorrect_module!(
+                "sparse page data length exceeded initial heap size"
+            ));
+        }
+
+        let name = CString::new("lucet_shared_heap").expect("name has no nul bytes");
+        let heap = SharedHeap {
+            fd: memfd_create(&name, MemFdCreateFlag::MFD_CLOEXEC)?,
+            size,
+            started: AtomicBool::new(false),
+            waiters: Mutex::new(HashMap::new()),
+        };
+        ftruncate(heap.fd, size as libc::off_t)?;
+
+        if size > 0 {
+            // the file starts out zeroed, so only the pages with data need to be written
+            let mem = unsafe { heap.map(ptr::null_mut(), MapFlags::empty())? };
+            let contents = unsafe { std::slice::from_raw_parts_mut(mem as *mut u8, size) };
+            for page_num in 0..module.sparse_page_data_len() {
+                if let Some(page) = module.get_sparse_page_data(page_num) {
+                    let page_base = page_num * host_page_size();
+                    contents[page_base..page_base + host_page_size()].copy_from_slice(page);
+                }
+            }
+            unsafe { munmap(mem, size)? };
+        }
+
+        Ok(Arc::new(heap))
+    }
+
+    /// The size of the heap in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Mark the `start` section as run against this heap, returning whether it already was.
+    pub(crate) fn mark_started(&self) -> bool {
+        self.started.swap(true, Ordering::SeqCst)
+    }
+
+    /// Map the heap read/write at `addr`, or anywhere if it is null.
+    ///
+    /// With `MAP_FIXED` in `flags`, this replaces any existing mapping at `addr`.
+    pub(crate) unsafe fn map(
+        &self,
+        addr: *mut c_void,
+        flags: MapFlags,
+    ) -> Result<*mut c_void, Error> {
+        let mem = mmap(
+            addr,
+            self.size,
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            MapFlags::MAP_SHARED | flags,
+            self.fd,
+            0,
+        )?;
+        Ok(mem)
+    }
+
+    /// Block the calling thread until it is woken by [`notify()`](#method.notify) on the same
+    /// address, or until `timeout` has passed if there is one.
+    ///
+    /// `check` runs before the thread starts waiting, while no other thread can notify, and the
+    /// thread only waits if it returns `true`. This is how `memory.atomic.wait` checks the value at
+    /// `addr` without missing a notification.
+    ///
+    /// Returns 0 if the thread was woken, 1 if `check` returned `false`, and 2 if it timed out, as
+    /// `memory.atomic.wait` does.
+    pub fn wait<F: FnOnce() -> bool>(&self, addr: u32, check: F, timeout: Option<Duration>) -> u32 {
+        let waiter = {
+            let mut waiters = self.waiters.lock().unwrap();
+            if !check() {
+                return 1;
+            }
+            let waiter = Arc::new(Waiter {
+                woken: Mutex::new(false),
+                cond: Condvar::new(),
+            });
+            waiters.entry(addr).or_default().push_back(waiter.clone());
+            waiter
+        };
+
+        // a timeout too long to represent is as good as none
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        let mut woken = waiter.woken.lock().unwrap();
+        while !*woken {
+            match deadline {
+                None => woken = waiter.cond.wait(woken).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    woken = waiter.cond.wait_timeout(woken, deadline - now).unwrap().0;
+                }
+            }
+        }
+        if *woken {
+            return 0;
+        }
+        drop(woken);
+
+        // Timed out, but a notification may have taken the waiter off the queue in the meantime.
+        // If so, it counted this thread as woken, so report that.
+        let mut waiters = self.waiters.lock().unwrap();
+        let pos = waiters
+            .get(&addr)
+            .and_then(|queue| queue.iter().position(|w| Arc::ptr_eq(w, &waiter)));
+        match pos {
+            Some(pos) => {
+                let queue = waiters.get_mut(&addr).unwrap();
+                queue.remove(pos);
+                if queue.is_empty() {
+                    waiters.remove(&addr);
+                }
+                2
+            }
+            None => 0,
+        }
+    }
+
+    /// Wake up to `count` threads waiting on `addr`, in the order they started waiting.
+    ///
+    /// Returns the number of threads woken.
+    pub fn notify(&self, addr: u32, count: u32) -> u32 {
+        let mut waiters = self.waiters.lock().unwrap();
+        let mut woken = 0;
+        if let Some(queue) = waiters.get_mut(&addr) {
+            while woken < count {
+                match queue.pop_front() {
+                    Some(waiter) => {
+                        *waiter.woken.lock().unwrap() = true;
+                        waiter.cond.notify_one();
+                        woken += 1;
+                    }
+                    None => break,
+                }
+            }
+            if queue.is_empty() {
+                waiters.remove(&addr);
+            }
+        }
+        woken
+    }
+}
+
+impl Drop for SharedHeap {
+    fn drop(&mut self) {
+        close(self.fd).expect("close succeeds during drop");
+    }
+}
@@ -7,7 +7,7 @@ macro_rules! alloc_tests {
         use $crate::alloc::Limits;
         use $crate::context::{Context, ContextHandle};
         use $crate::instance::InstanceInternal;
-        use $crate::module::{HeapSpec, MockModuleBuilder};
+        use $crate::module::{HeapSpec, MockModuleBuilder, Module};
         use $crate::region::Region;
         use $crate::val::Val;
 
@@ -15,12 +15,14 @@ macro_rules! alloc_tests {
         const LIMITS_HEAP_ADDRSPACE_SIZE: usize = 8 * 1024 * 1024;
         const LIMITS_STACK_SIZE: usize = 64 * 1024;
         const LIMITS_GLOBALS_SIZE: usize = 4 * 1024;
+        const LIMITS_TABLE_ELEMENTS: usize = 1024;
 
         const LIMITS: Limits = Limits {
             heap_memory_size: LIMITS_HEAP_MEM_SIZE,
             heap_address_space_size: LIMITS_HEAP_ADDRSPACE_SIZE,
             stack_size: LIMITS_STACK_SIZE,
             globals_size: LIMITS_GLOBALS_SIZE,
+            table_elements: LIMITS_TABLE_ELEMENTS,
         };
 
         const SPEC_HEAP_RESERVED_SIZE: u64 = LIMITS_HEAP_ADDRSPACE_SIZE as u64 / 2;
@@ -35,6 +37,7 @@ macro_rules! alloc_tests {
             guard_size: SPEC_HEAP_GUARD_SIZE,
             initial_size: ONEPAGE_INITIAL_SIZE,
             max_size: Some(ONEPAGE_MAX_SIZE),
+            shared: false,
         };
 
         const THREEPAGE_INITIAL_SIZE: u64 = 64 * 1024;
@@ -45,6 +48,7 @@ macro_rules! alloc_tests {
             guard_size: 0,
             initial_size: THREEPAGE_INITIAL_SIZE,
             max_size: Some(THREEPAGE_MAX_SIZE),
+            shared: false,
         };
 
         /// This test shows an `AllocHandle` passed to `Region::allocate_runtime` will have its heap
@@ -193,6 +197,7 @@ macro_rules! alloc_tests {
             guard_size: SPEC_HEAP_GUARD_SIZE,
             initial_size: EXPANDPASTLIMIT_INITIAL_SIZE,
             max_size: Some(EXPANDPASTLIMIT_MAX_SIZE),
+            shared: false,
         };
 
         /// This test shows that a heap refuses to grow past the alloc limits, even if the runtime
@@ -240,6 +245,7 @@ macro_rules! alloc_tests {
             guard_size: SPEC_HEAP_GUARD_SIZE,
             initial_size: SPEC_HEAP_RESERVED_SIZE + (64 * 1024),
             max_size: None,
+            shared: false,
         };
 
         /// This test shows that a heap refuses to grow past the alloc limits, even if the runtime
@@ -256,6 +262,16 @@ macro_rules! alloc_tests {
             assert!(res.is_err(), "new_instance fails");
         }
 
+        /// This test shows that a module whose heap would exceed the limits can be rejected before
+        /// any region or instance is created.
+        #[test]
+        fn validate_rejects_initial_oversize_heap() {
+            let module = MockModuleBuilder::new()
+                .with_heap_spec(INITIAL_OVERSIZE_HEAP)
+                .build();
+            assert!(module.validate(&LIMITS).is_err(), "validation fails");
+        }
+
         /// This test shows that we reject limits with a larger memory size than address space size
         #[test]
         fn reject_undersized_address_space() {
@@ -264,6 +280,7 @@ macro_rules! alloc_tests {
                 heap_address_space_size: LIMITS_HEAP_ADDRSPACE_SIZE,
                 stack_size: LIMITS_STACK_SIZE,
                 globals_size: LIMITS_GLOBALS_SIZE,
+                table_elements: LIMITS_TABLE_ELEMENTS,
             };
             let res = TestRegion::create(10, &LIMITS);
             assert!(res.is_err(), "region creation fails");
@@ -274,6 +291,7 @@ macro_rules! alloc_tests {
             guard_size: SPEC_HEAP_GUARD_SIZE - 1,
             initial_size: LIMITS_HEAP_MEM_SIZE as u64,
             max_size: None,
+            shared: false,
         };
 
         /// This test shows that a heap spec with a guard size smaller than the limits is
@@ -290,11 +308,22 @@ macro_rules! alloc_tests {
                 .expect("new_instance succeeds");
         }
 
+        /// This test shows that validating a module agrees with instance creation for an
+        /// acceptable heap spec.
+        #[test]
+        fn validate_accepts_small_guard_heap() {
+            let module = MockModuleBuilder::new()
+                .with_heap_spec(SMALL_GUARD_HEAP)
+                .build();
+            module.validate(&LIMITS).expect("validation succeeds");
+        }
+
         const LARGE_GUARD_HEAP: HeapSpec = HeapSpec {
             reserved_size: SPEC_HEAP_RESERVED_SIZE,
             guard_size: SPEC_HEAP_GUARD_SIZE + 1,
             initial_size: ONEPAGE_INITIAL_SIZE,
             max_size: None,
+            shared: false,
         };
 
         /// This test shows that a `HeapSpec` with a guard size larger than the limits is not
@@ -440,6 +469,7 @@ macro_rules! alloc_tests {
             guard_size: 0,
             initial_size: ONEPAGE_INITIAL_SIZE,
             max_size: None,
+            shared: false,
         };
 
         /// This test shows the alloc works even with a zero guard size.
@@ -490,6 +520,7 @@ macro_rules! alloc_tests {
             guard_size: SPEC_HEAP_GUARD_SIZE,
             initial_size: 0,
             max_size: None,
+            shared: false,
         };
 
         /// This test shows an initially-empty heap works properly after a single expand.
@@ -503,6 +534,7 @@ macro_rules! alloc_tests {
             guard_size: 0,
             initial_size: 0,
             max_size: None,
+            shared: false,
         };
 
         /// This test shows an initially-empty, guardless heap works properly after a single
@@ -517,6 +549,7 @@ macro_rules! alloc_tests {
             heap_address_space_size: 2 * 4096,
             stack_size: 4096,
             globals_size: 4096,
+            table_elements: 1024,
         };
         const CONTEXT_TEST_INITIAL_SIZE: u64 = 4096;
         const CONTEXT_TEST_HEAP: HeapSpec = HeapSpec {
@@ -524,6 +557,7 @@ macro_rules! alloc_tests {
             guard_size: 4096,
             initial_size: CONTEXT_TEST_INITIAL_SIZE,
             max_size: Some(4096),
+            shared: false,
         };
 
         /// This test shows that alloced memory will create a heap and a stack that child context
@@ -613,6 +647,68 @@ macro_rules! alloc_tests {
                 }
             }
         }
+
+        const SHARED_HEAP: HeapSpec = HeapSpec {
+            reserved_size: SPEC_HEAP_RESERVED_SIZE,
+            guard_size: SPEC_HEAP_GUARD_SIZE,
+            initial_size: ONEPAGE_INITIAL_SIZE,
+            max_size: None,
+            shared: true,
+        };
+
+        /// This test shows that instances using the same shared heap see each other's writes, and
+        /// that the heap outlives the instances but cannot be expanded.
+        #[test]
+        fn shared_heap_works() {
+            let region = TestRegion::create(2, &LIMITS).expect("region created");
+            let module = MockModuleBuilder::new().with_heap_spec(SHARED_HEAP).build();
+            let mut first = region
+                .new_instance(module.clone())
+                .expect("new_instance succeeds");
+            let shared_heap = first.shared_heap().expect("heap is shared").clone();
+            assert_eq!(shared_heap.size(), ONEPAGE_INITIAL_SIZE as usize);
+
+            let mut second = region
+                .new_instance_builder(module.clone())
+                .with_shared_heap(shared_heap.clone())
+                .build()
+                .expect("new_instance succeeds");
+            unsafe { first.alloc_mut().heap_mut()[0] = 0xFF };
+            assert_eq!(unsafe { second.alloc().heap()[0] }, 0xFF);
+
+            // resetting an instance does not clear a shared heap
+            second.reset().expect("reset succeeds");
+            assert_eq!(unsafe { second.alloc().heap()[0] }, 0xFF);
+
+            assert!(second
+                .alloc_mut()
+                .expand_heap(64 * 1024, module.as_ref())
+                .is_err());
+
+            // the slot of a dropped instance gets a heap of its own again
+            drop(first);
+            let third = region
+                .new_instance(
+                    MockModuleBuilder::new()
+                        .with_heap_spec(ONE_PAGE_HEAP)
+                        .build(),
+                )
+                .expect("new_instance succeeds");
+            assert_eq!(unsafe { third.alloc().heap()[0] }, 0);
+            assert_eq!(unsafe { second.alloc().heap()[0] }, 0xFF);
+
+            // a shared heap is only for modules whose memory is shared
+            drop(third);
+            assert!(region
+                .new_instance_builder(
+                    MockModuleBuilder::new()
+                        .with_heap_spec(ONE_PAGE_HEAP)
+                        .build(),
+                )
+                .with_shared_heap(shared_heap)
+                .build()
+                .is_err());
+        }
     };
 }
 
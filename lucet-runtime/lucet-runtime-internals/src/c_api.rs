@@ -110,7 +110,8 @@ pub struct lucet_dl_module {
 
 /// Runtime limits for the various memories that back a Lucet instance.
 ///
-/// Each value is specified in bytes, and must be evenly divisible by the host page size (4K).
+/// Each value other than `table_elements` is specified in bytes, and must be evenly divisible by the
+/// host page size (4K).
 #[derive(Clone, Debug)]
 #[repr(C)]
 pub struct lucet_alloc_limits {
@@ -122,6 +123,8 @@ pub struct lucet_alloc_limits {
     pub stack_size: u64,
     /// Size of the globals region in bytes; each global uses 8 bytes. (default 4K)
     pub globals_size: u64,
+    /// Max number of elements in each table. (default 1M)
+    pub table_elements: u64,
 }
 
 impl From<Limits> for lucet_alloc_limits {
@@ -137,6 +140,7 @@ impl From<&Limits> for lucet_alloc_limits {
             heap_address_space_size: limits.heap_address_space_size as u64,
             stack_size: limits.stack_size as u64,
             globals_size: limits.globals_size as u64,
+            table_elements: limits.table_elements as u64,
         }
     }
 }
@@ -154,6 +158,7 @@ impl From<&lucet_alloc_limits> for Limits {
             heap_address_space_size: limits.heap_address_space_size as usize,
             stack_size: limits.stack_size as usize,
             globals_size: limits.globals_size as usize,
+            table_elements: limits.table_elements as usize,
         }
     }
 }
@@ -194,11 +199,12 @@ pub type lucet_fatal_handler = unsafe extern "C" fn(inst: *mut lucet_instance);
 
 pub mod lucet_state {
     use crate::c_api::lucet_val;
+    use crate::instance::siginfo_ext::SiginfoExt;
     use crate::instance::{State, TerminationDetails};
     use crate::module::AddrDetails;
     use crate::sysdeps::UContext;
     use crate::trapcode::{TrapCode, TrapCodeType};
-    use libc::{c_char, c_void};
+    use libc::{c_char, c_int, c_void};
     use num_derive::FromPrimitive;
     use std::ffi::CString;
     use std::ptr;
@@ -240,10 +246,12 @@ pub mod lucet_state {
                             TerminationDetails::Signal => lucet_terminated {
                                 reason: lucet_terminated_reason::Signal,
                                 provided: std::ptr::null_mut(),
+                                panic_message: std::ptr::null(),
                             },
                             TerminationDetails::GetEmbedCtx => lucet_terminated {
                                 reason: lucet_terminated_reason::GetEmbedCtx,
                                 provided: std::ptr::null_mut(),
+                                panic_message: std::ptr::null(),
                             },
                             TerminationDetails::Provided(p) => lucet_terminated {
                                 reason: lucet_terminated_reason::Provided,
@@ -251,6 +259,23 @@ pub mod lucet_state {
                                     .downcast_ref()
                                     .map(|v| *v)
                                     .unwrap_or(std::ptr::null_mut()),
+                                panic_message: std::ptr::null(),
+                            },
+                            TerminationDetails::Panic(payload) => lucet_terminated {
+                                reason: lucet_terminated_reason::Panic,
+                                provided: std::ptr::null_mut(),
+                                panic_message: payload
+                                    .downcast_ref::<&'static str>()
+                                    .map(|s| s.to_string())
+                                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                                    .and_then(|s| CString::new(s).ok())
+                                    .map(|s| s.into_raw() as *const _)
+                                    .unwrap_or(ptr::null()),
+                            },
+                            TerminationDetails::UnlinkedImport => lucet_terminated {
+                                reason: lucet_terminated_reason::UnlinkedImport,
+                                provided: std::ptr::null_mut(),
+                                panic_message: std::ptr::null(),
                             },
                         },
                     },
@@ -290,14 +315,18 @@ pub mod lucet_state {
     pub struct lucet_terminated {
         pub reason: lucet_terminated_reason,
         pub provided: *mut c_void,
+        /// The message of the panic that terminated the instance, if the payload was a string.
+        pub panic_message: *const c_char,
     }
 
     #[repr(C)]
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Debug, FromPrimitive)]
     pub enum lucet_terminated_reason {
         Signal,
         GetEmbedCtx,
         Provided,
+        Panic,
+        UnlinkedImport,
     }
 
     #[repr(C)]
@@ -311,8 +340,37 @@ pub mod lucet_state {
         pub context: UContext,
     }
 
+    /// A description of a runtime fault that does not depend on the layout of `siginfo_t` or
+    /// `ucontext_t`.
+    ///
+    /// The names in `rip_addr_details` are owned by the `lucet_state` these details were read from,
+    /// and remain valid until it is passed to `lucet_state_release()`.
     #[repr(C)]
     #[derive(Clone, Copy, Debug)]
+    pub struct lucet_fault_details {
+        pub fatal: bool,
+        pub trapcode: lucet_trapcode,
+        pub rip_addr: libc::uintptr_t,
+        pub rip_addr_details: lucet_module_addr_details,
+        pub signal_number: c_int,
+        pub fault_addr: libc::uintptr_t,
+    }
+
+    impl From<&lucet_runtime_fault> for lucet_fault_details {
+        fn from(fault: &lucet_runtime_fault) -> lucet_fault_details {
+            lucet_fault_details {
+                fatal: fault.fatal,
+                trapcode: fault.trapcode,
+                rip_addr: fault.rip_addr,
+                rip_addr_details: fault.rip_addr_details,
+                signal_number: fault.signal_info.si_signo,
+                fault_addr: fault.signal_info.si_addr() as libc::uintptr_t,
+            }
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, FromPrimitive)]
     pub enum lucet_trapcode_type {
         StackOverflow,
         HeapOutOfBounds,
@@ -356,8 +414,8 @@ pub mod lucet_state {
     #[repr(C)]
     #[derive(Clone, Copy, Debug)]
     pub struct lucet_trapcode {
-        code: lucet_trapcode_type,
-        tag: u16,
+        pub code: lucet_trapcode_type,
+        pub tag: u16,
     }
 
     impl From<TrapCode> for lucet_trapcode {
@@ -458,6 +516,7 @@ pub mod lucet_val {
         Bool,     // = (2 << 16) | 0x0700,
         F32,      // = (4 << 16) | 0x0800,
         F64,      // = (5 << 16) | 0x0801,
+        V128,     // = (6 << 16) | 0x0900,
     }
 
     #[repr(C)]
@@ -468,6 +527,7 @@ pub mod lucet_val {
         as_i64: i64,           // (3 << 16)
         as_f32: f32,           // (4 << 16)
         as_f64: f64,           // (5 << 16)
+        as_v128: [u64; 2],     // (6 << 16), low half first
     }
 
     #[repr(C)]
@@ -501,6 +561,10 @@ pub mod lucet_val {
                 lucet_val_type::Bool => Val::Bool(unsafe { val.inner_val.as_u64 } != 0),
                 lucet_val_type::F32 => Val::F32(unsafe { val.inner_val.as_f32 } as _),
                 lucet_val_type::F64 => Val::F64(unsafe { val.inner_val.as_f64 } as _),
+                lucet_val_type::V128 => {
+                    let [lo, hi] = unsafe { val.inner_val.as_v128 };
+                    Val::V128(u128::from(lo) | u128::from(hi) << 64)
+                }
             }
         }
     }
@@ -574,6 +638,17 @@ pub mod lucet_val {
                     ty: lucet_val_type::F64,
                     inner_val: lucet_val_inner_val { as_f64: *a as _ },
                 },
+                // references are passed to guest code as 64-bit values
+                Val::ExternRef(a) => lucet_val {
+                    ty: lucet_val_type::U64,
+                    inner_val: lucet_val_inner_val { as_u64: *a as _ },
+                },
+                Val::V128(a) => lucet_val {
+                    ty: lucet_val_type::V128,
+                    inner_val: lucet_val_inner_val {
+                        as_v128: [*a as u64, (*a >> 64) as u64],
+                    },
+                },
             }
         }
     }
@@ -3,7 +3,7 @@
 #[cfg(test)]
 mod tests;
 
-use crate::val::{val_to_reg, val_to_stack, RegVal, UntypedRetVal, Val};
+use crate::val::{val_to_reg, val_to_stack, RegVal, StackVal, UntypedRetVal, Val};
 use failure::Fail;
 use nix;
 use nix::sys::signal;
@@ -318,12 +318,28 @@ impl Context {
             }
         }
 
+        // The spilled arguments start 16-byte aligned, so a vector argument only needs padding
+        // when an odd number of words precede it.
+        let mut spilled_words = vec![];
+        for arg in spilled_args {
+            match val_to_stack(arg) {
+                StackVal::Word(v) => spilled_words.push(v),
+                StackVal::Vector(lo, hi) => {
+                    if spilled_words.len() % 2 != 0 {
+                        spilled_words.push(0);
+                    }
+                    spilled_words.push(lo);
+                    spilled_words.push(hi);
+                }
+            }
+        }
+
         // the top of the stack; should not be used as an index, always subtracted from
         let sp = stack.len();
 
         let stack_start = 3 // the bootstrap ret addr, then guest func ret addr, then the backstop ret addr
-        + spilled_args.len() // then any args to guest func that don't fit in registers
-        + spilled_args.len() % 2 // padding to keep the stack 16-byte aligned when we spill an odd number of spilled arguments
+        + spilled_words.len() // then any args to guest func that don't fit in registers
+        + spilled_words.len() % 2 // padding to keep the stack 16-byte aligned when we spill an odd number of words
         + 4; // then the backstop args and terminator
 
         // stack-saved arguments start 3 below the top of the stack
@@ -332,8 +348,7 @@ impl Context {
 
         // If there are more additional args to the guest function than available registers, they
         // have to be pushed on the stack underneath the return address.
-        for arg in spilled_args {
-            let v = val_to_stack(arg);
+        for v in spilled_words {
             stack[sp + stack_args_ix - stack_start] = v;
             stack_args_ix += 1;
         }
@@ -1,7 +1,7 @@
 //! A port of the tests from `lib/lucet-runtime-c/test/context_suite.c`
 
 use crate::context::{Context, ContextHandle};
-use crate::val::{Val, __m128_as_f32, __m128_as_f64};
+use crate::val::{Val, __m128_as_f32, __m128_as_f64, __m128_as_u128};
 use lazy_static::lazy_static;
 use std::cell::RefCell;
 use std::fmt::Write;
@@ -248,6 +248,97 @@ fn returning_add_f64() {
     });
 }
 
+#[test]
+fn returning_add_v128() {
+    use std::arch::x86_64::{__m128i, _mm_add_epi32};
+
+    extern "C" fn add(x: __m128i, y: __m128i) -> __m128i {
+        unsafe { _mm_add_epi32(x, y) }
+    }
+
+    let lanes = |l: [u32; 4]| {
+        u128::from(l[0]) | u128::from(l[1]) << 32 | u128::from(l[2]) << 64 | u128::from(l[3]) << 96
+    };
+
+    test_body!(stack, {
+        init_and_swap!(
+            stack,
+            add,
+            [
+                Val::V128(lanes([1, 2, 3, 4])),
+                Val::V128(lanes([10, 20, 30, 0xffff_ffff]))
+            ]
+        );
+
+        unsafe {
+            let reg = CHILD.as_ref().unwrap().get_retval_fp();
+            assert_eq!(__m128_as_u128(reg), lanes([11, 22, 33, 3]));
+        }
+    });
+}
+
+#[test]
+fn returning_spilled_v128() {
+    use std::arch::x86_64::{__m128i, _mm_add_epi32, _mm_set1_epi32};
+
+    // the seventh integer argument and the ninth vector argument both go on the stack, so the
+    // vector argument needs padding to stay 16-byte aligned
+    extern "C" fn add(
+        _a0: u64,
+        _a1: u64,
+        _a2: u64,
+        _a3: u64,
+        _a4: u64,
+        _a5: u64,
+        a6: u64,
+        _f0: f64,
+        _f1: f64,
+        _f2: f64,
+        _f3: f64,
+        _f4: f64,
+        _f5: f64,
+        _f6: f64,
+        _f7: f64,
+        v: __m128i,
+    ) -> __m128i {
+        unsafe { _mm_add_epi32(v, _mm_set1_epi32(a6 as i32)) }
+    }
+
+    let lanes = |l: [u32; 4]| {
+        u128::from(l[0]) | u128::from(l[1]) << 32 | u128::from(l[2]) << 64 | u128::from(l[3]) << 96
+    };
+
+    test_body!(stack, {
+        init_and_swap!(
+            stack,
+            add,
+            [
+                Val::U64(0),
+                Val::U64(1),
+                Val::U64(2),
+                Val::U64(3),
+                Val::U64(4),
+                Val::U64(5),
+                Val::U64(6),
+                Val::F64(0.0),
+                Val::F64(1.0),
+                Val::F64(2.0),
+                Val::F64(3.0),
+                Val::F64(4.0),
+                Val::F64(5.0),
+                Val::F64(6.0),
+                Val::F64(7.0),
+                Val::V128(lanes([1, 2, 3, 4]))
+            ]
+        );
+
+        unsafe {
+            let reg = CHILD.as_ref().unwrap().get_retval_fp();
+            assert_eq!(__m128_as_u128(reg), lanes([7, 8, 9, 10]));
+        }
+    });
+}
+
 macro_rules! child_n_args {
     ( $name: ident, $prefix:expr, { $( $arg:ident : $val:expr ),* } ) => {
         #[test]
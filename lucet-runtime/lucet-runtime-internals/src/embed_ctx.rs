@@ -5,8 +5,40 @@ use std::collections::HashMap;
 ///
 /// This is similar to the type provided by the `anymap` crate, but we can get away with simpler
 /// types on the methods due to our more specialized use case.
+///
+/// In addition to the values it owns, the map can hold borrowed values for the duration of a
+/// single run (see `CtxMap::insert_scoped()`). Borrowed values are keyed by a `CtxKey` rather than
+/// by their own type, so they need not be `'static`, and they never collide with owned values.
 pub struct CtxMap {
     map: HashMap<TypeId, Box<dyn Any>>,
+    scoped: HashMap<TypeId, *mut ()>,
+}
+
+/// A `'static` type that names the type of a borrowed context value, for every lifetime `'a` the
+/// value may borrow for.
+///
+/// Borrowed context values are lent to hostcalls with `Instance::run_with_ctx()`, and retrieved
+/// with `Vmctx::get_scoped_ctx()` and `Vmctx::get_scoped_ctx_mut()`. Because the key is `'static`
+/// even when the value is not, the value can hold borrows of its own:
+///
+/// ```
+/// use lucet_runtime_internals::embed_ctx::CtxKey;
+///
+/// struct Request<'a> {
+///     body: &'a [u8],
+///     response: &'a mut Vec<u8>,
+/// }
+///
+/// struct RequestKey;
+///
+/// impl<'a> CtxKey<'a> for RequestKey {
+///     type Ctx = Request<'a>;
+/// }
+/// ```
+///
+/// A `'static` type can serve as its own key, with `type Ctx = Self`.
+pub trait CtxKey<'a>: 'static {
+    type Ctx: 'a;
 }
 
 impl CtxMap {
@@ -42,12 +74,57 @@ impl CtxMap {
             })
     }
 
+    /// Get a reference to the borrowed value with key `K`.
+    ///
+    /// # Safety
+    ///
+    /// The lifetime `'a` is not that of the original borrow, so the caller must not let any
+    /// reference that lives for less than the original borrow become reachable from the value.
+    pub unsafe fn get_scoped<'a, K: CtxKey<'a>>(&'a self) -> Option<&'a K::Ctx> {
+        self.scoped
+            .get(&TypeId::of::<K>())
+            .map(|x| &*(*x as *const K::Ctx))
+    }
+
+    /// Get a mutable reference to the borrowed value with key `K`.
+    ///
+    /// # Safety
+    ///
+    /// As for `CtxMap::get_scoped()`.
+    pub unsafe fn get_scoped_mut<'a, K: CtxKey<'a>>(&'a mut self) -> Option<&'a mut K::Ctx> {
+        self.scoped
+            .get(&TypeId::of::<K>())
+            .map(|x| &mut *(*x as *mut K::Ctx))
+    }
+
+    /// Make a borrowed value available through the map under key `K` until the returned guard is
+    /// dropped.
+    ///
+    /// # Safety
+    ///
+    /// The map must not move, and the guard must be dropped before the borrow of `x` ends. The
+    /// guard holds neither borrow, so the caller is responsible for upholding both.
+    pub(crate) unsafe fn insert_scoped<'a, K: CtxKey<'a>>(
+        &mut self,
+        x: &mut K::Ctx,
+    ) -> ScopedCtxGuard {
+        let ty = TypeId::of::<K>();
+        let prev = self.scoped.insert(ty, x as *mut K::Ctx as *mut ());
+        ScopedCtxGuard {
+            map: self as *mut CtxMap,
+            ty,
+            prev,
+        }
+    }
+
     pub fn new() -> Self {
         CtxMap {
             map: HashMap::new(),
+            scoped: HashMap::new(),
         }
     }
 
+    /// Remove an owned value; borrowed values cannot be removed.
     pub fn remove<T: Any>(&mut self) -> Option<T> {
         self.map.remove(&TypeId::of::<T>()).map(|x| {
             *x.downcast::<T>()
@@ -55,3 +132,25 @@ impl CtxMap {
         })
     }
 }
+
+/// Removes a borrowed value from a `CtxMap` when dropped, restoring any borrowed value with the
+/// same key that it shadowed.
+pub(crate) struct ScopedCtxGuard {
+    map: *mut CtxMap,
+    ty: TypeId,
+    prev: Option<*mut ()>,
+}
+
+impl Drop for ScopedCtxGuard {
+    fn drop(&mut self) {
+        let map = unsafe { &mut *self.map };
+        match self.prev.take() {
+            Some(prev) => {
+                map.scoped.insert(self.ty, prev);
+            }
+            None => {
+                map.scoped.remove(&self.ty);
+            }
+        }
+    }
+}
@@ -0,0 +1,381 @@
+//! Checked pointers into guest memory, for use in hostcall implementations.
+//!
+//! WebAssembly guests pass pointers to hostcalls as 32-bit offsets into their linear memory. The
+//! types in this module wrap those offsets, and check them against the current length of the heap
+//! and the alignment of the pointee before handing out references into guest memory.
+//!
+//! Each accessor takes the heap as a byte slice, so they can be used with either
+//! [`Vmctx::heap()`](../vmctx/struct.Vmctx.html#method.heap) from within a hostcall, or with
+//! [`Instance::heap()`](../instance/struct.Instance.html#method.heap) from the host.
+//!
+//! Structs can be read and written through these types once they implement
+//! [`GuestType`](trait.GuestType.html), usually via `#[derive(GuestType)]` from `lucet-runtime`:
+//!
+//! ```no_run
+//! use lucet_runtime_internals::guest_types::{GuestPtr, GuestType};
+//! use lucet_runtime_internals::vmctx::{lucet_vmctx, Vmctx};
+//!
+//! #[repr(C)]
+//! #[derive(Clone, Copy)]
+//! struct Point {
+//!     x: u32,
+//!     y: u32,
+//! }
+//!
+//! // the equivalent of `#[derive(GuestType)]`
+//! unsafe impl GuestType for Point {}
+//!
+//! #[no_mangle]
+//! extern "C" fn hostcall_transpose(vmctx: *mut lucet_vmctx, point: GuestPtr<Point>) -> u32 {
+//!     let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+//!     match point.as_mut(vmctx.heap_mut()) {
+//!         Ok(p) => {
+//!             std::mem::swap(&mut p.x, &mut p.y);
+//!             0
+//!         }
+//!         Err(_) => 1,
+//!     }
+//! }
+//! ```
+
+pub use lucet_runtime_macros::GuestType;
+
+use failure::Fail;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+
+/// Errors arising from accessing guest memory through the types in this module.
+#[derive(Clone, Copy, Debug, Fail, PartialEq, Eq)]
+pub enum GuestMemoryError {
+    /// The region extends past the end of the guest heap.
+    #[fail(
+        display = "Guest memory out of bounds: offset {}, length {} bytes",
+        offset, len
+    )]
+    OutOfBounds { offset: u32, len: usize },
+
+    /// The pointer is not suitably aligned for the type it points to.
+    #[fail(
+        display = "Misaligned guest pointer: offset {}, alignment {}",
+        offset, align
+    )]
+    Misaligned { offset: u32, align: usize },
+
+    /// The size of the region in bytes overflows the host address space.
+    #[fail(display = "Guest memory region size overflows")]
+    Overflow,
+
+    /// A `GuestStr` does not contain valid UTF-8.
+    #[fail(display = "Guest string is not valid UTF-8")]
+    InvalidUtf8,
+}
+
+/// Types whose values can be read from and written to guest memory.
+///
+/// Implementations are provided for the fixed-width integer and floating point types, arrays of
+/// them, and the pointer types in this module. `#[repr(C)]` structs made of such fields can
+/// implement it with `#[derive(GuestType)]`.
+///
+/// # Safety
+///
+/// Implementors must have the same size, alignment, and layout in the host as in a wasm32 guest,
+/// and every bit pattern must be a valid value of the type. They must not contain padding, since
+/// padding bytes would expose uninitialized host memory to the guest when written.
+pub unsafe trait GuestType: Copy {}
+
+macro_rules! impl_guest_type {
+    ( $( $ty:ty ),* ) => {
+        $( unsafe impl GuestType for $ty {} )*
+    };
+}
+
+impl_guest_type!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+macro_rules! impl_guest_type_array {
+    ( $( $n:expr ),* ) => {
+        $( unsafe impl<T: GuestType> GuestType for [T; $n] {} )*
+    };
+}
+
+impl_guest_type_array!(
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
+    27, 28, 29, 30, 31, 32, 64, 128, 256
+);
+
+/// Check that `count` values of type `T` starting at `offset` lie within `heap`, and that the
+/// start is suitably aligned for `T`.
+///
+/// Returns the range of the heap in bytes.
+fn check_region<T>(
+    heap: &[u8],
+    offset: u32,
+    count: u32,
+) -> Result<std::ops::Range<usize>, GuestMemoryError> {
+    let start = offset as usize;
+    let len = size_of::<T>()
+        .checked_mul(count as usize)
+        .ok_or(GuestMemoryError::Overflow)?;
+    let end = start.checked_add(len).ok_or(GuestMemoryError::Overflow)?;
+    if end > heap.len() {
+        return Err(GuestMemoryError::OutOfBounds { offset, len });
+    }
+    // check the host address rather than the offset, so the check holds even if the heap slice
+    // itself is not page-aligned
+    if (heap.as_ptr() as usize + start) % align_of::<T>() != 0 {
+        return Err(GuestMemoryError::Misaligned {
+            offset,
+            align: align_of::<T>(),
+        });
+    }
+    Ok(start..end)
+}
+
+/// A pointer to a `T` in guest memory.
+///
+/// This has the same representation as a wasm32 pointer, so it can be used directly as a hostcall
+/// argument or as a field of a `GuestType` struct.
+#[repr(transparent)]
+pub struct GuestPtr<T> {
+    offset: u32,
+    _ty: PhantomData<T>,
+}
+
+impl<T> Clone for GuestPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GuestPtr<T> {}
+
+impl<T> PartialEq for GuestPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+
+impl<T> Eq for GuestPtr<T> {}
+
+impl<T> std::fmt::Debug for GuestPtr<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "GuestPtr({:#x})", self.offset)
+    }
+}
+
+unsafe impl<T> GuestType for GuestPtr<T> {}
+
+impl<T> GuestPtr<T> {
+    pub fn new(offset: u32) -> Self {
+        GuestPtr {
+            offset,
+            _ty: PhantomData,
+        }
+    }
+
+    /// The offset of the pointee from the start of the guest heap.
+    pub fn offset(self) -> u32 {
+        self.offset
+    }
+
+    pub fn cast<U>(self) -> GuestPtr<U> {
+        GuestPtr::new(self.offset)
+    }
+}
+
+impl<T: GuestType> GuestPtr<T> {
+    pub fn as_ref(self, heap: &[u8]) -> Result<&T, GuestMemoryError> {
+        let range = check_region::<T>(heap, self.offset, 1)?;
+        Ok(unsafe { &*(heap[range].as_ptr() as *const T) })
+    }
+
+    pub fn as_mut(self, heap: &mut [u8]) -> Result<&mut T, GuestMemoryError> {
+        let range = check_region::<T>(heap, self.offset, 1)?;
+        Ok(unsafe { &mut *(heap[range].as_mut_ptr() as *mut T) })
+    }
+
+    pub fn read(self, heap: &[u8]) -> Result<T, GuestMemoryError> {
+        self.as_ref(heap).map(|t| *t)
+    }
+
+    pub fn write(self, heap: &mut [u8], val: T) -> Result<(), GuestMemoryError> {
+        self.as_mut(heap).map(|t| *t = val)
+    }
+}
+
+/// A slice of `T`s in guest memory.
+///
+/// This has the same representation as a wasm32 pointer followed by a 32-bit length, such as the
+/// buffers in a WASI `iovec`.
+#[repr(C)]
+pub struct GuestSlice<T> {
+    ptr: GuestPtr<T>,
+    len: u32,
+}
+
+impl<T> Clone for GuestSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GuestSlice<T> {}
+
+impl<T> PartialEq for GuestSlice<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr && self.len == other.len
+    }
+}
+
+impl<T> Eq for GuestSlice<T> {}
+
+impl<T> std::fmt::Debug for GuestSlice<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "GuestSlice({:#x}, {})", self.ptr.offset, self.len)
+    }
+}
+
+unsafe impl<T> GuestType for GuestSlice<T> {}
+
+impl<T> GuestSlice<T> {
+    pub fn new(ptr: GuestPtr<T>, len: u32) -> Self {
+        GuestSlice { ptr, len }
+    }
+
+    pub fn ptr(self) -> GuestPtr<T> {
+        self.ptr
+    }
+
+    /// The number of elements in the slice.
+    pub fn len(self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T: GuestType> GuestSlice<T> {
+    pub fn as_slice(self, heap: &[u8]) -> Result<&[T], GuestMemoryError> {
+        let range = check_region::<T>(heap, self.ptr.offset, self.len)?;
+        Ok(unsafe {
+            std::slice::from_raw_parts(heap[range].as_ptr() as *const T, self.len as usize)
+        })
+    }
+
+    pub fn as_slice_mut(self, heap: &mut [u8]) -> Result<&mut [T], GuestMemoryError> {
+        let range = check_region::<T>(heap, self.ptr.offset, self.len)?;
+        Ok(unsafe {
+            std::slice::from_raw_parts_mut(heap[range].as_mut_ptr() as *mut T, self.len as usize)
+        })
+    }
+
+    pub fn to_vec(self, heap: &[u8]) -> Result<Vec<T>, GuestMemoryError> {
+        self.as_slice(heap).map(|s| s.to_vec())
+    }
+}
+
+/// A UTF-8 string in guest memory, represented as a pointer and a length in bytes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GuestStr {
+    bytes: GuestSlice<u8>,
+}
+
+unsafe impl GuestType for GuestStr {}
+
+impl GuestStr {
+    pub fn new(ptr: GuestPtr<u8>, len: u32) -> Self {
+        GuestStr {
+            bytes: GuestSlice::new(ptr, len),
+        }
+    }
+
+    /// The length of the string in bytes.
+    pub fn len(self) -> u32 {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn as_bytes(self, heap: &[u8]) -> Result<&[u8], GuestMemoryError> {
+        self.bytes.as_slice(heap)
+    }
+
+    pub fn as_str(self, heap: &[u8]) -> Result<&str, GuestMemoryError> {
+        std::str::from_utf8(self.as_bytes(heap)?).map_err(|_| GuestMemoryError::InvalidUtf8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a heap with a known alignment, so that misalignment is only ever caused by the offsets
+    #[repr(align(8))]
+    struct Heap([u8; 64]);
+
+    #[test]
+    fn guest_ptr_read_write() {
+        let mut heap = Heap([0; 64]);
+        let p = GuestPtr::<u32>::new(8);
+        p.write(&mut heap.0, 0xdeadbeef).expect("write in bounds");
+        assert_eq!(p.read(&heap.0).expect("read in bounds"), 0xdeadbeef);
+        assert_eq!(&heap.0[8..12], &0xdeadbeefu32.to_le_bytes());
+    }
+
+    #[test]
+    fn guest_ptr_bounds() {
+        let heap = Heap([0; 64]);
+        assert!(GuestPtr::<u64>::new(56).read(&heap.0).is_ok());
+        assert_eq!(
+            GuestPtr::<u64>::new(64).read(&heap.0),
+            Err(GuestMemoryError::OutOfBounds {
+                offset: 64,
+                len: 8
+            })
+        );
+        assert_eq!(
+            GuestPtr::<u64>::new(std::u32::MAX).read(&heap.0),
+            Err(GuestMemoryError::OutOfBounds {
+                offset: std::u32::MAX,
+                len: 8
+            })
+        );
+    }
+
+    #[test]
+    fn guest_ptr_alignment() {
+        let heap = Heap([0; 64]);
+        assert_eq!(
+            GuestPtr::<u32>::new(2).read(&heap.0),
+            Err(GuestMemoryError::Misaligned {
+                offset: 2,
+                align: 4
+            })
+        );
+        assert!(GuestPtr::<u8>::new(3).read(&heap.0).is_ok());
+    }
+
+    #[test]
+    fn guest_slice_and_str() {
+        let mut heap = Heap([0; 64]);
+        heap.0[16..21].copy_from_slice(b"hello");
+        let s = GuestStr::new(GuestPtr::new(16), 5);
+        assert_eq!(s.as_str(&heap.0).expect("valid string"), "hello");
+
+        heap.0[16] = 0xff;
+        assert_eq!(s.as_str(&heap.0), Err(GuestMemoryError::InvalidUtf8));
+
+        let slice = GuestSlice::<u16>::new(GuestPtr::new(60), 3);
+        assert_eq!(
+            slice.as_slice(&heap.0),
+            Err(GuestMemoryError::OutOfBounds {
+                offset: 60,
+                len: 6
+            })
+        );
+    }
+}
@@ -0,0 +1,54 @@
+/// Define one or more hostcalls, catching any panics before they unwind into guest code.
+///
+/// Hostcalls are called directly from compiled WebAssembly, so a Rust panic that escapes one would
+/// unwind across the `extern "C"` boundary and through the guest's native stack frames, which is
+/// undefined behavior. Functions defined with this macro are exported as `#[no_mangle] extern "C"`
+/// functions, and run their bodies inside
+/// [`std::panic::catch_unwind`](https://doc.rust-lang.org/std/panic/fn.catch_unwind.html). If the
+/// body panics, the instance is terminated with
+/// [`TerminationDetails::Panic`](instance/enum.TerminationDetails.html#variant.Panic) carrying the
+/// panic payload, and the call to `Instance::run()` returns `Error::RuntimeTerminated`.
+///
+/// The first argument of each hostcall must be the raw `vmctx` pointer.
+///
+/// ```no_run
+/// use lucet_runtime_internals::lucet_hostcalls;
+/// use lucet_runtime_internals::vmctx::{lucet_vmctx, Vmctx};
+///
+/// lucet_hostcalls! {
+///     pub extern "C" fn hostcall_add(vmctx: *mut lucet_vmctx, x: u32, y: u32) -> u32 {
+///         let _vmctx = unsafe { Vmctx::from_raw(vmctx) };
+///         x.checked_add(y).expect("addition does not overflow")
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! lucet_hostcalls {
+    {
+        $(
+            $(#[$attr:meta])*
+            $vis:vis extern "C" fn $name:ident(
+                $vmctx:ident : $vmctx_ty:ty
+                $(, $arg:ident : $arg_ty:ty )*
+                $(,)?
+            ) $(-> $ret_ty:ty)? $body:block
+        )*
+    } => {
+        $(
+            #[no_mangle]
+            $(#[$attr])*
+            $vis extern "C" fn $name(
+                $vmctx: $vmctx_ty,
+                $( $arg: $arg_ty ),*
+            ) $(-> $ret_ty)? {
+                let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || $body));
+                match res {
+                    Ok(res) => res,
+                    Err(payload) => unsafe {
+                        $crate::vmctx::terminate_on_panic($vmctx, payload)
+                    },
+                }
+            }
+        )*
+    }
+}
@@ -1,22 +1,29 @@
-mod siginfo_ext;
+mod atomics;
+mod bulk;
+pub(crate) mod siginfo_ext;
 pub mod signals;
+mod tables;
 
+pub use crate::instance::atomics::AtomicRmwOp;
 pub use crate::instance::signals::{signal_handler_none, SignalBehavior, SignalHandler};
 
-use crate::alloc::{Alloc, HOST_PAGE_SIZE_EXPECTED};
+use crate::alloc::{host_page_size, Alloc, HOST_PAGE_SIZE_EXPECTED};
 use crate::context::Context;
-use crate::embed_ctx::CtxMap;
+use crate::embed_ctx::{CtxKey, CtxMap};
 use crate::error::Error;
 use crate::instance::siginfo_ext::SiginfoExt;
-use crate::module::{self, Global, Module};
+use crate::instance::tables::TableDescriptor;
+use crate::module::{self, Global, Module, TableElement, ValueType};
 use crate::sysdeps::UContext;
 use crate::trapcode::{TrapCode, TrapCodeType};
 use crate::val::{UntypedRetVal, Val};
+use crate::vmctx::{lucet_vmctx, terminate_unlinked_import};
 use crate::WASM_PAGE_SIZE;
 use libc::{c_void, siginfo_t, uintptr_t, SIGBUS, SIGSEGV};
 use memoffset::offset_of;
 use std::any::Any;
 use std::cell::{RefCell, UnsafeCell};
+use std::collections::{HashMap, HashSet};
 use std::ffi::{CStr, CString};
 use std::mem;
 use std::ops::{Deref, DerefMut};
@@ -25,6 +32,31 @@ use std::sync::Arc;
 
 pub const LUCET_INSTANCE_MAGIC: u64 = 746932922;
 
+/// The number of bytes below its stack pointer that a function may use without adjusting it, as
+/// allowed by the System V x86-64 ABI.
+const RED_ZONE_SIZE: usize = 128;
+
+/// An upper bound on the stack used below `Instance::call_func_idx()` before it switches to the
+/// nested context: the frame of `Context::init()`, including the allocation it makes for spilled
+/// arguments, and the return address pushed by the call to `Context::swap()`.
+///
+/// These frame sizes depend on the compiler and the allocator, so this leaves generous headroom
+/// rather than being an exact figure.
+const NESTED_CALL_SETUP_STACK: usize = 4096;
+
+/// The number of bytes of guest stack left untouched below the local that
+/// `Instance::call_func_idx()` uses to find the stack pointer: the red zone, the two `Context`s in
+/// its frame, which may be laid out below that local, and the stack used to set up the call.
+const NESTED_CALL_STACK_MARGIN: usize =
+    RED_ZONE_SIZE + 2 * mem::size_of::<Context>() + NESTED_CALL_SETUP_STACK;
+
+/// The least guest stack, beyond what spilled arguments take, that a nested call needs to start.
+/// A nested call with less room left fails rather than faulting before the callee runs.
+const NESTED_CALL_MIN_STACK: usize = 4096;
+
+/// The pattern written over the guest stack before a run when stack usage is being measured.
+const STACK_POISON: u64 = 0xdead_beef_dead_beef;
+
 thread_local! {
     /// The host context.
     ///
@@ -52,11 +84,17 @@ thread_local! {
 ///
 /// Since this type implements `Deref` and `DerefMut` to `Instance`, it can usually be treated as
 /// though it were a `&mut Instance`.
+///
+/// An `InstanceHandle` can be sent to another thread whenever the instance is not running, for
+/// example to let a work-stealing scheduler run an instance on whichever thread is free. The signal
+/// stack and other per-thread state for running the guest is set up on entry to
+/// [`Instance::run()`](struct.Instance.html#method.run) and torn down before it returns, so nothing
+/// ties an idle instance to the thread that last ran it.
 pub struct InstanceHandle {
     inst: NonNull<Instance>,
 }
 
-// raw pointer lint
+// raw pointer lint; see the type documentation for why moving an idle instance is sound
 unsafe impl Send for InstanceHandle {}
 
 /// Create a new `InstanceHandle`.
@@ -194,6 +232,46 @@ pub struct Instance {
     /// Pointer to the function used as the entrypoint (for use in backtraces)
     entrypoint: *const extern "C" fn(),
 
+    /// The table of functions that satisfy the module's linked imports, indexed in the order of
+    /// `Module::linked_imports()`. Guest code finds this table through a pointer stored just
+    /// before the globals pointer at the end of the structure.
+    linked_funcs: Vec<LinkedFunc>,
+
+    /// Instances whose exports have been linked to this instance's imports.
+    linked_instances: Vec<NonNull<Instance>>,
+
+    /// This instance's copies of the module's tables, indexed by WebAssembly table index.
+    tables: Vec<Vec<TableElement>>,
+
+    /// A descriptor for each of `tables`, which guest code finds through a pointer stored before
+    /// the linked function table pointer at the end of the structure.
+    table_descriptors: Vec<TableDescriptor>,
+
+    /// The functions that have been handed to guest code as `funcref`s, whose handles are their
+    /// positions plus one.
+    funcrefs: Vec<TableElement>,
+
+    /// The handles of `funcrefs`, so that a function always gets the same handle.
+    funcref_handles: HashMap<TableElement, u64>,
+
+    /// The passive data segments discarded by `data.drop` since the instance was created or last
+    /// reset.
+    dropped_data: HashSet<u32>,
+
+    /// The passive element segments discarded by `elem.drop` since the instance was created or
+    /// last reset.
+    dropped_elems: HashSet<u32>,
+
+    /// Whether to measure the peak guest stack usage of each run.
+    measure_stack_usage: bool,
+
+    /// The number of bytes of guest stack available to each run, if fewer than
+    /// `Limits::stack_size`.
+    stack_size: Option<usize>,
+
+    /// Statistics gathered during the most recent run.
+    run_stats: RunStats,
+
     /// `_padding` must be the last member of the structure.
     /// This marks where the padding starts to make the structure exactly 4096 bytes long.
     /// It is also used to compute the size of the structure up to that point, i.e. without padding.
@@ -277,6 +355,108 @@ impl Instance {
         self.run_func(func, &args)
     }
 
+    /// Run a function with arguments in the guest context at the given entrypoint, and return each
+    /// of its results as the `Val` variant corresponding to its WebAssembly type.
+    ///
+    /// Unlike [`Instance::run()`](struct.Instance.html#method.run), this supports functions with
+    /// several results, which `lucetc` passes back through globals rather than registers. The types
+    /// of the results are taken from the module's description of the export, so the entrypoint
+    /// must be an exported function:
+    ///
+    /// ```no_run
+    /// # use lucet_runtime_internals::instance::InstanceHandle;
+    /// # use lucet_runtime_internals::val::Val;
+    /// # let mut instance: InstanceHandle = unimplemented!();
+    /// let results = instance.run_results(b"divmod", &[17u32.into(), 5u32.into()]).unwrap();
+    /// match results.as_slice() {
+    ///     [Val::I32(quotient), Val::I32(remainder)] => println!("{} r {}", quotient, remainder),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    ///
+    /// The same safety caveats of [`Instance::run()`](struct.Instance.html#method.run) apply.
+    pub fn run_results(&mut self, entrypoint: &[u8], args: &[Val]) -> Result<Vec<Val>, Error> {
+        let (ret_ty, result_globals) = {
+            let export = self
+                .module
+                .function_exports()
+                .iter()
+                .find(|export| export.name().as_bytes() == entrypoint)
+                .ok_or_else(|| {
+                    Error::SymbolNotFound(String::from_utf8_lossy(entrypoint).into_owned())
+                })?;
+            (export.signature().ret_ty, export.result_globals().to_vec())
+        };
+        let retval = self.run(entrypoint, args)?;
+        if result_globals.is_empty() {
+            let results = ret_ty.map(|ty| match ty {
+                ValueType::I32 => Val::I32(retval.as_i32()),
+                ValueType::I64 => Val::I64(retval.as_i64()),
+                ValueType::F32 => Val::F32(retval.as_f32()),
+                ValueType::F64 => Val::F64(retval.as_f64()),
+                ValueType::V128 => Val::V128(retval.as_v128()),
+            });
+            Ok(results.into_iter().collect())
+        } else {
+            let globals = self.globals();
+            result_globals
+                .iter()
+                .map(|(ty, idx)| {
+                    let raw = globals.get(*idx as usize).ok_or_else(|| {
+                        lucet_incorrect_module!("result global {} is out of bounds", idx)
+                    })?;
+                    global_val(*ty, *raw)
+                })
+                .collect()
+        }
+    }
+
+    /// Run a function with arguments in the guest context at the given entrypoint, with a borrowed
+    /// embedder context available to hostcalls for the duration of the call.
+    ///
+    /// During the call, `Vmctx::get_scoped_ctx::<K>()` and `Vmctx::get_scoped_ctx_mut::<K>()`
+    /// return `ctx`. Once the call returns, successfully or otherwise, `ctx` is no longer reachable
+    /// from the instance. The context is keyed by the `'static` type `K` rather than by its own
+    /// type, so unlike the values passed to
+    /// [`Instance::insert_embed_ctx()`](struct.Instance.html#method.insert_embed_ctx), it can borrow
+    /// from the caller:
+    ///
+    /// ```no_run
+    /// # use lucet_runtime_internals::embed_ctx::CtxKey;
+    /// # use lucet_runtime_internals::instance::InstanceHandle;
+    /// # let mut instance: InstanceHandle = unimplemented!();
+    /// struct Request<'a> {
+    ///     response: &'a mut Vec<u8>,
+    /// }
+    ///
+    /// struct RequestKey;
+    ///
+    /// impl<'a> CtxKey<'a> for RequestKey {
+    ///     type Ctx = Request<'a>;
+    /// }
+    ///
+    /// let mut response = vec![];
+    /// let mut request = Request { response: &mut response };
+    /// instance
+    ///     .run_with_ctx::<RequestKey>(&mut request, b"handle_request", &[])
+    ///     .unwrap();
+    /// println!("wrote {} bytes", response.len());
+    /// ```
+    ///
+    /// The same safety caveats of [`Instance::run()`](struct.Instance.html#method.run) apply.
+    pub fn run_with_ctx<'a, K: CtxKey<'a>>(
+        &mut self,
+        ctx: &mut K::Ctx,
+        entrypoint: &[u8],
+        args: &[Val],
+    ) -> Result<UntypedRetVal, Error> {
+        let func = self.module.get_export_func(entrypoint)?;
+        // safety: the instance does not move while it is borrowed here, and the guard is dropped
+        // before this function returns, even if the run unwinds, so `ctx` outlives its entry
+        let _guard = unsafe { self.embed_ctx.insert_scoped::<K>(ctx) };
+        self.run_func(func, &args)
+    }
+
     /// Run a function with arguments in the guest context from the [WebAssembly function
     /// table](https://webassembly.github.io/spec/core/syntax/modules.html#tables).
     ///
@@ -287,7 +467,7 @@ impl Instance {
         func_idx: u32,
         args: &[Val],
     ) -> Result<UntypedRetVal, Error> {
-        let func = self.module.get_func_from_idx(table_idx, func_idx)?;
+        let func = self.get_table_element(table_idx, func_idx)?.func();
         self.run_func(func, &args)
     }
 
@@ -295,6 +475,10 @@ impl Instance {
     ///
     /// The WebAssembly `start` section will also be run, if one exists.
     ///
+    /// A shared heap is left as it is, since other instances may be using it, and the `start`
+    /// section is not run again for it: like the data segments, it initializes the heap only once,
+    /// for the first instance created with the heap.
+    ///
     /// The embedder contexts present at instance creation or added with
     /// [`Instance::insert_embed_ctx()`](struct.Instance.html#method.insert_embed_ctx) are not
     /// modified by this call; it is the embedder's responsibility to clear or reset their state if
@@ -306,6 +490,9 @@ impl Instance {
     /// code is potentially unsafe; see [`Instance::run()`](struct.Instance.html#method.run).
     pub fn reset(&mut self) -> Result<(), Error> {
         self.alloc.reset_heap(self.module.as_ref())?;
+        self.reset_tables()?;
+        self.dropped_data.clear();
+        self.dropped_elems.clear();
         let globals = unsafe { self.alloc.globals_mut() };
         let mod_globals = self.module.globals();
         for (i, v) in mod_globals.iter().enumerate() {
@@ -324,7 +511,13 @@ impl Instance {
             retval: UntypedRetVal::default(),
         };
 
-        self.run_start()?;
+        let run_start = match self.alloc.shared_heap {
+            Some(ref shared_heap) => !shared_heap.mark_started(),
+            None => true,
+        };
+        if run_start {
+            self.run_start()?;
+        }
 
         Ok(())
     }
@@ -369,6 +562,100 @@ impl Instance {
         unsafe { self.alloc.globals_mut() }
     }
 
+    /// Return the value of the global exported under `name`.
+    ///
+    /// The value is returned as the `Val` variant corresponding to the global's WebAssembly type.
+    pub fn get_global(&self, name: &str) -> Result<Val, Error> {
+        let (idx, ty) = self.exported_global(name)?;
+        global_val(ty, self.globals()[idx])
+    }
+
+    /// Set the value of the global exported under `name`.
+    ///
+    /// Returns `Error::InvalidArgument` if the variant of `val` does not match the global's
+    /// WebAssembly type.
+    pub fn set_global(&mut self, name: &str, val: Val) -> Result<(), Error> {
+        let (idx, ty) = self.exported_global(name)?;
+        let raw = match (ty, val) {
+            (ValueType::I32, Val::I32(v)) => v as i64,
+            (ValueType::I64, Val::I64(v)) => v,
+            (ValueType::F32, Val::F32(v)) => v.to_bits() as i64,
+            (ValueType::F64, Val::F64(v)) => v.to_bits() as i64,
+            (ValueType::V128, _) => return Err(Error::Unsupported("v128 globals".to_owned())),
+            _ => return Err(Error::InvalidArgument("value does not match global type")),
+        };
+        self.globals_mut()[idx] = raw;
+        Ok(())
+    }
+
+    /// Find the index and type of the global exported under `name`.
+    fn exported_global(&self, name: &str) -> Result<(usize, ValueType), Error> {
+        self.module
+            .globals()
+            .iter()
+            .enumerate()
+            .find(|(_, spec)| spec.export() == Some(name))
+            .map(|(idx, spec)| (idx, spec.ty()))
+            .ok_or_else(|| Error::SymbolNotFound(name.to_owned()))
+    }
+
+    /// Return the elements of a WebAssembly table.
+    ///
+    /// These start as the elements defined by the module, and reflect any changes made by
+    /// [`Instance::set_table_element()`](struct.Instance.html#method.set_table_element),
+    /// [`Instance::grow_table()`](struct.Instance.html#method.grow_table), or guest code since the
+    /// instance was created or last reset.
+    pub fn table_elements(&self, table_idx: u32) -> Result<&[TableElement], Error> {
+        self.tables
+            .get(table_idx as usize)
+            .map(|table| table.as_slice())
+            .ok_or(Error::InvalidArgument("table index out of bounds"))
+    }
+
+    /// Return an element of a WebAssembly table.
+    pub fn get_table_element(&self, table_idx: u32, elem_idx: u32) -> Result<&TableElement, Error> {
+        self.table_elements(table_idx)?
+            .get(elem_idx as usize)
+            .ok_or(Error::FuncNotFound(table_idx, elem_idx))
+    }
+
+    /// Replace an element of a WebAssembly table.
+    ///
+    /// This lets the host install functions into the guest's indirect call tables, such as
+    /// hostcalls implementing callbacks for an imported table, or values into an `externref`
+    /// table; see [`TableElement::extern_ref()`](../module/struct.TableElement.html#method.extern_ref).
+    /// `elem_idx` must be in bounds; tables can be grown with
+    /// [`Instance::grow_table()`](struct.Instance.html#method.grow_table).
+    ///
+    /// # Safety
+    ///
+    /// Guest code will call the function in the element with the `vmctx` of this instance and
+    /// arguments matching the signature index of the element. The function must be valid to call
+    /// that way; see [`TableElement::new()`](../module/struct.TableElement.html#method.new).
+    pub unsafe fn set_table_element(
+        &mut self,
+        table_idx: u32,
+        elem_idx: u32,
+        element: TableElement,
+    ) -> Result<(), Error> {
+        let slot = self
+            .table_mut(table_idx)?
+            .get_mut(elem_idx as usize)
+            .ok_or(Error::FuncNotFound(table_idx, elem_idx))?;
+        *slot = element;
+        Ok(())
+    }
+
+    /// Find the index of the WebAssembly table exported under `name`.
+    pub fn get_export_table(&self, name: &str) -> Result<u32, Error> {
+        self.module
+            .tables()
+            .iter()
+            .position(|t| t.export() == Some(name))
+            .map(|idx| idx as u32)
+            .ok_or_else(|| Error::SymbolNotFound(name.to_owned()))
+    }
+
     /// Check whether a given range in the host address space overlaps with the memory that backs
     /// the instance heap.
     pub fn check_heap<T>(&self, ptr: *const T, len: usize) -> bool {
@@ -427,6 +714,74 @@ impl Instance {
         self.signal_handler = Box::new(handler) as Box<SignalHandler>;
     }
 
+    /// Satisfy this instance's imports from the WebAssembly module `module_name` with the exports
+    /// of another instance.
+    ///
+    /// After linking, guest calls to those imports run the exported functions of `instance`, using
+    /// its heap and globals. Each import must name a function that `instance` exports with the
+    /// signature the import expects; if any does not, no imports are linked. Calls to linked
+    /// imports that have not been satisfied terminate the instance with
+    /// [`TerminationDetails::UnlinkedImport`](enum.TerminationDetails.html#variant.UnlinkedImport).
+    ///
+    /// # Safety
+    ///
+    /// The linked instance must outlive this instance, and must not be running on another thread
+    /// while this instance runs. Linking does not run any guest code, but the same caveats as
+    /// [`Instance::run()`](struct.Instance.html#method.run) apply to the functions that become
+    /// callable through the linked imports.
+    pub unsafe fn link_instance(
+        &mut self,
+        module_name: &str,
+        instance: &mut Instance,
+    ) -> Result<(), Error> {
+        lucet_ensure!(
+            instance as *const Instance != self as *const Instance,
+            "an instance cannot be linked to itself"
+        );
+        // resolve every import before installing any of them, so that a failure leaves the table
+        // as it was
+        let mut linked_funcs = self.linked_funcs.clone();
+        let mut linked_any = false;
+        for (idx, import) in self.module.linked_imports().iter().enumerate() {
+            if import.module() != module_name {
+                continue;
+            }
+            let export = instance
+                .module
+                .function_exports()
+                .iter()
+                .find(|export| export.name() == import.field())
+                .ok_or_else(|| Error::SymbolNotFound(import.field().to_owned()))?;
+            if export.signature() != import.signature() {
+                return Err(lucet_incorrect_module!(
+                    "linked import {}::{} expects signature {:?}, but the export has signature {:?}",
+                    import.module(),
+                    import.field(),
+                    import.signature(),
+                    export.signature()
+                ));
+            }
+            let func = instance.module.get_export_func(import.field().as_bytes())?;
+            linked_funcs[idx] = LinkedFunc {
+                func,
+                vmctx: instance.alloc.slot().heap as *mut lucet_vmctx,
+            };
+            linked_any = true;
+        }
+        if !linked_any {
+            return Err(Error::InvalidArgument(
+                "module has no linked imports from the given module name",
+            ));
+        }
+        // copy rather than replace the table, since guest code holds a pointer to its storage
+        self.linked_funcs.copy_from_slice(&linked_funcs);
+        let instance_ptr = NonNull::new_unchecked(instance as *mut Instance);
+        if !self.linked_instances.contains(&instance_ptr) {
+            self.linked_instances.push(instance_ptr);
+        }
+        Ok(())
+    }
+
     /// Set the handler run for signals that do not arise from a known WebAssembly trap, or that
     /// involve memory outside of the current instance.
     ///
@@ -449,12 +804,69 @@ impl Instance {
     pub fn set_c_fatal_handler(&mut self, handler: unsafe extern "C" fn(*mut Instance)) {
         self.c_fatal_handler = Some(handler);
     }
+
+    /// Enable or disable measurement of the peak guest stack usage of each run.
+    ///
+    /// When enabled, the guest stack is filled with a poison pattern before each call to
+    /// `Instance::run()`, and scanned for the deepest overwritten word after the guest returns,
+    /// faults, or is terminated. The result is available from `Instance::run_stats()`.
+    ///
+    /// Filling and scanning touch the entire stack, so this adds a cost proportional to
+    /// `Limits::stack_size` to every run. It is intended for sizing limits rather than for use in
+    /// production.
+    pub fn set_measure_stack_usage(&mut self, measure: bool) {
+        self.measure_stack_usage = measure;
+    }
+
+    /// Set the number of bytes of guest stack available to each run, or `None` to make all of
+    /// `Limits::stack_size` available.
+    ///
+    /// A guest that uses more than this faults with `TrapCodeType::StackOverflow`, as it would on
+    /// overflowing a region's stack of that size, so this can be used to run a module with a
+    /// smaller stack than the region provides. The size must be a nonzero multiple of the host page
+    /// size, and no greater than `Limits::stack_size`.
+    ///
+    /// The rest of the stack is made inaccessible for the duration of each run, which costs two
+    /// calls to `mprotect()` per run.
+    pub fn set_stack_size(&mut self, stack_size: Option<usize>) -> Result<(), Error> {
+        if let Some(stack_size) = stack_size {
+            if stack_size == 0 || stack_size % host_page_size() != 0 {
+                return Err(Error::InvalidArgument(
+                    "stack size must be a nonzero multiple of the host page size",
+                ));
+            }
+            if stack_size > self.alloc.slot().limits.stack_size {
+                bail_limits_exceeded!(
+                    "stack size {} is larger than the region's stack size {}",
+                    stack_size,
+                    self.alloc.slot().limits.stack_size
+                );
+            }
+        }
+        self.stack_size = stack_size;
+        Ok(())
+    }
+
+    /// Statistics gathered during the most recent run of this instance.
+    pub fn run_stats(&self) -> &RunStats {
+        &self.run_stats
+    }
 }
 
 // Private API
 impl Instance {
     fn new(alloc: Alloc, module: Arc<dyn Module>, embed_ctx: CtxMap) -> Self {
         let globals_ptr = alloc.slot().globals as *mut i64;
+        let unlinked = LinkedFunc {
+            func: unlinked_import as *const extern "C" fn(),
+            vmctx: alloc.slot().heap as *mut lucet_vmctx,
+        };
+        let linked_funcs = vec![unlinked; module.linked_imports().len()];
+        // the elements are filled in by `reset()`; modules without a table section still have an
+        // empty table 0 for indirect calls
+        let table_count = module.tables().len().max(1);
+        let tables = vec![vec![]; table_count];
+        let table_descriptors = vec![TableDescriptor::new(&[]); table_count];
         let mut inst = Instance {
             magic: LUCET_INSTANCE_MAGIC,
             embed_ctx: embed_ctx,
@@ -468,16 +880,55 @@ impl Instance {
             c_fatal_handler: None,
             signal_handler: Box::new(signal_handler_none) as Box<SignalHandler>,
             entrypoint: ptr::null(),
+            linked_funcs,
+            linked_instances: vec![],
+            tables,
+            table_descriptors,
+            funcrefs: vec![],
+            funcref_handles: HashMap::new(),
+            dropped_data: HashSet::new(),
+            dropped_elems: HashSet::new(),
+            measure_stack_usage: false,
+            stack_size: None,
+            run_stats: RunStats::default(),
             _padding: (),
         };
         inst.set_globals_ptr(globals_ptr);
+        // the `Vec` is never resized, so this pointer remains valid for the life of the instance
+        let linked_funcs_ptr = inst.linked_funcs.as_ptr();
+        inst.set_linked_funcs_ptr(linked_funcs_ptr);
+        // likewise for the table descriptors, which are updated in place as the tables change
+        let table_ptr = inst.table_descriptors.as_ptr();
+        inst.set_table_ptr(table_ptr);
 
         assert_eq!(mem::size_of::<Instance>(), HOST_PAGE_SIZE_EXPECTED);
         let unpadded_size = offset_of!(Instance, _padding);
-        assert!(unpadded_size <= HOST_PAGE_SIZE_EXPECTED - mem::size_of::<*mut i64>());
+        assert!(unpadded_size <= HOST_PAGE_SIZE_EXPECTED - 3 * mem::size_of::<*mut i64>());
         inst
     }
 
+    /// Scan the guest stack for the deepest word that no longer holds `STACK_POISON`, and return
+    /// the number of bytes between it and the top of the stack.
+    fn measured_stack_usage(&mut self) -> usize {
+        let unusable_words = self.unusable_stack_size() / mem::size_of::<u64>();
+        let stack = unsafe { self.alloc.stack_u64_mut() };
+        // the stack grows down, so the deepest word is the one with the lowest index
+        let deepest = stack[unusable_words..]
+            .iter()
+            .position(|&word| word != STACK_POISON)
+            .map(|idx| unusable_words + idx)
+            .unwrap_or(stack.len());
+        (stack.len() - deepest) * mem::size_of::<u64>()
+    }
+
+    /// The number of bytes at the bottom of the guest stack that are inaccessible during runs,
+    /// because of `Instance::set_stack_size()`.
+    fn unusable_stack_size(&self) -> usize {
+        self.stack_size
+            .map(|stack_size| self.alloc.slot().limits.stack_size - stack_size)
+            .unwrap_or(0)
+    }
+
     // The globals pointer must be stored right before the end of the structure, padded to the page size,
     // so that it is 8 bytes before the heap.
     // For this reason, the alignment of the structure is set to 4096, and we define accessors that
@@ -500,6 +951,50 @@ impl Instance {
         }
     }
 
+    // Like the globals pointer, the pointer to the linked function table has a fixed offset from
+    // the heap: it is stored in bytes [4096-16..4096-8] of the structure.
+    #[inline]
+    fn set_linked_funcs_ptr(&mut self, linked_funcs_ptr: *const LinkedFunc) {
+        unsafe {
+            *((self as *mut _ as *mut u8)
+                .offset((HOST_PAGE_SIZE_EXPECTED - 2 * mem::size_of::<*mut i64>()) as isize)
+                as *mut *const LinkedFunc) = linked_funcs_ptr;
+        }
+    }
+
+    // The pointer to the instance's table descriptors is stored in bytes [4096-24..4096-16] of the
+    // structure.
+    #[inline]
+    fn set_table_ptr(&mut self, table_ptr: *const TableDescriptor) {
+        unsafe {
+            *((self as *mut _ as *mut u8)
+                .offset((HOST_PAGE_SIZE_EXPECTED - 3 * mem::size_of::<*mut i64>()) as isize)
+                as *mut *const TableDescriptor) = table_ptr;
+        }
+    }
+
+    /// Check whether `inst` is this instance, or an instance linked to it.
+    pub(crate) fn is_self_or_linked(&self, inst: *const Instance) -> bool {
+        inst == self as *const Instance
+            || self
+                .linked_instances
+                .iter()
+                .any(|linked| linked.as_ptr() as *const Instance == inst)
+    }
+
+    /// Look up an instruction pointer in the trap manifests of this instance's module, and of the
+    /// modules of any linked instances.
+    ///
+    /// This function must be signal-safe.
+    pub(crate) fn lookup_trapcode(&self, rip: *const c_void) -> Option<TrapCode> {
+        self.module.lookup_trapcode(rip).or_else(|| {
+            self.linked_instances
+                .iter()
+                .filter_map(|linked| unsafe { linked.as_ref() }.module.lookup_trapcode(rip))
+                .next()
+        })
+    }
+
     /// Run a function in guest context at the given entrypoint.
     fn run_func(
         &mut self,
@@ -520,6 +1015,14 @@ impl Instance {
         let mut args_with_vmctx = vec![Val::from(self.alloc.slot().heap)];
         args_with_vmctx.extend_from_slice(args);
 
+        self.run_stats = RunStats::default();
+        if self.measure_stack_usage {
+            let unusable_words = self.unusable_stack_size() / mem::size_of::<u64>();
+            for word in unsafe { self.alloc.stack_u64_mut() }[unusable_words..].iter_mut() {
+                *word = STACK_POISON;
+            }
+        }
+
         HOST_CTX.with(|host_ctx| {
             Context::init(
                 unsafe { self.alloc.stack_u64_mut() },
@@ -530,6 +1033,12 @@ impl Instance {
             )
         })?;
 
+        // Limit the stack only after the steps that can fail, so that an error never leaves it
+        // limited; it is restored once the guest returns to the host context.
+        if let Some(stack_size) = self.stack_size {
+            self.alloc.limit_stack(stack_size)?;
+        }
+
         self.state = State::Running;
 
         // there should never be another instance running on this thread when we enter this function
@@ -557,6 +1066,15 @@ impl Instance {
             *current_instance.borrow_mut() = None;
         });
 
+        if self.stack_size.is_some() {
+            let full_stack_size = self.alloc.slot().limits.stack_size;
+            self.alloc.limit_stack(full_stack_size)?;
+        }
+
+        if self.measure_stack_usage {
+            self.run_stats.stack_usage = Some(self.measured_stack_usage());
+        }
+
         // Sandbox has jumped back to the host process, indicating it has either:
         //
         // * trapped, or called hostcall_error: state tag changed to something other than `Running`
@@ -601,6 +1119,89 @@ impl Instance {
         }
     }
 
+    /// Run a function in guest context from within a hostcall of this instance.
+    ///
+    /// The nested call runs on the unused part of the guest stack below the hostcall's frame, and
+    /// returns to the hostcall when the function returns. If the function faults or the instance is
+    /// terminated, control instead returns to the host context of the outermost call to
+    /// `Instance::run()`, which reports the fault or termination; the hostcall does not resume.
+    pub(crate) fn run_func_nested(
+        &mut self,
+        func: *const extern "C" fn(),
+        args: &[Val],
+    ) -> Result<UntypedRetVal, Error> {
+        if !self.state.is_running() {
+            return Err(Error::InvalidArgument(
+                "nested guest calls can only be made while the instance is running",
+            ));
+        }
+        let is_current = CURRENT_INSTANCE.with(|current_instance| {
+            current_instance
+                .borrow()
+                .map(|nn| nn.as_ptr() == self as *mut Instance)
+                .unwrap_or(false)
+        });
+        if !is_current {
+            return Err(Error::InvalidArgument(
+                "nested guest calls must be made from a hostcall of the running instance",
+            ));
+        }
+        if func.is_null() {
+            return Err(Error::InvalidArgument(
+                "entrypoint function cannot be null; this is probably a malformed module",
+            ));
+        }
+
+        let mut args_with_vmctx = vec![Val::from(self.alloc.slot().heap)];
+        args_with_vmctx.extend_from_slice(args);
+
+        // The address of a local approximates the stack pointer of the hostcall; everything below
+        // it on the guest stack is unused.
+        let marker = 0u8;
+        let sp = &marker as *const u8 as usize;
+        // Only the part of the stack left accessible by `Instance::set_stack_size()` is usable.
+        let full_stack_size = self.alloc.slot().limits.stack_size;
+        let stack_size = self.stack_size.unwrap_or(full_stack_size);
+        let stack_end = self.alloc.slot().stack as usize + full_stack_size;
+        let stack_start = stack_end - stack_size;
+        if sp <= stack_start || sp > stack_end {
+            return Err(Error::InvalidArgument(
+                "nested guest calls must be made on the guest stack",
+            ));
+        }
+        // Leave room for the rest of the current frame and the calls below, and keep the end of
+        // the nested stack 16-byte aligned.
+        let nested_len = (sp - stack_start).saturating_sub(NESTED_CALL_STACK_MARGIN) / 16 * 2;
+        let min_len =
+            (NESTED_CALL_MIN_STACK + 2 * mem::size_of::<u64>() * args_with_vmctx.len()) / 8;
+        if nested_len < min_len {
+            return Err(Error::LimitsExceeded(
+                "not enough guest stack left for a nested call".to_owned(),
+            ));
+        }
+        // Safety: `[stack_start, stack_start + nested_len * 8)` lies within the usable guest stack
+        // and ends at least `NESTED_CALL_STACK_MARGIN` bytes below the current frame, so it holds
+        // no live frames of the guest or of this hostcall. Only that range is borrowed; the frames
+        // above it are never covered by a reference.
+        let nested_stack =
+            unsafe { std::slice::from_raw_parts_mut(stack_start as *mut u64, nested_len) };
+
+        let mut parent = Context::new();
+        let mut child = Context::new();
+        Context::init(
+            nested_stack,
+            &mut parent,
+            &mut child,
+            func,
+            &args_with_vmctx,
+        )?;
+        // Faults and terminations in the nested call set the instance state and switch directly
+        // to the host context, so we only get back here if the function returns normally.
+        unsafe { Context::swap(&mut parent, &child) };
+
+        Ok(child.get_untyped_retval())
+    }
+
     fn run_start(&mut self) -> Result<(), Error> {
         if let Some(start) = self.module.get_start_func()? {
             self.run_func(start, &[])?;
@@ -625,14 +1226,45 @@ impl Instance {
             // We do this after returning from the signal handler because it requires `dladdr`
             // calls, which are not signal safe
             *rip_addr_details = self.module.addr_details(rip_addr as *const c_void)?.clone();
+            if !rip_addr_details
+                .as_ref()
+                .map(|d| d.in_module_code)
+                .unwrap_or(false)
+            {
+                // the fault may have been raised by the code of a linked instance
+                for linked in self.linked_instances.iter() {
+                    let details = unsafe { linked.as_ref() }
+                        .module
+                        .addr_details(rip_addr as *const c_void)?;
+                    if details.as_ref().map(|d| d.in_module_code).unwrap_or(false) {
+                        *rip_addr_details = details;
+                        break;
+                    }
+                }
+            }
 
             // If the trap table lookup returned unknown, it is a fatal error
             let unknown_fault = trapcode.ty == TrapCodeType::Unknown;
 
+            // The part of the stack made inaccessible by `Instance::set_stack_size()` acts as a
+            // guard for the rest of it
+            let in_unusable_stack = match self.stack_size {
+                Some(stack_size) => self
+                    .alloc
+                    .addr_in_unusable_stack(siginfo.si_addr(), stack_size),
+                None => false,
+            };
+
             // If the trap was a segv or bus fault and the addressed memory was outside the
             // guard pages, it is also a fatal error
             let outside_guard = (siginfo.si_signo == SIGSEGV || siginfo.si_signo == SIGBUS)
-                && !self.alloc.addr_in_heap_guard(siginfo.si_addr());
+                && !self.alloc.addr_in_heap_guard(siginfo.si_addr())
+                && !in_unusable_stack
+                && !self.linked_instances.iter().any(|linked| {
+                    unsafe { linked.as_ref() }
+                        .alloc
+                        .addr_in_heap_guard(siginfo.si_addr())
+                });
 
             *fatal = unknown_fault || outside_guard;
         }
@@ -655,6 +1287,39 @@ pub enum State {
     },
 }
 
+/// Statistics about the most recent run of an instance.
+///
+/// These are reset at the start of every call to `Instance::run()`.
+#[derive(Clone, Debug, Default)]
+pub struct RunStats {
+    /// The peak number of bytes of guest stack used during the run, if stack usage measurement is
+    /// enabled with `Instance::set_measure_stack_usage()`.
+    ///
+    /// This is an upper bound on the guest's own usage: it includes the frames set up to enter the
+    /// guest, and any hostcalls that ran on the guest stack.
+    pub stack_usage: Option<usize>,
+}
+
+/// An entry in an instance's linked function table.
+///
+/// Calls to linked imports compiled by `lucetc` load the function pointer and `vmctx` from this
+/// table, and call the function with that `vmctx` as its first argument. The layout must match
+/// the one assumed by the code generator.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LinkedFunc {
+    func: *const extern "C" fn(),
+    vmctx: *mut lucet_vmctx,
+}
+
+/// The target of linked imports that have not been satisfied by `Instance::link_instance()`.
+///
+/// The `vmctx` in an unlinked table entry is that of the calling instance, so this can terminate
+/// it directly. Since this never returns, it is safe to call with any signature.
+extern "C" fn unlinked_import(vmctx: *mut lucet_vmctx) -> ! {
+    unsafe { terminate_unlinked_import(vmctx) }
+}
+
 /// Information about a runtime fault.
 ///
 /// Runtime faults are raised implictly by signal handlers that return `SignalBehavior::Default` in
@@ -705,9 +1370,9 @@ impl std::fmt::Display for FaultDetails {
 
 /// Information about a terminated guest.
 ///
-/// Guests are terminated either explicitly by `Vmctx::terminate()`, or implicitly by signal
-/// handlers that return `SignalBehavior::Terminate`. It usually indicates that an unrecoverable
-/// error has occurred in a hostcall, rather than in WebAssembly code.
+/// Guests are terminated either explicitly by `Vmctx::terminate()`, implicitly by signal handlers
+/// that return `SignalBehavior::Terminate`, or by a panic in a hostcall. It usually indicates that
+/// an unrecoverable error has occurred in a hostcall, rather than in WebAssembly code.
 #[derive(Clone)]
 pub enum TerminationDetails {
     Signal,
@@ -715,6 +1380,12 @@ pub enum TerminationDetails {
     /// Calls to `Vmctx::terminate()` may attach an arbitrary pointer for extra debugging
     /// information.
     Provided(Arc<dyn Any>),
+    /// A hostcall defined with [`lucet_hostcalls!`](../macro.lucet_hostcalls.html) panicked. The
+    /// panic payload is kept so that it can be inspected or rethrown by the host.
+    Panic(Arc<dyn Any + Send>),
+    /// The guest called a linked import that has not been satisfied by
+    /// [`Instance::link_instance()`](struct.Instance.html#method.link_instance).
+    UnlinkedImport,
 }
 
 impl TerminationDetails {
@@ -727,6 +1398,12 @@ impl TerminationDetails {
             _ => None,
         }
     }
+    pub fn panic_payload(&self) -> Option<&(dyn Any + Send)> {
+        match self {
+            TerminationDetails::Panic(p) => Some(p.as_ref()),
+            _ => None,
+        }
+    }
 }
 
 // Because of deref coercions, the code above was tricky to get right-
@@ -751,6 +1428,8 @@ impl std::fmt::Debug for TerminationDetails {
                 TerminationDetails::Signal => "Signal",
                 TerminationDetails::GetEmbedCtx => "GetEmbedCtx",
                 TerminationDetails::Provided(_) => "Provided(Any)",
+                TerminationDetails::Panic(_) => "Panic(Any)",
+                TerminationDetails::UnlinkedImport => "UnlinkedImport",
             }
         )
     }
@@ -838,6 +1517,19 @@ impl State {
     }
 }
 
+/// Interpret the raw bits of a global as a value of its WebAssembly type.
+///
+/// Globals are stored in 64-bit slots, so there are no `v128` globals.
+fn global_val(ty: ValueType, raw: i64) -> Result<Val, Error> {
+    match ty {
+        ValueType::I32 => Ok(Val::I32(raw as i32)),
+        ValueType::I64 => Ok(Val::I64(raw)),
+        ValueType::F32 => Ok(Val::F32(f32::from_bits(raw as u32))),
+        ValueType::F64 => Ok(Val::F64(f64::from_bits(raw as u64))),
+        ValueType::V128 => Err(Error::Unsupported("v128 globals".to_owned())),
+    }
+}
+
 fn default_fatal_handler(inst: &Instance) -> ! {
     panic!("> instance {:p} had fatal error: {}", inst, inst.state);
 }
@@ -0,0 +1,188 @@
+//! The atomic memory operations, which `lucetc` compiles as calls into the runtime.
+//!
+//! Guest code checks the bounds and alignment of each access before calling into the runtime, but
+//! they are checked again here for other callers. Accesses are 1, 2, 4 or 8 bytes wide, and are
+//! sequentially consistent. Values are passed zero-extended to 64 bits, and truncated to the width
+//! of the access.
+
+use crate::alloc::SharedHeap;
+use crate::error::Error;
+use crate::instance::Instance;
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering::SeqCst};
+use std::sync::Arc;
+use std::time::Duration;
+
+const MEMORY_OUT_OF_BOUNDS: Error = Error::InvalidArgument("memory access out of bounds");
+const UNALIGNED_ACCESS: Error = Error::InvalidArgument("unaligned atomic memory access");
+const INVALID_WIDTH: Error = Error::InvalidArgument("atomic accesses are 1, 2, 4 or 8 bytes wide");
+
+/// The read-modify-write operations, in the order `lucetc` numbers them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AtomicRmwOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Xchg,
+}
+
+impl AtomicRmwOp {
+    pub fn from_u32(op: u32) -> Option<AtomicRmwOp> {
+        use AtomicRmwOp::*;
+        [Add, Sub, And, Or, Xor, Xchg].get(op as usize).cloned()
+    }
+}
+
+/// An atomic in the heap.
+#[derive(Clone, Copy)]
+enum Access<'a> {
+    U8(&'a AtomicU8),
+    U16(&'a AtomicU16),
+    U32(&'a AtomicU32),
+    U64(&'a AtomicU64),
+}
+
+/// Evaluate `$body` for the atomic of `$access` as `$a`, with `$t` as its value type, and
+/// zero-extend the result.
+macro_rules! on_access {
+    ($access:expr, $a:ident: $t:ident => $body:expr) => {
+        match $access {
+            Access::U8($a) => {
+                type $t = u8;
+                ($body) as u64
+            }
+            Access::U16($a) => {
+                type $t = u16;
+                ($body) as u64
+            }
+            Access::U32($a) => {
+                type $t = u32;
+                ($body) as u64
+            }
+            Access::U64($a) => {
+                type $t = u64;
+                ($body) as u64
+            }
+        }
+    };
+}
+
+impl Instance {
+    /// Get the heap shared with other instances, if the module's memory is declared `shared`.
+    ///
+    /// Pass it to
+    /// [`InstanceBuilder::with_shared_heap()`](../region/struct.InstanceBuilder.html#method.with_shared_heap)
+    /// to create more instances that use the same heap.
+    pub fn shared_heap(&self) -> Option<&Arc<SharedHeap>> {
+        self.alloc.shared_heap.as_ref()
+    }
+
+    fn atomic_access(&self, addr: u32, width: u32) -> Result<Access, Error> {
+        match width {
+            1 | 2 | 4 | 8 => (),
+            _ => return Err(INVALID_WIDTH),
+        }
+        let heap = self.heap();
+        if addr as usize + width as usize > heap.len() {
+            return Err(MEMORY_OUT_OF_BOUNDS);
+        }
+        // the heap is page-aligned, so aligned addresses are aligned pointers
+        if addr % width != 0 {
+            return Err(UNALIGNED_ACCESS);
+        }
+        let ptr = heap[addr as usize..].as_ptr();
+        Ok(unsafe {
+            match width {
+                1 => Access::U8(&*(ptr as *const AtomicU8)),
+                2 => Access::U16(&*(ptr as *const AtomicU16)),
+                4 => Access::U32(&*(ptr as *const AtomicU32)),
+                _ => Access::U64(&*(ptr as *const AtomicU64)),
+            }
+        })
+    }
+
+    /// Load `width` bytes at `addr`.
+    pub fn atomic_load(&self, addr: u32, width: u32) -> Result<u64, Error> {
+        let access = self.atomic_access(addr, width)?;
+        Ok(on_access!(access, a: T => a.load(SeqCst)))
+    }
+
+    /// Store the low `width` bytes of `value` at `addr`.
+    pub fn atomic_store(&self, addr: u32, value: u64, width: u32) -> Result<(), Error> {
+        let access = self.atomic_access(addr, width)?;
+        on_access!(access, a: T => {
+            a.store(value as T, SeqCst);
+            0
+        });
+        Ok(())
+    }
+
+    /// Apply `op` with `value` to the `width` bytes at `addr`, returning the old value.
+    pub fn atomic_rmw(
+        &self,
+        addr: u32,
+        op: AtomicRmwOp,
+        value: u64,
+        width: u32,
+    ) -> Result<u64, Error> {
+        let access = self.atomic_access(addr, width)?;
+        Ok(on_access!(access, a: T => match op {
+            AtomicRmwOp::Add => a.fetch_add(value as T, SeqCst),
+            AtomicRmwOp::Sub => a.fetch_sub(value as T, SeqCst),
+            AtomicRmwOp::And => a.fetch_and(value as T, SeqCst),
+            AtomicRmwOp::Or => a.fetch_or(value as T, SeqCst),
+            AtomicRmwOp::Xor => a.fetch_xor(value as T, SeqCst),
+            AtomicRmwOp::Xchg => a.swap(value as T, SeqCst),
+        }))
+    }
+
+    /// Replace the `width` bytes at `addr` with `replacement` if they equal `expected`, returning
+    /// the old value.
+    pub fn atomic_cmpxchg(
+        &self,
+        addr: u32,
+        expected: u64,
+        replacement: u64,
+        width: u32,
+    ) -> Result<u64, Error> {
+        let access = self.atomic_access(addr, width)?;
+        Ok(on_access!(access, a: T => {
+            match a.compare_exchange(expected as T, replacement as T, SeqCst, SeqCst) {
+                Ok(old) | Err(old) => old,
+            }
+        }))
+    }
+
+    /// Wait for a notification on `addr` if the `width` bytes there equal `expected`, as
+    /// `memory.atomic.wait` does; see [`SharedHeap::wait()`](../alloc/struct.SharedHeap.html#method.wait).
+    ///
+    /// Waiting is only possible on a shared heap.
+    pub fn atomic_wait(
+        &self,
+        addr: u32,
+        expected: u64,
+        width: u32,
+        timeout: Option<Duration>,
+    ) -> Result<u32, Error> {
+        let access = self.atomic_access(addr, width)?;
+        let shared_heap = self.shared_heap().ok_or(Error::InvalidArgument(
+            "cannot wait on a heap that is not shared",
+        ))?;
+        Ok(shared_heap.wait(
+            addr,
+            || on_access!(access, a: T => a.load(SeqCst) == expected as T) != 0,
+            timeout,
+        ))
+    }
+
+    /// Wake up to `count` threads waiting on `addr`, returning the number woken.
+    pub fn atomic_notify(&self, addr: u32, count: u32) -> Result<u32, Error> {
+        self.atomic_access(addr, 4)?;
+        // nothing can be waiting on a heap that is not shared
+        Ok(self
+            .shared_heap()
+            .map(|shared_heap| shared_heap.notify(addr, count))
+            .unwrap_or(0))
+    }
+}
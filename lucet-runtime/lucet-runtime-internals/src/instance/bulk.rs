@@ -0,0 +1,140 @@
+//! The bulk memory and table operations, which `lucetc` compiles as calls into the runtime.
+//!
+//! Each operation checks all of its bounds before modifying anything, so an out-of-bounds
+//! operation has no effect. Segments that were dropped, or that are active and so were never
+//! recorded as passive, behave as if they were empty.
+
+use crate::error::Error;
+use crate::instance::Instance;
+use crate::module::TableElement;
+use std::ops::Range;
+use std::ptr;
+
+const MEMORY_OUT_OF_BOUNDS: Error = Error::InvalidArgument("memory access out of bounds");
+pub(super) const TABLE_OUT_OF_BOUNDS: Error = Error::InvalidArgument("table access out of bounds");
+
+/// The range of `len` items starting at `offset`, if it fits within `bound` items.
+pub(super) fn checked_range(offset: u32, len: u32, bound: usize) -> Option<Range<usize>> {
+    let end = offset as usize + len as usize;
+    if end > bound {
+        None
+    } else {
+        Some(offset as usize..end)
+    }
+}
+
+impl Instance {
+    /// Copy `len` bytes of the heap from `src` to `dst`, as `memory.copy` does.
+    ///
+    /// The ranges may overlap.
+    pub fn memory_copy(&mut self, dst: u32, src: u32, len: u32) -> Result<(), Error> {
+        let heap = self.heap_mut();
+        let dst = checked_range(dst, len, heap.len()).ok_or(MEMORY_OUT_OF_BOUNDS)?;
+        let src = checked_range(src, len, heap.len()).ok_or(MEMORY_OUT_OF_BOUNDS)?;
+        unsafe {
+            ptr::copy(heap[src].as_ptr(), heap[dst].as_mut_ptr(), len as usize);
+        }
+        Ok(())
+    }
+
+    /// Set `len` bytes of the heap starting at `dst` to `val`, as `memory.fill` does.
+    pub fn memory_fill(&mut self, dst: u32, val: u8, len: u32) -> Result<(), Error> {
+        let heap = self.heap_mut();
+        let dst = checked_range(dst, len, heap.len()).ok_or(MEMORY_OUT_OF_BOUNDS)?;
+        for byte in heap[dst].iter_mut() {
+            *byte = val;
+        }
+        Ok(())
+    }
+
+    /// Copy `len` bytes starting at `src` in the passive data segment `segment` to `dst` in the
+    /// heap, as `memory.init` does.
+    pub fn memory_init(&mut self, segment: u32, dst: u32, src: u32, len: u32) -> Result<(), Error> {
+        let module = self.module.clone();
+        let data: &[u8] = if self.dropped_data.contains(&segment) {
+            &[]
+        } else {
+            module
+                .passive_data()
+                .iter()
+                .find(|s| s.index() == segment)
+                .map(|s| s.data())
+                .unwrap_or(&[])
+        };
+        let src = checked_range(src, len, data.len()).ok_or(MEMORY_OUT_OF_BOUNDS)?;
+        let heap = self.heap_mut();
+        let dst = checked_range(dst, len, heap.len()).ok_or(MEMORY_OUT_OF_BOUNDS)?;
+        heap[dst].copy_from_slice(&data[src]);
+        Ok(())
+    }
+
+    /// Discard the passive data segment `segment`, as `data.drop` does.
+    ///
+    /// The segment behaves as if it were empty until the instance is reset.
+    pub fn data_drop(&mut self, segment: u32) {
+        self.dropped_data.insert(segment);
+    }
+
+    /// Copy `len` elements starting at `src` in the table `src_table` to `dst` in the table
+    /// `dst_table`, as `table.copy` does.
+    ///
+    /// The tables may be the same, and the ranges may overlap.
+    pub fn table_copy(
+        &mut self,
+        dst_table: u32,
+        dst: u32,
+        src_table: u32,
+        src: u32,
+        len: u32,
+    ) -> Result<(), Error> {
+        let src_elements = self.table_elements(src_table)?;
+        let src = checked_range(src, len, src_elements.len()).ok_or(TABLE_OUT_OF_BOUNDS)?;
+        // copy the source out first, so that overlapping ranges are not overwritten before they
+        // are read
+        let elements = src_elements[src].to_vec();
+        let table = self.table_mut(dst_table)?;
+        let dst = checked_range(dst, len, table.len()).ok_or(TABLE_OUT_OF_BOUNDS)?;
+        table[dst].copy_from_slice(&elements);
+        Ok(())
+    }
+
+    /// Copy `len` elements starting at `src` in the passive element segment `segment` to `dst` in
+    /// the table `table_idx`, as `table.init` does.
+    pub fn table_init(
+        &mut self,
+        table_idx: u32,
+        segment: u32,
+        dst: u32,
+        src: u32,
+        len: u32,
+    ) -> Result<(), Error> {
+        let module = self.module.clone();
+        let elements: &[TableElement] = if self.dropped_elems.contains(&segment) {
+            &[]
+        } else {
+            match module
+                .passive_elements()
+                .iter()
+                .find(|s| s.index() == segment)
+            {
+                Some(s) => {
+                    let start = s.start() as usize;
+                    &module.passive_table_elements()?[start..start + s.len() as usize]
+                }
+                None => &[],
+            }
+        };
+        let src = checked_range(src, len, elements.len()).ok_or(TABLE_OUT_OF_BOUNDS)?;
+        let table = self.table_mut(table_idx)?;
+        let dst = checked_range(dst, len, table.len()).ok_or(TABLE_OUT_OF_BOUNDS)?;
+        table[dst].copy_from_slice(&elements[src]);
+        Ok(())
+    }
+
+    /// Discard the passive element segment `segment`, as `elem.drop` does.
+    ///
+    /// The segment behaves as if it were empty until the instance is reset.
+    pub fn elem_drop(&mut self, segment: u32) {
+        self.dropped_elems.insert(segment);
+    }
+}
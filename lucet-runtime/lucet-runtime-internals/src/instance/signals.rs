@@ -5,17 +5,31 @@ use crate::instance::{
 use crate::sysdeps::UContextPtr;
 use crate::trapcode::{TrapCode, TrapCodeType};
 use failure::Error;
-use lazy_static::lazy_static;
 use libc::{c_int, c_void, siginfo_t};
 use nix::sys::signal::{
     pthread_sigmask, raise, sigaction, SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal,
 };
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
 
-lazy_static! {
-    // TODO: work out an alternative to this that is signal-safe for `reraise_host_signal_in_handler`
-    static ref LUCET_SIGNAL_STATE: Mutex<Option<SignalState>> = Mutex::new(None);
-}
+/// The signals that Lucet handles on behalf of running instances.
+const LUCET_SIGNALS: [Signal; 4] = [
+    Signal::SIGBUS,
+    Signal::SIGFPE,
+    Signal::SIGILL,
+    Signal::SIGSEGV,
+];
+
+static LUCET_SIGNAL_HANDLERS_INSTALLED: Once = Once::new();
+
+/// Set if the embedder has opted out of Lucet installing its signal handlers.
+static LUCET_SIGNAL_HANDLERS_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// The actions that were installed for `LUCET_SIGNALS` before Lucet's handlers, in the same order.
+///
+/// These are written exactly once, before Lucet's handlers are installed, and never modified
+/// afterwards. The signal handler can therefore read them without taking any locks.
+static mut HOST_SIGACTIONS: [Option<libc::sigaction>; 4] = [None, None, None, None];
 
 /// The value returned by
 /// [`Instance.signal_handler`](struct.Instance.html#structfield.signal_handler) to determine the
@@ -47,6 +61,8 @@ impl Instance {
     where
         F: FnOnce(&mut Instance) -> Result<R, Error>,
     {
+        ensure_signal_handlers_installed();
+
         // setup signal stack for this thread
         let guest_sigstack = SigStack::new(
             self.alloc.slot().sigstack,
@@ -56,56 +72,80 @@ impl Instance {
         let saved_sigstack =
             unsafe { sigaltstack(&guest_sigstack).expect("saving sigaltstack succeeds") };
 
-        let mut ostate = LUCET_SIGNAL_STATE.lock().unwrap();
-        if let Some(ref mut state) = *ostate {
-            state.counter += 1;
-        } else {
-            unsafe {
-                setup_guest_signal_state(&mut ostate);
-            }
-        }
-        drop(ostate);
-
         // run the body
         let res = f(self);
 
-        let mut ostate = LUCET_SIGNAL_STATE.lock().unwrap();
-        let counter_zero = if let Some(ref mut state) = *ostate {
-            state.counter -= 1;
-            if state.counter == 0 {
-                unsafe {
-                    // restore the host signal stack
-                    sigaltstack(&saved_sigstack).expect("sigaltstack restoration succeeds");
-                    restore_host_signal_state(state);
-                }
-                true
-            } else {
-                false
-            }
-        } else {
-            panic!("signal handlers weren't installed at instance exit");
-        };
-        if counter_zero {
-            *ostate = None;
+        // restore the host signal stack for this thread, so that no part of the signal state refers
+        // to this instance once it stops running; it may next be run from a different thread
+        unsafe {
+            sigaltstack(&saved_sigstack).expect("sigaltstack restoration succeeds");
         }
 
         res
     }
 }
 
-/// Signal handler installed during instance execution.
+/// Install the Lucet signal handlers, if they have not already been installed in this process and
+/// the embedder has not opted out with
+/// [`disable_signal_handler_installation()`](fn.disable_signal_handler_installation.html).
+///
+/// The handlers stay installed for the life of the process, and pass any signal that does not
+/// arise from a running instance on to the handler that was installed before them. After the first
+/// call, this is a single atomic load, so it is cheap enough to call on every run.
+pub(crate) fn ensure_signal_handlers_installed() {
+    LUCET_SIGNAL_HANDLERS_INSTALLED.call_once(|| unsafe { install_signal_handlers() });
+}
+
+/// Prevent Lucet from installing its own handlers for `SIGBUS`, `SIGFPE`, `SIGILL`, and `SIGSEGV`.
+///
+/// This is for embedders that manage those signal handlers themselves. Their handlers must call
+/// [`handle_signal()`](fn.handle_signal.html) before doing anything else, and must be installed
+/// with `SA_SIGINFO` and `SA_ONSTACK` so that they run on the signal stack Lucet sets up for each
+/// running instance.
+///
+/// This must be called before any instance runs. Returns `Error::InvalidArgument` if Lucet's
+/// handlers have already been installed.
+pub fn disable_signal_handler_installation() -> Result<(), crate::error::Error> {
+    LUCET_SIGNAL_HANDLERS_INSTALLED.call_once(|| {
+        LUCET_SIGNAL_HANDLERS_DISABLED.store(true, Ordering::SeqCst);
+    });
+    if LUCET_SIGNAL_HANDLERS_DISABLED.load(Ordering::SeqCst) {
+        Ok(())
+    } else {
+        Err(crate::error::Error::InvalidArgument(
+            "Lucet signal handlers are already installed",
+        ))
+    }
+}
+
+/// Handle a signal on behalf of the instance running on this thread, if there is one.
+///
+/// Lucet's own signal handler is built on this function. Embedders that have called
+/// [`disable_signal_handler_installation()`](fn.disable_signal_handler_installation.html) must call
+/// it first from their handlers for `SIGBUS`, `SIGFPE`, `SIGILL`, and `SIGSEGV`, passing along the
+/// arguments of an `SA_SIGINFO` handler.
+///
+/// Returns `false` if the signal did not arise from a running Lucet instance, in which case the
+/// embedder should handle it as it would have otherwise. Returns `true` if the signal was handled
+/// and the interrupted code can continue. If the signal ends the instance's run, this function does
+/// not return at all, but switches back to the host context that started the run.
 ///
 /// This function is only designed to handle signals that are the direct result of execution of a
 /// hardware instruction from the faulting WASM thread. It thus safely assumes the signal is
 /// directed specifically at this thread (i.e. not a different thread or the process as a whole).
-extern "C" fn handle_signal(signum: c_int, siginfo_ptr: *mut siginfo_t, ucontext_ptr: *mut c_void) {
-    let signal = Signal::from_c_int(signum).expect("signum is a valid signal");
-    if !(signal == Signal::SIGBUS
-        || signal == Signal::SIGSEGV
-        || signal == Signal::SIGILL
-        || signal == Signal::SIGFPE)
-    {
-        panic!("unexpected signal in guest signal handler: {:?}", signal);
+///
+/// # Safety
+///
+/// This function must only be called from a signal handler, with the arguments the handler was
+/// invoked with.
+pub unsafe fn handle_signal(
+    signum: c_int,
+    siginfo_ptr: *mut siginfo_t,
+    ucontext_ptr: *mut c_void,
+) -> bool {
+    match Signal::from_c_int(signum) {
+        Ok(signal) if LUCET_SIGNALS.contains(&signal) => (),
+        _ => return false,
     }
     assert!(!siginfo_ptr.is_null(), "siginfo must not be null");
 
@@ -120,27 +160,19 @@ extern "C" fn handle_signal(signum: c_int, siginfo_ptr: *mut siginfo_t, ucontext
 
         if current_instance.is_none() {
             // If there is no current instance, we've caught a signal raised by a thread that's not
-            // running a lucet instance. Restore the host signal handler and reraise the signal,
-            // then return if the host handler returns
-            unsafe {
-                reraise_host_signal_in_handler(signal, signum, siginfo_ptr, ucontext_ptr);
-            }
-            // don't try context-switching
-            return false;
+            // running a lucet instance
+            return None;
         }
 
         // Safety: the memory pointed to by CURRENT_INSTANCE should be a valid instance. This is not
         // a trivial property, but relies on the compiler not emitting guest programs that can
         // overwrite the instance.
-        let inst = unsafe {
-            current_instance
-                .as_mut()
-                .expect("current instance exists")
-                .as_mut()
-        };
+        let inst = current_instance
+            .as_mut()
+            .expect("current instance exists")
+            .as_mut();
 
         let trapcode = inst
-            .module
             .lookup_trapcode(rip)
             // if we couldn't find a code in the manifest, return an unknown trapcode that will be
             // converted to a fatal trap when we switch back to the host
@@ -153,14 +185,14 @@ extern "C" fn handle_signal(signum: c_int, siginfo_ptr: *mut siginfo_t, ucontext
         match behavior {
             SignalBehavior::Continue => {
                 // return to the guest context without making any modifications to the instance
-                false
+                Some(false)
             }
             SignalBehavior::Terminate => {
                 // set the state before jumping back to the host context
                 inst.state = State::Terminated {
                     details: TerminationDetails::Signal,
                 };
-                true
+                Some(true)
             }
             SignalBehavior::Default => {
                 // record the fault and jump back to the host context
@@ -176,132 +208,129 @@ extern "C" fn handle_signal(signum: c_int, siginfo_ptr: *mut siginfo_t, ucontext
                     },
                     // safety: pointer is checked for null at the top of the function, and the
                     // manpage guarantees that a siginfo_t will be passed as the second argument
-                    siginfo: unsafe { *siginfo_ptr },
+                    siginfo: *siginfo_ptr,
                     context: ctx.into(),
                 };
-                true
+                Some(true)
             }
         }
     });
 
-    if switch_to_host {
-        HOST_CTX.with(|host_ctx| unsafe {
-            Context::set_from_signal(&*host_ctx.get())
-                .expect("can successfully switch back to the host context");
-        });
-        unreachable!()
+    match switch_to_host {
+        None => false,
+        Some(false) => true,
+        Some(true) => {
+            HOST_CTX.with(|host_ctx| {
+                Context::set_from_signal(&*host_ctx.get())
+                    .expect("can successfully switch back to the host context");
+            });
+            unreachable!()
+        }
     }
 }
 
-struct SignalState {
-    counter: usize,
-    saved_sigbus: SigAction,
-    saved_sigfpe: SigAction,
-    saved_sigill: SigAction,
-    saved_sigsegv: SigAction,
+/// Signal handler installed by Lucet, unless the embedder has opted out with
+/// [`disable_signal_handler_installation()`](fn.disable_signal_handler_installation.html).
+extern "C" fn lucet_signal_handler(
+    signum: c_int,
+    siginfo_ptr: *mut siginfo_t,
+    ucontext_ptr: *mut c_void,
+) {
+    let signal = Signal::from_c_int(signum).expect("signum is a valid signal");
+    if !LUCET_SIGNALS.contains(&signal) {
+        panic!("unexpected signal in guest signal handler: {:?}", signal);
+    }
+    unsafe {
+        if !handle_signal(signum, siginfo_ptr, ucontext_ptr) {
+            // Pass the signal on to the host signal handler, then return if the host handler
+            // returns
+            chain_to_host_handler(signal, signum, siginfo_ptr, ucontext_ptr);
+        }
+    }
 }
 
-// raw pointers in the saved types
-unsafe impl Send for SignalState {}
-
-unsafe fn setup_guest_signal_state(ostate: &mut Option<SignalState>) {
+unsafe fn install_signal_handlers() {
     let mut masked_signals = SigSet::empty();
-    masked_signals.add(Signal::SIGBUS);
-    masked_signals.add(Signal::SIGFPE);
-    masked_signals.add(Signal::SIGILL);
-    masked_signals.add(Signal::SIGSEGV);
+    for sig in LUCET_SIGNALS.iter() {
+        masked_signals.add(*sig);
+    }
 
-    // setup signal handlers
     let sa = SigAction::new(
-        SigHandler::SigAction(handle_signal),
+        SigHandler::SigAction(lucet_signal_handler),
         SaFlags::SA_RESTART | SaFlags::SA_SIGINFO | SaFlags::SA_ONSTACK,
         masked_signals,
     );
-    let saved_sigbus = sigaction(Signal::SIGBUS, &sa).expect("sigaction succeeds");
-    let saved_sigfpe = sigaction(Signal::SIGFPE, &sa).expect("sigaction succeeds");
-    let saved_sigill = sigaction(Signal::SIGILL, &sa).expect("sigaction succeeds");
-    let saved_sigsegv = sigaction(Signal::SIGSEGV, &sa).expect("sigaction succeeds");
-
-    *ostate = Some(SignalState {
-        counter: 1,
-        saved_sigbus,
-        saved_sigfpe,
-        saved_sigill,
-        saved_sigsegv,
-    });
-}
 
-unsafe fn restore_host_signal_state(state: &mut SignalState) {
-    // restore signal handlers
-    sigaction(Signal::SIGBUS, &state.saved_sigbus).expect("sigaction succeeds");
-    sigaction(Signal::SIGFPE, &state.saved_sigfpe).expect("sigaction succeeds");
-    sigaction(Signal::SIGILL, &state.saved_sigill).expect("sigaction succeeds");
-    sigaction(Signal::SIGSEGV, &state.saved_sigsegv).expect("sigaction succeeds");
+    for (i, sig) in LUCET_SIGNALS.iter().enumerate() {
+        // save the host action before installing ours, so that it is always available once our
+        // handler can run
+        let mut host_sa: libc::sigaction = std::mem::zeroed();
+        nix::errno::Errno::result(libc::sigaction(
+            *sig as c_int,
+            std::ptr::null(),
+            &mut host_sa,
+        ))
+        .expect("sigaction succeeds");
+        HOST_SIGACTIONS[i] = Some(host_sa);
+
+        sigaction(*sig, &sa).expect("sigaction succeeds");
+    }
 }
 
-unsafe fn reraise_host_signal_in_handler(
+/// Pass a signal that did not arise from a running instance to the handler that was installed
+/// before Lucet's.
+///
+/// This function must be signal-safe: it only reads `HOST_SIGACTIONS`, which are never modified
+/// once Lucet's handlers are installed.
+unsafe fn chain_to_host_handler(
     sig: Signal,
     signum: libc::c_int,
     siginfo_ptr: *mut libc::siginfo_t,
     ucontext_ptr: *mut c_void,
 ) {
-    let saved_handler = {
-        // TODO: avoid taking a mutex here, probably by having some static muts just for this
-        // function
-        if let Some(ref state) = *LUCET_SIGNAL_STATE.lock().unwrap() {
-            match sig {
-                Signal::SIGBUS => state.saved_sigbus.clone(),
-                Signal::SIGFPE => state.saved_sigfpe.clone(),
-                Signal::SIGILL => state.saved_sigill.clone(),
-                Signal::SIGSEGV => state.saved_sigsegv.clone(),
-                sig => panic!(
-                    "unexpected signal in reraise_host_signal_in_handler: {:?}",
-                    sig
-                ),
-            }
-        } else {
-            // this case is very fishy; it can arise when the last lucet instance spins down and
-            // uninstalls the lucet handlers while a signal handler is running on this thread, but
-            // before taking the mutex above. The theory is that if this has happened, the host
-            // handler has been reinstalled, so we shouldn't end up back here if we reraise
-
-            // unmask the signal to reraise; we don't have to restore it because the handler will return
-            // after this. If it signals again between here and now, that's a double fault and the
-            // process is going to die anyway
-            let mut unmask = SigSet::empty();
-            unmask.add(sig);
-            pthread_sigmask(SigmaskHow::SIG_UNBLOCK, Some(&unmask), None)
-                .expect("pthread_sigmask succeeds");
-            // if there's no current signal state, just re-raise and hope for the best
-            raise(sig).expect("raise succeeds");
-            return;
-        }
-    };
-
-    match saved_handler.handler() {
-        SigHandler::SigDfl => {
+    let host_sa = LUCET_SIGNALS
+        .iter()
+        .position(|s| *s == sig)
+        .and_then(|i| HOST_SIGACTIONS[i])
+        .expect("host sigaction is saved for every handled signal");
+
+    match host_sa.sa_sigaction {
+        libc::SIG_DFL => {
             // reinstall default signal handler and reraise the signal; this should terminate the
             // program
-            sigaction(sig, &saved_handler).expect("sigaction succeeds");
+            sigaction(
+                sig,
+                &SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty()),
+            )
+            .expect("sigaction succeeds");
             let mut unmask = SigSet::empty();
             unmask.add(sig);
             pthread_sigmask(SigmaskHow::SIG_UNBLOCK, Some(&unmask), None)
                 .expect("pthread_sigmask succeeds");
             raise(sig).expect("raise succeeds");
         }
-        SigHandler::SigIgn => {
+        libc::SIG_IGN => {
             // don't do anything; if we hit this case, whatever program is hosting us is almost
             // certainly doing something wrong, because our set of signals requires intervention to
             // proceed
-            return;
-        }
-        SigHandler::Handler(f) => {
-            // call the saved handler directly so there is no altstack confusion
-            f(signum)
         }
-        SigHandler::SigAction(f) => {
+        handler => {
+            // block the signals the host handler asked to have blocked while it runs, as the kernel
+            // would have if it had invoked the handler directly
+            let mut saved_mask: libc::sigset_t = std::mem::zeroed();
+            libc::pthread_sigmask(libc::SIG_BLOCK, &host_sa.sa_mask, &mut saved_mask);
+
             // call the saved handler directly so there is no altstack confusion
-            f(signum, siginfo_ptr, ucontext_ptr)
+            if host_sa.sa_flags & libc::SA_SIGINFO != 0 {
+                let f: extern "C" fn(c_int, *mut siginfo_t, *mut c_void) =
+                    std::mem::transmute(handler);
+                f(signum, siginfo_ptr, ucontext_ptr);
+            } else {
+                let f: extern "C" fn(c_int) = std::mem::transmute(handler);
+                f(signum);
+            }
+
+            libc::pthread_sigmask(libc::SIG_SETMASK, &saved_mask, std::ptr::null_mut());
         }
     }
 }
@@ -0,0 +1,213 @@
+//! The WebAssembly tables of an instance, and the reference types operations that `lucetc`
+//! compiles as calls into the runtime.
+//!
+//! Each instance has its own copy of every table of its module. Guest code finds the tables through
+//! an array with a descriptor for each one, holding a pointer to its elements and their number.
+//! Growing a table can move its elements, so the descriptor is updated whenever a table changes
+//! size.
+//!
+//! Guest code represents references as 64-bit values, where zero is the null reference. The value
+//! of an `externref` is the one the host provided. A `funcref` is a handle that the instance assigns
+//! to a function the first time guest code reads it out of a table or names it with `ref.func`.
+
+use crate::error::Error;
+use crate::instance::bulk::{checked_range, TABLE_OUT_OF_BOUNDS};
+use crate::instance::Instance;
+use crate::module::{RefType, TableElement};
+
+const TABLE_INDEX_OUT_OF_BOUNDS: Error = Error::InvalidArgument("table index out of bounds");
+
+/// Where guest code finds one of the tables of an instance.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TableDescriptor {
+    elements: *const TableElement,
+    len: u64,
+}
+
+impl TableDescriptor {
+    pub(crate) fn new(elements: &[TableElement]) -> TableDescriptor {
+        TableDescriptor {
+            elements: elements.as_ptr(),
+            len: elements.len() as u64,
+        }
+    }
+}
+
+impl Instance {
+    /// Return the number of elements in a WebAssembly table, as `table.size` does.
+    pub fn table_size(&self, table_idx: u32) -> Result<u32, Error> {
+        Ok(self.table_elements(table_idx)?.len() as u32)
+    }
+
+    /// Grow a WebAssembly table by `delta` elements, each set to `init`, and return its previous
+    /// size.
+    ///
+    /// The table cannot grow beyond the maximum size declared by the module, if any, nor beyond the
+    /// `table_elements` limit of the instance. Like the elements the module defines, the new
+    /// elements are discarded when the instance is reset.
+    pub fn grow_table(
+        &mut self,
+        table_idx: u32,
+        delta: u32,
+        init: TableElement,
+    ) -> Result<u32, Error> {
+        let max_size = self
+            .module
+            .tables()
+            .get(table_idx as usize)
+            .and_then(|spec| spec.max_size())
+            .unwrap_or(std::u32::MAX);
+        let table = self
+            .tables
+            .get_mut(table_idx as usize)
+            .ok_or(TABLE_INDEX_OUT_OF_BOUNDS)?;
+        let old_size = table.len();
+        let new_size = old_size as u64 + delta as u64;
+        if new_size > max_size as u64 {
+            return Err(Error::InvalidArgument(
+                "table cannot grow beyond its maximum size",
+            ));
+        }
+        if new_size > self.alloc.slot().limits.table_elements as u64 {
+            bail_limits_exceeded!("grown table would exceed the table element limit");
+        }
+        table.resize(new_size as usize, init);
+        self.sync_table_descriptor(table_idx as usize);
+        Ok(old_size as u32)
+    }
+
+    /// Grow a table by `delta` elements set to the reference `init`, as `table.grow` does.
+    pub fn table_grow(&mut self, table_idx: u32, init: u64, delta: u32) -> Result<u32, Error> {
+        let init = self.ref_to_element(table_idx, init)?;
+        self.grow_table(table_idx, delta, init)
+    }
+
+    /// Check that `len` elements starting at `index` are within a table.
+    ///
+    /// Guest code performs this check before `table.get`, so that an out-of-bounds access traps
+    /// before a reference is handed out.
+    pub fn table_check(&self, table_idx: u32, index: u32, len: u32) -> Result<(), Error> {
+        let table = self.table_elements(table_idx)?;
+        checked_range(index, len, table.len()).ok_or(TABLE_OUT_OF_BOUNDS)?;
+        Ok(())
+    }
+
+    /// Return the reference at `index` in a table, as `table.get` does.
+    pub fn table_get(&mut self, table_idx: u32, index: u32) -> Result<u64, Error> {
+        let element = *self
+            .table_elements(table_idx)?
+            .get(index as usize)
+            .ok_or(TABLE_OUT_OF_BOUNDS)?;
+        Ok(self.element_to_ref(table_idx, element))
+    }
+
+    /// Store the reference `value` at `index` in a table, as `table.set` does.
+    pub fn table_set(&mut self, table_idx: u32, index: u32, value: u64) -> Result<(), Error> {
+        let element = self.ref_to_element(table_idx, value)?;
+        let table = self.table_mut(table_idx)?;
+        let slot = table.get_mut(index as usize).ok_or(TABLE_OUT_OF_BOUNDS)?;
+        *slot = element;
+        Ok(())
+    }
+
+    /// Set `len` elements of a table starting at `dst` to the reference `value`, as `table.fill`
+    /// does.
+    pub fn table_fill(
+        &mut self,
+        table_idx: u32,
+        dst: u32,
+        value: u64,
+        len: u32,
+    ) -> Result<(), Error> {
+        let element = self.ref_to_element(table_idx, value)?;
+        let table = self.table_mut(table_idx)?;
+        let dst = checked_range(dst, len, table.len()).ok_or(TABLE_OUT_OF_BOUNDS)?;
+        for slot in table[dst].iter_mut() {
+            *slot = element;
+        }
+        Ok(())
+    }
+
+    /// Return a reference to the function that `ref.func` names with the operand `func`.
+    pub fn ref_func(&mut self, func: u32) -> Result<u64, Error> {
+        let module = self.module.clone();
+        let element = *module
+            .ref_func_elements()?
+            .get(func as usize)
+            .ok_or(Error::InvalidArgument("ref.func operand out of bounds"))?;
+        Ok(self.funcref(element))
+    }
+
+    /// Return the elements of a table for modification.
+    pub(crate) fn table_mut(&mut self, table_idx: u32) -> Result<&mut Vec<TableElement>, Error> {
+        self.tables
+            .get_mut(table_idx as usize)
+            .ok_or(TABLE_INDEX_OUT_OF_BOUNDS)
+    }
+
+    /// Replace the elements of every table with the ones defined by the module, and forget the
+    /// `funcref` handles handed out so far.
+    pub(crate) fn reset_tables(&mut self) -> Result<(), Error> {
+        let module = self.module.clone();
+        for (idx, table) in self.tables.iter_mut().enumerate() {
+            table.clear();
+            table.extend_from_slice(module.table_elements(idx as u32)?);
+        }
+        for idx in 0..self.tables.len() {
+            self.sync_table_descriptor(idx);
+        }
+        self.funcrefs.clear();
+        self.funcref_handles.clear();
+        Ok(())
+    }
+
+    /// Point the descriptor of a table at its current elements.
+    fn sync_table_descriptor(&mut self, table_idx: usize) {
+        self.table_descriptors[table_idx] = TableDescriptor::new(&self.tables[table_idx]);
+    }
+
+    fn elem_type(&self, table_idx: u32) -> RefType {
+        // modules without a table section still have an empty table for indirect calls
+        self.module
+            .tables()
+            .get(table_idx as usize)
+            .map(|spec| spec.elem_type())
+            .unwrap_or(RefType::FuncRef)
+    }
+
+    /// Convert an element of a table to the reference that guest code sees.
+    fn element_to_ref(&mut self, table_idx: u32, element: TableElement) -> u64 {
+        match self.elem_type(table_idx) {
+            RefType::ExternRef => element.extern_ref_value(),
+            RefType::FuncRef => self.funcref(element),
+        }
+    }
+
+    /// Convert a reference from guest code to an element of a table.
+    fn ref_to_element(&self, table_idx: u32, value: u64) -> Result<TableElement, Error> {
+        match self.elem_type(table_idx) {
+            RefType::ExternRef => Ok(TableElement::extern_ref(value)),
+            RefType::FuncRef if value == 0 => Ok(TableElement::empty()),
+            RefType::FuncRef => self
+                .funcrefs
+                .get(value as usize - 1)
+                .cloned()
+                .ok_or(Error::InvalidArgument("invalid funcref")),
+        }
+    }
+
+    /// Return the `funcref` handle of a function, assigning one if it has none yet.
+    fn funcref(&mut self, element: TableElement) -> u64 {
+        if element == TableElement::empty() {
+            return 0;
+        }
+        if let Some(handle) = self.funcref_handles.get(&element) {
+            return *handle;
+        }
+        self.funcrefs.push(element);
+        let handle = self.funcrefs.len() as u64;
+        self.funcref_handles.insert(element, handle);
+        handle
+    }
+}
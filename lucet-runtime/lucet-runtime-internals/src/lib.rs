@@ -7,6 +7,9 @@
 #[macro_use]
 pub mod error;
 
+#[macro_use]
+mod hostcall_macros;
+
 #[macro_use]
 #[cfg(test)]
 pub mod test_helpers;
@@ -15,6 +18,7 @@ pub mod alloc;
 pub mod c_api;
 pub mod context;
 pub mod embed_ctx;
+pub mod guest_types;
 pub mod instance;
 pub mod module;
 pub mod region;
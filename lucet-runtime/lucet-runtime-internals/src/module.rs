@@ -5,7 +5,10 @@ mod sparse_page_data;
 
 pub use crate::module::dl::DlModule;
 pub use crate::module::mock::MockModuleBuilder;
-pub use lucet_module_data::{Global, GlobalSpec, HeapSpec};
+pub use lucet_module_data::{
+    FunctionExport, Global, GlobalSpec, HeapSpec, LinkedImport, PassiveDataSegment,
+    PassiveElemSegment, RefType, Signature, TableSpec, ValueType,
+};
 
 use crate::alloc::Limits;
 use crate::error::Error;
@@ -62,13 +65,194 @@ pub struct TrapSite {
     pub trapcode: u32,
 }
 
+/// An element of a WebAssembly table: a function pointer, and the index of its signature in the
+/// module's type section.
+///
+/// The signature index is checked against the expected signature when the guest makes an indirect
+/// call through the table. Elements of `externref` tables instead hold an opaque value from the
+/// host; see [`TableElement::extern_ref()`](#method.extern_ref).
 #[repr(C)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TableElement {
     ty: u64,
     rf: u64,
 }
 
+impl TableElement {
+    /// Create a table element for a function whose signature has index `ty` in the module.
+    ///
+    /// The function must take a `*mut lucet_vmctx` as its first argument, followed by the arguments
+    /// of the WebAssembly signature. It may be a guest function of the same module or a hostcall.
+    pub fn new(ty: u32, func: *const extern "C" fn()) -> TableElement {
+        TableElement {
+            ty: ty as u64,
+            rf: func as u64,
+        }
+    }
+
+    /// An empty table element. Indirect calls to an empty element trap with
+    /// [`TrapCodeType::BadSignature`](../trapcode/enum.TrapCodeType.html#variant.BadSignature).
+    pub fn empty() -> TableElement {
+        TableElement {
+            // signature indices are 32 bits in wasm, so this never matches a signature
+            ty: std::u64::MAX,
+            rf: 0,
+        }
+    }
+
+    /// The signature index of the function, or `None` if the element is empty.
+    pub fn ty(&self) -> Option<u32> {
+        if self.ty > std::u32::MAX as u64 {
+            None
+        } else {
+            Some(self.ty as u32)
+        }
+    }
+
+    /// The function pointer of the element, which is null if the element is empty.
+    pub fn func(&self) -> *const extern "C" fn() {
+        self.rf as *const extern "C" fn()
+    }
+
+    /// Create an element of an `externref` table holding `value`, which the guest can only pass
+    /// around and compare against null. A `value` of zero is the null reference, and is the same as
+    /// an [empty](#method.empty) element.
+    pub fn extern_ref(value: u64) -> TableElement {
+        TableElement {
+            ty: std::u64::MAX,
+            rf: value,
+        }
+    }
+
+    /// The value held by an element of an `externref` table.
+    pub fn extern_ref_value(&self) -> u64 {
+        self.rf
+    }
+
+    /// A pointer to the function pointer stored in the element.
+    pub(crate) fn func_ref(&self) -> *const extern "C" fn() {
+        &self.rf as *const u64 as *const extern "C" fn()
+    }
+}
+
+/// An item exported by a WebAssembly module, as listed by
+/// [`Module::exports()`](trait.Module.html#method.exports).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Export<'a> {
+    /// An exported function, which can be run with
+    /// [`Instance::run()`](../instance/struct.Instance.html#method.run).
+    Function {
+        name: &'a str,
+        signature: &'a Signature,
+    },
+    /// An exported global, which can be accessed with
+    /// [`Instance::get_global()`](../instance/struct.Instance.html#method.get_global).
+    Global {
+        name: &'a str,
+        idx: u32,
+        ty: ValueType,
+    },
+    /// The exported linear memory of the module.
+    Memory {
+        name: &'a str,
+        heap_spec: &'a HeapSpec,
+    },
+    /// An exported table, which can be accessed with
+    /// [`Instance::table_elements()`](../instance/struct.Instance.html#method.table_elements).
+    Table { name: &'a str, idx: u32 },
+}
+
+impl<'a> Export<'a> {
+    /// The name under which the item is exported.
+    pub fn name(&self) -> &'a str {
+        match self {
+            Export::Function { name, .. } => *name,
+            Export::Global { name, .. } => *name,
+            Export::Memory { name, .. } => *name,
+            Export::Table { name, .. } => *name,
+        }
+    }
+}
+
+/// A summary of a module's specifications, as returned by
+/// [`Module::info()`](trait.Module.html#method.info).
+///
+/// All of this information is available as soon as the module is loaded, without creating an
+/// instance.
+#[derive(Clone, Debug)]
+pub struct ModuleInfo<'a> {
+    pub heap_spec: &'a HeapSpec,
+    pub globals: &'a [GlobalSpec<'a>],
+    pub tables: &'a [TableSpec<'a>],
+    pub linked_imports: &'a [LinkedImport<'a>],
+    pub exports: Vec<Export<'a>>,
+    /// The number of functions with entries in the trap manifest.
+    pub trap_manifest_len: usize,
+    /// The total number of trap sites across all functions in the trap manifest.
+    pub trap_sites_len: usize,
+    pub has_start_func: bool,
+}
+
+impl<'a> std::fmt::Display for ModuleInfo<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let heap = self.heap_spec;
+        writeln!(
+            f,
+            "heap: initial {} bytes, max {}, reserved {} bytes, guard {} bytes",
+            heap.initial_size,
+            heap.max_size
+                .map(|max| format!("{} bytes", max))
+                .unwrap_or_else(|| "unbounded".to_owned()),
+            heap.reserved_size,
+            heap.guard_size
+        )?;
+        let global_imports = self
+            .globals
+            .iter()
+            .filter(|g| match g.global() {
+                Global::Import { .. } => true,
+                Global::Def { .. } => false,
+            })
+            .count();
+        writeln!(
+            f,
+            "globals: {} ({} imported)",
+            self.globals.len(),
+            global_imports
+        )?;
+        writeln!(f, "tables: {}", self.tables.len())?;
+        writeln!(f, "linked imports: {}", self.linked_imports.len())?;
+        for import in self.linked_imports {
+            writeln!(
+                f,
+                "  {}::{}: {:?}",
+                import.module(),
+                import.field(),
+                import.signature()
+            )?;
+        }
+        writeln!(f, "exports: {}", self.exports.len())?;
+        for export in self.exports.iter() {
+            match export {
+                Export::Function { name, signature } => {
+                    writeln!(f, "  function {}: {:?}", name, signature)?
+                }
+                Export::Global { name, idx, ty } => {
+                    writeln!(f, "  global {} (index {}): {:?}", name, idx, ty)?
+                }
+                Export::Memory { name, .. } => writeln!(f, "  memory {}", name)?,
+                Export::Table { name, idx } => writeln!(f, "  table {} (index {})", name, idx)?,
+            }
+        }
+        writeln!(
+            f,
+            "trap manifest: {} functions, {} trap sites",
+            self.trap_manifest_len, self.trap_sites_len
+        )?;
+        write!(f, "start function: {}", self.has_start_func)
+    }
+}
+
 /// Details about a program address.
 ///
 /// It is possible to determine whether an address lies within the module code if the module is
@@ -86,7 +270,77 @@ pub struct AddrDetails {
 ///
 /// Types that implement this trait are suitable for use with
 /// [`Region::new_instance()`](trait.Region.html#method.new_instance).
-pub trait Module: ModuleInternal {}
+pub trait Module: ModuleInternal {
+    /// List the items exported by the module, along with their types.
+    ///
+    /// This lets an embedder discover the interface of a module without knowing its contents ahead
+    /// of time.
+    fn exports(&self) -> Box<dyn Iterator<Item = Export<'_>> + '_> {
+        let functions = self.function_exports().iter().map(|f| Export::Function {
+            name: f.name(),
+            signature: f.signature(),
+        });
+        let globals = self.globals().iter().enumerate().filter_map(|(idx, g)| {
+            g.export().map(|name| Export::Global {
+                name,
+                idx: idx as u32,
+                ty: g.ty(),
+            })
+        });
+        let memories = self
+            .memory_exports()
+            .iter()
+            .map(move |name| Export::Memory {
+                name: *name,
+                heap_spec: self.heap_spec(),
+            });
+        let tables = self.tables().iter().enumerate().filter_map(|(idx, t)| {
+            t.export().map(|name| Export::Table {
+                name,
+                idx: idx as u32,
+            })
+        });
+        Box::new(functions.chain(globals).chain(memories).chain(tables))
+    }
+
+    /// Check that instances of the module can be created in a region with the given `Limits`.
+    ///
+    /// This performs the same checks as instance creation, so modules that would exceed the limits
+    /// of a region can be rejected as soon as they are loaded.
+    fn validate(&self, limits: &Limits) -> Result<(), Error> {
+        self.validate_runtime_spec(limits)?;
+        for idx in 0..self.tables().len().max(1) {
+            self.table_elements(idx as u32)?;
+        }
+        self.ref_func_elements()?;
+        let passive_table_elements = self.passive_table_elements()?;
+        for segment in self.passive_elements() {
+            if segment.start() as usize + segment.len() as usize > passive_table_elements.len() {
+                return Err(lucet_incorrect_module!(
+                    "passive element segment {} is out of bounds",
+                    segment.index()
+                ));
+            }
+        }
+        self.get_start_func()?;
+        Ok(())
+    }
+
+    /// Summarize the specifications of the module.
+    fn info(&self) -> Result<ModuleInfo<'_>, Error> {
+        let trap_manifest = self.trap_manifest();
+        Ok(ModuleInfo {
+            heap_spec: self.heap_spec(),
+            globals: self.globals(),
+            tables: self.tables(),
+            linked_imports: self.linked_imports(),
+            exports: self.exports().collect(),
+            trap_manifest_len: trap_manifest.len(),
+            trap_sites_len: trap_manifest.iter().map(|r| r.table_len as usize).sum(),
+            has_start_func: self.get_start_func()?.is_some(),
+        })
+    }
+}
 
 pub trait ModuleInternal: Send + Sync {
     fn heap_spec(&self) -> &HeapSpec;
@@ -102,8 +356,41 @@ pub trait ModuleInternal: Send + Sync {
     /// Get the number of pages in the sparse page data.
     fn sparse_page_data_len(&self) -> usize;
 
-    /// Get the table elements from the module.
-    fn table_elements(&self) -> Result<&[TableElement], Error>;
+    /// Get the elements of a table from the module.
+    ///
+    /// These are the initial elements of the table; each instance has its own copy, which may be
+    /// modified with
+    /// [`Instance::set_table_element()`](../instance/struct.Instance.html#method.set_table_element)
+    /// and grown with
+    /// [`Instance::grow_table()`](../instance/struct.Instance.html#method.grow_table).
+    fn table_elements(&self, table_idx: u32) -> Result<&[TableElement], Error>;
+
+    /// Get the elements for the functions that `ref.func` refers to, in the order of the operands
+    /// that guest code passes to the runtime.
+    fn ref_func_elements(&self) -> Result<&[TableElement], Error>;
+
+    /// Get the passive data segments of the module, which are only copied into the heap by
+    /// `memory.init`.
+    fn passive_data(&self) -> &[PassiveDataSegment];
+
+    /// Get the passive element segments of the module, which are only copied into the table by
+    /// `table.init`.
+    fn passive_elements(&self) -> &[PassiveElemSegment];
+
+    /// Get the elements of all the passive element segments, which each
+    /// [`PassiveElemSegment`](struct.PassiveElemSegment.html) refers to a range of.
+    fn passive_table_elements(&self) -> Result<&[TableElement], Error>;
+
+    /// Get the WebAssembly tables of the module, with their import and export names.
+    ///
+    /// The indices into the returned slice correspond to the WebAssembly indices of the tables.
+    fn tables(&self) -> &[TableSpec];
+
+    /// Get the functions exported by the module, with their signatures.
+    fn function_exports(&self) -> &[FunctionExport];
+
+    /// Get the names under which the module exports its linear memory.
+    fn memory_exports(&self) -> &[&str];
 
     fn get_export_func(&self, sym: &[u8]) -> Result<*const extern "C" fn(), Error>;
 
@@ -115,6 +402,13 @@ pub trait ModuleInternal: Send + Sync {
 
     fn get_start_func(&self) -> Result<Option<*const extern "C" fn()>, Error>;
 
+    /// Get the function imports that must be satisfied by linking an instance of this module to
+    /// instances of other modules.
+    ///
+    /// The indices into the returned slice correspond to the slots in the instance's linked
+    /// function table.
+    fn linked_imports(&self) -> &[LinkedImport];
+
     fn trap_manifest(&self) -> &[TrapManifestRecord];
 
     fn addr_details(&self, addr: *const c_void) -> Result<Option<AddrDetails>, Error>;
@@ -175,6 +469,21 @@ pub trait ModuleInternal: Send + Sync {
             bail_limits_exceeded!("globals exceed limits");
         }
 
+        for idx in 0..self.tables().len() {
+            if self.table_elements(idx as u32)?.len() > limits.table_elements {
+                bail_limits_exceeded!("table {} elements exceed limits", idx);
+            }
+        }
+
+        for (i, global) in self.globals().iter().enumerate() {
+            if let Global::Import { .. } = global.global() {
+                return Err(Error::Unsupported(format!(
+                    "global imports are unsupported; found: {:?}",
+                    i
+                )));
+            }
+        }
+
         Ok(())
     }
 }
@@ -1,6 +1,7 @@
 use crate::error::Error;
 use crate::module::{
-    AddrDetails, GlobalSpec, HeapSpec, Module, ModuleInternal, TableElement, TrapManifestRecord,
+    AddrDetails, FunctionExport, GlobalSpec, HeapSpec, LinkedImport, Module, ModuleInternal,
+    PassiveDataSegment, PassiveElemSegment, TableElement, TableSpec, TrapManifestRecord,
 };
 use libc::c_void;
 use libloading::{Library, Symbol};
@@ -98,6 +99,34 @@ impl DlModule {
             trap_manifest,
         }))
     }
+
+    /// The elements of an array of table elements, whose length in bytes is stored in the symbol
+    /// `<sym>_len`.
+    fn sized_elements(
+        &self,
+        sym: &str,
+        elements: *const TableElement,
+    ) -> Result<&[TableElement], Error> {
+        let len_sym = format!("{}_len", sym);
+        let p_len: Symbol<*const usize> = unsafe {
+            self.lib.get(len_sym.as_bytes()).map_err(|e| {
+                lucet_incorrect_module!("error loading required symbol `{}`: {}", len_sym, e)
+            })?
+        };
+        let len = unsafe { **p_len };
+        let elem_size = mem::size_of::<TableElement>();
+        if len > std::u32::MAX as usize * elem_size {
+            return Err(lucet_incorrect_module!("table segment too long: {}", len));
+        }
+        if len % elem_size != 0 {
+            return Err(lucet_incorrect_module!(
+                "table segment length {} not a multiple of table element size: {}",
+                len,
+                elem_size
+            ));
+        }
+        Ok(unsafe { from_raw_parts(elements, len / elem_size) })
+    }
 }
 
 impl Module for DlModule {}
@@ -119,30 +148,86 @@ impl ModuleInternal for DlModule {
         self.module_data.sparse_data().len()
     }
 
-    fn table_elements(&self) -> Result<&[TableElement], Error> {
-        let p_table_segment: Symbol<*const TableElement> = unsafe {
-            self.lib.get(b"guest_table_0").map_err(|e| {
-                lucet_incorrect_module!("error loading required symbol `guest_table_0`: {}", e)
+    fn linked_imports(&self) -> &[LinkedImport] {
+        self.module_data.linked_imports()
+    }
+
+    fn tables(&self) -> &[TableSpec] {
+        self.module_data.tables_spec()
+    }
+
+    fn function_exports(&self) -> &[FunctionExport] {
+        self.module_data.function_exports()
+    }
+
+    fn memory_exports(&self) -> &[&str] {
+        self.module_data.memory_exports()
+    }
+
+    fn passive_data(&self) -> &[PassiveDataSegment] {
+        self.module_data.passive_data()
+    }
+
+    fn passive_elements(&self) -> &[PassiveElemSegment] {
+        self.module_data.passive_elements()
+    }
+
+    fn passive_table_elements(&self) -> Result<&[TableElement], Error> {
+        let len: usize = self
+            .passive_elements()
+            .iter()
+            .map(|segment| segment.len() as usize)
+            .sum();
+        // modules without passive element segments do not define the symbol
+        if len == 0 {
+            return Ok(&[]);
+        }
+        let p_elements: Symbol<*const TableElement> = unsafe {
+            self.lib.get(b"guest_passive_elements").map_err(|e| {
+                lucet_incorrect_module!(
+                    "error loading required symbol `guest_passive_elements`: {}",
+                    e
+                )
             })?
         };
-        let p_table_segment_len: Symbol<*const usize> = unsafe {
-            self.lib.get(b"guest_table_0_len").map_err(|e| {
-                lucet_incorrect_module!("error loading required symbol `guest_table_0_len`: {}", e)
-            })?
+        Ok(unsafe { from_raw_parts(*p_elements, len) })
+    }
+
+    fn table_elements(&self, table_idx: u32) -> Result<&[TableElement], Error> {
+        let sym = format!("guest_table_{}", table_idx);
+        let p_table_segment: Symbol<*const TableElement> = match unsafe {
+            self.lib.get(sym.as_bytes())
+        } {
+            // modules without a table do not define the table symbols
+            Err(ref e) if is_undefined_symbol(e) && table_idx as usize >= self.tables().len() => {
+                return Ok(&[]);
+            }
+            Err(e) => {
+                return Err(lucet_incorrect_module!(
+                    "error loading required symbol `{}`: {}",
+                    sym,
+                    e
+                ));
+            }
+            Ok(sym) => sym,
         };
-        let len = unsafe { **p_table_segment_len };
-        let elem_size = mem::size_of::<TableElement>();
-        if len > std::u32::MAX as usize * elem_size {
-            lucet_incorrect_module!("table segment too long: {}", len);
-        }
-        if len % elem_size != 0 {
-            lucet_incorrect_module!(
-                "table segment length {} not a multiple of table element size: {}",
-                len,
-                elem_size
-            );
-        }
-        Ok(unsafe { from_raw_parts(*p_table_segment, **p_table_segment_len as usize / elem_size) })
+        self.sized_elements(&sym, *p_table_segment)
+    }
+
+    fn ref_func_elements(&self) -> Result<&[TableElement], Error> {
+        let p_ref_funcs: Symbol<*const TableElement> =
+            match unsafe { self.lib.get(b"guest_ref_funcs") } {
+                // modules without `ref.func` do not define the symbols
+                Err(ref e) if is_undefined_symbol(e) => return Ok(&[]),
+                Err(e) => {
+                    return Err(lucet_incorrect_module!(
+                        "error loading required symbol `guest_ref_funcs`: {}",
+                        e
+                    ));
+                }
+                Ok(sym) => sym,
+            };
+        self.sized_elements("guest_ref_funcs", *p_ref_funcs)
     }
 
     fn get_export_func(&self, sym: &[u8]) -> Result<*const extern "C" fn(), Error> {
@@ -162,10 +247,7 @@ impl ModuleInternal for DlModule {
         table_id: u32,
         func_id: u32,
     ) -> Result<*const extern "C" fn(), Error> {
-        if table_id != 0 {
-            return Err(Error::FuncNotFound(table_id, func_id));
-        }
-        let table = self.table_elements()?;
+        let table = self.table_elements(table_id)?;
         let func: extern "C" fn() = table
             .get(func_id as usize)
             .map(|element| unsafe { std::mem::transmute(element.rf) })
@@ -182,7 +264,7 @@ impl ModuleInternal for DlModule {
                 .get::<*const *const extern "C" fn()>(b"guest_start")
         } {
             if start_func.is_null() {
-                lucet_incorrect_module!("`guest_start` is defined but null");
+                return Err(lucet_incorrect_module!("`guest_start` is defined but null"));
             }
             Ok(Some(unsafe { **start_func }))
         } else {
@@ -5,8 +5,11 @@ macro_rules! globals_tests {
         use $TestRegion as TestRegion;
         use $crate::alloc::Limits;
         use $crate::error::Error;
-        use $crate::module::{MockModuleBuilder, Module};
+        use $crate::module::{
+            Export, MockModuleBuilder, Module, ModuleInternal, Signature, ValueType,
+        };
         use $crate::region::Region;
+        use $crate::val::Val;
         use $crate::vmctx::{lucet_vmctx, Vmctx};
 
         fn mock_import_module() -> Arc<dyn Module> {
@@ -104,6 +107,92 @@ macro_rules! globals_tests {
             let retval = inst.run(b"get_global0", &[]).expect("instance runs");
             assert_eq!(i64::from(retval), 666);
         }
+
+        fn mock_typed_globals_module() -> Arc<dyn Module> {
+            extern "C" fn get_global1(vmctx: *mut lucet_vmctx) -> i64 {
+                unsafe { Vmctx::from_raw(vmctx) }.globals()[1]
+            }
+
+            MockModuleBuilder::new()
+                .with_exported_typed_global(0, ValueType::I32, -7, "counter")
+                .with_exported_typed_global(1, ValueType::F64, 1.5f64.to_bits() as i64, "ratio")
+                .with_global(2, 0)
+                .with_export_func(b"get_global1", get_global1 as *const extern "C" fn())
+                .with_function_export("get_global1", Signature::new(vec![], Some(ValueType::I64)))
+                .with_memory_export("memory")
+                .build()
+        }
+
+        #[test]
+        fn get_global_by_name() {
+            let module = mock_typed_globals_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.get_global("counter") {
+                Ok(Val::I32(-7)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match inst.get_global("ratio") {
+                Ok(Val::F64(v)) => assert_eq!(v, 1.5),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match inst.get_global("nonexistent") {
+                Err(Error::SymbolNotFound(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn set_global_by_name() {
+            let module = mock_typed_globals_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.set_global("ratio", Val::F64(-2.25))
+                .expect("global can be set");
+            let retval = inst.run(b"get_global1", &[]).expect("instance runs");
+            assert_eq!(i64::from(retval), (-2.25f64).to_bits() as i64);
+
+            match inst.set_global("counter", Val::I64(5)) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            assert_eq!(inst.globals()[0], -7);
+        }
+
+        #[test]
+        fn module_exports() {
+            let module = mock_typed_globals_module();
+            let exports: Vec<Export> = module.exports().collect();
+            assert_eq!(
+                exports,
+                vec![
+                    Export::Function {
+                        name: "get_global1",
+                        signature: &Signature::new(vec![], Some(ValueType::I64)),
+                    },
+                    Export::Global {
+                        name: "counter",
+                        idx: 0,
+                        ty: ValueType::I32,
+                    },
+                    Export::Global {
+                        name: "ratio",
+                        idx: 1,
+                        ty: ValueType::F64,
+                    },
+                    Export::Memory {
+                        name: "memory",
+                        heap_spec: module.heap_spec(),
+                    },
+                ]
+            );
+        }
     };
 }
 
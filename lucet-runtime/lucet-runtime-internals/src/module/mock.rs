@@ -1,9 +1,15 @@
 use crate::error::Error;
 use crate::module::{
-    AddrDetails, GlobalSpec, HeapSpec, Module, ModuleInternal, TableElement, TrapManifestRecord,
+    AddrDetails, FunctionExport, GlobalSpec, HeapSpec, LinkedImport, Module, ModuleInternal,
+    PassiveDataSegment, PassiveElemSegment, Signature, TableElement, TableSpec, TrapManifestRecord,
+    ValueType,
 };
 use libc::c_void;
-use lucet_module_data::owned::{OwnedGlobalSpec, OwnedModuleData, OwnedSparseData};
+use lucet_module_data::owned::{
+    OwnedFunctionExport, OwnedGlobal, OwnedGlobalSpec, OwnedModuleData, OwnedPassiveDataSegment,
+    OwnedSparseData,
+};
+use lucet_module_data::GlobalDef;
 use lucet_module_data::ModuleData;
 use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
@@ -18,6 +24,11 @@ pub struct MockModuleBuilder {
     func_table: HashMap<(u32, u32), *const extern "C" fn()>,
     start_func: Option<extern "C" fn()>,
     trap_manifest: Vec<TrapManifestRecord>,
+    function_exports: Vec<OwnedFunctionExport>,
+    memory_exports: Vec<String>,
+    passive_data: Vec<OwnedPassiveDataSegment>,
+    passive_elements: Vec<PassiveElemSegment>,
+    passive_table_elements: Vec<TableElement>,
 }
 
 impl MockModuleBuilder {
@@ -27,6 +38,7 @@ impl MockModuleBuilder {
             guard_size: 4 * 1024 * 1024,
             initial_size: 64 * 1024,
             max_size: Some(64 * 1024),
+            shared: false,
         };
         MockModuleBuilder::default().with_heap_spec(DEFAULT_HEAP_SPEC)
     }
@@ -68,6 +80,27 @@ impl MockModuleBuilder {
         self
     }
 
+    /// Add an exported global of type `ty`, whose slot initially contains `init_val`.
+    pub fn with_exported_typed_global(
+        mut self,
+        idx: u32,
+        ty: ValueType,
+        init_val: i64,
+        export_name: &str,
+    ) -> Self {
+        self.globals.insert(
+            idx as usize,
+            OwnedGlobalSpec::new(
+                OwnedGlobal::Def {
+                    def: GlobalDef::new(init_val),
+                },
+                ty,
+                Some(export_name.to_string()),
+            ),
+        );
+        self
+    }
+
     pub fn with_import(mut self, idx: u32, import_module: &str, import_field: &str) -> Self {
         self.globals.insert(
             idx as usize,
@@ -99,11 +132,42 @@ impl MockModuleBuilder {
         self
     }
 
+    /// Add a passive data segment with the given segment index.
+    pub fn with_passive_data(mut self, index: u32, data: &[u8]) -> Self {
+        self.passive_data
+            .push(OwnedPassiveDataSegment::new(index, data.to_vec()));
+        self
+    }
+
+    /// Add a passive element segment with the given segment index.
+    pub fn with_passive_elements(mut self, index: u32, elements: &[TableElement]) -> Self {
+        self.passive_elements.push(PassiveElemSegment::new(
+            index,
+            self.passive_table_elements.len() as u32,
+            elements.len() as u32,
+        ));
+        self.passive_table_elements.extend_from_slice(elements);
+        self
+    }
+
     pub fn with_export_func(mut self, sym: &[u8], func: *const extern "C" fn()) -> Self {
         self.export_funcs.insert(sym.to_vec(), func);
         self
     }
 
+    /// Describe an exported function's signature; the function itself is added with
+    /// [`with_export_func()`](struct.MockModuleBuilder.html#method.with_export_func).
+    pub fn with_function_export(mut self, name: &str, signature: Signature) -> Self {
+        self.function_exports
+            .push(OwnedFunctionExport::new(name.to_string(), signature));
+        self
+    }
+
+    pub fn with_memory_export(mut self, name: &str) -> Self {
+        self.memory_exports.push(name.to_string());
+        self
+    }
+
     pub fn with_table_func(
         mut self,
         table_idx: u32,
@@ -158,6 +222,12 @@ impl MockModuleBuilder {
             self.heap_spec,
             OwnedSparseData::new(self.sparse_page_data).expect("sparse data pages are valid"),
             globals_spec,
+            vec![],
+            vec![],
+            self.function_exports,
+            self.memory_exports,
+            self.passive_data,
+            self.passive_elements,
         );
         let serialized_module_data = owned_module_data
             .to_ref()
@@ -170,6 +240,7 @@ impl MockModuleBuilder {
             serialized_module_data,
             module_data,
             table_elements,
+            passive_table_elements: self.passive_table_elements,
             export_funcs: self.export_funcs,
             func_table: self.func_table,
             start_func: self.start_func,
@@ -184,6 +255,7 @@ pub struct MockModule {
     serialized_module_data: Vec<u8>,
     module_data: ModuleData<'static>,
     pub table_elements: Vec<TableElement>,
+    pub passive_table_elements: Vec<TableElement>,
     pub export_funcs: HashMap<Vec<u8>, *const extern "C" fn()>,
     pub func_table: HashMap<(u32, u32), *const extern "C" fn()>,
     pub start_func: Option<extern "C" fn()>,
@@ -212,10 +284,46 @@ impl ModuleInternal for MockModule {
         self.module_data.sparse_data().len()
     }
 
-    fn table_elements(&self) -> Result<&[TableElement], Error> {
+    fn linked_imports(&self) -> &[LinkedImport] {
+        self.module_data.linked_imports()
+    }
+
+    fn tables(&self) -> &[TableSpec] {
+        self.module_data.tables_spec()
+    }
+
+    fn function_exports(&self) -> &[FunctionExport] {
+        self.module_data.function_exports()
+    }
+
+    fn memory_exports(&self) -> &[&str] {
+        self.module_data.memory_exports()
+    }
+
+    fn passive_data(&self) -> &[PassiveDataSegment] {
+        self.module_data.passive_data()
+    }
+
+    fn passive_elements(&self) -> &[PassiveElemSegment] {
+        self.module_data.passive_elements()
+    }
+
+    fn passive_table_elements(&self) -> Result<&[TableElement], Error> {
+        Ok(&self.passive_table_elements)
+    }
+
+    fn table_elements(&self, table_idx: u32) -> Result<&[TableElement], Error> {
+        // mock modules only have the one table
+        if table_idx != 0 {
+            return Err(Error::InvalidArgument("table index out of bounds"));
+        }
         Ok(&self.table_elements)
     }
 
+    fn ref_func_elements(&self) -> Result<&[TableElement], Error> {
+        Ok(&[])
+    }
+
     fn get_export_func(&self, sym: &[u8]) -> Result<*const extern "C" fn(), Error> {
         self.export_funcs
             .get(sym)
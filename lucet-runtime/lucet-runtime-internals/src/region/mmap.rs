@@ -1,4 +1,4 @@
-use crate::alloc::{host_page_size, instance_heap_offset, Alloc, Limits, Slot};
+use crate::alloc::{host_page_size, instance_heap_offset, Alloc, Limits, SharedHeap, Slot};
 use crate::embed_ctx::CtxMap;
 use crate::error::Error;
 use crate::instance::{new_instance_handle, Instance, InstanceHandle};
@@ -23,7 +23,24 @@ impl RegionInternal for MmapRegion {
         &self,
         module: Arc<dyn Module>,
         embed_ctx: CtxMap,
+        shared_heap: Option<Arc<SharedHeap>>,
     ) -> Result<InstanceHandle, Error> {
+        let shared_heap = match (module.heap_spec().shared, shared_heap) {
+            (false, None) => None,
+            (false, Some(_)) => {
+                return Err(Error::InvalidArgument("module memory is not shared"));
+            }
+            (true, None) => Some(SharedHeap::new(module.as_ref())?),
+            (true, Some(shared_heap)) => {
+                if shared_heap.size() != module.heap_spec().initial_size as usize {
+                    return Err(Error::InvalidArgument(
+                        "shared heap size does not match the module's initial heap size",
+                    ));
+                }
+                Some(shared_heap)
+            }
+        };
+
         let slot = self
             .freelist
             .lock()
@@ -70,6 +87,7 @@ impl RegionInternal for MmapRegion {
             heap_inaccessible_size: slot.limits.heap_address_space_size,
             slot: Some(slot),
             region,
+            shared_heap,
         };
 
         let inst = new_instance_handle(inst_ptr, module, alloc, embed_ctx)?;
@@ -87,6 +105,21 @@ impl RegionInternal for MmapRegion {
             panic!("heap is not page-aligned");
         }
 
+        if let Some(shared_heap) = alloc.shared_heap.take() {
+            // replace the mapping of the shared heap, so the clearing below does not touch it
+            unsafe {
+                mmap(
+                    slot.heap,
+                    shared_heap.size(),
+                    ProtFlags::PROT_NONE,
+                    MapFlags::MAP_ANON | MapFlags::MAP_PRIVATE | MapFlags::MAP_FIXED,
+                    -1,
+                    0,
+                )
+                .expect("mmap succeeds during drop");
+            }
+        }
+
         // clear and disable access to the heap, stack, globals, and sigstack
         for (ptr, len) in [
             (slot.heap, slot.limits.heap_address_space_size),
@@ -118,9 +151,37 @@ impl RegionInternal for MmapRegion {
         Ok(())
     }
 
+    fn limit_stack(&self, slot: &Slot, usable_size: usize) -> Result<(), Error> {
+        // the stack grows down, so the unusable part is at the lowest addresses
+        let unusable_size = slot.limits.stack_size - usable_size;
+        unsafe {
+            if unusable_size > 0 {
+                mprotect(slot.stack, unusable_size, ProtFlags::PROT_NONE)?;
+            }
+            mprotect(
+                (slot.stack as usize + unusable_size) as *mut c_void,
+                usable_size,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            )?;
+        }
+        Ok(())
+    }
+
     fn reset_heap(&self, alloc: &mut Alloc, module: &dyn Module) -> Result<(), Error> {
         let heap = alloc.slot().heap;
 
+        if let Some(shared_heap) = alloc.shared_heap.clone() {
+            // The contents of a shared heap belong to all of the instances using it, so they are
+            // neither cleared nor initialized here. It only needs to be mapped the first time.
+            if alloc.heap_accessible_size == 0 && shared_heap.size() > 0 {
+                unsafe { shared_heap.map(heap, MapFlags::MAP_FIXED)? };
+                alloc.heap_accessible_size = shared_heap.size();
+                alloc.heap_inaccessible_size =
+                    alloc.slot().limits.heap_address_space_size - shared_heap.size();
+            }
+            return Ok(());
+        }
+
         if alloc.heap_accessible_size > 0 {
             // zero the whole heap, if any of it is currently accessible
             let heap_size = alloc.slot().limits.heap_address_space_size;
@@ -1,6 +1,6 @@
 pub mod mmap;
 
-use crate::alloc::{Alloc, Limits, Slot};
+use crate::alloc::{Alloc, Limits, SharedHeap, Slot};
 use crate::embed_ctx::CtxMap;
 use crate::error::Error;
 use crate::instance::InstanceHandle;
@@ -40,6 +40,7 @@ pub trait RegionInternal: Send + Sync {
         &self,
         module: Arc<dyn Module>,
         embed_ctx: CtxMap,
+        shared_heap: Option<Arc<SharedHeap>>,
     ) -> Result<InstanceHandle, Error>;
 
     /// Unmaps the heap, stack, and globals of an `Alloc`, while retaining the virtual address
@@ -51,6 +52,10 @@ pub trait RegionInternal: Send + Sync {
 
     fn reset_heap(&self, alloc: &mut Alloc, module: &dyn Module) -> Result<(), Error>;
 
+    /// Make the top `usable_size` bytes of the stack for the given slot accessible, and the rest
+    /// of it inaccessible.
+    fn limit_stack(&self, slot: &Slot, usable_size: usize) -> Result<(), Error>;
+
     fn as_dyn_internal(&self) -> &dyn RegionInternal;
 }
 
@@ -73,6 +78,7 @@ pub struct InstanceBuilder<'a> {
     region: &'a dyn RegionInternal,
     module: Arc<dyn Module>,
     embed_ctx: CtxMap,
+    shared_heap: Option<Arc<SharedHeap>>,
 }
 
 impl<'a> InstanceBuilder<'a> {
@@ -81,6 +87,7 @@ impl<'a> InstanceBuilder<'a> {
             region,
             module,
             embed_ctx: CtxMap::new(),
+            shared_heap: None,
         }
     }
 
@@ -93,6 +100,16 @@ impl<'a> InstanceBuilder<'a> {
         self
     }
 
+    /// Use a heap shared with other instances, such as the one returned by
+    /// [`Instance::shared_heap()`](struct.Instance.html#method.shared_heap).
+    ///
+    /// The module's memory must be declared `shared`, and the heap must be the module's initial heap
+    /// size. Without a shared heap, an instance of such a module gets a new one.
+    pub fn with_shared_heap(mut self, shared_heap: Arc<SharedHeap>) -> Self {
+        self.shared_heap = Some(shared_heap);
+        self
+    }
+
     /// Build the instance.
     ///
     /// # Safety
@@ -100,6 +117,7 @@ impl<'a> InstanceBuilder<'a> {
     /// This function runs the guest code for the WebAssembly `start` section, and running any guest
     /// code is potentially unsafe; see [`Instance::run()`](struct.Instance.html#method.run).
     pub fn build(self) -> Result<InstanceHandle, Error> {
-        self.region.new_instance_with(self.module, self.embed_ctx)
+        self.region
+            .new_instance_with(self.module, self.embed_ctx, self.shared_heap)
     }
 }
@@ -3,7 +3,7 @@
 
 use libc::c_void;
 use std::arch::x86_64::{
-    __m128, _mm_castpd_ps, _mm_castps_pd, _mm_load_pd1, _mm_load_ps1, _mm_setzero_ps,
+    __m128, _mm_castpd_ps, _mm_castps_pd, _mm_load_pd1, _mm_load_ps1, _mm_loadu_ps, _mm_setzero_ps,
     _mm_storeu_pd, _mm_storeu_ps,
 };
 
@@ -26,6 +26,14 @@ pub enum Val {
     Bool(bool),
     F32(f32),
     F64(f64),
+    /// A WebAssembly `v128` value, with lane 0 in the lowest bits.
+    ///
+    /// It is passed in an `xmm` register, like `__m128`. `lucetc` cannot compile SIMD code yet, so
+    /// only native functions can take or return these.
+    V128(u128),
+    /// A WebAssembly `externref` value: an opaque value from the host, where zero is the null
+    /// reference.
+    ExternRef(u64),
 }
 
 // the pointer variant is just a wrapper; the caller will know they're still responsible for their
@@ -72,14 +80,15 @@ impl_from_scalars!({
     ISize: isize,
     Bool: bool,
     F32: f32,
-    F64: f64
+    F64: f64,
+    V128: u128
 });
 
 /// Register representation of `Val`.
 ///
 /// When mapping `Val`s to x86_64 registers, we map floating point
-/// values into the SSE registers _xmmN_, and all other values into
-/// general-purpose (integer) registers.
+/// and vector values into the SSE registers _xmmN_, and all other
+/// values into general-purpose (integer) registers.
 pub enum RegVal {
     GpReg(u64),
     FpReg(__m128),
@@ -107,30 +116,44 @@ pub fn val_to_reg(val: &Val) -> RegVal {
         Bool(true) => GpReg(1u64),
         Val::F32(v) => FpReg(unsafe { _mm_load_ps1(&v as *const f32) }),
         Val::F64(v) => FpReg(unsafe { _mm_castpd_ps(_mm_load_pd1(&v as *const f64)) }),
+        V128(v) => FpReg(unsafe { _mm_loadu_ps(&v as *const u128 as *const f32) }),
+        ExternRef(v) => GpReg(v),
     }
 }
 
+/// Stack representation of `Val`.
+///
+/// Vector values take two stack slots, and must be 16-byte aligned; all
+/// other values take one.
+pub enum StackVal {
+    Word(u64),
+    Vector(u64, u64),
+}
+
 /// Convert a `Val` to its representation when spilled onto the
 /// stack.
-pub fn val_to_stack(val: &Val) -> u64 {
+pub fn val_to_stack(val: &Val) -> StackVal {
+    use self::StackVal::*;
     use self::Val::*;
     match *val {
-        CPtr(v) => v as u64,
-        GuestPtr(v) => v as u64,
-        U8(v) => v as u64,
-        U16(v) => v as u64,
-        U32(v) => v as u64,
-        U64(v) => v as u64,
-        I8(v) => v as u64,
-        I16(v) => v as u64,
-        I32(v) => v as u64,
-        I64(v) => v as u64,
-        USize(v) => v as u64,
-        ISize(v) => v as u64,
-        Bool(false) => 0u64,
-        Bool(true) => 1u64,
-        F32(v) => v.to_bits() as u64,
-        F64(v) => v.to_bits(),
+        CPtr(v) => Word(v as u64),
+        GuestPtr(v) => Word(v as u64),
+        U8(v) => Word(v as u64),
+        U16(v) => Word(v as u64),
+        U32(v) => Word(v as u64),
+        U64(v) => Word(v as u64),
+        I8(v) => Word(v as u64),
+        I16(v) => Word(v as u64),
+        I32(v) => Word(v as u64),
+        I64(v) => Word(v as u64),
+        USize(v) => Word(v as u64),
+        ISize(v) => Word(v as u64),
+        Bool(false) => Word(0u64),
+        Bool(true) => Word(1u64),
+        F32(v) => Word(v.to_bits() as u64),
+        F64(v) => Word(v.to_bits()),
+        V128(v) => Vector(v as u64, (v >> 64) as u64),
+        ExternRef(v) => Word(v),
     }
 }
 
@@ -180,6 +203,7 @@ macro_rules! impl_from_fp {
 
 impl_from_fp!(f32, __m128_as_f32, as_f32);
 impl_from_fp!(f64, __m128_as_f64, as_f64);
+impl_from_fp!(u128, __m128_as_u128, as_v128);
 
 macro_rules! impl_from_gp {
     ( $ty:ty, $as:ident ) => {
@@ -281,3 +305,12 @@ pub fn __m128_as_f64(v: __m128) -> f64 {
     }
     out[0]
 }
+
+/// Interpret the contents of a `__m128` register as a `v128`, with lane 0 in the lowest bits.
+pub fn __m128_as_u128(v: __m128) -> u128 {
+    let mut out: u128 = 0;
+    unsafe {
+        _mm_storeu_ps(&mut out as *mut u128 as *mut f32, v);
+    }
+    out
+}
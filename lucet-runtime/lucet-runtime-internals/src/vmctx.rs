@@ -7,11 +7,13 @@ pub use crate::c_api::lucet_vmctx;
 
 use crate::alloc::instance_heap_offset;
 use crate::context::Context;
+use crate::embed_ctx::CtxKey;
 use crate::error::Error;
 use crate::instance::{
     Instance, InstanceHandle, InstanceInternal, State, TerminationDetails, CURRENT_INSTANCE,
     HOST_CTX,
 };
+use crate::val::{UntypedRetVal, Val};
 use std::any::Any;
 
 /// An opaque handle to a running instance's context.
@@ -94,6 +96,28 @@ impl Vmctx {
         unsafe { self.instance_mut().get_embed_ctx_mut_or_term() }
     }
 
+    /// Get a reference to the context value lent to the current run with key `K` by
+    /// `Instance::run_with_ctx()`. If it does not exist, the context will terminate.
+    ///
+    /// ### Safety
+    ///
+    /// The context value may borrow for longer than the returned reference lives. Do not make any
+    /// reference that does not outlive the call to `Instance::run_with_ctx()` reachable from it,
+    /// for example through a `Cell`.
+    pub unsafe fn get_scoped_ctx<'a, K: CtxKey<'a>>(&'a self) -> &'a K::Ctx {
+        self.instance_mut().get_scoped_ctx_or_term::<K>()
+    }
+
+    /// Get a mutable reference to the context value lent to the current run with key `K` by
+    /// `Instance::run_with_ctx()`. If it does not exist, the context will terminate.
+    ///
+    /// ### Safety
+    ///
+    /// As for `Vmctx::get_scoped_ctx()`.
+    pub unsafe fn get_scoped_ctx_mut<'a, K: CtxKey<'a>>(&'a mut self) -> &'a mut K::Ctx {
+        self.instance_mut().get_scoped_ctx_mut_or_term::<K>()
+    }
+
     /// Terminate this guest and return to the host context.
     ///
     /// This will return an `Error::RuntimeTerminated` value to the caller of `Instance::run()`.
@@ -153,8 +177,55 @@ impl Vmctx {
         func_idx: u32,
     ) -> Result<*const extern "C" fn(), Error> {
         self.instance()
-            .module()
-            .get_func_from_idx(table_idx, func_idx)
+            .get_table_element(table_idx, func_idx)
+            .map(|element| element.func_ref())
+    }
+
+    /// Call a guest function by WebAssembly table and function index from within a hostcall.
+    ///
+    /// This allows hostcalls to call back into the guest, for example to invoke a comparison
+    /// function passed to a host-side sort. The `vmctx` argument is supplied automatically, so
+    /// `args` should contain only the WebAssembly arguments of the function. The call runs on the
+    /// remaining guest stack beneath the calling hostcall. If too little of it is left, this returns
+    /// `Error::LimitsExceeded` without calling the function; a function that runs out of stack once
+    /// called faults with a stack overflow, as any other guest code would.
+    ///
+    /// If the guest function returns normally, its return value is returned to the hostcall. If it
+    /// faults or the instance is terminated, this function does not return; instead, the outermost
+    /// `Instance::run()` returns the fault or termination as it would for any other guest code.
+    ///
+    /// ```no_run
+    /// use lucet_runtime_internals::vmctx::{lucet_vmctx, Vmctx};
+    /// #[no_mangle]
+    /// extern "C" fn hostcall_call_binop(
+    ///     vmctx: *mut lucet_vmctx,
+    ///     binop_table_idx: u32,
+    ///     binop_func_idx: u32,
+    ///     operand1: u32,
+    ///     operand2: u32,
+    /// ) -> u32 {
+    ///     let mut ctx = unsafe { Vmctx::from_raw(vmctx) };
+    ///     match ctx.call_func_idx(
+    ///         binop_table_idx,
+    ///         binop_func_idx,
+    ///         &[operand1.into(), operand2.into()],
+    ///     ) {
+    ///         Ok(retval) => retval.into(),
+    ///         Err(_) => ctx.terminate("invalid function index"),
+    ///     }
+    /// }
+    /// ```
+    pub fn call_func_idx(
+        &mut self,
+        table_idx: u32,
+        func_idx: u32,
+        args: &[Val],
+    ) -> Result<UntypedRetVal, Error> {
+        let func = self
+            .instance()
+            .get_table_element(table_idx, func_idx)?
+            .func();
+        unsafe { self.instance_mut().run_func_nested(func, args) }
     }
 }
 
@@ -162,9 +233,20 @@ impl Vmctx {
 /// host context. The mutable borrow may conflict with a mutable borrow of the embed_ctx if
 /// this is performed via a method call. We use a macro so we can convince the borrow checker that
 /// this is safe at each use site.
+///
+/// The termination is recorded in the state of the instance running on this thread. This differs
+/// from `$self` when `$self` is a linked instance whose exports were called through an import.
 macro_rules! inst_terminate {
     ($self:ident, $details:expr) => {{
-        $self.state = State::Terminated { details: $details };
+        let details = $details;
+        let running = CURRENT_INSTANCE
+            .with(|current_instance| current_instance.borrow().map(|nn| nn.as_ptr()));
+        #[allow(unused_unsafe)]
+        let state: &mut State = match running {
+            Some(running) => unsafe { &mut (*running).state },
+            None => &mut $self.state,
+        };
+        *state = State::Terminated { details };
         #[allow(unused_unsafe)] // The following unsafe will be incorrectly warned as unused
         HOST_CTX.with(|host_ctx| unsafe { Context::set(&*host_ctx.get()) })
     }};
@@ -181,12 +263,13 @@ pub unsafe fn instance_from_vmctx<'a>(vmctx: *mut lucet_vmctx) -> &'a mut Instan
     // We shouldn't actually need to access the thread local, only the exception handler should
     // need to. But, as long as the thread local exists, we should make sure that the guest
     // hasn't pulled any shenanigans and passed a bad vmctx. (Codegen should ensure the guest
-    // cant pull any shenanigans but there have been bugs before.)
+    // cant pull any shenanigans but there have been bugs before.) The vmctx may also belong to
+    // an instance linked to the current one, if the guest called into it through an import.
     CURRENT_INSTANCE.with(|current_instance| {
         if let Some(current_inst_ptr) = current_instance.borrow().map(|nn| nn.as_ptr()) {
-            assert_eq!(
-                inst_ptr, current_inst_ptr,
-                "vmctx corresponds to current instance"
+            assert!(
+                (*current_inst_ptr).is_self_or_linked(inst_ptr),
+                "vmctx corresponds to current instance or an instance linked to it"
             );
         } else {
             panic!(
@@ -220,6 +303,22 @@ impl Instance {
         }
     }
 
+    /// Helper function specific to Vmctx::get_scoped_ctx. See above.
+    unsafe fn get_scoped_ctx_or_term<'a, K: CtxKey<'a>>(&'a mut self) -> &'a K::Ctx {
+        match self.embed_ctx.get_scoped::<K>() {
+            Some(t) => t,
+            None => inst_terminate!(self, TerminationDetails::GetEmbedCtx),
+        }
+    }
+
+    /// Helper function specific to Vmctx::get_scoped_ctx_mut. See above.
+    unsafe fn get_scoped_ctx_mut_or_term<'a, K: CtxKey<'a>>(&'a mut self) -> &'a mut K::Ctx {
+        match self.embed_ctx.get_scoped_mut::<K>() {
+            Some(t) => t,
+            None => inst_terminate!(self, TerminationDetails::GetEmbedCtx),
+        }
+    }
+
     /// Terminate the guest and swap back to the host context.
     ///
     /// Only safe to call from within the guest context.
@@ -228,6 +327,23 @@ impl Instance {
     }
 }
 
+/// Terminate the guest with the payload of a panic caught at the hostcall boundary.
+///
+/// This is used by [`lucet_hostcalls!`](../macro.lucet_hostcalls.html), and is only safe to call
+/// from within the guest context.
+#[doc(hidden)]
+pub unsafe fn terminate_on_panic(vmctx: *mut lucet_vmctx, payload: Box<dyn Any + Send>) -> ! {
+    let details = TerminationDetails::Panic(payload.into());
+    instance_from_vmctx(vmctx).terminate(details)
+}
+
+/// Terminate the guest after it called a linked import that has not been satisfied.
+///
+/// Only safe to call from within the guest context.
+pub(crate) unsafe fn terminate_unlinked_import(vmctx: *mut lucet_vmctx) -> ! {
+    instance_from_vmctx(vmctx).terminate(TerminationDetails::UnlinkedImport)
+}
+
 /// Unsafely get a `Vmctx` from an `InstanceHandle`, and fake a current instance TLS variable.
 ///
 /// This is provided for compatibility with the Terrarium memory management test suite, but should
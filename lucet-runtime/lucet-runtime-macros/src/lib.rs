@@ -0,0 +1,85 @@
+//! Derive macros for `lucet-runtime`.
+//!
+//! These are re-exported by `lucet-runtime`, and should be used through that crate rather than
+//! depended on directly.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Meta, NestedMeta};
+
+/// Implement `lucet_runtime::GuestType` for a struct.
+///
+/// The struct must be `#[repr(C)]`, must not be generic, and all of its fields must themselves
+/// implement `GuestType`. A compile-time check rejects structs whose layout contains padding.
+#[proc_macro_derive(GuestType)]
+pub fn derive_guest_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match guest_type_impl(&input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn guest_type_impl(input: &DeriveInput) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "GuestType can only be derived for structs",
+            ));
+        }
+    };
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "GuestType cannot be derived for generic structs",
+        ));
+    }
+
+    if !is_repr_c(input) {
+        return Err(syn::Error::new_spanned(
+            input,
+            "GuestType can only be derived for #[repr(C)] structs",
+        ));
+    }
+
+    let field_tys = fields.iter().map(|f| &f.ty).collect::<Vec<_>>();
+    let field_tys_bound = field_tys.clone();
+
+    Ok(quote! {
+        unsafe impl lucet_runtime::GuestType for #name
+        where
+            #( #field_tys_bound: lucet_runtime::GuestType, )*
+        {}
+
+        impl #name {
+            // fails to type check if the struct layout contains padding
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            const __LUCET_GUEST_TYPE_NO_PADDING: [(); 1] = [
+                ();
+                (::std::mem::size_of::<#name>() == 0 #( + ::std::mem::size_of::<#field_tys>() )*)
+                    as usize
+            ];
+        }
+    })
+}
+
+fn is_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attr| match attr.parse_meta() {
+        Ok(Meta::List(list)) => {
+            list.ident == "repr"
+                && list.nested.iter().any(|nested| match nested {
+                    NestedMeta::Meta(Meta::Word(word)) => word == "C",
+                    _ => false,
+                })
+        }
+        _ => false,
+    })
+}
@@ -0,0 +1,12 @@
+(module
+  (global $counter (mut i64) (i64.const 7))
+  (func $add_2 (export "add_2") (param i64 i64) (result i64)
+    (i64.add (get_local 0) (get_local 1))
+  )
+  (func $div_2 (export "div_2") (param i64 i64) (result i64)
+    (i64.div_u (get_local 0) (get_local 1))
+  )
+  (func $get_counter (export "get_counter") (result i64)
+    (get_global $counter)
+  )
+)
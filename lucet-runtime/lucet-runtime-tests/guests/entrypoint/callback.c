@@ -1,13 +1,34 @@
 #include <stdint.h>
 
 extern uint64_t callback_hostcall(uint64_t (*)(uint64_t), uint64_t);
+extern uint64_t callback_hostcall_nested(uint64_t (*)(uint64_t), uint64_t);
 
 uint64_t callback_callback(uint64_t x)
 {
     return x + 1;
 }
 
+uint64_t callback_callback_nested(uint64_t x)
+{
+    return callback_hostcall_nested(callback_callback, x) * 2;
+}
+
+uint64_t callback_callback_div(uint64_t x)
+{
+    return 100 / x;
+}
+
 __attribute__((visibility("default"))) uint64_t callback_entrypoint(uint64_t x)
 {
     return callback_hostcall(callback_callback, x) + 1;
 }
+
+__attribute__((visibility("default"))) uint64_t callback_nested_entrypoint(uint64_t x)
+{
+    return callback_hostcall_nested(callback_callback_nested, x) + 1;
+}
+
+__attribute__((visibility("default"))) uint64_t callback_div_entrypoint(uint64_t x)
+{
+    return callback_hostcall_nested(callback_callback_div, x) + 1;
+}
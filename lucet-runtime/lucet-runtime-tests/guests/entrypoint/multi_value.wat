@@ -0,0 +1,17 @@
+(module
+  (memory 1)
+  (func $divmod (export "divmod") (param i32 i32) (result i32 i32)
+    (i32.div_u (get_local 0) (get_local 1))
+    (i32.rem_u (get_local 0) (get_local 1))
+  )
+  ;; the low and high halves of a 64-bit value, and half of the value
+  (func $split (export "split") (param i64) (result i32 i64 f64)
+    (i32.wrap/i64 (get_local 0))
+    (i64.shr_u (get_local 0) (i64.const 32))
+    (f64.div (f64.convert_u/i64 (get_local 0)) (f64.const 2))
+  )
+  (func $add (export "add") (param i32 i32) (result i32)
+    (i32.add (get_local 0) (get_local 1))
+  )
+  (func $nothing (export "nothing"))
+)
@@ -0,0 +1,23 @@
+(module
+  (import "env" "callback_hostcall_nested" (func $nested (param i32 i64) (result i64)))
+  (import "env" "callback_hostcall_until_exhausted" (func $until_exhausted (param i32 i64) (result i64)))
+  (table anyfunc (elem $deeper $recurse))
+
+  ;; Nest another call from a hostcall, until there is no stack left to nest in
+  (func $deeper (param i64) (result i64)
+    (call $until_exhausted (i32.const 0) (i64.add (get_local 0) (i64.const 1)))
+  )
+
+  ;; Recurse until the stack overflows
+  (func $recurse (param i64) (result i64)
+    (i64.add (call $recurse (i64.add (get_local 0) (i64.const 1))) (i64.const 1))
+  )
+
+  (func (export "nest_until_exhausted") (result i64)
+    (call $until_exhausted (i32.const 0) (i64.const 0))
+  )
+
+  (func (export "overflow_nested") (result i64)
+    (call $nested (i32.const 1) (i64.const 0))
+  )
+)
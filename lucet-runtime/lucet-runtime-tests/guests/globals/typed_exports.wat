@@ -0,0 +1,13 @@
+(module
+  (global $counter (export "counter") (mut i32) (i32.const -7))
+  (global $ratio (export "ratio") (mut f64) (f64.const 1.5))
+  (memory (export "memory") 1)
+  (func $bump (export "bump") (param $n i32) (result i32)
+    (set_global $counter (i32.add (get_global $counter) (get_local $n)))
+    (get_global $counter)
+  )
+  (func $double_ratio (export "double_ratio") (result f64)
+    (set_global $ratio (f64.mul (get_global $ratio) (f64.const 2)))
+    (get_global $ratio)
+  )
+)
@@ -0,0 +1,10 @@
+#include <stddef.h>
+
+extern void hostcall_test_func_hello_scoped(const char *hello_ptr, size_t hello_len);
+
+int main(void)
+{
+    char hello[] = "hello world";
+    hostcall_test_func_hello_scoped(hello, sizeof(hello));
+    return 0;
+}
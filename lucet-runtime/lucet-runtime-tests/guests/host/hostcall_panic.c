@@ -0,0 +1,9 @@
+#include <stddef.h>
+
+extern void hostcall_test_func_hostcall_panic(void);
+
+int main(void)
+{
+    hostcall_test_func_hostcall_panic();
+    return 0;
+}
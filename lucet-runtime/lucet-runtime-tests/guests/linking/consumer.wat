@@ -0,0 +1,12 @@
+(module
+  (import "provider" "add" (func $add (param i32 i32) (result i32)))
+  (import "provider" "fault" (func $fault))
+  (memory 1)
+  (func $main (export "main") (param i32 i32) (result i32)
+    (i32.store (i32.const 0) (i32.const 42))
+    (call $add (get_local 0) (get_local 1))
+  )
+  (func $call_fault (export "call_fault")
+    (call $fault)
+  )
+)
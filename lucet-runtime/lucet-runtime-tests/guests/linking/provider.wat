@@ -0,0 +1,12 @@
+(module
+  (global $calls (mut i32) (i32.const 0))
+  (memory 1)
+  (func $add (export "add") (param i32 i32) (result i32)
+    (set_global $calls (i32.add (get_global $calls) (i32.const 1)))
+    (i32.store (i32.const 0) (get_global $calls))
+    (i32.add (get_local 0) (get_local 1))
+  )
+  (func $fault (export "fault")
+    unreachable
+  )
+)
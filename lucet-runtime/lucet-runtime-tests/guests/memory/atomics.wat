@@ -0,0 +1,16 @@
+(module
+  (memory 1 1 shared)
+  (data (i32.const 0) "\2a")
+  (func $add (export "add") (param i32 i32) (result i32)
+    (i32.atomic.rmw.add (get_local 0) (get_local 1))
+  )
+  (func $wait (export "wait") (param i32 i32) (result i32)
+    (i32.atomic.wait (get_local 0) (get_local 1) (i64.const -1))
+  )
+  (func $notify (export "notify") (param i32) (result i32)
+    (atomic.notify (get_local 0) (i32.const 1))
+  )
+  (func $grow (export "grow") (result i32)
+    (memory.grow (i32.const 1))
+  )
+)
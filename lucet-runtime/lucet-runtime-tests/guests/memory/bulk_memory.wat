@@ -0,0 +1,16 @@
+(module
+  (memory 1)
+  (data passive "hello")
+  (func $copy (export "copy") (param i32 i32 i32)
+    (memory.copy (get_local 0) (get_local 1) (get_local 2))
+  )
+  (func $fill (export "fill") (param i32 i32 i32)
+    (memory.fill (get_local 0) (get_local 1) (get_local 2))
+  )
+  (func $init (export "init") (param i32 i32 i32)
+    (memory.init 0 (get_local 0) (get_local 1) (get_local 2))
+  )
+  (func $drop (export "drop")
+    (data.drop 0)
+  )
+)
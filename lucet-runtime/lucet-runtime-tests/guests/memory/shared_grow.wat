@@ -0,0 +1,9 @@
+(module
+  (memory 1 2 shared)
+  (func $grow (export "grow") (result i32)
+    (memory.grow (i32.const 1))
+  )
+  (func $size (export "size") (result i32)
+    (memory.size)
+  )
+)
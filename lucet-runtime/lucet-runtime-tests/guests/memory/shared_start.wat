@@ -0,0 +1,7 @@
+(module
+  (memory 1 1 shared)
+  (func $start
+    (drop (i32.atomic.rmw.add (i32.const 0) (i32.const 1)))
+  )
+  (start $start)
+)
@@ -0,0 +1,24 @@
+(module
+  (type $get (func (result i32)))
+  (table 4 anyfunc)
+  (func $one (type $get)
+    (i32.const 1)
+  )
+  (func $two (type $get)
+    (i32.const 2)
+  )
+  (elem (i32.const 0) $one)
+  (elem passive $one $two)
+  (func $call (export "call") (param i32) (result i32)
+    (call_indirect (type $get) (get_local 0))
+  )
+  (func $init (export "init") (param i32 i32 i32)
+    (table.init 1 (get_local 0) (get_local 1) (get_local 2))
+  )
+  (func $elem_drop (export "elem_drop")
+    (elem.drop 1)
+  )
+  (func $copy (export "copy") (param i32 i32 i32)
+    (table.copy (get_local 0) (get_local 1) (get_local 2))
+  )
+)
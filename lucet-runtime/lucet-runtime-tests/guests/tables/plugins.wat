@@ -0,0 +1,12 @@
+(module
+  (import "env" "plugins" (table $plugins 2 anyfunc))
+  (type $binop (func (param i32 i32) (result i32)))
+  (func $sub (type $binop)
+    (i32.sub (get_local 0) (get_local 1))
+  )
+  (elem (i32.const 0) $sub)
+  (export "plugins" (table $plugins))
+  (func $call_plugin (export "call_plugin") (param i32 i32 i32) (result i32)
+    (call_indirect (type $binop) (get_local 1) (get_local 2) (get_local 0))
+  )
+)
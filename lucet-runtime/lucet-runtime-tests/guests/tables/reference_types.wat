@@ -0,0 +1,41 @@
+(module
+  (type $ret_i32 (func (result i32)))
+  (table $funcs 2 anyfunc)
+  (table $more 2 anyfunc)
+  (table $refs 1 4 anyref)
+  (elem (i32.const 0) $seven)
+  (elem 1 (i32.const 1) $nine)
+  (func $seven (type $ret_i32)
+    (i32.const 7)
+  )
+  (func $nine (type $ret_i32)
+    (i32.const 9)
+  )
+  (func $call (export "call") (param i32) (result i32)
+    (call_indirect $funcs (type $ret_i32) (get_local 0))
+  )
+  (func $call_more (export "call_more") (param i32) (result i32)
+    (call_indirect $more (type $ret_i32) (get_local 0))
+  )
+  (func $install_nine (export "install_nine") (param i32)
+    (table.set $funcs (get_local 0) (ref.func $nine))
+  )
+  (func $copy_func (export "copy_func") (param i32 i32)
+    (table.set $funcs (get_local 0) (table.get $funcs (get_local 1)))
+  )
+  (func $get_ref (export "get_ref") (param i32) (result anyref)
+    (table.get $refs (get_local 0))
+  )
+  (func $set_ref (export "set_ref") (param i32 anyref)
+    (table.set $refs (get_local 0) (get_local 1))
+  )
+  (func $grow_refs (export "grow_refs") (param i32) (result i32)
+    (table.grow $refs (ref.null) (get_local 0))
+  )
+  (func $size_refs (export "size_refs") (result i32)
+    (table.size $refs)
+  )
+  (func $is_null (export "is_null") (param anyref) (result i32)
+    (ref.is_null (get_local 0))
+  )
+)
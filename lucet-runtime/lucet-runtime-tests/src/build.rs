@@ -1,7 +1,7 @@
 use failure::Error;
 use lucet_runtime_internals::module::DlModule;
 use lucet_wasi_sdk::{CompileOpts, Link, LinkOpts};
-use lucetc::{Bindings, Lucetc, LucetcOpts};
+use lucetc::{Bindings, ExternalLinker, Lucetc, LucetcOpts};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tempfile::TempDir;
@@ -12,6 +12,14 @@ pub fn test_module_c(dir: &str, cfile: &str) -> Result<Arc<DlModule>, Error> {
     c_test(c_path, bindings_path)
 }
 
+/// Like `test_module_c()`, but link the shared object with an external `ld` rather than the linker
+/// built into `lucetc`.
+pub fn test_module_c_ld(dir: &str, cfile: &str) -> Result<Arc<DlModule>, Error> {
+    let c_path = guest_file(dir, cfile);
+    let bindings_path = guest_file(dir, "bindings.json");
+    c_test_with_linker(c_path, bindings_path, Some(ExternalLinker::default()))
+}
+
 pub fn guest_file(dir: &str, fname: &str) -> PathBuf {
     let root = env!("CARGO_MANIFEST_DIR");
     let mut p = PathBuf::from(root);
@@ -23,6 +31,18 @@ pub fn guest_file(dir: &str, fname: &str) -> PathBuf {
 }
 
 pub fn c_test<P, Q>(c_file: P, bindings_file: Q) -> Result<Arc<DlModule>, Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    c_test_with_linker(c_file, bindings_file, None)
+}
+
+fn c_test_with_linker<P, Q>(
+    c_file: P,
+    bindings_file: Q,
+    external_linker: Option<ExternalLinker>,
+) -> Result<Arc<DlModule>, Error>
 where
     P: AsRef<Path>,
     Q: AsRef<Path>,
@@ -41,7 +61,10 @@ where
 
     let bindings = Bindings::from_file(bindings_file.as_ref())?;
 
-    let native_build = Lucetc::new(wasm_file).with_bindings(bindings);
+    let mut native_build = Lucetc::new(wasm_file).with_bindings(bindings);
+    if let Some(linker) = external_linker {
+        native_build.external_linker(linker);
+    }
 
     let so_file = workdir.path().join("out.so");
 
@@ -63,17 +86,65 @@ where
     P: AsRef<Path>,
     Q: AsRef<Path>,
 {
-    let workdir = TempDir::new().expect("create working directory");
+    wasm_test_linked(wasm_file, bindings_file, &[])
+}
 
-    let bindings = Bindings::from_file(&bindings_file)?;
+pub fn test_module_wasm_linked(
+    dir: &str,
+    wasmfile: &str,
+    linked_modules: &[&str],
+) -> Result<Arc<DlModule>, Error> {
+    let wasm_path = guest_file(dir, wasmfile);
+    let bindings_path = guest_file(dir, "bindings.json");
+    wasm_test_linked(wasm_path, bindings_path, linked_modules)
+}
 
-    let native_build = Lucetc::new(wasm_file).with_bindings(bindings);
+pub fn wasm_test_linked<P, Q>(
+    wasm_file: P,
+    bindings_file: Q,
+    linked_modules: &[&str],
+) -> Result<Arc<DlModule>, Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let workdir = TempDir::new().expect("create working directory");
 
     let so_file = workdir.path().join("out.so");
 
-    native_build.shared_object_file(so_file.clone())?;
+    wasm_so_linked(wasm_file, bindings_file, linked_modules, &so_file)?;
 
     let dlmodule = DlModule::load(so_file)?;
 
     Ok(dlmodule)
 }
+
+/// Compile a WebAssembly test guest to a shared object at `so_file`, for tests that load the
+/// module some other way than `DlModule::load()`.
+pub fn test_module_wasm_so(dir: &str, wasmfile: &str, so_file: &Path) -> Result<(), Error> {
+    let wasm_path = guest_file(dir, wasmfile);
+    let bindings_path = guest_file(dir, "bindings.json");
+    wasm_so_linked(wasm_path, bindings_path, &[], so_file)
+}
+
+fn wasm_so_linked<P, Q>(
+    wasm_file: P,
+    bindings_file: Q,
+    linked_modules: &[&str],
+    so_file: &Path,
+) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let bindings = Bindings::from_file(&bindings_file)?;
+
+    let mut native_build = Lucetc::new(wasm_file).with_bindings(bindings);
+    for module in linked_modules {
+        native_build.link_module(module);
+    }
+
+    native_build.shared_object_file(so_file)?;
+
+    Ok(())
+}
@@ -135,7 +135,9 @@ macro_rules! entrypoint_tests {
     ( $TestRegion:path ) => {
         use libc::c_void;
         use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
-        use lucet_runtime::{DlModule, Error, Limits, Module, Region, Val, WASM_PAGE_SIZE};
+        use lucet_runtime::{
+            DlModule, Error, Limits, Module, Region, TrapCodeType, Val, WASM_PAGE_SIZE,
+        };
         use std::sync::Arc;
         use $TestRegion as TestRegion;
         use $crate::entrypoint::{mock_calculator_module, wat_calculator_module};
@@ -461,6 +463,50 @@ macro_rules! entrypoint_tests {
             );
         }
 
+        #[test]
+        fn wat_run_results() {
+            let module = $crate::build::test_module_wasm("entrypoint", "multi_value.wat")
+                .expect("module builds and loads");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let results = inst
+                .run_results(b"divmod", &[17u32.into(), 5u32.into()])
+                .expect("instance runs");
+            match results.as_slice() {
+                [Val::I32(3), Val::I32(2)] => (),
+                results => panic!("unexpected results: {:?}", results),
+            }
+
+            let results = inst
+                .run_results(b"split", &[0x3_0000_0008u64.into()])
+                .expect("instance runs");
+            match results.as_slice() {
+                [Val::I32(8), Val::I64(3), Val::F64(half)] => {
+                    assert_eq!(*half, 0x3_0000_0008u64 as f64 / 2.0)
+                }
+                results => panic!("unexpected results: {:?}", results),
+            }
+
+            // functions with at most one result return it as usual
+            let results = inst
+                .run_results(b"add", &[123u32.into(), 456u32.into()])
+                .expect("instance runs");
+            match results.as_slice() {
+                [Val::I32(579)] => (),
+                results => panic!("unexpected results: {:?}", results),
+            }
+            let results = inst.run_results(b"nothing", &[]).expect("instance runs");
+            assert!(results.is_empty());
+
+            match inst.run_results(b"invalid", &[]) {
+                Err(Error::SymbolNotFound(sym)) => assert_eq!(sym, "invalid"),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
         use $crate::build::test_module_c;
         const TEST_REGION_INIT_VAL: libc::c_int = 123;
         const TEST_REGION_SIZE: libc::size_t = 4;
@@ -736,5 +782,137 @@ macro_rules! entrypoint_tests {
                 .expect("instance runs");
             assert_eq!(u64::from(retval), 3);
         }
+
+        #[no_mangle]
+        extern "C" fn callback_hostcall_nested(
+            vmctx: *mut lucet_vmctx,
+            cb_idx: u32,
+            x: u64,
+        ) -> u64 {
+            let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+            let retval = vmctx
+                .call_func_idx(0, cb_idx, &[x.into()])
+                .expect("nested call succeeds");
+            u64::from(retval) + 1
+        }
+
+        #[test]
+        fn entrypoint_callback_nested() {
+            let module =
+                test_module_c("entrypoint", "callback.c").expect("module builds and loads");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            // ((((0 + 1) + 1) * 2) + 1) + 1
+            let retval = inst
+                .run(b"callback_nested_entrypoint", &[0u64.into()])
+                .expect("instance runs");
+            assert_eq!(u64::from(retval), 6);
+
+            // the instance is ready to run again after nested calls return
+            let retval = inst
+                .run(b"callback_entrypoint", &[0u64.into()])
+                .expect("instance runs");
+            assert_eq!(u64::from(retval), 3);
+        }
+
+        #[test]
+        fn entrypoint_callback_nested_fault() {
+            let module =
+                test_module_c("entrypoint", "callback.c").expect("module builds and loads");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.run(b"callback_div_entrypoint", &[0u64.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode.ty, TrapCodeType::IntegerDivByZero);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            inst.reset().expect("instance resets");
+            let retval = inst
+                .run(b"callback_nested_entrypoint", &[0u64.into()])
+                .expect("instance runs");
+            assert_eq!(u64::from(retval), 6);
+        }
+
+        #[no_mangle]
+        extern "C" fn callback_hostcall_until_exhausted(
+            vmctx: *mut lucet_vmctx,
+            cb_idx: u32,
+            depth: u64,
+        ) -> u64 {
+            let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+            match vmctx.call_func_idx(0, cb_idx, &[depth.into()]) {
+                Ok(retval) => u64::from(retval),
+                Err(Error::LimitsExceeded(_)) => depth,
+                Err(e) => vmctx.terminate(e),
+            }
+        }
+
+        #[test]
+        fn entrypoint_nested_until_stack_exhausted() {
+            let module = $crate::build::test_module_wasm("entrypoint", "nested_stack.wat")
+                .expect("module builds and loads");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            // nested calls eventually fail for lack of stack, and the calls made until then
+            // return normally
+            let depth = inst
+                .run(b"nest_until_exhausted", &[])
+                .expect("instance runs");
+            let depth = u64::from(depth);
+            assert!(depth > 1);
+
+            // the stack is intact, so the same number of calls can be nested again
+            let retval = inst
+                .run(b"nest_until_exhausted", &[])
+                .expect("instance runs");
+            assert_eq!(u64::from(retval), depth);
+
+            // with half the stack, fewer calls fit
+            inst.set_stack_size(Some(Limits::default().stack_size / 2))
+                .expect("stack size can be set");
+            let retval = inst
+                .run(b"nest_until_exhausted", &[])
+                .expect("instance runs");
+            assert!(u64::from(retval) < depth);
+        }
+
+        #[test]
+        fn entrypoint_nested_stack_overflow() {
+            let module = $crate::build::test_module_wasm("entrypoint", "nested_stack.wat")
+                .expect("module builds and loads");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.run(b"overflow_nested", &[]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.fatal, false);
+                    assert_eq!(details.trapcode.ty, TrapCodeType::StackOverflow);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            inst.reset().expect("instance resets");
+            let retval = inst
+                .run(b"nest_until_exhausted", &[])
+                .expect("instance runs after a nested stack overflow");
+            assert!(u64::from(retval) > 1);
+        }
     };
 }
@@ -2,7 +2,7 @@
 macro_rules! globals_tests {
     ( $TestRegion:path ) => {
         use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
-        use lucet_runtime::{Limits, Region};
+        use lucet_runtime::{Export, Limits, Module, Region, Signature, Val, ValueType};
         use lucet_runtime_internals::instance::InstanceInternal;
         use std::sync::Arc;
         use $TestRegion as TestRegion;
@@ -41,5 +41,81 @@ macro_rules! globals_tests {
             let heap_u32 = unsafe { inst.alloc().heap_u32() };
             assert_eq!(heap_u32[0..=2], [3, 2, 6]);
         }
+
+        #[test]
+        fn typed_exported_globals() {
+            let module = test_module_wasm("globals", "typed_exports.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.get_global("counter") {
+                Ok(Val::I32(-7)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            let retval = inst.run(b"bump", &[10i32.into()]).expect("instance runs");
+            assert_eq!(i32::from(retval), 3);
+            match inst.get_global("counter") {
+                Ok(Val::I32(3)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            inst.set_global("ratio", Val::F64(0.25))
+                .expect("global can be set");
+            let retval = inst.run(b"double_ratio", &[]).expect("instance runs");
+            assert_eq!(f64::from(retval), 0.5);
+            match inst.get_global("ratio") {
+                Ok(Val::F64(v)) => assert_eq!(v, 0.5),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn module_exports() {
+            let module = test_module_wasm("globals", "typed_exports.wat")
+                .expect("module compiled and loaded");
+
+            let mut functions = vec![];
+            let mut globals = vec![];
+            let mut memories = vec![];
+            for export in module.exports() {
+                match export {
+                    Export::Function { name, signature } => {
+                        functions.push((name.to_owned(), signature.clone()))
+                    }
+                    Export::Global { name, ty, .. } => globals.push((name.to_owned(), ty)),
+                    Export::Memory { name, heap_spec } => {
+                        assert_eq!(heap_spec.initial_size, 64 * 1024);
+                        memories.push(name.to_owned())
+                    }
+                    Export::Table { name, .. } => panic!("unexpected table export: {}", name),
+                }
+            }
+            functions.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                functions,
+                vec![
+                    (
+                        "bump".to_owned(),
+                        Signature::new(vec![ValueType::I32], Some(ValueType::I32))
+                    ),
+                    (
+                        "double_ratio".to_owned(),
+                        Signature::new(vec![], Some(ValueType::F64))
+                    ),
+                ]
+            );
+            assert_eq!(
+                globals,
+                vec![
+                    ("counter".to_owned(), ValueType::I32),
+                    ("ratio".to_owned(), ValueType::F64),
+                ]
+            );
+            assert_eq!(memories, vec!["memory".to_owned()]);
+        }
     };
 }
@@ -232,6 +232,108 @@ macro_rules! guest_fault_tests {
             });
         }
 
+        #[test]
+        fn oob_moved_between_threads() {
+            test_nonex(|| {
+                let module = mock_traps_module();
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                // fault on one thread
+                let mut inst = std::thread::spawn(move || {
+                    let mut inst = inst;
+                    match inst.run(b"oob", &[]) {
+                        Err(Error::RuntimeFault(details)) => {
+                            assert_eq!(details.trapcode.ty, TrapCodeType::HeapOutOfBounds);
+                        }
+                        res => panic!("unexpected result: {:?}", res),
+                    }
+                    inst
+                })
+                .join()
+                .expect("can join on first thread");
+
+                // then reset and fault again on another, which must have its own signal stack
+                inst.reset().expect("instance resets");
+                let mut inst = std::thread::spawn(move || {
+                    let mut inst = inst;
+                    run_onetwothree(&mut inst);
+                    match inst.run(b"oob", &[]) {
+                        Err(Error::RuntimeFault(details)) => {
+                            assert_eq!(details.trapcode.ty, TrapCodeType::HeapOutOfBounds);
+                        }
+                        res => panic!("unexpected result: {:?}", res),
+                    }
+                    inst
+                })
+                .join()
+                .expect("can join on second thread");
+
+                // and finally back on the original thread
+                inst.reset().expect("instance resets");
+                run_onetwothree(&mut inst);
+            });
+        }
+
+        #[test]
+        fn instances_moved_between_threads_concurrently() {
+            const INSTANCES: usize = 4;
+            const THREADS: usize = 4;
+            const RUNS: usize = 50;
+
+            test_nonex(|| {
+                let module = mock_traps_module();
+                let region = TestRegion::create(INSTANCES, &Limits::default())
+                    .expect("region can be created");
+
+                // a shared queue of idle instances, which each worker pops from and pushes back to,
+                // so an instance is run by many different threads in turn
+                let queue = Arc::new(Mutex::new(Vec::new()));
+                for _ in 0..INSTANCES {
+                    let inst = region
+                        .new_instance(module.clone())
+                        .expect("instance can be created");
+                    queue.lock().unwrap().push(inst);
+                }
+
+                let workers: Vec<_> = (0..THREADS)
+                    .map(|_| {
+                        let queue = queue.clone();
+                        std::thread::spawn(move || {
+                            for i in 0..RUNS {
+                                let inst = queue.lock().unwrap().pop();
+                                if let Some(mut inst) = inst {
+                                    if i % 5 == 0 {
+                                        match inst.run(b"oob", &[]) {
+                                            Err(Error::RuntimeFault(details)) => assert_eq!(
+                                                details.trapcode.ty,
+                                                TrapCodeType::HeapOutOfBounds
+                                            ),
+                                            res => panic!("unexpected result: {:?}", res),
+                                        }
+                                        inst.reset().expect("instance resets");
+                                    } else {
+                                        run_onetwothree(&mut inst);
+                                    }
+                                    queue.lock().unwrap().push(inst);
+                                } else {
+                                    std::thread::yield_now();
+                                }
+                            }
+                        })
+                    })
+                    .collect();
+
+                for worker in workers {
+                    worker.join().expect("can join on worker");
+                }
+                assert_eq!(queue.lock().unwrap().len(), INSTANCES);
+            });
+        }
+
         #[test]
         fn hostcall_error() {
             test_nonex(|| {
@@ -370,8 +472,11 @@ macro_rules! guest_fault_tests {
             })
         }
 
+        /// Lucet installs its handlers once per process and no longer saves and restores the host's
+        /// handlers around each run, so a host handler installed over Lucet's must stay in place.
+        /// Chaining from Lucet's handlers to one installed earlier is tested in `signal_chaining`.
         #[test]
-        fn sigsegv_handler_saved_restored() {
+        fn sigsegv_handler_not_overwritten() {
             lazy_static! {
                 static ref HOST_SIGSEGV_TRIGGERED: Mutex<bool> = Mutex::new(false);
             }
@@ -401,7 +506,8 @@ macro_rules! guest_fault_tests {
                     SaFlags::SA_RESTART,
                     SigSet::all(),
                 );
-                unsafe { sigaction(Signal::SIGSEGV, &sa).expect("sigaction succeeds") };
+                let saved_sa =
+                    unsafe { sigaction(Signal::SIGSEGV, &sa).expect("sigaction succeeds") };
 
                 match inst.run(b"illegal_instr", &[]) {
                     Err(Error::RuntimeFault(details)) => {
@@ -410,7 +516,7 @@ macro_rules! guest_fault_tests {
                     res => panic!("unexpected result: {:?}", res),
                 }
 
-                // now make sure that the host sigaction has been restored
+                // now make sure that the host sigaction still handles signals outside of the guest
                 unsafe {
                     recoverable_ptr_setup();
                 }
@@ -426,11 +532,9 @@ macro_rules! guest_fault_tests {
                 // clean up
                 unsafe {
                     recoverable_ptr_teardown();
-                    sigaction(
-                        Signal::SIGSEGV,
-                        &SigAction::new(SigHandler::SigDfl, SaFlags::SA_RESTART, SigSet::empty()),
-                    )
-                    .expect("sigaction succeeds");
+                    // put back whichever action was installed before ours, which may be the Lucet
+                    // handler installed by an earlier test
+                    sigaction(Signal::SIGSEGV, &saved_sa).expect("sigaction succeeds");
                 }
 
                 drop(recoverable_ptr_lock);
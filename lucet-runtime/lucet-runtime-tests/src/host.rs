@@ -3,10 +3,13 @@ macro_rules! host_tests {
     ( $TestRegion:path ) => {
         use libc::c_void;
         use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
-        use lucet_runtime::{DlModule, Error, Limits, Region, TrapCodeType};
+        use lucet_runtime::{
+            lucet_hostcalls, CtxKey, DlModule, Error, Limits, Region, TerminationDetails,
+            TrapCodeType,
+        };
         use std::sync::Arc;
         use $TestRegion as TestRegion;
-        use $crate::build::test_module_c;
+        use $crate::build::{test_module_c, test_module_c_ld};
         #[test]
         fn load_module() {
             let _module = test_module_c("host", "trivial.c").expect("build and load module");
@@ -38,12 +41,51 @@ macro_rules! host_tests {
             }
         }
 
+        /// Greetings seen by `hostcall_test_func_hello_scoped`, borrowed for a single run.
+        struct Greetings<'a> {
+            seen: &'a mut Vec<String>,
+        }
+
+        struct GreetingsKey;
+
+        impl<'a> CtxKey<'a> for GreetingsKey {
+            type Ctx = Greetings<'a>;
+        }
+
+        #[no_mangle]
+        extern "C" fn hostcall_test_func_hello_scoped(
+            vmctx: *mut lucet_vmctx,
+            hello_ptr: u32,
+            hello_len: u32,
+        ) {
+            unsafe {
+                let mut vmctx = Vmctx::from_raw(vmctx);
+                let heap = vmctx.heap();
+                let hello = heap.as_ptr() as usize + hello_ptr as usize;
+                if !vmctx.check_heap(hello as *const c_void, hello_len as usize) {
+                    vmctx.terminate("heap access");
+                }
+                let hello = std::slice::from_raw_parts(hello as *const u8, hello_len as usize);
+                let greetings = vmctx.get_scoped_ctx_mut::<GreetingsKey>();
+                greetings
+                    .seen
+                    .push(String::from_utf8_lossy(hello).into_owned());
+            }
+        }
+
         const ERROR_MESSAGE: &'static str = "hostcall_test_func_hostcall_error";
         #[no_mangle]
         extern "C" fn hostcall_test_func_hostcall_error(vmctx: *mut lucet_vmctx) {
             unsafe { Vmctx::from_raw(vmctx).terminate(ERROR_MESSAGE) }
         }
 
+        const PANIC_MESSAGE: &'static str = "hostcall_test_func_hostcall_panic";
+        lucet_hostcalls! {
+            pub extern "C" fn hostcall_test_func_hostcall_panic(vmctx: *mut lucet_vmctx) {
+                panic!(PANIC_MESSAGE)
+            }
+        }
+
         #[test]
         fn instantiate_trivial() {
             let module = test_module_c("host", "trivial.c").expect("build and load module");
@@ -79,6 +121,80 @@ macro_rules! host_tests {
             assert!(inst.get_embed_ctx::<bool>().unwrap());
         }
 
+        #[test]
+        fn run_hello_linked_with_ld() {
+            use lucet_runtime_internals::module::ModuleInternal;
+
+            // a shared object linked by `ld` loads, resolves its symbols, and runs just like the
+            // one from the built-in linker
+            let builtin = test_module_c("host", "hello.c").expect("build and load module");
+            let module = test_module_c_ld("host", "hello.c").expect("build and load module");
+            let exports = |m: &DlModule| {
+                m.function_exports()
+                    .iter()
+                    .map(|e| e.name().to_owned())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(exports(&module), exports(&builtin));
+            for name in exports(&module) {
+                builtin
+                    .get_export_func(name.as_bytes())
+                    .expect("built-in linker export resolves");
+                module
+                    .get_export_func(name.as_bytes())
+                    .expect("ld export resolves");
+            }
+            assert_eq!(module.trap_manifest().len(), builtin.trap_manifest().len());
+
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(false)
+                .build()
+                .expect("instance can be created");
+
+            inst.run(b"main", &[]).expect("instance runs");
+
+            assert!(inst.get_embed_ctx::<bool>().unwrap());
+        }
+
+        #[test]
+        fn run_hello_with_ctx() {
+            let module = test_module_c("host", "hello_scoped.c").expect("build and load module");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let mut seen = vec![];
+            let mut greetings = Greetings { seen: &mut seen };
+            inst.run_with_ctx::<GreetingsKey>(&mut greetings, b"main", &[])
+                .expect("instance runs");
+            assert_eq!(seen, vec!["hello world\0".to_owned()]);
+
+            // the borrowed context is gone once the run is over
+            match inst.run(b"main", &[]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::GetEmbedCtx)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn run_hello_missing_ctx() {
+            let module = test_module_c("host", "hello.c").expect("build and load module");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.run(b"main", &[]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::GetEmbedCtx)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
         #[test]
         fn run_hostcall_error() {
             let module = test_module_c("host", "hostcall_error.c").expect("build and load module");
@@ -102,6 +218,29 @@ macro_rules! host_tests {
             }
         }
 
+        #[test]
+        fn run_hostcall_panic() {
+            let module = test_module_c("host", "hostcall_panic.c").expect("build and load module");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.run(b"main", &[]) {
+                Err(Error::RuntimeTerminated(term)) => {
+                    assert_eq!(
+                        *term
+                            .panic_payload()
+                            .expect("termination was caused by a panic")
+                            .downcast_ref::<&'static str>()
+                            .expect("panic payload was static str"),
+                        PANIC_MESSAGE
+                    );
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
         #[test]
         fn run_fpe() {
             let module = test_module_c("host", "fpe.c").expect("build and load module");
@@ -4,7 +4,11 @@ pub mod globals;
 pub mod guest_fault;
 pub mod helpers;
 pub mod host;
+pub mod linking;
 pub mod memory;
+pub mod signal_chaining;
+pub mod signal_opt_out;
 pub mod stack;
 pub mod start;
 pub mod strcmp;
+pub mod tables;
@@ -0,0 +1,134 @@
+#[macro_export]
+macro_rules! linking_tests {
+    ( $TestRegion:path ) => {
+        use lucet_runtime::{Error, Limits, Region, TerminationDetails, TrapCodeType};
+        use lucet_runtime_internals::instance::InstanceInternal;
+        use $TestRegion as TestRegion;
+        use $crate::build::{test_module_wasm, test_module_wasm_linked};
+
+        #[test]
+        fn call_linked_import() {
+            let provider_module =
+                test_module_wasm("linking", "provider.wat").expect("module compiled and loaded");
+            let consumer_module = test_module_wasm_linked("linking", "consumer.wat", &["provider"])
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut provider = region
+                .new_instance(provider_module)
+                .expect("instance can be created");
+            let mut consumer = region
+                .new_instance(consumer_module)
+                .expect("instance can be created");
+
+            unsafe { consumer.link_instance("provider", &mut provider) }
+                .expect("instances can be linked");
+
+            let retval = consumer
+                .run(b"main", &[2u32.into(), 3u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 5);
+            consumer
+                .run(b"main", &[4u32.into(), 5u32.into()])
+                .expect("instance runs");
+
+            // each instance keeps its own heap and globals
+            let consumer_heap = unsafe { consumer.alloc().heap_u32() };
+            assert_eq!(consumer_heap[0], 42);
+            let provider_heap = unsafe { provider.alloc().heap_u32() };
+            assert_eq!(provider_heap[0], 2);
+        }
+
+        #[test]
+        fn call_unlinked_import() {
+            let consumer_module = test_module_wasm_linked("linking", "consumer.wat", &["provider"])
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut consumer = region
+                .new_instance(consumer_module)
+                .expect("instance can be created");
+
+            match consumer.run(b"main", &[2u32.into(), 3u32.into()]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::UnlinkedImport)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn fault_in_linked_import() {
+            let provider_module =
+                test_module_wasm("linking", "provider.wat").expect("module compiled and loaded");
+            let consumer_module = test_module_wasm_linked("linking", "consumer.wat", &["provider"])
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut provider = region
+                .new_instance(provider_module)
+                .expect("instance can be created");
+            let mut consumer = region
+                .new_instance(consumer_module)
+                .expect("instance can be created");
+
+            unsafe { consumer.link_instance("provider", &mut provider) }
+                .expect("instances can be linked");
+
+            match consumer.run(b"call_fault", &[]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode.ty, TrapCodeType::Unreachable);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            // the fault is recorded in the calling instance
+            consumer.reset().expect("instance resets");
+            let retval = consumer
+                .run(b"main", &[2u32.into(), 3u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 5);
+        }
+
+        #[test]
+        fn link_unknown_module() {
+            let provider_module =
+                test_module_wasm("linking", "provider.wat").expect("module compiled and loaded");
+            let consumer_module = test_module_wasm_linked("linking", "consumer.wat", &["provider"])
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut provider = region
+                .new_instance(provider_module)
+                .expect("instance can be created");
+            let mut consumer = region
+                .new_instance(consumer_module)
+                .expect("instance can be created");
+
+            match unsafe { consumer.link_instance("other", &mut provider) } {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn link_mismatched_signature() {
+            let provider_module =
+                test_module_wasm("linking", "provider.wat").expect("module compiled and loaded");
+            let consumer_module = test_module_wasm_linked("linking", "mismatch.wat", &["provider"])
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut provider = region
+                .new_instance(provider_module)
+                .expect("instance can be created");
+            let mut consumer = region
+                .new_instance(consumer_module)
+                .expect("instance can be created");
+
+            match unsafe { consumer.link_instance("provider", &mut provider) } {
+                Err(Error::ModuleError(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            // the import with a matching signature was not linked either
+            match consumer.run(b"main", &[2u32.into(), 3u32.into()]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::UnlinkedImport)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+    };
+}
@@ -2,8 +2,9 @@
 macro_rules! memory_tests {
     ( $TestRegion:path ) => {
         use lazy_static::lazy_static;
-        use lucet_runtime::{DlModule, Limits, Region};
+        use lucet_runtime::{DlModule, Error, Limits, Region, TrapCodeType, WASM_PAGE_SIZE};
         use std::sync::Mutex;
+        use std::thread;
         use $TestRegion as TestRegion;
         use $crate::build::test_module_wasm;
 
@@ -38,5 +39,194 @@ macro_rules! memory_tests {
             // guest then puts the result of the current memory call in heap[4] (indexed by bytes)
             assert_eq!(heap[1], 5);
         }
+
+        #[test]
+        fn bulk_memory_copy_and_fill() {
+            let module = test_module_wasm("memory", "bulk_memory.wat")
+                .expect("compile and load bulk_memory.wasm");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.heap_mut()[0..6].copy_from_slice(b"abcdef");
+            // overlapping ranges are copied as if through a temporary buffer
+            inst.run(b"copy", &[2u32.into(), 0u32.into(), 4u32.into()])
+                .expect("instance runs");
+            assert_eq!(&inst.heap()[0..6], b"ababcd");
+            inst.run(b"fill", &[1u32.into(), (b'z' as u32).into(), 3u32.into()])
+                .expect("instance runs");
+            assert_eq!(&inst.heap()[0..6], b"azzzcd");
+        }
+
+        #[test]
+        fn bulk_memory_copy_out_of_bounds() {
+            let module = test_module_wasm("memory", "bulk_memory.wat")
+                .expect("compile and load bulk_memory.wasm");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.heap_mut()[0..4].copy_from_slice(b"abcd");
+            let heap_len = inst.heap().len() as u32;
+            match inst.run(b"copy", &[(heap_len - 2).into(), 0u32.into(), 4u32.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode.ty, TrapCodeType::HeapOutOfBounds);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+            // nothing was copied, not even the part that was in bounds
+            assert_eq!(&inst.heap()[heap_len as usize - 2..], &[0, 0]);
+        }
+
+        #[test]
+        fn bulk_memory_init_and_drop() {
+            let module = test_module_wasm("memory", "bulk_memory.wat")
+                .expect("compile and load bulk_memory.wasm");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.run(b"init", &[10u32.into(), 1u32.into(), 3u32.into()])
+                .expect("instance runs");
+            assert_eq!(&inst.heap()[10..13], b"ell");
+
+            // a dropped segment behaves as if it were empty
+            inst.run(b"drop", &[]).expect("instance runs");
+            inst.run(b"init", &[0u32.into(), 0u32.into(), 0u32.into()])
+                .expect("empty init of a dropped segment succeeds");
+            match inst.run(b"init", &[0u32.into(), 0u32.into(), 1u32.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode.ty, TrapCodeType::HeapOutOfBounds);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            // until the instance is reset
+            inst.reset().expect("instance resets");
+            inst.run(b"init", &[0u32.into(), 0u32.into(), 5u32.into()])
+                .expect("instance runs");
+            assert_eq!(&inst.heap()[0..5], b"hello");
+        }
+
+        #[test]
+        fn shared_memory_between_instances() {
+            let module =
+                test_module_wasm("memory", "atomics.wat").expect("compile and load atomics.wasm");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut first = region
+                .new_instance(module.clone())
+                .expect("instance can be created");
+            let shared_heap = first.shared_heap().expect("heap is shared").clone();
+            let mut second = region
+                .new_instance_builder(module)
+                .with_shared_heap(shared_heap)
+                .build()
+                .expect("instance can be created");
+
+            // the data segments are only copied in when the shared heap is created
+            assert_eq!(second.heap()[0], 42);
+            first.heap_mut()[0] = 0;
+            second.reset().expect("instance resets");
+            assert_eq!(second.heap()[0], 0);
+
+            let retval = first
+                .run(b"add", &[16u32.into(), 5u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 0);
+            let retval = second
+                .run(b"add", &[16u32.into(), 2u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 5);
+            assert_eq!(first.heap_u32()[4], 7);
+
+            // shared heaps cannot grow
+            let retval = first.run(b"grow", &[]).expect("instance runs");
+            assert_eq!(u32::from(retval) as i32, -1);
+        }
+
+        #[test]
+        fn shared_memory_start_runs_once() {
+            let module = test_module_wasm("memory", "shared_start.wat")
+                .expect("compile and load shared_start.wasm");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut first = region
+                .new_instance(module.clone())
+                .expect("instance can be created");
+            assert_eq!(first.heap_u32()[0], 1);
+
+            let mut second = region
+                .new_instance_builder(module)
+                .with_shared_heap(first.shared_heap().expect("heap is shared").clone())
+                .build()
+                .expect("instance can be created");
+            assert_eq!(second.heap_u32()[0], 1);
+
+            first.reset().expect("instance resets");
+            second.reset().expect("instance resets");
+            assert_eq!(first.heap_u32()[0], 1);
+        }
+
+        #[test]
+        fn shared_memory_cannot_grow() {
+            let module = test_module_wasm("memory", "shared_grow.wat")
+                .expect("compile and load shared_grow.wasm");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            // the module allows two pages, but a shared heap keeps its initial size
+            let retval = inst.run(b"grow", &[]).expect("instance runs");
+            assert_eq!(u32::from(retval) as i32, -1);
+            match inst.grow_memory(1) {
+                Err(Error::LimitsExceeded(_)) => (),
+                res => panic!("unexpected result from growing a shared heap: {:?}", res),
+            }
+            let retval = inst.run(b"size", &[]).expect("instance runs");
+            assert_eq!(u32::from(retval), 1);
+            assert_eq!(inst.heap().len(), WASM_PAGE_SIZE as usize);
+        }
+
+        #[test]
+        fn shared_memory_wait_and_notify() {
+            let module =
+                test_module_wasm("memory", "atomics.wat").expect("compile and load atomics.wasm");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut notifier = region
+                .new_instance(module.clone())
+                .expect("instance can be created");
+            let mut waiter = region
+                .new_instance_builder(module)
+                .with_shared_heap(notifier.shared_heap().unwrap().clone())
+                .build()
+                .expect("instance can be created");
+
+            // a wait on a different value returns immediately
+            let retval = waiter
+                .run(b"wait", &[0u32.into(), 0u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 1);
+
+            let waiting = thread::spawn(move || {
+                let retval = waiter
+                    .run(b"wait", &[0u32.into(), 42u32.into()])
+                    .expect("instance runs");
+                u32::from(retval)
+            });
+            // notify until the other thread has started waiting
+            loop {
+                let retval = notifier
+                    .run(b"notify", &[0u32.into()])
+                    .expect("instance runs");
+                if u32::from(retval) == 1 {
+                    break;
+                }
+                thread::yield_now();
+            }
+            assert_eq!(waiting.join().unwrap(), 0);
+        }
     };
 }
@@ -0,0 +1,110 @@
+/// Tests that Lucet's signal handlers chain to a host handler installed before them.
+///
+/// Lucet installs its handlers once per process, so these tests must be instantiated in a test
+/// binary of their own, where no instance has run before the host handler is installed.
+#[macro_export]
+macro_rules! signal_chaining_tests {
+    ( $TestRegion:path ) => {
+        use libc::{c_void, siginfo_t, SIGSEGV};
+        use lucet_runtime::vmctx::lucet_vmctx;
+        use lucet_runtime::{Error, Instance, Limits, Region, SignalBehavior, TrapCode};
+        use nix::sys::signal::{raise, sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+        use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+        use $TestRegion as TestRegion;
+        use $crate::helpers::{test_ex, MockModuleBuilder};
+
+        static HOST_SIGSEGV_COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
+        static GUEST_SIGSEGV_COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
+
+        extern "C" fn host_sigsegv_handler(
+            signum: libc::c_int,
+            _siginfo_ptr: *mut siginfo_t,
+            _ucontext_ptr: *mut c_void,
+        ) {
+            assert!(signum == SIGSEGV);
+            HOST_SIGSEGV_COUNT.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn guest_sigsegv_handler(
+            _inst: &Instance,
+            _trapcode: &TrapCode,
+            signum: libc::c_int,
+            _siginfo_ptr: *const siginfo_t,
+            _ucontext_ptr: *const c_void,
+        ) -> SignalBehavior {
+            assert!(signum == SIGSEGV);
+            GUEST_SIGSEGV_COUNT.fetch_add(1, Ordering::SeqCst);
+            SignalBehavior::Terminate
+        }
+
+        extern "C" fn raise_sigsegv(_vmctx: *mut lucet_vmctx) {
+            raise(Signal::SIGSEGV).expect("raise succeeds");
+        }
+
+        fn run_raise_sigsegv(inst: &mut Instance) {
+            match inst.run(b"raise_sigsegv", &[]) {
+                Err(Error::RuntimeTerminated(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            inst.reset().expect("instance resets");
+        }
+
+        fn current_sigsegv_handler() -> libc::sighandler_t {
+            unsafe {
+                let mut sa: libc::sigaction = std::mem::zeroed();
+                assert_eq!(libc::sigaction(SIGSEGV, std::ptr::null(), &mut sa), 0);
+                sa.sa_sigaction
+            }
+        }
+
+        #[test]
+        fn host_handler_chained() {
+            test_ex(|| {
+                let sa = SigAction::new(
+                    SigHandler::SigAction(host_sigsegv_handler),
+                    SaFlags::SA_RESTART | SaFlags::SA_SIGINFO,
+                    SigSet::empty(),
+                );
+                unsafe { sigaction(Signal::SIGSEGV, &sa).expect("sigaction succeeds") };
+
+                let module = MockModuleBuilder::new()
+                    .with_export_func(b"raise_sigsegv", raise_sigsegv as *const extern "C" fn())
+                    .build();
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+                inst.set_signal_handler(guest_sigsegv_handler);
+
+                // a signal in the guest goes to the instance, not the host handler
+                run_raise_sigsegv(&mut inst);
+                assert_eq!(GUEST_SIGSEGV_COUNT.load(Ordering::SeqCst), 1);
+                assert_eq!(HOST_SIGSEGV_COUNT.load(Ordering::SeqCst), 0);
+
+                // the Lucet handler stays installed after the instance stops running...
+                assert_ne!(
+                    current_sigsegv_handler(),
+                    host_sigsegv_handler as libc::sighandler_t
+                );
+
+                // ...and passes signals outside of the guest on to the host handler
+                raise(Signal::SIGSEGV).expect("raise succeeds");
+                assert_eq!(HOST_SIGSEGV_COUNT.load(Ordering::SeqCst), 1);
+                assert_eq!(GUEST_SIGSEGV_COUNT.load(Ordering::SeqCst), 1);
+
+                // and the instance can still handle its own signals afterwards, on any thread
+                let mut inst = std::thread::spawn(move || {
+                    let mut inst = inst;
+                    run_raise_sigsegv(&mut inst);
+                    inst
+                })
+                .join()
+                .expect("can join on child");
+                run_raise_sigsegv(&mut inst);
+                assert_eq!(GUEST_SIGSEGV_COUNT.load(Ordering::SeqCst), 3);
+                assert_eq!(HOST_SIGSEGV_COUNT.load(Ordering::SeqCst), 1);
+            })
+        }
+    };
+}
@@ -0,0 +1,112 @@
+/// Tests for embedders that install their own signal handlers and call `handle_signal()` from them.
+///
+/// Opting out of Lucet's signal handlers must happen before any instance runs in the process, so
+/// these tests must be instantiated in a test binary of their own.
+#[macro_export]
+macro_rules! signal_opt_out_tests {
+    ( $TestRegion:path ) => {
+        use libc::{c_void, siginfo_t, SIGSEGV};
+        use lucet_runtime::vmctx::lucet_vmctx;
+        use lucet_runtime::{
+            disable_signal_handler_installation, handle_signal, Error, Instance, Limits, Region,
+            SignalBehavior, TrapCode,
+        };
+        use nix::sys::signal::{raise, sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+        use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+        use $TestRegion as TestRegion;
+        use $crate::helpers::{test_ex, MockModuleBuilder};
+
+        static EMBEDDER_SIGSEGV_COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
+        static GUEST_SIGSEGV_COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
+
+        extern "C" fn embedder_sigsegv_handler(
+            signum: libc::c_int,
+            siginfo_ptr: *mut siginfo_t,
+            ucontext_ptr: *mut c_void,
+        ) {
+            if unsafe { handle_signal(signum, siginfo_ptr, ucontext_ptr) } {
+                return;
+            }
+            assert!(signum == SIGSEGV);
+            EMBEDDER_SIGSEGV_COUNT.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn guest_sigsegv_handler(
+            _inst: &Instance,
+            _trapcode: &TrapCode,
+            signum: libc::c_int,
+            _siginfo_ptr: *const siginfo_t,
+            _ucontext_ptr: *const c_void,
+        ) -> SignalBehavior {
+            assert!(signum == SIGSEGV);
+            GUEST_SIGSEGV_COUNT.fetch_add(1, Ordering::SeqCst);
+            SignalBehavior::Terminate
+        }
+
+        extern "C" fn raise_sigsegv(_vmctx: *mut lucet_vmctx) {
+            raise(Signal::SIGSEGV).expect("raise succeeds");
+        }
+
+        #[test]
+        fn embedder_handler_calls_handle_signal() {
+            test_ex(|| {
+                disable_signal_handler_installation().expect("can opt out of signal handlers");
+                // opting out is idempotent
+                disable_signal_handler_installation().expect("can opt out of signal handlers");
+
+                let sa = SigAction::new(
+                    SigHandler::SigAction(embedder_sigsegv_handler),
+                    SaFlags::SA_RESTART | SaFlags::SA_SIGINFO | SaFlags::SA_ONSTACK,
+                    SigSet::empty(),
+                );
+                unsafe { sigaction(Signal::SIGSEGV, &sa).expect("sigaction succeeds") };
+
+                let module = MockModuleBuilder::new()
+                    .with_export_func(b"raise_sigsegv", raise_sigsegv as *const extern "C" fn())
+                    .build();
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+                inst.set_signal_handler(guest_sigsegv_handler);
+
+                // a signal in the guest is handled by Lucet through the embedder's handler
+                match inst.run(b"raise_sigsegv", &[]) {
+                    Err(Error::RuntimeTerminated(_)) => (),
+                    res => panic!("unexpected result: {:?}", res),
+                }
+                assert_eq!(GUEST_SIGSEGV_COUNT.load(Ordering::SeqCst), 1);
+                assert_eq!(EMBEDDER_SIGSEGV_COUNT.load(Ordering::SeqCst), 0);
+
+                // Lucet did not replace the embedder's handler...
+                let current_handler = unsafe {
+                    let mut current_sa: libc::sigaction = std::mem::zeroed();
+                    assert_eq!(
+                        libc::sigaction(SIGSEGV, std::ptr::null(), &mut current_sa),
+                        0
+                    );
+                    current_sa.sa_sigaction
+                };
+                assert_eq!(
+                    current_handler,
+                    embedder_sigsegv_handler as libc::sighandler_t
+                );
+
+                // ...which handles signals outside of the guest itself
+                raise(Signal::SIGSEGV).expect("raise succeeds");
+                assert_eq!(EMBEDDER_SIGSEGV_COUNT.load(Ordering::SeqCst), 1);
+                assert_eq!(GUEST_SIGSEGV_COUNT.load(Ordering::SeqCst), 1);
+
+                // clean up
+                unsafe {
+                    sigaction(
+                        Signal::SIGSEGV,
+                        &SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty()),
+                    )
+                    .expect("sigaction succeeds");
+                }
+            })
+        }
+    };
+}
@@ -130,9 +130,10 @@ macro_rules! stack_tests {
             expect_ok(stack_testcase(3).expect("generate stack_testcase 3"), 1);
         }
 
-        // The test with 64 locals should cause a stack overflow on the 481st recursion.  The trap
-        // table knows about all of the instructions in the function that manipulate the stack, so
-        // the catch mechanism for this is the usual one.
+        // The test with 64 locals should cause a stack overflow on the 481st recursion with the
+        // default stack size, given the frame size Cranelift currently chooses for the function.
+        // The trap table knows about all of the instructions in the function that manipulate the
+        // stack, so the catch mechanism for this is the usual one.
 
         #[test]
         fn expect_ok_locals64_1() {
@@ -229,5 +230,106 @@ macro_rules! stack_tests {
                 true,
             );
         }
+
+        fn measured_stack_usage(module: Arc<DlModule>, recursion_depth: i32) -> Option<usize> {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+            inst.set_measure_stack_usage(true);
+            let _ = inst.run(b"localpalooza", &[recursion_depth.into()]);
+            inst.run_stats().stack_usage
+        }
+
+        /// Measure the stack used by each recursion of `localpalooza`, so that the tests below
+        /// can derive recursion depths from stack sizes rather than depend on its frame size.
+        fn stack_usage_per_recursion(module: Arc<DlModule>) -> usize {
+            let shallow = measured_stack_usage(module.clone(), 1).expect("usage is measured");
+            let deep = measured_stack_usage(module, 101).expect("usage is measured");
+            (deep - shallow) / 100
+        }
+
+        #[test]
+        fn stack_usage_not_measured_by_default() {
+            let module = stack_testcase(64).expect("generate stack_testcase 64");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+            inst.run(b"localpalooza", &[2i32.into()]).expect("instance runs");
+            assert_eq!(inst.run_stats().stack_usage, None);
+        }
+
+        #[test]
+        fn stack_usage_grows_with_recursion() {
+            let module = stack_testcase(64).expect("generate stack_testcase 64");
+            let shallow = measured_stack_usage(module.clone(), 1).expect("usage is measured");
+            let deep = measured_stack_usage(module, 100).expect("usage is measured");
+            assert!(shallow > 0);
+            assert!(deep > shallow);
+            assert!(deep <= Limits::default().stack_size);
+        }
+
+        #[test]
+        fn stack_usage_after_overflow() {
+            let module = stack_testcase(64).expect("generate stack_testcase 64");
+            let per_recursion = stack_usage_per_recursion(module.clone());
+            // enough recursions to need more than the whole stack
+            let depth = Limits::default().stack_size / per_recursion + 1;
+            let usage = measured_stack_usage(module, depth as i32).expect("usage is measured");
+            // overflowing the stack should have touched nearly all of it
+            assert!(usage > Limits::default().stack_size / 2);
+        }
+
+        #[test]
+        fn smaller_stack_size() {
+            let module = stack_testcase(64).expect("generate stack_testcase 64");
+            let per_recursion = stack_usage_per_recursion(module.clone());
+            let stack_size = Limits::default().stack_size;
+            // enough recursions to need about three quarters of the whole stack
+            let depth = (stack_size * 3 / 4 / per_recursion) as i32;
+
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+            inst.run(b"localpalooza", &[depth.into()])
+                .expect("instance runs with the whole stack");
+
+            inst.set_stack_size(Some(stack_size / 2))
+                .expect("stack size can be set");
+            inst.set_measure_stack_usage(true);
+            match inst.run(b"localpalooza", &[depth.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.fatal, false);
+                    assert_eq!(details.trapcode.ty, TrapCodeType::StackOverflow);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+            let usage = inst.run_stats().stack_usage.expect("usage is measured");
+            assert!(usage <= stack_size / 2);
+
+            inst.reset().expect("instance resets");
+            inst.set_stack_size(None).expect("stack size can be unset");
+            inst.run(b"localpalooza", &[depth.into()])
+                .expect("instance runs with the whole stack again");
+        }
+
+        #[test]
+        fn invalid_stack_size() {
+            let module = stack_testcase(3).expect("generate stack_testcase 3");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+            match inst.set_stack_size(Some(0)) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match inst.set_stack_size(Some(Limits::default().stack_size * 2)) {
+                Err(Error::LimitsExceeded(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
     };
 }
@@ -1,7 +1,7 @@
 #[macro_export]
 macro_rules! start_tests {
     ( $TestRegion:path ) => {
-        use lucet_runtime::{DlModule, Limits, Region};
+        use lucet_runtime::{DlModule, Export, Limits, Module, Region};
         use std::sync::Arc;
         use $TestRegion as TestRegion;
         use $crate::build::test_module_wasm;
@@ -65,5 +65,26 @@ macro_rules! start_tests {
             let heap = inst.heap_u32();
             assert_eq!(heap[0], 17);
         }
+
+        #[test]
+        fn module_info() {
+            let start_module = test_module_wasm("start", "start_and_call.wat")
+                .expect("module compiled and loaded");
+            start_module
+                .validate(&Limits::default())
+                .expect("module is valid");
+            let info = start_module.info().expect("module info is available");
+            assert!(info.has_start_func);
+            assert_eq!(info.globals.len(), 1);
+            assert_eq!(info.heap_spec.initial_size, 64 * 1024);
+            assert!(info.linked_imports.is_empty());
+            let export_names: Vec<&str> = info.exports.iter().map(Export::name).collect();
+            assert_eq!(export_names, vec!["main", "memory"]);
+
+            let no_start_module =
+                test_module_wasm("start", "no_start.wat").expect("module compiled and loaded");
+            let info = no_start_module.info().expect("module info is available");
+            assert!(!info.has_start_func);
+        }
     };
 }
@@ -0,0 +1,320 @@
+#[macro_export]
+macro_rules! table_tests {
+    ( $TestRegion:path ) => {
+        use lucet_runtime::vmctx::lucet_vmctx;
+        use lucet_runtime::{
+            lucet_hostcalls, Error, Limits, RefType, Region, TableElement, TrapCodeType, Val,
+        };
+        use lucet_runtime_internals::module::ModuleInternal;
+        use $TestRegion as TestRegion;
+        use $crate::build::test_module_wasm;
+
+        // the index of the `$binop` type in `plugins.wat`
+        const BINOP_TYPE_INDEX: u32 = 0;
+
+        lucet_hostcalls! {
+            pub extern "C" fn table_test_mul(_vmctx: *mut lucet_vmctx, x: u32, y: u32) -> u32 {
+                x * y
+            }
+        }
+
+        #[test]
+        fn imported_table_spec() {
+            let module =
+                test_module_wasm("tables", "plugins.wat").expect("module compiled and loaded");
+            let tables = module.tables();
+            assert_eq!(tables.len(), 1);
+            assert_eq!(tables[0].import(), Some(("env", "plugins")));
+            assert_eq!(tables[0].export(), Some("plugins"));
+            assert_eq!(tables[0].min_size(), 2);
+        }
+
+        #[test]
+        fn install_hostcall_in_table() {
+            let module =
+                test_module_wasm("tables", "plugins.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let table_idx = inst.get_export_table("plugins").expect("table is exported");
+            let elements = inst.table_elements(table_idx).expect("table exists");
+            assert_eq!(elements.len(), 2);
+            assert_eq!(elements[0].ty(), Some(BINOP_TYPE_INDEX));
+            assert_eq!(elements[1], TableElement::empty());
+
+            let retval = inst
+                .run(b"call_plugin", &[0u32.into(), 7u32.into(), 3u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 4);
+
+            unsafe {
+                inst.set_table_element(
+                    table_idx,
+                    1,
+                    TableElement::new(
+                        BINOP_TYPE_INDEX,
+                        table_test_mul as *const extern "C" fn(),
+                    ),
+                )
+            }
+            .expect("table element can be set");
+
+            let retval = inst
+                .run(b"call_plugin", &[1u32.into(), 7u32.into(), 3u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 21);
+
+            // resetting the instance restores the module's table
+            inst.reset().expect("instance resets");
+            match inst.run(b"call_plugin", &[1u32.into(), 7u32.into(), 3u32.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode.ty, TrapCodeType::BadSignature);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn set_table_element_out_of_bounds() {
+            let module =
+                test_module_wasm("tables", "plugins.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match unsafe { inst.set_table_element(0, 2, TableElement::empty()) } {
+                Err(Error::FuncNotFound(0, 2)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match unsafe { inst.set_table_element(1, 0, TableElement::empty()) } {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn table_init_and_copy() {
+            let module =
+                test_module_wasm("tables", "bulk_table.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.run(b"init", &[2u32.into(), 0u32.into(), 2u32.into()])
+                .expect("instance runs");
+            let retval = inst.run(b"call", &[2u32.into()]).expect("instance runs");
+            assert_eq!(u32::from(retval), 1);
+            let retval = inst.run(b"call", &[3u32.into()]).expect("instance runs");
+            assert_eq!(u32::from(retval), 2);
+
+            inst.run(b"copy", &[0u32.into(), 3u32.into(), 1u32.into()])
+                .expect("instance runs");
+            let retval = inst.run(b"call", &[0u32.into()]).expect("instance runs");
+            assert_eq!(u32::from(retval), 2);
+        }
+
+        #[test]
+        fn table_init_after_elem_drop() {
+            let module =
+                test_module_wasm("tables", "bulk_table.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.run(b"elem_drop", &[]).expect("instance runs");
+            match inst.run(b"init", &[0u32.into(), 0u32.into(), 1u32.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode.ty, TrapCodeType::TableOutOfBounds);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+            // the table was left as it was
+            assert_eq!(inst.table_elements(0).unwrap()[1], TableElement::empty());
+
+            inst.reset().expect("instance resets");
+            inst.run(b"init", &[1u32.into(), 1u32.into(), 1u32.into()])
+                .expect("segment can be used again after a reset");
+            let retval = inst.run(b"call", &[1u32.into()]).expect("instance runs");
+            assert_eq!(u32::from(retval), 2);
+        }
+
+        #[test]
+        fn multiple_tables() {
+            let module = test_module_wasm("tables", "reference_types.wat")
+                .expect("module compiled and loaded");
+            let tables = module.tables();
+            assert_eq!(tables.len(), 3);
+            assert_eq!(tables[1].elem_type(), RefType::FuncRef);
+            assert_eq!(tables[2].elem_type(), RefType::ExternRef);
+            assert_eq!(tables[2].max_size(), Some(4));
+
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let retval = inst.run(b"call", &[0u32.into()]).expect("instance runs");
+            assert_eq!(u32::from(retval), 7);
+            let retval = inst
+                .run(b"call_more", &[1u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 9);
+            match inst.run(b"call_more", &[0u32.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode.ty, TrapCodeType::BadSignature);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn funcref_get_set() {
+            let module = test_module_wasm("tables", "reference_types.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.run(b"install_nine", &[1u32.into()])
+                .expect("instance runs");
+            let retval = inst.run(b"call", &[1u32.into()]).expect("instance runs");
+            assert_eq!(u32::from(retval), 9);
+
+            inst.run(b"copy_func", &[1u32.into(), 0u32.into()])
+                .expect("instance runs");
+            let retval = inst.run(b"call", &[1u32.into()]).expect("instance runs");
+            assert_eq!(u32::from(retval), 7);
+            assert_eq!(
+                inst.table_elements(0).unwrap()[0],
+                inst.table_elements(0).unwrap()[1]
+            );
+
+            match inst.run(b"copy_func", &[0u32.into(), 2u32.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode.ty, TrapCodeType::TableOutOfBounds);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn externref_round_trip() {
+            let module = test_module_wasm("tables", "reference_types.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let retval = inst.run(b"get_ref", &[0u32.into()]).expect("instance runs");
+            assert_eq!(u64::from(retval), 0);
+
+            inst.run(b"set_ref", &[0u32.into(), Val::ExternRef(0xdead_beef)])
+                .expect("instance runs");
+            let retval = inst.run(b"get_ref", &[0u32.into()]).expect("instance runs");
+            assert_eq!(u64::from(retval), 0xdead_beef);
+            assert_eq!(
+                inst.table_elements(2).unwrap()[0],
+                TableElement::extern_ref(0xdead_beef)
+            );
+
+            let retval = inst
+                .run(b"is_null", &[Val::ExternRef(0)])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 1);
+            let retval = inst
+                .run(b"is_null", &[Val::ExternRef(0xdead_beef)])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 0);
+        }
+
+        #[test]
+        fn grow_table() {
+            let module = test_module_wasm("tables", "reference_types.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let retval = inst
+                .run(b"grow_refs", &[2u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 1);
+            let retval = inst.run(b"size_refs", &[]).expect("instance runs");
+            assert_eq!(u32::from(retval), 3);
+
+            // the host can grow the table too, up to its maximum size
+            assert_eq!(
+                inst.grow_table(2, 1, TableElement::extern_ref(5)).unwrap(),
+                3
+            );
+            let retval = inst.run(b"get_ref", &[3u32.into()]).expect("instance runs");
+            assert_eq!(u64::from(retval), 5);
+            let retval = inst
+                .run(b"grow_refs", &[1u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), std::u32::MAX);
+            match inst.grow_table(2, 1, TableElement::empty()) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            // resetting the instance restores the module's tables
+            inst.reset().expect("instance resets");
+            assert_eq!(inst.table_size(2).unwrap(), 1);
+        }
+
+        #[test]
+        fn grow_table_past_limit() {
+            let module = test_module_wasm("tables", "reference_types.wat")
+                .expect("module compiled and loaded");
+            let limits = Limits {
+                table_elements: 3,
+                ..Limits::default()
+            };
+            let region = TestRegion::create(1, &limits).expect("region can be created");
+            let mut inst = region
+                .new_instance(module.clone())
+                .expect("instance can be created");
+
+            let retval = inst
+                .run(b"grow_refs", &[2u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), 1);
+            // the module allows four elements, but the limit is three
+            let retval = inst
+                .run(b"grow_refs", &[1u32.into()])
+                .expect("instance runs");
+            assert_eq!(u32::from(retval), std::u32::MAX);
+            match inst.grow_table(2, 1, TableElement::empty()) {
+                Err(Error::LimitsExceeded(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            // a table without a maximum size is capped by the limit too, without allocating
+            match inst.grow_table(0, std::u32::MAX, TableElement::empty()) {
+                Err(Error::LimitsExceeded(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            assert_eq!(inst.table_size(0).unwrap(), 2);
+
+            // modules whose tables start out larger than the limit are rejected
+            drop(inst);
+            let limits = Limits {
+                table_elements: 1,
+                ..Limits::default()
+            };
+            let region = TestRegion::create(1, &limits).expect("region can be created");
+            match region.new_instance(module) {
+                Err(Error::LimitsExceeded(_)) => (),
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            }
+        }
+    };
+}
@@ -4,15 +4,18 @@ use crate::{DlModule, Instance, Limits, MmapRegion, Module, Region, TrapCode};
 use libc::{c_char, c_int, c_void};
 use lucet_runtime_internals::c_api::*;
 use lucet_runtime_internals::instance::{
-    instance_handle_from_raw, instance_handle_to_raw, InstanceInternal,
+    instance_handle_from_raw, instance_handle_to_raw, signals, InstanceInternal,
 };
-use lucet_runtime_internals::vmctx::{instance_from_vmctx, lucet_vmctx, Vmctx, VmctxInternal};
-use lucet_runtime_internals::WASM_PAGE_SIZE;
+use lucet_runtime_internals::module::ModuleInternal;
 use lucet_runtime_internals::{assert_nonnull, with_ffi_arcs};
 use num_traits::FromPrimitive;
 use std::ffi::CStr;
 use std::ptr;
-use std::sync::{Arc, Once};
+use std::sync::Arc;
+
+mod vmctx;
+
+pub use self::vmctx::ensure_linked;
 
 macro_rules! with_instance_ptr {
     ( $name:ident, $body:block ) => {{
@@ -67,6 +70,47 @@ pub extern "C" fn lucet_state_tag_name(tag: libc::c_int) -> *const c_char {
     }
 }
 
+#[no_mangle]
+pub extern "C" fn lucet_trapcode_type_name(ty: libc::c_int) -> *const c_char {
+    if let Some(ty) = lucet_trapcode_type::from_i32(ty) {
+        use self::lucet_trapcode_type::*;
+        match ty {
+            StackOverflow => "lucet_trapcode_type_stack_overflow\0".as_ptr() as _,
+            HeapOutOfBounds => "lucet_trapcode_type_heap_out_of_bounds\0".as_ptr() as _,
+            OutOfBounds => "lucet_trapcode_type_out_of_bounds\0".as_ptr() as _,
+            IndirectCallToNull => "lucet_trapcode_type_indirect_call_to_null\0".as_ptr() as _,
+            BadSignature => "lucet_trapcode_type_bad_signature\0".as_ptr() as _,
+            IntegerOverflow => "lucet_trapcode_type_integer_overflow\0".as_ptr() as _,
+            IntegerDivByZero => "lucet_trapcode_type_integer_div_by_zero\0".as_ptr() as _,
+            BadConversionToInteger => {
+                "lucet_trapcode_type_bad_conversion_to_integer\0".as_ptr() as _
+            }
+            Interrupt => "lucet_trapcode_type_interrupt\0".as_ptr() as _,
+            TableOutOfBounds => "lucet_trapcode_type_table_out_of_bounds\0".as_ptr() as _,
+            User => "lucet_trapcode_type_user\0".as_ptr() as _,
+            Unknown => "lucet_trapcode_type_unknown\0".as_ptr() as _,
+        }
+    } else {
+        "!!! unknown lucet_trapcode_type variant!\0".as_ptr() as _
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lucet_terminated_reason_name(reason: libc::c_int) -> *const c_char {
+    if let Some(reason) = lucet_terminated_reason::from_i32(reason) {
+        use self::lucet_terminated_reason::*;
+        match reason {
+            Signal => "lucet_terminated_reason_signal\0".as_ptr() as _,
+            GetEmbedCtx => "lucet_terminated_reason_get_embed_ctx\0".as_ptr() as _,
+            Provided => "lucet_terminated_reason_provided\0".as_ptr() as _,
+            Panic => "lucet_terminated_reason_panic\0".as_ptr() as _,
+            UnlinkedImport => "lucet_terminated_reason_unlinked_import\0".as_ptr() as _,
+        }
+    } else {
+        "!!! unknown lucet_terminated_reason variant!\0".as_ptr() as _
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn lucet_mmap_region_create(
     instance_capacity: u64,
@@ -124,6 +168,34 @@ pub unsafe extern "C" fn lucet_region_new_instance(
     lucet_region_new_instance_with_ctx(region, module, ptr::null_mut(), inst_out)
 }
 
+/// Prevent Lucet from installing its own handlers for `SIGBUS`, `SIGFPE`, `SIGILL`, and `SIGSEGV`.
+///
+/// Embedders that call this must call `lucet_handle_signal()` first from their own handlers for
+/// those signals, which must be installed with `SA_SIGINFO` and `SA_ONSTACK`. This must be called
+/// before any instance runs; it returns `lucet_error_invalid_argument` if Lucet's handlers have
+/// already been installed.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_disable_signal_handler_installation() -> lucet_error {
+    signals::disable_signal_handler_installation()
+        .map(|_| lucet_error::Ok)
+        .unwrap_or_else(|e| e.into())
+}
+
+/// Handle a signal on behalf of the instance running on this thread, if there is one.
+///
+/// Returns `false` if the signal did not arise from a running Lucet instance, in which case the
+/// embedder's signal handler should handle it as it would have otherwise. Returns `true` if the
+/// signal was handled and the interrupted code can continue. If the signal ends the instance's run,
+/// this function does not return.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_handle_signal(
+    signum: c_int,
+    siginfo: *mut libc::siginfo_t,
+    ucontext: *mut c_void,
+) -> bool {
+    signals::handle_signal(signum, siginfo, ucontext)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn lucet_dl_module_load(
     path: *const c_char,
@@ -144,6 +216,47 @@ pub unsafe extern "C" fn lucet_dl_module_release(module: *const lucet_dl_module)
     Arc::from_raw(module as *const DlModule);
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn lucet_dl_module_get_export_func(
+    module: *const lucet_dl_module,
+    sym: *const c_char,
+    func_out: *mut *const c_void,
+) -> lucet_error {
+    assert_nonnull!(sym);
+    assert_nonnull!(func_out);
+    with_ffi_arcs!([module: DlModule], {
+        let sym = CStr::from_ptr(sym);
+        module
+            .get_export_func(sym.to_bytes())
+            .map(|f| {
+                func_out.write(f as *const c_void);
+                lucet_error::Ok
+            })
+            .unwrap_or_else(|e| e.into())
+    })
+}
+
+/// Check that instances of the module can be created in a region with the given limits, without
+/// creating an instance.
+///
+/// If `limits` is `NULL`, the default limits are used.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_dl_module_validate(
+    module: *const lucet_dl_module,
+    limits: *const lucet_alloc_limits,
+) -> lucet_error {
+    let limits = limits
+        .as_ref()
+        .map(|l| l.into())
+        .unwrap_or(Limits::default());
+    with_ffi_arcs!([module: DlModule], {
+        module
+            .validate(&limits)
+            .map(|_| lucet_error::Ok)
+            .unwrap_or_else(|e| e.into())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn lucet_instance_run(
     inst: *mut lucet_instance,
@@ -218,11 +331,65 @@ pub unsafe extern "C" fn lucet_state_release(state: *mut lucet_state::lucet_stat
     use std::ffi::CString;
 
     let state = state.read();
-    if let lucet_state_tag::Fault = state.tag {
-        let addr_details = state.val.fault.rip_addr_details;
-        // free the strings
-        CString::from_raw(addr_details.file_name as *mut _);
-        CString::from_raw(addr_details.sym_name as *mut _);
+    match state.tag {
+        lucet_state_tag::Fault => {
+            let addr_details = state.val.fault.rip_addr_details;
+            // free the strings
+            if !addr_details.file_name.is_null() {
+                CString::from_raw(addr_details.file_name as *mut _);
+            }
+            if !addr_details.sym_name.is_null() {
+                CString::from_raw(addr_details.sym_name as *mut _);
+            }
+        }
+        lucet_state_tag::Terminated => {
+            let panic_message = state.val.terminated.panic_message;
+            if !panic_message.is_null() {
+                CString::from_raw(panic_message as *mut _);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Get a description of the fault in a state with the `lucet_state_tag_fault` tag.
+///
+/// The strings in the details are owned by `state`.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_state_fault_details(
+    state: *const lucet_state::lucet_state,
+    details_out: *mut lucet_state::lucet_fault_details,
+) -> lucet_error {
+    assert_nonnull!(state);
+    assert_nonnull!(details_out);
+    let state = &*state;
+    match state.tag {
+        lucet_state_tag::Fault => {
+            details_out.write((&state.val.fault).into());
+            lucet_error::Ok
+        }
+        _ => lucet_error::InvalidArgument,
+    }
+}
+
+/// Get the pointer passed to `lucet_vmctx_terminate()` in a state with the
+/// `lucet_state_tag_terminated` tag.
+///
+/// The pointer is `NULL` if the instance was terminated for any other reason.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_state_terminated_provided(
+    state: *const lucet_state::lucet_state,
+    provided_out: *mut *mut c_void,
+) -> lucet_error {
+    assert_nonnull!(state);
+    assert_nonnull!(provided_out);
+    let state = &*state;
+    match state.tag {
+        lucet_state_tag::Terminated => {
+            provided_out.write(state.val.terminated.provided);
+            lucet_error::Ok
+        }
+        _ => lucet_error::InvalidArgument,
     }
 }
 
@@ -278,6 +445,65 @@ pub unsafe extern "C" fn lucet_instance_grow_heap(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_global_count(inst: *const lucet_instance) -> u32 {
+    with_instance_ptr_unchecked!(inst, { inst.module().globals().len() as u32 })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_get_global(
+    inst: *const lucet_instance,
+    global_idx: u32,
+    value_out: *mut i64,
+) -> lucet_error {
+    assert_nonnull!(value_out);
+    with_instance_ptr!(inst, {
+        if global_idx as usize >= inst.module().globals().len() {
+            return lucet_error::InvalidArgument;
+        }
+        value_out.write(inst.globals()[global_idx as usize]);
+        lucet_error::Ok
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_set_global(
+    inst: *mut lucet_instance,
+    global_idx: u32,
+    value: i64,
+) -> lucet_error {
+    with_instance_ptr!(inst, {
+        if global_idx as usize >= inst.module().globals().len() {
+            return lucet_error::InvalidArgument;
+        }
+        inst.globals_mut()[global_idx as usize] = value;
+        lucet_error::Ok
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_get_export_table(
+    inst: *const lucet_instance,
+    name: *const c_char,
+    table_idx_out: *mut u32,
+) -> lucet_error {
+    assert_nonnull!(name);
+    assert_nonnull!(table_idx_out);
+    with_instance_ptr!(inst, {
+        let name = CStr::from_ptr(name);
+        match name.to_str() {
+            Ok(name) => inst
+                .get_export_table(name)
+                .map(|table_idx| {
+                    table_idx_out.write(table_idx);
+                    lucet_error::Ok
+                })
+                .unwrap_or_else(|e| e.into()),
+            Err(_) => lucet_error::InvalidArgument,
+        }
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn lucet_instance_embed_ctx(inst: *mut lucet_instance) -> *mut c_void {
     with_instance_ptr_unchecked!(inst, {
@@ -287,6 +513,26 @@ pub unsafe extern "C" fn lucet_instance_embed_ctx(inst: *mut lucet_instance) ->
     })
 }
 
+/// Set the embedder context of an instance, returning the previous context or `NULL` if there was
+/// none.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_insert_embed_ctx(
+    inst: *mut lucet_instance,
+    embed_ctx: *mut c_void,
+) -> *mut c_void {
+    with_instance_ptr_unchecked!(inst, {
+        inst.insert_embed_ctx(embed_ctx).unwrap_or(ptr::null_mut())
+    })
+}
+
+/// Remove the embedder context of an instance, returning it or `NULL` if there was none.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_remove_embed_ctx(inst: *mut lucet_instance) -> *mut c_void {
+    with_instance_ptr_unchecked!(inst, {
+        inst.remove_embed_ctx::<*mut c_void>().unwrap_or(ptr::null_mut())
+    })
+}
+
 /// Release or run* must not be called in the body of this function!
 #[no_mangle]
 pub unsafe extern "C" fn lucet_instance_set_signal_handler(
@@ -348,89 +594,3 @@ pub unsafe extern "C" fn lucet_retval_f64(retval: *const lucet_untyped_retval) -
     );
     v
 }
-
-static C_API_INIT: Once = Once::new();
-
-/// Should never actually be called, but should be reachable via a trait method to prevent DCE.
-pub fn ensure_linked() {
-    use std::ptr::read_volatile;
-    C_API_INIT.call_once(|| unsafe {
-        read_volatile(lucet_vmctx_get_heap as *const extern "C" fn());
-        read_volatile(lucet_vmctx_current_memory as *const extern "C" fn());
-        read_volatile(lucet_vmctx_grow_memory as *const extern "C" fn());
-    });
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn lucet_vmctx_get_heap(vmctx: *mut lucet_vmctx) -> *mut u8 {
-    Vmctx::from_raw(vmctx).instance().alloc().slot().heap as *mut u8
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn lucet_vmctx_get_globals(vmctx: *mut lucet_vmctx) -> *mut i64 {
-    Vmctx::from_raw(vmctx).instance().alloc().slot().globals as *mut i64
-}
-
-/// Get the number of WebAssembly pages currently in the heap.
-#[no_mangle]
-pub unsafe extern "C" fn lucet_vmctx_current_memory(vmctx: *mut lucet_vmctx) -> libc::uint32_t {
-    Vmctx::from_raw(vmctx).instance().alloc().heap_len() as u32 / WASM_PAGE_SIZE
-}
-
-#[no_mangle]
-/// Grows the guest heap by the given number of WebAssembly pages.
-///
-/// On success, returns the number of pages that existed before the call. On failure, returns `-1`.
-pub unsafe extern "C" fn lucet_vmctx_grow_memory(
-    vmctx: *mut lucet_vmctx,
-    additional_pages: libc::uint32_t,
-) -> libc::int32_t {
-    let inst = instance_from_vmctx(vmctx);
-    if let Ok(old_pages) = inst.grow_memory(additional_pages) {
-        old_pages as libc::int32_t
-    } else {
-        -1
-    }
-}
-
-#[no_mangle]
-/// Check if a memory region is inside the instance heap.
-pub unsafe extern "C" fn lucet_vmctx_check_heap(
-    vmctx: *mut lucet_vmctx,
-    ptr: *mut c_void,
-    len: libc::size_t,
-) -> bool {
-    let inst = instance_from_vmctx(vmctx);
-    inst.check_heap(ptr, len)
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn lucet_vmctx_get_func_from_idx(
-    vmctx: *mut lucet_vmctx,
-    table_idx: u32,
-    func_idx: u32,
-) -> *const c_void {
-    let inst = instance_from_vmctx(vmctx);
-    inst.module()
-        .get_func_from_idx(table_idx, func_idx)
-        // the Rust API actually returns a pointer to a function pointer, so we want to dereference
-        // one layer of that to make it nicer in C
-        .map(|fptr| *(fptr as *const *const c_void))
-        .unwrap_or(std::ptr::null())
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn lucet_vmctx_terminate(vmctx: *mut lucet_vmctx, info: *mut c_void) {
-    Vmctx::from_raw(vmctx).terminate(info);
-}
-
-#[no_mangle]
-/// Get the delegate object for the current instance.
-///
-/// TODO: rename
-pub unsafe extern "C" fn lucet_vmctx_get_delegate(vmctx: *mut lucet_vmctx) -> *mut c_void {
-    let inst = instance_from_vmctx(vmctx);
-    inst.get_embed_ctx::<*mut c_void>()
-        .map(|p| *p)
-        .unwrap_or(std::ptr::null_mut())
-}
@@ -0,0 +1,515 @@
+//! The C API available to hostcalls, generated into `include/lucet_vmctx.h`.
+//!
+//! These functions take the `vmctx` pointer that guest code passes to hostcalls. They are declared
+//! `const` on the C side, since they do not expose any of the instance's internal state.
+
+use libc::{c_char, c_void};
+use lucet_runtime_internals::error::Error;
+use lucet_runtime_internals::instance::{AtomicRmwOp, InstanceInternal};
+use lucet_runtime_internals::vmctx::{instance_from_vmctx, lucet_vmctx, Vmctx, VmctxInternal};
+use lucet_runtime_internals::WASM_PAGE_SIZE;
+use std::sync::atomic::{self, Ordering};
+use std::sync::Once;
+use std::time::Duration;
+
+static C_API_INIT: Once = Once::new();
+
+/// Should never actually be called, but should be reachable via a trait method to prevent DCE.
+pub fn ensure_linked() {
+    use std::ptr::read_volatile;
+    C_API_INIT.call_once(|| unsafe {
+        read_volatile(lucet_vmctx_get_heap as *const extern "C" fn());
+        read_volatile(lucet_vmctx_current_memory as *const extern "C" fn());
+        read_volatile(lucet_vmctx_grow_memory as *const extern "C" fn());
+        read_volatile(lucet_vmctx_memory_init as *const extern "C" fn());
+        read_volatile(lucet_vmctx_data_drop as *const extern "C" fn());
+        read_volatile(lucet_vmctx_memory_copy as *const extern "C" fn());
+        read_volatile(lucet_vmctx_memory_fill as *const extern "C" fn());
+        read_volatile(lucet_vmctx_table_init as *const extern "C" fn());
+        read_volatile(lucet_vmctx_elem_drop as *const extern "C" fn());
+        read_volatile(lucet_vmctx_table_copy as *const extern "C" fn());
+        read_volatile(lucet_vmctx_table_grow as *const extern "C" fn());
+        read_volatile(lucet_vmctx_table_size as *const extern "C" fn());
+        read_volatile(lucet_vmctx_table_fill as *const extern "C" fn());
+        read_volatile(lucet_vmctx_table_check as *const extern "C" fn());
+        read_volatile(lucet_vmctx_table_get as *const extern "C" fn());
+        read_volatile(lucet_vmctx_table_set as *const extern "C" fn());
+        read_volatile(lucet_vmctx_ref_func as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_notify as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_wait32 as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_wait64 as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_fence as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_load_i32 as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_load_i64 as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_store_i32 as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_store_i64 as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_rmw_i32 as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_rmw_i64 as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_cmpxchg_i32 as *const extern "C" fn());
+        read_volatile(lucet_vmctx_atomic_cmpxchg_i64 as *const extern "C" fn());
+    });
+}
+
+/// Get a pointer to the instance heap.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_get_heap(vmctx: *const lucet_vmctx) -> *mut c_char {
+    Vmctx::from_raw(vmctx as *mut _)
+        .instance()
+        .alloc()
+        .slot()
+        .heap as *mut c_char
+}
+
+/// Get a pointer to the instance globals.
+///
+/// This is mostly for tests, as `lucetc` compiles global accesses directly.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_get_globals(vmctx: *const lucet_vmctx) -> *mut i64 {
+    Vmctx::from_raw(vmctx as *mut _)
+        .instance()
+        .alloc()
+        .slot()
+        .globals as *mut i64
+}
+
+/// Get the number of WebAssembly pages currently in the heap.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_current_memory(vmctx: *const lucet_vmctx) -> libc::uint32_t {
+    Vmctx::from_raw(vmctx as *mut _)
+        .instance()
+        .alloc()
+        .heap_len() as u32
+        / WASM_PAGE_SIZE
+}
+
+#[no_mangle]
+/// Grows the guest heap by the given number of WebAssembly pages.
+///
+/// On success, returns the number of pages that existed before the call. On failure, returns `-1`.
+pub unsafe extern "C" fn lucet_vmctx_grow_memory(
+    vmctx: *const lucet_vmctx,
+    additional_pages: libc::uint32_t,
+) -> libc::int32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    if let Ok(old_pages) = inst.grow_memory(additional_pages) {
+        old_pages as libc::int32_t
+    } else {
+        -1
+    }
+}
+
+/// Copy `len` bytes from `src` in the passive data segment `segment` to `dst` in the heap.
+///
+/// Returns nonzero, having copied nothing, if either range is out of bounds.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_memory_init(
+    vmctx: *const lucet_vmctx,
+    dst: libc::uint32_t,
+    src: libc::uint32_t,
+    len: libc::uint32_t,
+    segment: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.memory_init(segment, dst, src, len).is_err() as libc::uint32_t
+}
+
+/// Discard the passive data segment `segment`.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_data_drop(vmctx: *const lucet_vmctx, segment: libc::uint32_t) {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.data_drop(segment);
+}
+
+/// Copy `len` bytes of the heap from `src` to `dst`; the ranges may overlap.
+///
+/// Returns nonzero, having copied nothing, if either range is out of bounds.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_memory_copy(
+    vmctx: *const lucet_vmctx,
+    dst: libc::uint32_t,
+    src: libc::uint32_t,
+    len: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.memory_copy(dst, src, len).is_err() as libc::uint32_t
+}
+
+/// Set `len` bytes of the heap starting at `dst` to the low byte of `val`.
+///
+/// Returns nonzero, having written nothing, if the range is out of bounds.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_memory_fill(
+    vmctx: *const lucet_vmctx,
+    dst: libc::uint32_t,
+    val: libc::uint32_t,
+    len: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.memory_fill(dst, val as u8, len).is_err() as libc::uint32_t
+}
+
+/// Copy `len` elements from `src` in the passive element segment `segment` to `dst` in the table
+/// `table`.
+///
+/// Returns nonzero, having copied nothing, if either range is out of bounds.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_table_init(
+    vmctx: *const lucet_vmctx,
+    dst: libc::uint32_t,
+    src: libc::uint32_t,
+    len: libc::uint32_t,
+    segment: libc::uint32_t,
+    table: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.table_init(table, segment, dst, src, len).is_err() as libc::uint32_t
+}
+
+/// Discard the passive element segment `segment`.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_elem_drop(vmctx: *const lucet_vmctx, segment: libc::uint32_t) {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.elem_drop(segment);
+}
+
+/// Copy `len` elements from `src` in the table `src_table` to `dst` in the table `dst_table`; the
+/// ranges may overlap.
+///
+/// Returns nonzero, having copied nothing, if either range is out of bounds.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_table_copy(
+    vmctx: *const lucet_vmctx,
+    dst: libc::uint32_t,
+    src: libc::uint32_t,
+    len: libc::uint32_t,
+    dst_table: libc::uint32_t,
+    src_table: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.table_copy(dst_table, dst, src_table, src, len)
+        .is_err() as libc::uint32_t
+}
+
+// The reference types operators pass references as 64-bit values, where zero is the null
+// reference. Guest code checks the bounds of `table.get` with `lucet_vmctx_table_check` first, so
+// `lucet_vmctx_table_get` and `lucet_vmctx_ref_func` only fail for modules `lucetc` did not compile,
+// and terminate the instance if they do.
+
+/// Grow the table `table` by `delta` elements set to the reference `init`.
+///
+/// On success, returns the number of elements that existed before the call. On failure, returns
+/// `-1`.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_table_grow(
+    vmctx: *const lucet_vmctx,
+    init: libc::uint64_t,
+    delta: libc::uint32_t,
+    table: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.table_grow(table, init, delta).unwrap_or(std::u32::MAX)
+}
+
+/// Get the number of elements in the table `table`.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_table_size(
+    vmctx: *const lucet_vmctx,
+    table: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.table_size(table).unwrap_or(0)
+}
+
+/// Set `len` elements of the table `table` starting at `dst` to the reference `val`.
+///
+/// Returns nonzero, having written nothing, if the range is out of bounds.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_table_fill(
+    vmctx: *const lucet_vmctx,
+    dst: libc::uint32_t,
+    val: libc::uint64_t,
+    len: libc::uint32_t,
+    table: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.table_fill(table, dst, val, len).is_err() as libc::uint32_t
+}
+
+/// Check that `len` elements starting at `index` are within the table `table`.
+///
+/// Returns nonzero if the range is out of bounds.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_table_check(
+    vmctx: *const lucet_vmctx,
+    index: libc::uint32_t,
+    len: libc::uint32_t,
+    table: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.table_check(table, index, len).is_err() as libc::uint32_t
+}
+
+/// Get the reference at `index` in the table `table`.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_table_get(
+    vmctx: *const lucet_vmctx,
+    index: libc::uint32_t,
+    table: libc::uint32_t,
+) -> libc::uint64_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    match inst.table_get(table, index) {
+        Ok(val) => val,
+        Err(err) => Vmctx::from_raw(vmctx as *mut _).terminate(err),
+    }
+}
+
+/// Store the reference `val` at `index` in the table `table`.
+///
+/// Returns nonzero, having written nothing, if `index` is out of bounds.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_table_set(
+    vmctx: *const lucet_vmctx,
+    index: libc::uint32_t,
+    val: libc::uint64_t,
+    table: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.table_set(table, index, val).is_err() as libc::uint32_t
+}
+
+/// Get a reference to the function that `ref.func` names with the operand `func`.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_ref_func(
+    vmctx: *const lucet_vmctx,
+    func: libc::uint32_t,
+) -> libc::uint64_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    match inst.ref_func(func) {
+        Ok(val) => val,
+        Err(err) => Vmctx::from_raw(vmctx as *mut _).terminate(err),
+    }
+}
+
+// The atomic operators are compiled as calls to these functions, after the guest checks that the
+// access is in bounds and aligned. An access that fails the runtime's checks anyway terminates the
+// instance with the `Error` as the termination details.
+
+/// Terminate the instance with `err` if an atomic operation failed.
+unsafe fn atomic_or_terminate<T>(vmctx: *const lucet_vmctx, res: Result<T, Error>) -> T {
+    match res {
+        Ok(v) => v,
+        Err(err) => Vmctx::from_raw(vmctx as *mut _).terminate(err),
+    }
+}
+
+/// Wake up to `count` threads waiting on `addr`, returning the number woken.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_notify(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    count: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    atomic_or_terminate(vmctx, inst.atomic_notify(addr, count))
+}
+
+/// Convert a `memory.atomic.wait` timeout in nanoseconds, where a negative timeout never expires.
+fn wait_timeout(timeout: libc::int64_t) -> Option<Duration> {
+    if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_nanos(timeout as u64))
+    }
+}
+
+/// Wait for a notification on `addr` if the 32-bit value there equals `expected`.
+///
+/// Returns 0 if woken, 1 if the value was not equal, and 2 if `timeout` nanoseconds passed.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_wait32(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    expected: libc::uint32_t,
+    timeout: libc::int64_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    let res = inst.atomic_wait(addr, expected as u64, 4, wait_timeout(timeout));
+    atomic_or_terminate(vmctx, res)
+}
+
+/// Wait for a notification on `addr` if the 64-bit value there equals `expected`.
+///
+/// Returns 0 if woken, 1 if the value was not equal, and 2 if `timeout` nanoseconds passed.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_wait64(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    expected: libc::uint64_t,
+    timeout: libc::int64_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    let res = inst.atomic_wait(addr, expected, 8, wait_timeout(timeout));
+    atomic_or_terminate(vmctx, res)
+}
+
+/// Order the memory accesses before the call with those after it.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_fence(_vmctx: *const lucet_vmctx) {
+    atomic::fence(Ordering::SeqCst);
+}
+
+/// Load `width` bytes at `addr`, zero-extended.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_load_i32(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    width: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    atomic_or_terminate(vmctx, inst.atomic_load(addr, width)) as libc::uint32_t
+}
+
+/// Load `width` bytes at `addr`, zero-extended.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_load_i64(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    width: libc::uint32_t,
+) -> libc::uint64_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    atomic_or_terminate(vmctx, inst.atomic_load(addr, width))
+}
+
+/// Store the low `width` bytes of `value` at `addr`.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_store_i32(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    value: libc::uint32_t,
+    width: libc::uint32_t,
+) {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    atomic_or_terminate(vmctx, inst.atomic_store(addr, value as u64, width))
+}
+
+/// Store the low `width` bytes of `value` at `addr`.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_store_i64(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    value: libc::uint64_t,
+    width: libc::uint32_t,
+) {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    atomic_or_terminate(vmctx, inst.atomic_store(addr, value, width))
+}
+
+/// Apply the read-modify-write operation `op` to the `width` bytes at `addr`, returning the old
+/// value.
+///
+/// The operations are numbered 0 to 5, in the order of the `AtomicRmwOp` variants.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_rmw_i32(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    value: libc::uint32_t,
+    width: libc::uint32_t,
+    op: libc::uint32_t,
+) -> libc::uint32_t {
+    lucet_vmctx_atomic_rmw_i64(vmctx, addr, value as u64, width, op) as libc::uint32_t
+}
+
+/// Apply the read-modify-write operation `op` to the `width` bytes at `addr`, returning the old
+/// value.
+///
+/// The operations are numbered 0 to 5, in the order of the `AtomicRmwOp` variants.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_rmw_i64(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    value: libc::uint64_t,
+    width: libc::uint32_t,
+    op: libc::uint32_t,
+) -> libc::uint64_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    let res = AtomicRmwOp::from_u32(op)
+        .ok_or(Error::InvalidArgument(
+            "unknown atomic read-modify-write operation",
+        ))
+        .and_then(|op| inst.atomic_rmw(addr, op, value, width));
+    atomic_or_terminate(vmctx, res)
+}
+
+/// Replace the `width` bytes at `addr` with `replacement` if they equal `expected`, returning the
+/// old value.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_cmpxchg_i32(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    expected: libc::uint32_t,
+    replacement: libc::uint32_t,
+    width: libc::uint32_t,
+) -> libc::uint32_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    let res = inst.atomic_cmpxchg(addr, expected as u64, replacement as u64, width);
+    atomic_or_terminate(vmctx, res) as libc::uint32_t
+}
+
+/// Replace the `width` bytes at `addr` with `replacement` if they equal `expected`, returning the
+/// old value.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_atomic_cmpxchg_i64(
+    vmctx: *const lucet_vmctx,
+    addr: libc::uint32_t,
+    expected: libc::uint64_t,
+    replacement: libc::uint64_t,
+    width: libc::uint32_t,
+) -> libc::uint64_t {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    let res = inst.atomic_cmpxchg(addr, expected, replacement, width);
+    atomic_or_terminate(vmctx, res)
+}
+
+#[no_mangle]
+/// Check if a memory region is inside the instance heap.
+pub unsafe extern "C" fn lucet_vmctx_check_heap(
+    vmctx: *const lucet_vmctx,
+    ptr: *mut c_void,
+    len: libc::size_t,
+) -> bool {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.check_heap(ptr, len)
+}
+
+/// Get the address of a function by WebAssembly table and function index.
+///
+/// Returns `NULL` if there is no such function.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_get_func_from_idx(
+    vmctx: *const lucet_vmctx,
+    table_idx: u32,
+    func_idx: u32,
+) -> *mut c_void {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    // look up the instance's own table, since its elements may have been replaced by the host
+    inst.get_table_element(table_idx, func_idx)
+        .map(|element| element.func() as *mut c_void)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// Terminate the instance, making `info` available to the host through the instance state.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_vmctx_terminate(vmctx: *const lucet_vmctx, info: *mut c_void) {
+    Vmctx::from_raw(vmctx as *mut _).terminate(info);
+}
+
+#[no_mangle]
+/// Get the delegate object for the current instance.
+///
+/// TODO: rename
+pub unsafe extern "C" fn lucet_vmctx_get_delegate(vmctx: *const lucet_vmctx) -> *mut c_void {
+    let inst = instance_from_vmctx(vmctx as *mut _);
+    inst.get_embed_ctx::<*mut c_void>()
+        .map(|p| *p)
+        .unwrap_or(std::ptr::null_mut())
+}
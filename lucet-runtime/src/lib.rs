@@ -20,7 +20,8 @@
 //!
 //! - [`Instance`](struct.Instance.html): a Lucet program, together with its dedicated memory and
 //! signal handlers. Users of this API never own an `Instance` directly, but can own the
-//! [`InstanceHandle`](struct.InstanceHandle.html) smart pointer.
+//! [`InstanceHandle`](struct.InstanceHandle.html) smart pointer, which can be sent to another
+//! thread between runs.
 //!
 //! - [`Region`](trait.Region.html): the memory from which instances are created. This crate
 //! includes [`MmapRegion`](struct.MmapRegion.html), an implementation backed by `mmap`.
@@ -57,6 +58,17 @@
 //! assert_eq!(u64::from(retval), 120u64);
 //! ```
 //!
+//! A module can also be checked against a set of `Limits` before any region or instance exists,
+//! and its interface inspected with [`Module::info()`](trait.Module.html#method.info):
+//!
+//! ```no_run
+//! use lucet_runtime::{DlModule, Limits, Module};
+//!
+//! let module = DlModule::load("/my/lucet/module.so").unwrap();
+//! module.validate(&Limits::default()).unwrap();
+//! println!("{}", module.info().unwrap());
+//! ```
+//!
 //! ## Embedding With Hostcalls
 //!
 //! A "hostcall" is a function called by WebAssembly that is not defined in WebAssembly. Since
@@ -98,6 +110,40 @@
 //! assert_eq!(context_after.x, 42);
 //! ```
 //!
+//! State that only lives for a single call, such as the request being handled, can instead be lent
+//! to hostcalls with `Instance::run_with_ctx()`, which makes a mutable borrow available through
+//! `Vmctx::get_scoped_ctx_mut()` until the call returns. The borrowed state is identified by a
+//! `'static` key type implementing `CtxKey`, so the state itself may borrow from the caller:
+//!
+//! ```no_run
+//! use lucet_runtime::{CtxKey, DlModule, Limits, MmapRegion, Region};
+//! use lucet_runtime::vmctx::{Vmctx, lucet_vmctx};
+//!
+//! struct Request<'a> { response: &'a mut Vec<u8> }
+//!
+//! struct RequestKey;
+//!
+//! impl<'a> CtxKey<'a> for RequestKey {
+//!     type Ctx = Request<'a>;
+//! }
+//!
+//! #[no_mangle]
+//! unsafe extern "C" fn respond(vmctx: *mut lucet_vmctx) {
+//!     let mut vmctx = Vmctx::from_raw(vmctx);
+//!     let request = vmctx.get_scoped_ctx_mut::<RequestKey>();
+//!     request.response.extend_from_slice(b"hello");
+//! }
+//!
+//! # let module = DlModule::load("/my/lucet/module.so").unwrap();
+//! # let region = MmapRegion::create(1, &Limits::default()).unwrap();
+//! let mut inst = region.new_instance(module).unwrap();
+//!
+//! let mut response = vec![];
+//! let mut request = Request { response: &mut response };
+//! inst.run_with_ctx::<RequestKey>(&mut request, b"call_respond", &[]).unwrap();
+//! assert_eq!(response, b"hello");
+//! ```
+//!
 //! The embedder context is backed by a structure that can hold a single value of any type. Rust
 //! embedders should add their own custom state type (like `MyContext` above) for any context they
 //! require, rather than using a common type (such as the `u32`) from the standard library. This
@@ -130,6 +176,144 @@
 //! unsafe { Box::from_raw(foreign_ctx) };
 //! ```
 //!
+//! ### Guest Memory
+//!
+//! Guests pass pointers to hostcalls as offsets into their heap. The
+//! [`GuestPtr`](struct.GuestPtr.html), [`GuestSlice`](struct.GuestSlice.html), and
+//! [`GuestStr`](struct.GuestStr.html) types check these offsets against the current heap length and
+//! the alignment of the pointee before giving out references into guest memory. Structs shared
+//! with the guest can be accessed through them by deriving [`GuestType`](trait.GuestType.html):
+//!
+//! ```no_run
+//! use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
+//! use lucet_runtime::{GuestPtr, GuestType};
+//!
+//! #[repr(C)]
+//! #[derive(Clone, Copy, GuestType)]
+//! struct Point {
+//!     x: u32,
+//!     y: u32,
+//! }
+//!
+//! #[no_mangle]
+//! unsafe extern "C" fn hostcall_norm1(vmctx: *mut lucet_vmctx, point: GuestPtr<Point>) -> u32 {
+//!     let mut vmctx = Vmctx::from_raw(vmctx);
+//!     match point.read(vmctx.heap()) {
+//!         Ok(Point { x, y }) => x + y,
+//!         Err(e) => vmctx.terminate(e),
+//!     }
+//! }
+//! ```
+//!
+//! ### Panics in Hostcalls
+//!
+//! A Rust panic must never unwind out of a hostcall, since the frames above it belong to the guest
+//! and to the `extern "C"` boundary. Hostcalls written in Rust should be defined with the
+//! [`lucet_hostcalls!`](macro.lucet_hostcalls.html) macro, which catches any panic and terminates
+//! the instance instead:
+//!
+//! ```no_run
+//! use lucet_runtime::lucet_hostcalls;
+//! use lucet_runtime::vmctx::lucet_vmctx;
+//!
+//! lucet_hostcalls! {
+//!     pub extern "C" fn hostcall_unimplemented(vmctx: *mut lucet_vmctx) {
+//!         unimplemented!()
+//!     }
+//! }
+//! ```
+//!
+//! The call to `Instance::run()` then returns `Error::RuntimeTerminated` with
+//! `TerminationDetails::Panic`, which holds the panic payload.
+//!
+//! ### Calling Back Into the Guest
+//!
+//! A hostcall can call guest functions by their table index with
+//! [`Vmctx::call_func_idx()`](vmctx/struct.Vmctx.html#method.call_func_idx), for example to use a
+//! comparison function passed in by the guest. The nested call runs on the same instance and the
+//! remaining guest stack. If it faults or terminates the instance, the hostcall does not resume,
+//! and the outermost `Instance::run()` returns the error instead.
+//!
+//! ## Linking Modules
+//!
+//! A module's function imports can be satisfied by the exports of another Lucet module rather than
+//! by hostcalls. Compile the importing module with `lucetc --link-module <name>` (or
+//! `LucetcOpts::link_module()`), and then link an instance of it to an instance of the providing
+//! module with `Instance::link_instance()`. Each instance keeps its own heap and globals; tables
+//! and memories are not shared.
+//!
+//! ```no_run
+//! use lucet_runtime::{DlModule, Limits, MmapRegion, Region};
+//!
+//! let provider_module = DlModule::load("/my/lucet/provider.so").unwrap();
+//! let consumer_module = DlModule::load("/my/lucet/consumer.so").unwrap();
+//! let region = MmapRegion::create(2, &Limits::default()).unwrap();
+//! let mut provider = region.new_instance(provider_module).unwrap();
+//! let mut consumer = region.new_instance(consumer_module).unwrap();
+//!
+//! // the provider instance must outlive the consumer
+//! unsafe { consumer.link_instance("provider", &mut provider) }.unwrap();
+//! consumer.run(b"main", &[]).unwrap();
+//! ```
+//!
+//! ## Shared Memory
+//!
+//! An instance of a module whose memory is declared `shared` gets a
+//! [`SharedHeap`](struct.SharedHeap.html), which more instances can use through
+//! `InstanceBuilder::with_shared_heap()`. Each of these instances has its own stack and globals,
+//! and they can run on different threads at the same time, synchronizing through the atomic
+//! operators.
+//!
+//! This support comes with two limitations:
+//!
+//! - Shared heaps have a fixed size. A shared heap is created at the module's initial heap size,
+//!   and growing it fails, both for `memory.grow` in the guest and for `Instance::grow_memory()`,
+//!   even if the module declares a larger maximum.
+//!
+//! - Every atomic operator, including atomic loads and stores, is compiled to a call into the
+//!   runtime rather than to an inline instruction, so it costs much more than a plain memory
+//!   access.
+//!
+//! ```no_run
+//! use lucet_runtime::{DlModule, Limits, MmapRegion, Region};
+//! use std::thread;
+//!
+//! let module = DlModule::load("/my/lucet/threads.so").unwrap();
+//! let region = MmapRegion::create(2, &Limits::default()).unwrap();
+//! let mut main = region.new_instance(module.clone()).unwrap();
+//! let mut worker = region
+//!     .new_instance_builder(module)
+//!     .with_shared_heap(main.shared_heap().unwrap().clone())
+//!     .build()
+//!     .unwrap();
+//!
+//! let worker = thread::spawn(move || {
+//!     worker.run(b"worker", &[]).unwrap();
+//! });
+//! main.run(b"main", &[]).unwrap();
+//! worker.join().unwrap();
+//! ```
+//!
+//! ## Reference Types
+//!
+//! Each instance has its own copy of every table of its module, which guest code can read, write,
+//! and grow with the reference types operators, and the host with `Instance::table_elements()`,
+//! `Instance::set_table_element()`, and `Instance::grow_table()`. Tables of `externref` hold opaque
+//! 64-bit values from the host, where zero is the null reference; they are passed to guest
+//! functions as `Val::ExternRef`, and stored in tables as `TableElement::extern_ref()`.
+//!
+//! ```no_run
+//! use lucet_runtime::{DlModule, Limits, MmapRegion, Region, Val};
+//!
+//! let module = DlModule::load("/my/lucet/refs.so").unwrap();
+//! let region = MmapRegion::create(1, &Limits::default()).unwrap();
+//! let mut inst = region.new_instance(module).unwrap();
+//!
+//! inst.run(b"store", &[Val::ExternRef(42)]).unwrap();
+//! let retval = inst.run(b"load", &[]).unwrap();
+//! assert_eq!(u64::from(retval), 42);
+//! ```
+//!
 //! ## Custom Signal Handlers
 //!
 //! Since Lucet programs are run as native machine code, signals such as `SIGSEGV` and `SIGFPE` can
@@ -182,27 +366,63 @@
 //!
 //! ## Interaction With Host Signal Handlers
 //!
-//! Great care must be taken if host application installs or otherwise modifies signal handlers
-//! anywhere in the process. Lucet installs handlers for `SIGBUS`, `SIGFPE`, `SIGILL`, and `SIGSEGV`
-//! when the first Lucet instance begins running, and restores the preëxisting handlers when the
-//! last Lucet instance terminates. During this time, other threads in the host process *must not*
-//! modify those signal handlers, since signal handlers can only be installed on a process-wide
-//! basis.
-//!
-//! Despite this limitation, Lucet is designed to compose with other signal handlers in the host
-//! program. If one of the above signals is caught by the Lucet signal handler, but that thread is
-//! not currently running a Lucet instance, the saved host signal handler is called. This means
-//! that, for example, a `SIGSEGV` on a non-Lucet thread of a host program will still likely abort
-//! the entire process.
+//! Lucet installs handlers for `SIGBUS`, `SIGFPE`, `SIGILL`, and `SIGSEGV` when the first Lucet
+//! instance begins running, and leaves them installed for the rest of the life of the process.
+//! Signal handlers can only be installed on a process-wide basis, so the handlers that were
+//! installed before Lucet's are saved, and Lucet's handlers chain to them: if one of the above
+//! signals is caught by the Lucet signal handler, but that thread is not currently running a Lucet
+//! instance, the saved host signal handler is called with the signal mask it was installed with.
+//! This means that, for example, a `SIGSEGV` on a non-Lucet thread of a host program will still
+//! likely abort the entire process, and that runtimes such as the JVM or Go that use these signals
+//! for their own purposes keep working as long as they install their handlers first.
+//!
+//! The Lucet handlers never take locks, so they are safe to run on any thread at any time. A host
+//! application that installs or modifies handlers for these signals *after* a Lucet instance has
+//! run must itself chain to the handler it replaces, or guest faults will no longer be caught.
+//!
+//! Embedders that need to own these signal handlers outright, such as crash reporters or
+//! sanitizers, can instead call
+//! [`disable_signal_handler_installation()`](fn.disable_signal_handler_installation.html) before
+//! running any instances. Lucet then never installs its handlers, and the embedder's handlers must
+//! call [`handle_signal()`](fn.handle_signal.html) first, handling the signal themselves only if it
+//! returns `false`:
+//!
+//! ```no_run
+//! extern "C" fn embedder_sigsegv_handler(
+//!     signum: libc::c_int,
+//!     siginfo_ptr: *mut libc::siginfo_t,
+//!     ucontext_ptr: *mut libc::c_void,
+//! ) {
+//!     if unsafe { lucet_runtime::handle_signal(signum, siginfo_ptr, ucontext_ptr) } {
+//!         return;
+//!     }
+//!     // not caused by a Lucet instance; report the crash
+//!     std::process::abort();
+//! }
+//!
+//! lucet_runtime::disable_signal_handler_installation().unwrap();
+//! // ... install `embedder_sigsegv_handler` with `SA_SIGINFO | SA_ONSTACK`, then run instances
+//! ```
 
 mod c_api;
 
-pub use lucet_runtime_internals::alloc::Limits;
+pub use lucet_runtime_internals::alloc::{Limits, SharedHeap};
+pub use lucet_runtime_internals::embed_ctx::CtxKey;
 pub use lucet_runtime_internals::error::Error;
+pub use lucet_runtime_internals::guest_types::{
+    GuestMemoryError, GuestPtr, GuestSlice, GuestStr, GuestType,
+};
+pub use lucet_runtime_internals::instance::signals::{
+    disable_signal_handler_installation, handle_signal,
+};
 pub use lucet_runtime_internals::instance::{
-    FaultDetails, Instance, InstanceHandle, SignalBehavior, TerminationDetails,
+    AtomicRmwOp, FaultDetails, Instance, InstanceHandle, RunStats, SignalBehavior,
+    TerminationDetails,
+};
+pub use lucet_runtime_internals::lucet_hostcalls;
+pub use lucet_runtime_internals::module::{
+    DlModule, Export, Module, ModuleInfo, RefType, Signature, TableElement, TableSpec, ValueType,
 };
-pub use lucet_runtime_internals::module::{DlModule, Module};
 pub use lucet_runtime_internals::region::mmap::MmapRegion;
 pub use lucet_runtime_internals::region::{InstanceBuilder, Region, RegionCreate};
 pub use lucet_runtime_internals::trapcode::{TrapCode, TrapCodeType};
@@ -0,0 +1,48 @@
+use lucet_runtime_tests::build::test_module_wasm_so;
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// The directory where Cargo places `liblucet_runtime.so` when building this test.
+fn lib_dir() -> PathBuf {
+    let mut dir = env::current_exe().expect("can find test executable");
+    dir.pop();
+    if dir.ends_with("deps") {
+        dir.pop();
+    }
+    dir
+}
+
+/// Compile a C program against the generated headers, link it with the runtime's shared library,
+/// and use it to run a guest. This catches mismatches between the headers and the Rust definitions
+/// of the C API, such as enum values that differ.
+#[test]
+fn c_api_run_guest() {
+    let workdir = TempDir::new().expect("create working directory");
+    let so_file = workdir.path().join("guest.so");
+    test_module_wasm_so("c_api", "guest.wat", &so_file).expect("guest compiles");
+
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let exe = workdir.path().join("run_guest");
+    let cc_status = Command::new(env::var("CC").unwrap_or("cc".to_owned()))
+        .args(&["-std=gnu99", "-Wall", "-Werror"])
+        .arg("-I")
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests/c_api/run_guest.c"))
+        .arg("-L")
+        .arg(lib_dir())
+        .arg("-llucet_runtime")
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .expect("can run C compiler");
+    assert!(cc_status.success(), "C program compiles against lucet.h");
+
+    let run_status = Command::new(&exe)
+        .arg(&so_file)
+        .env("LD_LIBRARY_PATH", lib_dir())
+        .status()
+        .expect("can run C program");
+    assert!(run_status.success(), "C program runs the guest");
+}
@@ -0,0 +1,108 @@
+// Runs the `c_api/guest.wat` test guest through the C API, checking the results against the
+// declarations in `lucet.h`. Exits with a non-zero status if any check fails.
+
+#include <stdio.h>
+#include <string.h>
+
+#include "lucet.h"
+
+#define CHECK(C)                                                                   \
+    do {                                                                           \
+        if (!(C)) {                                                                \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #C); \
+            exit(1);                                                               \
+        }                                                                          \
+    } while (0)
+
+#define CHECK_OK(E)                                                                      \
+    do {                                                                                 \
+        enum lucet_error e_ = (E);                                                       \
+        if (e_ != lucet_error_ok) {                                                      \
+            fprintf(stderr, "%s:%d: %s: %s\n", __FILE__, __LINE__, #E, lucet_error_name(e_)); \
+            exit(1);                                                                     \
+        }                                                                                \
+    } while (0)
+
+int main(int argc, char **argv)
+{
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <guest.so>\n", argv[0]);
+        return 1;
+    }
+
+    struct lucet_dl_module *mod;
+    CHECK_OK(lucet_dl_module_load(argv[1], &mod));
+
+    struct lucet_region *region;
+    CHECK_OK(lucet_mmap_region_create(1, NULL, &region));
+
+    struct lucet_instance *inst;
+    CHECK_OK(lucet_region_new_instance(region, mod, &inst));
+
+    struct lucet_state state;
+
+    // a function that returns normally
+    CHECK_OK(lucet_instance_run(inst, "add_2", 2,
+                                (struct lucet_val[]){ LUCET_VAL_U64(123), LUCET_VAL_U64(456) }));
+    CHECK_OK(lucet_instance_state(inst, &state));
+    CHECK(state.tag == lucet_state_tag_returned);
+    CHECK(strcmp(lucet_state_tag_name(state.tag), "lucet_state_tag_returned") == 0);
+    CHECK(LUCET_UNTYPED_RETVAL_TO_U64(state.val.returned) == 579);
+    lucet_state_release(&state);
+
+    // globals can be read and written by index
+    int64_t counter;
+    CHECK(lucet_instance_global_count(inst) == 1);
+    CHECK_OK(lucet_instance_get_global(inst, 0, &counter));
+    CHECK(counter == 7);
+    CHECK_OK(lucet_instance_set_global(inst, 0, 42));
+    CHECK_OK(lucet_instance_run(inst, "get_counter", 0, NULL));
+    CHECK_OK(lucet_instance_state(inst, &state));
+    CHECK(LUCET_UNTYPED_RETVAL_TO_I64(state.val.returned) == 42);
+    lucet_state_release(&state);
+    CHECK(lucet_instance_get_global(inst, 1, &counter) == lucet_error_invalid_argument);
+
+    // exports can be looked up by name
+    const void *func;
+    CHECK_OK(lucet_dl_module_get_export_func(mod, "add_2", &func));
+    CHECK(func != NULL);
+    CHECK(lucet_dl_module_get_export_func(mod, "nonexistent", &func) ==
+          lucet_error_symbol_not_found);
+    CHECK(lucet_instance_run(inst, "nonexistent", 0, NULL) == lucet_error_symbol_not_found);
+    CHECK(strcmp(lucet_error_name(lucet_error_symbol_not_found), "lucet_error_symbol_not_found") ==
+          0);
+
+    // a function that faults
+    CHECK(lucet_instance_run(inst, "div_2", 2,
+                             (struct lucet_val[]){ LUCET_VAL_U64(1), LUCET_VAL_U64(0) }) ==
+          lucet_error_runtime_fault);
+    CHECK_OK(lucet_instance_state(inst, &state));
+    CHECK(state.tag == lucet_state_tag_fault);
+    struct lucet_fault_details details;
+    CHECK_OK(lucet_state_fault_details(&state, &details));
+    CHECK(!details.fatal);
+    CHECK(details.trapcode.code == lucet_trapcode_type_integer_div_by_zero);
+    CHECK(strcmp(lucet_trapcode_type_name(details.trapcode.code),
+                 "lucet_trapcode_type_integer_div_by_zero") == 0);
+    CHECK(details.rip_addr_details.module_code_resolvable);
+    CHECK(details.rip_addr_details.in_module_code);
+    CHECK(details.signal_number == SIGFPE || details.signal_number == SIGILL);
+    void *provided;
+    CHECK(lucet_state_terminated_provided(&state, &provided) == lucet_error_invalid_argument);
+    lucet_state_release(&state);
+
+    CHECK_OK(lucet_instance_reset(inst));
+
+    // the embedder context can be replaced after the instance is created
+    int embed_ctx = 0;
+    CHECK(lucet_instance_insert_embed_ctx(inst, &embed_ctx) == NULL);
+    CHECK(lucet_instance_embed_ctx(inst) == &embed_ctx);
+    CHECK(lucet_instance_remove_embed_ctx(inst) == &embed_ctx);
+    CHECK(lucet_instance_embed_ctx(inst) == NULL);
+
+    lucet_instance_release(inst);
+    lucet_region_release(region);
+    lucet_dl_module_release(mod);
+
+    return 0;
+}
@@ -0,0 +1,58 @@
+use lucet_runtime::{GuestMemoryError, GuestPtr, GuestSlice, GuestType};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, GuestType)]
+struct Iovec {
+    buf: GuestSlice<u8>,
+    flags: u32,
+    tag: [u8; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, GuestType)]
+struct Pair(u64, i64);
+
+#[repr(align(8))]
+struct Heap([u8; 64]);
+
+#[test]
+fn derived_struct_round_trip() {
+    let mut heap = Heap([0; 64]);
+    let iov = Iovec {
+        buf: GuestSlice::new(GuestPtr::new(32), 5),
+        flags: 7,
+        tag: *b"lucy",
+    };
+    let p = GuestPtr::<Iovec>::new(8);
+    p.write(&mut heap.0, iov).expect("write in bounds");
+    assert_eq!(&heap.0[8..12], &32u32.to_le_bytes());
+    assert_eq!(&heap.0[12..16], &5u32.to_le_bytes());
+    assert_eq!(&heap.0[20..24], b"lucy");
+
+    heap.0[32..37].copy_from_slice(b"hello");
+    let read = p.read(&heap.0).expect("read in bounds");
+    assert_eq!(read, iov);
+    assert_eq!(
+        read.buf.as_slice(&heap.0).expect("slice in bounds"),
+        b"hello"
+    );
+}
+
+#[test]
+fn derived_struct_alignment() {
+    let heap = Heap([0; 64]);
+    assert_eq!(
+        GuestPtr::<Pair>::new(4).read(&heap.0),
+        Err(GuestMemoryError::Misaligned {
+            offset: 4,
+            align: 8
+        })
+    );
+    assert_eq!(
+        GuestPtr::<Pair>::new(56).read(&heap.0),
+        Err(GuestMemoryError::OutOfBounds {
+            offset: 56,
+            len: 16
+        })
+    );
+}
@@ -0,0 +1,3 @@
+use lucet_runtime_tests::linking_tests;
+
+linking_tests!(lucet_runtime::MmapRegion);
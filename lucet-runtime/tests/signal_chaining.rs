@@ -0,0 +1,3 @@
+use lucet_runtime_tests::signal_chaining_tests;
+
+signal_chaining_tests!(lucet_runtime::MmapRegion);
@@ -0,0 +1,3 @@
+use lucet_runtime_tests::signal_opt_out_tests;
+
+signal_opt_out_tests!(lucet_runtime::MmapRegion);
@@ -0,0 +1,3 @@
+use lucet_runtime_tests::table_tests;
+
+table_tests!(lucet_runtime::MmapRegion);
@@ -32,6 +32,10 @@ impl Instance {
         }
     }
 
+    pub fn lucet_instance_mut(&mut self) -> &mut lucet_runtime::Instance {
+        &mut self.lucet_instance
+    }
+
     pub fn run(&mut self, field: &str, args: &[Val]) -> Result<UntypedRetVal, RuntimeError> {
         let res = self.lucet_instance.run(field.as_bytes(), args);
         if let Err(_) = res {
@@ -1,22 +1,23 @@
 use crate::bindings;
 use crate::instance::Instance;
-use failure::{format_err, Error, Fail};
+use failure::{Error, Fail};
 use lucet_runtime::{self, MmapRegion, Module as LucetModule, Region, UntypedRetVal, Val};
 use lucetc::{
     compile,
     compiler::OptLevel,
     error::{LucetcError, LucetcErrorKind},
+    link,
+    load::read_module_buffer,
     program::{HeapSettings, Program},
 };
-use parity_wasm::{self, deserialize_buffer};
+use std::collections::HashSet;
 use std::io;
-use std::process::Command;
 use std::sync::Arc;
 
 #[derive(Fail, Debug)]
 pub enum ScriptError {
     #[fail(display = "Deserialization error: {}", _0)]
-    DeserializeError(parity_wasm::elements::Error),
+    DeserializeError(LucetcError),
     #[fail(display = "Validation error: {}", _0)]
     ValidationError(LucetcError),
     #[fail(display = "Program creation error: {}", _0)]
@@ -58,6 +59,15 @@ impl From<io::Error> for ScriptError {
 
 pub struct ScriptEnv {
     instances: Vec<(Option<String>, Instance)>,
+    registered: Vec<String>,
+}
+
+fn read_error(e: Error) -> ScriptError {
+    let e = LucetcError::from(e);
+    match e.get_context() {
+        LucetcErrorKind::Validation => ScriptError::ValidationError(e),
+        _ => ScriptError::DeserializeError(e),
+    }
 }
 
 fn program_error(e: LucetcError) -> ScriptError {
@@ -71,6 +81,7 @@ impl ScriptEnv {
     pub fn new() -> Self {
         Self {
             instances: Vec::new(),
+            registered: Vec::new(),
         }
     }
     pub fn instantiate(
@@ -78,9 +89,15 @@ impl ScriptEnv {
         module: Vec<u8>,
         name: &Option<String>,
     ) -> Result<(), ScriptError> {
-        let bindings = bindings::spec_test_bindings();
+        let mut bindings = bindings::spec_test_bindings();
+        // function imports from registered instances are satisfied by linking to them, rather than
+        // by the spectest hostcalls
+        for registered in self.registered.iter() {
+            bindings.link_module(registered);
+        }
 
-        let module = deserialize_buffer(&module).map_err(ScriptError::DeserializeError)?;
+        // the same validation and lowering that `lucetc` applies to the modules it reads
+        let module = read_module_buffer(&module).map_err(read_error)?;
 
         let program =
             Program::new(module, bindings, HeapSettings::default()).map_err(program_error)?;
@@ -104,19 +121,7 @@ impl ScriptEnv {
                 .map_err(ScriptError::CodegenError)?;
         }
 
-        let mut cmd_ld = Command::new("ld");
-        cmd_ld.arg(objfile_path.clone());
-        cmd_ld.arg("-shared");
-        cmd_ld.arg("-o");
-        cmd_ld.arg(sofile_path.clone());
-        let run_ld = cmd_ld.output()?;
-        if !run_ld.status.success() {
-            Err(ScriptError::CodegenError(format_err!(
-                "ld {:?}: {}",
-                objfile_path,
-                String::from_utf8_lossy(&run_ld.stderr)
-            )))?;
-        }
+        link::shared_object_file(&objfile_path, &sofile_path).map_err(ScriptError::CodegenError)?;
 
         let lucet_module: Arc<dyn LucetModule> =
             lucet_runtime::DlModule::load(sofile_path).map_err(ScriptError::LoadError)?;
@@ -124,10 +129,23 @@ impl ScriptEnv {
         let lucet_region =
             MmapRegion::create(1, &lucet_runtime::Limits::default()).expect("valid region");
 
-        let lucet_instance = lucet_region
+        let mut lucet_instance = lucet_region
             .new_instance(lucet_module.clone())
             .map_err(ScriptError::InstantiateError)?;
 
+        let linked_modules: HashSet<String> = program
+            .linked_import_functions()
+            .iter()
+            .map(|f| f.module().to_owned())
+            .collect();
+        for module_name in linked_modules {
+            let (_, linked) = self.instance_named_mut(&Some(module_name.clone()))?;
+            // safety: registered instances are kept alive for the rest of the script, and
+            // instances are only run from this thread
+            unsafe { lucet_instance.link_instance(&module_name, linked.lucet_instance_mut()) }
+                .map_err(ScriptError::InstantiateError)?;
+        }
+
         self.instances.push((
             name.clone(),
             Instance::new(program, lucet_module, lucet_region, lucet_instance),
@@ -184,6 +202,7 @@ impl ScriptEnv {
     pub fn register(&mut self, name: &Option<String>, as_name: &str) -> Result<(), ScriptError> {
         let (ref mut oldname, _) = self.instance_named_mut(name)?;
         *oldname = Some(as_name.to_owned());
+        self.registered.push(as_name.to_owned());
         Ok(())
     }
 
@@ -0,0 +1,213 @@
+//! Run the modules of the spec tests through `lucetc::validate::validate_code` on its own, apart
+//! from the validation `pwasm_validation` does afterwards, to check that it accepts every valid
+//! function body and rejects every invalid one.
+
+use lucetc::bindings::Bindings;
+use lucetc::error::LucetcErrorKind;
+use lucetc::load::read_module_buffer;
+use lucetc::program::{HeapSettings, Program};
+use lucetc::validate::validate_code;
+use std::fs;
+use std::path::PathBuf;
+use wabt::script::{CommandKind, ScriptParser};
+
+const SECTION_CODE: u8 = 10;
+
+fn validate_core_spec_test(name: &str) {
+    let file = PathBuf::from(&format!("spec/test/core/{}.wast", name));
+    assert!(file.exists());
+    let wast = fs::read_to_string(&file).unwrap();
+    let mut parser: ScriptParser = ScriptParser::from_str(&wast).unwrap();
+
+    let mut failures = vec![];
+    while let Some(cmd) = parser.next().unwrap() {
+        let line = cmd.line;
+        match cmd.kind {
+            CommandKind::Module { module, .. }
+            | CommandKind::AssertUninstantiable { module, .. }
+            | CommandKind::AssertUnlinkable { module, .. } => {
+                if let Err(e) = validate_code(&module.into_vec()) {
+                    failures.push(format!("line {}: valid module rejected: {}", line, e));
+                }
+            }
+            CommandKind::AssertInvalid { module, message } => {
+                let wasm = module.into_vec();
+                if validate_code(&wasm).is_ok() && !invalid_outside_code(&wasm) {
+                    failures.push(format!(
+                        "line {}: invalid module accepted, expected \"{}\"",
+                        line, message
+                    ));
+                }
+            }
+            CommandKind::AssertMalformed { module, .. } => {
+                // the rest of lucetc may be the one to reject it, as long as this does not panic
+                let _ = validate_code(&module.into_vec());
+            }
+            _ => (),
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "{} had {} failures:\n{}",
+            name,
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+/// Whether a module is still invalid once the body of every function is replaced by
+/// `unreachable`, so that `validate_code` is not the pass that should reject it.
+fn invalid_outside_code(wasm: &[u8]) -> bool {
+    let stubbed = stub_code(wasm);
+    match read_module_buffer(&stubbed) {
+        // imports are not bound to anything, so only errors from validation count
+        Ok(module) => match Program::new(module, Bindings::empty(), HeapSettings::default()) {
+            Ok(_) => false,
+            Err(e) => *e.get_context() == LucetcErrorKind::Validation,
+        },
+        Err(_) => true,
+    }
+}
+
+/// Replace the body of every function in a binary module with `unreachable`, which is valid for
+/// any function type.
+fn stub_code(wasm: &[u8]) -> Vec<u8> {
+    let mut out = wasm[..8].to_vec();
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let start = pos;
+        let id = wasm[pos];
+        let (len, payload_start) = read_uleb(wasm, pos + 1);
+        pos = payload_start + len as usize;
+        if id == SECTION_CODE {
+            let (count, _) = read_uleb(wasm, payload_start);
+            let mut section = vec![];
+            write_uleb(&mut section, count);
+            for _ in 0..count {
+                // no locals, `unreachable`, `end`
+                section.extend_from_slice(&[3, 0, 0x00, 0x0b]);
+            }
+            out.push(id);
+            write_uleb(&mut out, section.len() as u32);
+            out.extend_from_slice(&section);
+        } else {
+            out.extend_from_slice(&wasm[start..pos]);
+        }
+    }
+    out
+}
+
+fn read_uleb(bytes: &[u8], mut pos: usize) -> (u32, usize) {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[pos];
+        pos += 1;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return (value, pos);
+        }
+        shift += 7;
+    }
+}
+
+fn write_uleb(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = value as u8 & 0x7f;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+macro_rules! validate_core_spec_test {
+    ($base_name:ident) => {
+        #[test]
+        pub fn $base_name() {
+            validate_core_spec_test(stringify!($base_name));
+        }
+    };
+    ($base_name:ident, $file_name:expr) => {
+        #[test]
+        pub fn $base_name() {
+            validate_core_spec_test($file_name);
+        }
+    };
+}
+
+validate_core_spec_test!(address);
+validate_core_spec_test!(align);
+validate_core_spec_test!(binary);
+validate_core_spec_test!(block);
+validate_core_spec_test!(break_drop, "break-drop");
+validate_core_spec_test!(br_if);
+validate_core_spec_test!(br_table);
+validate_core_spec_test!(br);
+validate_core_spec_test!(call_indirect);
+validate_core_spec_test!(call);
+validate_core_spec_test!(comments);
+validate_core_spec_test!(const_, "const");
+validate_core_spec_test!(conversions);
+validate_core_spec_test!(custom);
+validate_core_spec_test!(data);
+validate_core_spec_test!(elem);
+validate_core_spec_test!(endianness);
+validate_core_spec_test!(exports);
+validate_core_spec_test!(f32_bitwise);
+validate_core_spec_test!(f32_cmp);
+validate_core_spec_test!(f32_, "f32");
+validate_core_spec_test!(f64_bitwise);
+validate_core_spec_test!(f64_cmp);
+validate_core_spec_test!(f64_, "f64");
+validate_core_spec_test!(fac);
+validate_core_spec_test!(float_exprs);
+validate_core_spec_test!(float_literals);
+validate_core_spec_test!(float_memory);
+validate_core_spec_test!(float_misc);
+validate_core_spec_test!(forward);
+validate_core_spec_test!(func_ptrs);
+validate_core_spec_test!(func);
+validate_core_spec_test!(get_local);
+validate_core_spec_test!(globals);
+validate_core_spec_test!(i32_, "i32");
+validate_core_spec_test!(i64_, "i64");
+validate_core_spec_test!(if_, "if");
+validate_core_spec_test!(imports);
+validate_core_spec_test!(inline_module, "inline-module");
+validate_core_spec_test!(int_exprs);
+validate_core_spec_test!(int_literals);
+validate_core_spec_test!(labels);
+validate_core_spec_test!(left_to_right, "left-to-right");
+validate_core_spec_test!(linking);
+validate_core_spec_test!(loop_, "loop");
+validate_core_spec_test!(memory_grow);
+validate_core_spec_test!(memory_redundancy);
+validate_core_spec_test!(memory_trap);
+validate_core_spec_test!(memory);
+validate_core_spec_test!(names);
+validate_core_spec_test!(nop);
+validate_core_spec_test!(return_, "return");
+validate_core_spec_test!(select);
+validate_core_spec_test!(set_local);
+validate_core_spec_test!(skip_stack_guard_page, "skip-stack-guard-page");
+validate_core_spec_test!(stack);
+validate_core_spec_test!(start);
+validate_core_spec_test!(store_retval);
+validate_core_spec_test!(switch);
+validate_core_spec_test!(tee_local);
+validate_core_spec_test!(token);
+validate_core_spec_test!(traps);
+validate_core_spec_test!(typecheck);
+validate_core_spec_test!(type_, "type");
+validate_core_spec_test!(unreachable);
+validate_core_spec_test!(unreached_invaild, "unreached-invalid");
+validate_core_spec_test!(unwind);
+validate_core_spec_test!(utf8_custom_section_id, "utf8-custom-section-id");
+validate_core_spec_test!(utf8_import_field, "utf8-import-field");
+validate_core_spec_test!(utf8_import_module, "utf8-import-module");
+validate_core_spec_test!(utf8_invalid_encoding, "utf8-invalid-encoding");
@@ -93,4 +93,30 @@ fn main() {
         .expect("can generate host bindings")
         .write_to_file(out_path.join("wasi_host.rs"))
         .expect("can write host bindings");
+
+    generate_headers();
+}
+
+/// Regenerate `include/lucet_wasi.h` from the Rust sources.
+///
+/// The header is checked in, so this only runs with the `generate-headers` feature; otherwise
+/// building the crate leaves the source tree untouched. Run `make headers` after changing the C
+/// API; CI runs `make headers-check`, which fails if the checked-in headers are out of date.
+#[cfg(feature = "generate-headers")]
+fn generate_headers() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml").to_str().unwrap())
+        .expect("can read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/c_api.rs"))
+        .generate()
+        .expect("can generate lucet_wasi.h")
+        .write_to_file(crate_dir.join("include/lucet_wasi.h"));
+
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/c_api.rs");
 }
+
+#[cfg(not(feature = "generate-headers"))]
+fn generate_headers() {}
@@ -1,3 +1,5 @@
+/* Generated from the lucet-wasi C API by cbindgen. Do not edit by hand. */
+
 #ifndef LUCET_WASI_H
 #define LUCET_WASI_H
 
@@ -5,17 +7,24 @@
 
 struct lucet_wasi_ctx;
 
-struct lucet_wasi_ctx *lucet_wasi_ctx_create();
+/**
+ * Create a Lucet instance with the given WASI context.
+ *
+ * After this call, the `wasi_ctx` pointer is no longer valid.
+ */
+enum lucet_error lucet_region_new_instance_with_wasi_ctx(const struct lucet_region *region,
+                                                         const struct lucet_dl_module *module,
+                                                         struct lucet_wasi_ctx *wasi_ctx,
+                                                         struct lucet_instance **inst_out);
 
-enum lucet_error lucet_wasi_ctx_args(struct lucet_wasi_ctx *wasi_ctx, size_t argc, char **argv);
+enum lucet_error lucet_wasi_ctx_args(struct lucet_wasi_ctx *wasi_ctx,
+                                     uintptr_t argc,
+                                     const char *const *argv);
 
-enum lucet_error lucet_wasi_ctx_inherit_env(struct lucet_wasi_ctx *wasi_ctx);
+struct lucet_wasi_ctx *lucet_wasi_ctx_create(void);
 
 void lucet_wasi_ctx_destroy(struct lucet_wasi_ctx *wasi_ctx);
 
-enum lucet_error lucet_region_new_instance_with_wasi_ctx(const struct lucet_region *   region,
-                                                         const struct lucet_dl_module *module,
-                                                         struct lucet_wasi_ctx *       wasi_ctx,
-                                                         struct lucet_instance **      inst_out);
+enum lucet_error lucet_wasi_ctx_inherit_env(struct lucet_wasi_ctx *wasi_ctx);
 
 #endif /* LUCET_WASI_H */
@@ -6,7 +6,6 @@ use lucet_runtime_internals::{assert_nonnull, with_ffi_arcs};
 use std::ffi::CStr;
 use std::sync::Arc;
 
-#[repr(C)]
 pub struct lucet_wasi_ctx {
     _unused: [u8; 0],
 }
@@ -15,6 +15,7 @@ use crate::memory::*;
 use crate::{host, wasm32};
 
 use cast::From as _0;
+use lucet_runtime::lucet_hostcalls;
 use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
 
 use nix::convert_ioctl_res;
@@ -30,686 +31,674 @@ const O_RSYNC: nix::fcntl::OFlag = nix::fcntl::OFlag::O_RSYNC;
 #[cfg(not(target_os = "linux"))]
 const O_RSYNC: nix::fcntl::OFlag = nix::fcntl::OFlag::O_SYNC;
 
+// `proc_exit` never returns, and terminating an instance does not unwind, so it does not need the
+// panic handling provided by `lucet_hostcalls!`
 #[no_mangle]
 pub extern "C" fn __wasi_proc_exit(vmctx: *mut lucet_vmctx, rval: wasm32::__wasi_exitcode_t) -> ! {
     let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
     vmctx.terminate(dec_exitcode(rval))
 }
 
-#[no_mangle]
-pub extern "C" fn __wasi_args_get(
-    vmctx_raw: *mut lucet_vmctx,
-    argv_ptr: wasm32::uintptr_t,
-    argv_buf: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
-    let ctx: &WasiCtx = vmctx.get_embed_ctx();
+lucet_hostcalls! {
+    pub extern "C" fn __wasi_args_get(
+        vmctx_raw: *mut lucet_vmctx,
+        argv_ptr: wasm32::uintptr_t,
+        argv_buf: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
+        let ctx: &WasiCtx = vmctx.get_embed_ctx();
 
-    let mut argv_buf_offset = 0;
-    let mut argv = vec![];
+        let mut argv_buf_offset = 0;
+        let mut argv = vec![];
 
-    for arg in ctx.args.iter() {
-        let arg_bytes = arg.as_bytes_with_nul();
-        let arg_ptr = argv_buf + argv_buf_offset;
+        for arg in ctx.args.iter() {
+            let arg_bytes = arg.as_bytes_with_nul();
+            let arg_ptr = argv_buf + argv_buf_offset;
 
-        // nasty aliasing here, but we aren't interfering with the borrow for `ctx`
-        // TODO: rework vmctx interface to avoid this
-        let mut vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
-        if let Err(e) = unsafe { enc_slice_of(&mut vmctx, arg_bytes, arg_ptr) } {
-            return enc_errno(e);
-        }
+            // nasty aliasing here, but we aren't interfering with the borrow for `ctx`
+            // TODO: rework vmctx interface to avoid this
+            let mut vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
+            if let Err(e) = unsafe { enc_slice_of(&mut vmctx, arg_bytes, arg_ptr) } {
+                return enc_errno(e);
+            }
 
-        argv.push(arg_ptr);
+            argv.push(arg_ptr);
 
-        argv_buf_offset = if let Some(new_offset) = argv_buf_offset.checked_add(
-            wasm32::uintptr_t::cast(arg_bytes.len())
-                .expect("cast overflow would have been caught by `enc_slice_of` above"),
-        ) {
-            new_offset
-        } else {
-            return wasm32::__WASI_EOVERFLOW;
+            argv_buf_offset = if let Some(new_offset) = argv_buf_offset.checked_add(
+                wasm32::uintptr_t::cast(arg_bytes.len())
+                    .expect("cast overflow would have been caught by `enc_slice_of` above"),
+            ) {
+                new_offset
+            } else {
+                return wasm32::__WASI_EOVERFLOW;
+            }
         }
-    }
 
-    unsafe {
-        enc_slice_of(&mut vmctx, argv.as_slice(), argv_ptr)
-            .map(|_| wasm32::__WASI_ESUCCESS)
-            .unwrap_or_else(|e| e)
+        unsafe {
+            enc_slice_of(&mut vmctx, argv.as_slice(), argv_ptr)
+                .map(|_| wasm32::__WASI_ESUCCESS)
+                .unwrap_or_else(|e| e)
+        }
     }
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_args_sizes_get(
-    vmctx: *mut lucet_vmctx,
-    argc_ptr: wasm32::uintptr_t,
-    argv_buf_size_ptr: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+    pub extern "C" fn __wasi_args_sizes_get(
+        vmctx: *mut lucet_vmctx,
+        argc_ptr: wasm32::uintptr_t,
+        argv_buf_size_ptr: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
 
-    let ctx: &WasiCtx = vmctx.get_embed_ctx();
+        let ctx: &WasiCtx = vmctx.get_embed_ctx();
 
-    let argc = ctx.args.len();
-    let argv_size = ctx
-        .args
-        .iter()
-        .map(|arg| arg.as_bytes_with_nul().len())
-        .sum();
+        let argc = ctx.args.len();
+        let argv_size = ctx
+            .args
+            .iter()
+            .map(|arg| arg.as_bytes_with_nul().len())
+            .sum();
 
-    unsafe {
-        if let Err(e) = enc_usize_byref(&mut vmctx, argc_ptr, argc) {
-            return enc_errno(e);
-        }
-        if let Err(e) = enc_usize_byref(&mut vmctx, argv_buf_size_ptr, argv_size) {
-            return enc_errno(e);
+        unsafe {
+            if let Err(e) = enc_usize_byref(&mut vmctx, argc_ptr, argc) {
+                return enc_errno(e);
+            }
+            if let Err(e) = enc_usize_byref(&mut vmctx, argv_buf_size_ptr, argv_size) {
+                return enc_errno(e);
+            }
         }
+        wasm32::__WASI_ESUCCESS
     }
-    wasm32::__WASI_ESUCCESS
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_clock_res_get(
-    vmctx: *mut lucet_vmctx,
-    clock_id: wasm32::__wasi_clockid_t,
-    resolution_ptr: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+    pub extern "C" fn __wasi_clock_res_get(
+        vmctx: *mut lucet_vmctx,
+        clock_id: wasm32::__wasi_clockid_t,
+        resolution_ptr: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+
+        // convert the supported clocks to the libc types, or return EINVAL
+        let clock_id = match dec_clockid(clock_id) {
+            host::__WASI_CLOCK_REALTIME => libc::CLOCK_REALTIME,
+            host::__WASI_CLOCK_MONOTONIC => libc::CLOCK_MONOTONIC,
+            host::__WASI_CLOCK_PROCESS_CPUTIME_ID => libc::CLOCK_PROCESS_CPUTIME_ID,
+            host::__WASI_CLOCK_THREAD_CPUTIME_ID => libc::CLOCK_THREAD_CPUTIME_ID,
+            _ => return wasm32::__WASI_EINVAL,
+        };
 
-    // convert the supported clocks to the libc types, or return EINVAL
-    let clock_id = match dec_clockid(clock_id) {
-        host::__WASI_CLOCK_REALTIME => libc::CLOCK_REALTIME,
-        host::__WASI_CLOCK_MONOTONIC => libc::CLOCK_MONOTONIC,
-        host::__WASI_CLOCK_PROCESS_CPUTIME_ID => libc::CLOCK_PROCESS_CPUTIME_ID,
-        host::__WASI_CLOCK_THREAD_CPUTIME_ID => libc::CLOCK_THREAD_CPUTIME_ID,
-        _ => return wasm32::__WASI_EINVAL,
-    };
-
-    // no `nix` wrapper for clock_getres, so we do it ourselves
-    let mut timespec = unsafe { std::mem::uninitialized::<libc::timespec>() };
-    let res = unsafe { libc::clock_getres(clock_id, &mut timespec as *mut libc::timespec) };
-    if res != 0 {
-        return wasm32::errno_from_nix(nix::errno::Errno::last());
-    }
+        // no `nix` wrapper for clock_getres, so we do it ourselves
+        let mut timespec = unsafe { std::mem::uninitialized::<libc::timespec>() };
+        let res = unsafe { libc::clock_getres(clock_id, &mut timespec as *mut libc::timespec) };
+        if res != 0 {
+            return wasm32::errno_from_nix(nix::errno::Errno::last());
+        }
 
-    // convert to nanoseconds, returning EOVERFLOW in case of overflow; this is freelancing a bit
-    // from the spec but seems like it'll be an unusual situation to hit
-    (timespec.tv_sec as host::__wasi_timestamp_t)
-        .checked_mul(1_000_000_000)
-        .and_then(|sec_ns| sec_ns.checked_add(timespec.tv_nsec as host::__wasi_timestamp_t))
-        .map(|resolution| {
-            // a supported clock can never return zero; this case will probably never get hit, but
-            // make sure we follow the spec
-            if resolution == 0 {
-                wasm32::__WASI_EINVAL
-            } else {
-                unsafe {
-                    enc_timestamp_byref(&mut vmctx, resolution_ptr, resolution)
-                        .map(|_| wasm32::__WASI_ESUCCESS)
-                        .unwrap_or_else(|e| e)
+        // convert to nanoseconds, returning EOVERFLOW in case of overflow; this is freelancing a bit
+        // from the spec but seems like it'll be an unusual situation to hit
+        (timespec.tv_sec as host::__wasi_timestamp_t)
+            .checked_mul(1_000_000_000)
+            .and_then(|sec_ns| sec_ns.checked_add(timespec.tv_nsec as host::__wasi_timestamp_t))
+            .map(|resolution| {
+                // a supported clock can never return zero; this case will probably never get hit, but
+                // make sure we follow the spec
+                if resolution == 0 {
+                    wasm32::__WASI_EINVAL
+                } else {
+                    unsafe {
+                        enc_timestamp_byref(&mut vmctx, resolution_ptr, resolution)
+                            .map(|_| wasm32::__WASI_ESUCCESS)
+                            .unwrap_or_else(|e| e)
+                    }
                 }
-            }
-        })
-        .unwrap_or(wasm32::__WASI_EOVERFLOW)
-}
-
-#[no_mangle]
-pub extern "C" fn __wasi_clock_time_get(
-    vmctx: *mut lucet_vmctx,
-    clock_id: wasm32::__wasi_clockid_t,
-    // ignored for now, but will be useful once we put optional limits on precision to reduce side
-    // channels
-    _precision: wasm32::__wasi_timestamp_t,
-    time_ptr: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
-
-    // convert the supported clocks to the libc types, or return EINVAL
-    let clock_id = match dec_clockid(clock_id) {
-        host::__WASI_CLOCK_REALTIME => libc::CLOCK_REALTIME,
-        host::__WASI_CLOCK_MONOTONIC => libc::CLOCK_MONOTONIC,
-        host::__WASI_CLOCK_PROCESS_CPUTIME_ID => libc::CLOCK_PROCESS_CPUTIME_ID,
-        host::__WASI_CLOCK_THREAD_CPUTIME_ID => libc::CLOCK_THREAD_CPUTIME_ID,
-        _ => return wasm32::__WASI_EINVAL,
-    };
-
-    // no `nix` wrapper for clock_getres, so we do it ourselves
-    let mut timespec = unsafe { std::mem::uninitialized::<libc::timespec>() };
-    let res = unsafe { libc::clock_gettime(clock_id, &mut timespec as *mut libc::timespec) };
-    if res != 0 {
-        return wasm32::errno_from_nix(nix::errno::Errno::last());
+            })
+            .unwrap_or(wasm32::__WASI_EOVERFLOW)
     }
 
-    // convert to nanoseconds, returning EOVERFLOW in case of overflow; this is freelancing a bit
-    // from the spec but seems like it'll be an unusual situation to hit
-    (timespec.tv_sec as host::__wasi_timestamp_t)
-        .checked_mul(1_000_000_000)
-        .and_then(|sec_ns| sec_ns.checked_add(timespec.tv_nsec as host::__wasi_timestamp_t))
-        .map(|time| unsafe {
-            enc_timestamp_byref(&mut vmctx, time_ptr, time)
-                .map(|_| wasm32::__WASI_ESUCCESS)
-                .unwrap_or_else(|e| e)
-        })
-        .unwrap_or(wasm32::__WASI_EOVERFLOW)
-}
-
-#[no_mangle]
-pub extern "C" fn __wasi_environ_get(
-    vmctx_raw: *mut lucet_vmctx,
-    environ_ptr: wasm32::uintptr_t,
-    environ_buf: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
-    let ctx: &WasiCtx = vmctx.get_embed_ctx();
-
-    let mut environ_buf_offset = 0;
-    let mut environ = vec![];
-
-    for pair in ctx.env.iter() {
-        let env_bytes = pair.as_bytes_with_nul();
-        let env_ptr = environ_buf + environ_buf_offset;
+    pub extern "C" fn __wasi_clock_time_get(
+        vmctx: *mut lucet_vmctx,
+        clock_id: wasm32::__wasi_clockid_t,
+        // ignored for now, but will be useful once we put optional limits on precision to reduce side
+        // channels
+        _precision: wasm32::__wasi_timestamp_t,
+        time_ptr: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+
+        // convert the supported clocks to the libc types, or return EINVAL
+        let clock_id = match dec_clockid(clock_id) {
+            host::__WASI_CLOCK_REALTIME => libc::CLOCK_REALTIME,
+            host::__WASI_CLOCK_MONOTONIC => libc::CLOCK_MONOTONIC,
+            host::__WASI_CLOCK_PROCESS_CPUTIME_ID => libc::CLOCK_PROCESS_CPUTIME_ID,
+            host::__WASI_CLOCK_THREAD_CPUTIME_ID => libc::CLOCK_THREAD_CPUTIME_ID,
+            _ => return wasm32::__WASI_EINVAL,
+        };
 
-        // nasty aliasing here, but we aren't interfering with the borrow for `ctx`
-        // TODO: rework vmctx interface to avoid this
-        let mut vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
-        if let Err(e) = unsafe { enc_slice_of(&mut vmctx, env_bytes, env_ptr) } {
-            return enc_errno(e);
+        // no `nix` wrapper for clock_getres, so we do it ourselves
+        let mut timespec = unsafe { std::mem::uninitialized::<libc::timespec>() };
+        let res = unsafe { libc::clock_gettime(clock_id, &mut timespec as *mut libc::timespec) };
+        if res != 0 {
+            return wasm32::errno_from_nix(nix::errno::Errno::last());
         }
 
-        environ.push(env_ptr);
-
-        environ_buf_offset = if let Some(new_offset) = environ_buf_offset.checked_add(
-            wasm32::uintptr_t::cast(env_bytes.len())
-                .expect("cast overflow would have been caught by `enc_slice_of` above"),
-        ) {
-            new_offset
-        } else {
-            return wasm32::__WASI_EOVERFLOW;
-        }
+        // convert to nanoseconds, returning EOVERFLOW in case of overflow; this is freelancing a bit
+        // from the spec but seems like it'll be an unusual situation to hit
+        (timespec.tv_sec as host::__wasi_timestamp_t)
+            .checked_mul(1_000_000_000)
+            .and_then(|sec_ns| sec_ns.checked_add(timespec.tv_nsec as host::__wasi_timestamp_t))
+            .map(|time| unsafe {
+                enc_timestamp_byref(&mut vmctx, time_ptr, time)
+                    .map(|_| wasm32::__WASI_ESUCCESS)
+                    .unwrap_or_else(|e| e)
+            })
+            .unwrap_or(wasm32::__WASI_EOVERFLOW)
     }
 
-    unsafe {
-        enc_slice_of(&mut vmctx, environ.as_slice(), environ_ptr)
-            .map(|_| wasm32::__WASI_ESUCCESS)
-            .unwrap_or_else(|e| e)
-    }
-}
+    pub extern "C" fn __wasi_environ_get(
+        vmctx_raw: *mut lucet_vmctx,
+        environ_ptr: wasm32::uintptr_t,
+        environ_buf: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
+        let ctx: &WasiCtx = vmctx.get_embed_ctx();
 
-#[no_mangle]
-pub extern "C" fn __wasi_environ_sizes_get(
-    vmctx: *mut lucet_vmctx,
-    environ_count_ptr: wasm32::uintptr_t,
-    environ_size_ptr: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+        let mut environ_buf_offset = 0;
+        let mut environ = vec![];
 
-    let ctx: &WasiCtx = vmctx.get_embed_ctx();
+        for pair in ctx.env.iter() {
+            let env_bytes = pair.as_bytes_with_nul();
+            let env_ptr = environ_buf + environ_buf_offset;
 
-    let environ_count = ctx.env.len();
-    if let Some(environ_size) = ctx.env.iter().try_fold(0, |acc: u32, pair| {
-        acc.checked_add(pair.as_bytes_with_nul().len() as u32)
-    }) {
-        unsafe {
-            if let Err(e) = enc_usize_byref(&mut vmctx, environ_count_ptr, environ_count) {
+            // nasty aliasing here, but we aren't interfering with the borrow for `ctx`
+            // TODO: rework vmctx interface to avoid this
+            let mut vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
+            if let Err(e) = unsafe { enc_slice_of(&mut vmctx, env_bytes, env_ptr) } {
                 return enc_errno(e);
             }
-            if let Err(e) = enc_usize_byref(&mut vmctx, environ_size_ptr, environ_size as usize) {
-                return enc_errno(e);
+
+            environ.push(env_ptr);
+
+            environ_buf_offset = if let Some(new_offset) = environ_buf_offset.checked_add(
+                wasm32::uintptr_t::cast(env_bytes.len())
+                    .expect("cast overflow would have been caught by `enc_slice_of` above"),
+            ) {
+                new_offset
+            } else {
+                return wasm32::__WASI_EOVERFLOW;
             }
         }
-        wasm32::__WASI_ESUCCESS
-    } else {
-        wasm32::__WASI_EOVERFLOW
+
+        unsafe {
+            enc_slice_of(&mut vmctx, environ.as_slice(), environ_ptr)
+                .map(|_| wasm32::__WASI_ESUCCESS)
+                .unwrap_or_else(|e| e)
+        }
     }
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_fd_close(
-    vmctx: *mut lucet_vmctx,
-    fd: wasm32::__wasi_fd_t,
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
-    let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
-    let fd = dec_fd(fd);
-    if let Some(fdent) = ctx.fds.get(&fd) {
-        // can't close preopened files
-        if fdent.preopen_path.is_some() {
-            return wasm32::__WASI_ENOTSUP;
+    pub extern "C" fn __wasi_environ_sizes_get(
+        vmctx: *mut lucet_vmctx,
+        environ_count_ptr: wasm32::uintptr_t,
+        environ_size_ptr: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+
+        let ctx: &WasiCtx = vmctx.get_embed_ctx();
+
+        let environ_count = ctx.env.len();
+        if let Some(environ_size) = ctx.env.iter().try_fold(0, |acc: u32, pair| {
+            acc.checked_add(pair.as_bytes_with_nul().len() as u32)
+        }) {
+            unsafe {
+                if let Err(e) = enc_usize_byref(&mut vmctx, environ_count_ptr, environ_count) {
+                    return enc_errno(e);
+                }
+                if let Err(e) = enc_usize_byref(&mut vmctx, environ_size_ptr, environ_size as usize) {
+                    return enc_errno(e);
+                }
+            }
+            wasm32::__WASI_ESUCCESS
+        } else {
+            wasm32::__WASI_EOVERFLOW
         }
     }
-    if let Some(mut fdent) = ctx.fds.remove(&fd) {
-        fdent.fd_object.needs_close = false;
-        match nix::unistd::close(fdent.fd_object.rawfd) {
-            Ok(_) => wasm32::__WASI_ESUCCESS,
-            Err(e) => wasm32::errno_from_nix(e.as_errno().unwrap()),
+
+    pub extern "C" fn __wasi_fd_close(
+        vmctx: *mut lucet_vmctx,
+        fd: wasm32::__wasi_fd_t,
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+        let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
+        let fd = dec_fd(fd);
+        if let Some(fdent) = ctx.fds.get(&fd) {
+            // can't close preopened files
+            if fdent.preopen_path.is_some() {
+                return wasm32::__WASI_ENOTSUP;
+            }
+        }
+        if let Some(mut fdent) = ctx.fds.remove(&fd) {
+            fdent.fd_object.needs_close = false;
+            match nix::unistd::close(fdent.fd_object.rawfd) {
+                Ok(_) => wasm32::__WASI_ESUCCESS,
+                Err(e) => wasm32::errno_from_nix(e.as_errno().unwrap()),
+            }
+        } else {
+            wasm32::__WASI_EBADF
         }
-    } else {
-        wasm32::__WASI_EBADF
     }
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_fd_fdstat_get(
-    vmctx: *mut lucet_vmctx,
-    fd: wasm32::__wasi_fd_t,
-    fdstat_ptr: wasm32::uintptr_t, // *mut wasm32::__wasi_fdstat_t
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+    pub extern "C" fn __wasi_fd_fdstat_get(
+        vmctx: *mut lucet_vmctx,
+        fd: wasm32::__wasi_fd_t,
+        fdstat_ptr: wasm32::uintptr_t, // *mut wasm32::__wasi_fdstat_t
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
 
-    let host_fd = dec_fd(fd);
-    let mut host_fdstat = match unsafe { dec_fdstat_byref(&mut vmctx, fdstat_ptr) } {
-        Ok(host_fdstat) => host_fdstat,
-        Err(e) => return enc_errno(e),
-    };
-
-    let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
-    let errno = if let Some(fe) = ctx.fds.get(&host_fd) {
-        host_fdstat.fs_filetype = fe.fd_object.ty;
-        host_fdstat.fs_rights_base = fe.rights_base;
-        host_fdstat.fs_rights_inheriting = fe.rights_inheriting;
-        use nix::fcntl::{fcntl, OFlag, F_GETFL};
-        match fcntl(fe.fd_object.rawfd, F_GETFL).map(OFlag::from_bits_truncate) {
-            Ok(flags) => {
-                host_fdstat.fs_flags = host::fdflags_from_nix(flags);
-                wasm32::__WASI_ESUCCESS
+        let host_fd = dec_fd(fd);
+        let mut host_fdstat = match unsafe { dec_fdstat_byref(&mut vmctx, fdstat_ptr) } {
+            Ok(host_fdstat) => host_fdstat,
+            Err(e) => return enc_errno(e),
+        };
+
+        let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
+        let errno = if let Some(fe) = ctx.fds.get(&host_fd) {
+            host_fdstat.fs_filetype = fe.fd_object.ty;
+            host_fdstat.fs_rights_base = fe.rights_base;
+            host_fdstat.fs_rights_inheriting = fe.rights_inheriting;
+            use nix::fcntl::{fcntl, OFlag, F_GETFL};
+            match fcntl(fe.fd_object.rawfd, F_GETFL).map(OFlag::from_bits_truncate) {
+                Ok(flags) => {
+                    host_fdstat.fs_flags = host::fdflags_from_nix(flags);
+                    wasm32::__WASI_ESUCCESS
+                }
+                Err(e) => wasm32::errno_from_nix(e.as_errno().unwrap()),
             }
-            Err(e) => wasm32::errno_from_nix(e.as_errno().unwrap()),
+        } else {
+            wasm32::__WASI_EBADF
+        };
+
+        unsafe {
+            enc_fdstat_byref(&mut vmctx, fdstat_ptr, host_fdstat)
+                .expect("can write back into the pointer we read from");
         }
-    } else {
-        wasm32::__WASI_EBADF
-    };
 
-    unsafe {
-        enc_fdstat_byref(&mut vmctx, fdstat_ptr, host_fdstat)
-            .expect("can write back into the pointer we read from");
+        errno
     }
 
-    errno
-}
-
-#[no_mangle]
-pub extern "C" fn __wasi_fd_fdstat_set_flags(
-    vmctx: *mut lucet_vmctx,
-    fd: wasm32::__wasi_fd_t,
-    fdflags: wasm32::__wasi_fdflags_t,
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+    pub extern "C" fn __wasi_fd_fdstat_set_flags(
+        vmctx: *mut lucet_vmctx,
+        fd: wasm32::__wasi_fd_t,
+        fdflags: wasm32::__wasi_fdflags_t,
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
 
-    let host_fd = dec_fd(fd);
-    let host_fdflags = dec_fdflags(fdflags);
-    let nix_flags = host::nix_from_fdflags(host_fdflags);
+        let host_fd = dec_fd(fd);
+        let host_fdflags = dec_fdflags(fdflags);
+        let nix_flags = host::nix_from_fdflags(host_fdflags);
 
-    let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
+        let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
 
-    if let Some(fe) = ctx.fds.get(&host_fd) {
-        match nix::fcntl::fcntl(fe.fd_object.rawfd, nix::fcntl::F_SETFL(nix_flags)) {
-            Ok(_) => wasm32::__WASI_ESUCCESS,
-            Err(e) => wasm32::errno_from_nix(e.as_errno().unwrap()),
+        if let Some(fe) = ctx.fds.get(&host_fd) {
+            match nix::fcntl::fcntl(fe.fd_object.rawfd, nix::fcntl::F_SETFL(nix_flags)) {
+                Ok(_) => wasm32::__WASI_ESUCCESS,
+                Err(e) => wasm32::errno_from_nix(e.as_errno().unwrap()),
+            }
+        } else {
+            wasm32::__WASI_EBADF
         }
-    } else {
-        wasm32::__WASI_EBADF
     }
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_fd_seek(
-    vmctx: *mut lucet_vmctx,
-    fd: wasm32::__wasi_fd_t,
-    offset: wasm32::__wasi_filedelta_t,
-    whence: wasm32::__wasi_whence_t,
-    newoffset: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
-    let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
-    let fd = dec_fd(fd);
-    let offset = dec_filedelta(offset);
-    let whence = dec_whence(whence);
-
-    let host_newoffset = {
-        use nix::unistd::{lseek, Whence};
-        let nwhence = match whence as u32 {
-            host::__WASI_WHENCE_CUR => Whence::SeekCur,
-            host::__WASI_WHENCE_END => Whence::SeekEnd,
-            host::__WASI_WHENCE_SET => Whence::SeekSet,
-            _ => return wasm32::__WASI_EINVAL,
-        };
+    pub extern "C" fn __wasi_fd_seek(
+        vmctx: *mut lucet_vmctx,
+        fd: wasm32::__wasi_fd_t,
+        offset: wasm32::__wasi_filedelta_t,
+        whence: wasm32::__wasi_whence_t,
+        newoffset: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+        let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
+        let fd = dec_fd(fd);
+        let offset = dec_filedelta(offset);
+        let whence = dec_whence(whence);
+
+        let host_newoffset = {
+            use nix::unistd::{lseek, Whence};
+            let nwhence = match whence as u32 {
+                host::__WASI_WHENCE_CUR => Whence::SeekCur,
+                host::__WASI_WHENCE_END => Whence::SeekEnd,
+                host::__WASI_WHENCE_SET => Whence::SeekSet,
+                _ => return wasm32::__WASI_EINVAL,
+            };
 
-        let rights = if offset == 0 && whence as u32 == host::__WASI_WHENCE_CUR {
-            host::__WASI_RIGHT_FD_TELL
-        } else {
-            host::__WASI_RIGHT_FD_SEEK | host::__WASI_RIGHT_FD_TELL
+            let rights = if offset == 0 && whence as u32 == host::__WASI_WHENCE_CUR {
+                host::__WASI_RIGHT_FD_TELL
+            } else {
+                host::__WASI_RIGHT_FD_SEEK | host::__WASI_RIGHT_FD_TELL
+            };
+            match ctx.get_fd_entry(fd, rights.into(), 0) {
+                Ok(fe) => match lseek(fe.fd_object.rawfd, offset, nwhence) {
+                    Ok(newoffset) => newoffset,
+                    Err(e) => return wasm32::errno_from_nix(e.as_errno().unwrap()),
+                },
+                Err(e) => return enc_errno(e),
+            }
         };
-        match ctx.get_fd_entry(fd, rights.into(), 0) {
-            Ok(fe) => match lseek(fe.fd_object.rawfd, offset, nwhence) {
-                Ok(newoffset) => newoffset,
-                Err(e) => return wasm32::errno_from_nix(e.as_errno().unwrap()),
-            },
-            Err(e) => return enc_errno(e),
-        }
-    };
 
-    unsafe {
-        enc_filesize_byref(&mut vmctx, newoffset, host_newoffset as u64)
-            .map(|_| wasm32::__WASI_ESUCCESS)
-            .unwrap_or_else(|e| e)
+        unsafe {
+            enc_filesize_byref(&mut vmctx, newoffset, host_newoffset as u64)
+                .map(|_| wasm32::__WASI_ESUCCESS)
+                .unwrap_or_else(|e| e)
+        }
     }
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_fd_prestat_get(
-    vmctx_raw: *mut lucet_vmctx,
-    fd: wasm32::__wasi_fd_t,
-    prestat_ptr: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    let vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
-    let ctx: &WasiCtx = vmctx.get_embed_ctx();
-    let fd = dec_fd(fd);
-    // TODO: is this the correct right for this?
-    match ctx.get_fd_entry(fd, host::__WASI_RIGHT_PATH_OPEN.into(), 0) {
-        Ok(fe) => {
-            if let Some(po_path) = &fe.preopen_path {
-                if fe.fd_object.ty != host::__WASI_FILETYPE_DIRECTORY as host::__wasi_filetype_t {
-                    return wasm32::__WASI_ENOTDIR;
-                }
-                // nasty aliasing here, but we aren't interfering with the borrow for `ctx`
-                // TODO: rework vmctx interface to avoid this
-                unsafe {
-                    enc_prestat_byref(
-                        &mut Vmctx::from_raw(vmctx_raw),
-                        prestat_ptr,
-                        host::__wasi_prestat_t {
-                            pr_type: host::__WASI_PREOPENTYPE_DIR as host::__wasi_preopentype_t,
-                            u: host::__wasi_prestat_t___wasi_prestat_u {
-                                dir:
-                                    host::__wasi_prestat_t___wasi_prestat_u___wasi_prestat_u_dir_t {
-                                        pr_name_len: po_path.as_os_str().as_bytes().len(),
-                                    },
+    pub extern "C" fn __wasi_fd_prestat_get(
+        vmctx_raw: *mut lucet_vmctx,
+        fd: wasm32::__wasi_fd_t,
+        prestat_ptr: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        let vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
+        let ctx: &WasiCtx = vmctx.get_embed_ctx();
+        let fd = dec_fd(fd);
+        // TODO: is this the correct right for this?
+        match ctx.get_fd_entry(fd, host::__WASI_RIGHT_PATH_OPEN.into(), 0) {
+            Ok(fe) => {
+                if let Some(po_path) = &fe.preopen_path {
+                    if fe.fd_object.ty != host::__WASI_FILETYPE_DIRECTORY as host::__wasi_filetype_t {
+                        return wasm32::__WASI_ENOTDIR;
+                    }
+                    // nasty aliasing here, but we aren't interfering with the borrow for `ctx`
+                    // TODO: rework vmctx interface to avoid this
+                    unsafe {
+                        enc_prestat_byref(
+                            &mut Vmctx::from_raw(vmctx_raw),
+                            prestat_ptr,
+                            host::__wasi_prestat_t {
+                                pr_type: host::__WASI_PREOPENTYPE_DIR as host::__wasi_preopentype_t,
+                                u: host::__wasi_prestat_t___wasi_prestat_u {
+                                    dir:
+                                        host::__wasi_prestat_t___wasi_prestat_u___wasi_prestat_u_dir_t {
+                                            pr_name_len: po_path.as_os_str().as_bytes().len(),
+                                        },
+                                },
                             },
-                        },
-                    )
-                    .map(|_| wasm32::__WASI_ESUCCESS)
-                    .unwrap_or_else(|e| e)
+                        )
+                        .map(|_| wasm32::__WASI_ESUCCESS)
+                        .unwrap_or_else(|e| e)
+                    }
+                } else {
+                    wasm32::__WASI_ENOTSUP
                 }
-            } else {
-                wasm32::__WASI_ENOTSUP
             }
+            Err(e) => enc_errno(e),
         }
-        Err(e) => enc_errno(e),
     }
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_fd_prestat_dir_name(
-    vmctx_raw: *mut lucet_vmctx,
-    fd: wasm32::__wasi_fd_t,
-    path_ptr: wasm32::uintptr_t,
-    path_len: wasm32::size_t,
-) -> wasm32::__wasi_errno_t {
-    let vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
-    let ctx: &WasiCtx = vmctx.get_embed_ctx();
-    let fd = dec_fd(fd);
-    match ctx.get_fd_entry(fd, host::__WASI_RIGHT_PATH_OPEN.into(), 0) {
-        Ok(fe) => {
-            if let Some(po_path) = &fe.preopen_path {
-                if fe.fd_object.ty != host::__WASI_FILETYPE_DIRECTORY as host::__wasi_filetype_t {
-                    return wasm32::__WASI_ENOTDIR;
-                }
-                let path_bytes = po_path.as_os_str().as_bytes();
-                if path_bytes.len() > dec_usize(path_len) {
-                    return wasm32::__WASI_ENAMETOOLONG;
-                }
-                // nasty aliasing here, but we aren't interfering with the borrow for `ctx`
-                // TODO: rework vmctx interface to avoid this
-                unsafe {
-                    enc_slice_of(&mut Vmctx::from_raw(vmctx_raw), path_bytes, path_ptr)
-                        .map(|_| wasm32::__WASI_ESUCCESS)
-                        .unwrap_or_else(|e| e)
+    pub extern "C" fn __wasi_fd_prestat_dir_name(
+        vmctx_raw: *mut lucet_vmctx,
+        fd: wasm32::__wasi_fd_t,
+        path_ptr: wasm32::uintptr_t,
+        path_len: wasm32::size_t,
+    ) -> wasm32::__wasi_errno_t {
+        let vmctx = unsafe { Vmctx::from_raw(vmctx_raw) };
+        let ctx: &WasiCtx = vmctx.get_embed_ctx();
+        let fd = dec_fd(fd);
+        match ctx.get_fd_entry(fd, host::__WASI_RIGHT_PATH_OPEN.into(), 0) {
+            Ok(fe) => {
+                if let Some(po_path) = &fe.preopen_path {
+                    if fe.fd_object.ty != host::__WASI_FILETYPE_DIRECTORY as host::__wasi_filetype_t {
+                        return wasm32::__WASI_ENOTDIR;
+                    }
+                    let path_bytes = po_path.as_os_str().as_bytes();
+                    if path_bytes.len() > dec_usize(path_len) {
+                        return wasm32::__WASI_ENAMETOOLONG;
+                    }
+                    // nasty aliasing here, but we aren't interfering with the borrow for `ctx`
+                    // TODO: rework vmctx interface to avoid this
+                    unsafe {
+                        enc_slice_of(&mut Vmctx::from_raw(vmctx_raw), path_bytes, path_ptr)
+                            .map(|_| wasm32::__WASI_ESUCCESS)
+                            .unwrap_or_else(|e| e)
+                    }
+                } else {
+                    wasm32::__WASI_ENOTSUP
                 }
-            } else {
-                wasm32::__WASI_ENOTSUP
             }
+            Err(e) => enc_errno(e),
         }
-        Err(e) => enc_errno(e),
     }
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_fd_read(
-    vmctx: *mut lucet_vmctx,
-    fd: wasm32::__wasi_fd_t,
-    iovs_ptr: wasm32::uintptr_t,
-    iovs_len: wasm32::size_t,
-    nread: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    use nix::sys::uio::{readv, IoVec};
+    pub extern "C" fn __wasi_fd_read(
+        vmctx: *mut lucet_vmctx,
+        fd: wasm32::__wasi_fd_t,
+        iovs_ptr: wasm32::uintptr_t,
+        iovs_len: wasm32::size_t,
+        nread: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        use nix::sys::uio::{readv, IoVec};
+
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+        let fd = dec_fd(fd);
+        let mut iovs = match unsafe { dec_ciovec_slice(&mut vmctx, iovs_ptr, iovs_len) } {
+            Ok(iovs) => iovs,
+            Err(e) => return enc_errno(e),
+        };
 
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
-    let fd = dec_fd(fd);
-    let mut iovs = match unsafe { dec_ciovec_slice(&mut vmctx, iovs_ptr, iovs_len) } {
-        Ok(iovs) => iovs,
-        Err(e) => return enc_errno(e),
-    };
-
-    let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
-    let fe = match ctx.get_fd_entry(fd, host::__WASI_RIGHT_FD_READ.into(), 0) {
-        Ok(fe) => fe,
-        Err(e) => return enc_errno(e),
-    };
-
-    let mut iovs: Vec<IoVec<&mut [u8]>> = iovs
-        .iter_mut()
-        .map(|iov| unsafe { host::ciovec_to_nix_mut(iov) })
-        .collect();
-
-    let full_nread = iovs.iter().map(|iov| iov.as_slice().len()).sum();
-
-    let host_nread = match readv(fe.fd_object.rawfd, &mut iovs) {
-        Ok(len) => len,
-        Err(e) => return wasm32::errno_from_nix(e.as_errno().unwrap()),
-    };
-
-    if host_nread < full_nread {
-        // we hit eof, so remove the fdentry from the context
-        let mut fe = ctx.fds.remove(&fd).expect("file entry is still there");
-        fe.fd_object.needs_close = false;
-    }
+        let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
+        let fe = match ctx.get_fd_entry(fd, host::__WASI_RIGHT_FD_READ.into(), 0) {
+            Ok(fe) => fe,
+            Err(e) => return enc_errno(e),
+        };
 
-    unsafe {
-        enc_usize_byref(&mut vmctx, nread, host_nread)
-            .map(|_| wasm32::__WASI_ESUCCESS)
-            .unwrap_or_else(|e| e)
-    }
-}
+        let mut iovs: Vec<IoVec<&mut [u8]>> = iovs
+            .iter_mut()
+            .map(|iov| unsafe { host::ciovec_to_nix_mut(iov) })
+            .collect();
 
-#[no_mangle]
-pub extern "C" fn __wasi_fd_write(
-    vmctx: *mut lucet_vmctx,
-    fd: wasm32::__wasi_fd_t,
-    iovs_ptr: wasm32::uintptr_t,
-    iovs_len: wasm32::size_t,
-    nwritten: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    use nix::sys::uio::{writev, IoVec};
+        let full_nread = iovs.iter().map(|iov| iov.as_slice().len()).sum();
 
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
-    let fd = dec_fd(fd);
-    let iovs = match unsafe { dec_ciovec_slice(&mut vmctx, iovs_ptr, iovs_len) } {
-        Ok(iovs) => iovs,
-        Err(e) => return enc_errno(e),
-    };
-
-    let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
-    let fe = match ctx.get_fd_entry(fd, host::__WASI_RIGHT_FD_WRITE.into(), 0) {
-        Ok(fe) => fe,
-        Err(e) => return enc_errno(e),
-    };
-
-    let iovs: Vec<IoVec<&[u8]>> = iovs
-        .iter()
-        .map(|iov| unsafe { host::ciovec_to_nix(iov) })
-        .collect();
-
-    let host_nwritten = match writev(fe.fd_object.rawfd, &iovs) {
-        Ok(len) => len,
-        Err(e) => return wasm32::errno_from_nix(e.as_errno().unwrap()),
-    };
+        let host_nread = match readv(fe.fd_object.rawfd, &mut iovs) {
+            Ok(len) => len,
+            Err(e) => return wasm32::errno_from_nix(e.as_errno().unwrap()),
+        };
 
-    unsafe {
-        enc_usize_byref(&mut vmctx, nwritten, host_nwritten)
-            .map(|_| wasm32::__WASI_ESUCCESS)
-            .unwrap_or_else(|e| e)
+        if host_nread < full_nread {
+            // we hit eof, so remove the fdentry from the context
+            let mut fe = ctx.fds.remove(&fd).expect("file entry is still there");
+            fe.fd_object.needs_close = false;
+        }
+
+        unsafe {
+            enc_usize_byref(&mut vmctx, nread, host_nread)
+                .map(|_| wasm32::__WASI_ESUCCESS)
+                .unwrap_or_else(|e| e)
+        }
     }
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_fd_readdir(
-    vmctx: *mut lucet_vmctx,
-    fd: wasm32::__wasi_fd_t,
-    buf: wasm32::uintptr_t,
-    buf_len: wasm32::size_t,
-    cookie: wasm32::__wasi_dircookie_t,
-) -> wasm32::__wasi_errno_t {
-    panic!("Stub!");
-}
+    pub extern "C" fn __wasi_fd_write(
+        vmctx: *mut lucet_vmctx,
+        fd: wasm32::__wasi_fd_t,
+        iovs_ptr: wasm32::uintptr_t,
+        iovs_len: wasm32::size_t,
+        nwritten: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        use nix::sys::uio::{writev, IoVec};
+
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+        let fd = dec_fd(fd);
+        let iovs = match unsafe { dec_ciovec_slice(&mut vmctx, iovs_ptr, iovs_len) } {
+            Ok(iovs) => iovs,
+            Err(e) => return enc_errno(e),
+        };
 
-#[no_mangle]
-pub extern "C" fn __wasi_path_open(
-    vmctx: *mut lucet_vmctx,
-    dirfd: wasm32::__wasi_fd_t,
-    dirflags: wasm32::__wasi_lookupflags_t,
-    path_ptr: wasm32::uintptr_t,
-    path_len: wasm32::size_t,
-    oflags: wasm32::__wasi_oflags_t,
-    fs_rights_base: wasm32::__wasi_rights_t,
-    fs_rights_inheriting: wasm32::__wasi_rights_t,
-    fs_flags: wasm32::__wasi_fdflags_t,
-    fd_out_ptr: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    use nix::errno::Errno;
-    use nix::fcntl::{openat, AtFlags, OFlag};
-    use nix::sys::stat::{fstatat, Mode, SFlag};
-
-    let dirfd = dec_fd(dirfd);
-    let dirflags = dec_lookupflags(dirflags);
-    let oflags = dec_oflags(oflags);
-    let fs_rights_base = dec_rights(fs_rights_base);
-    let fs_rights_inheriting = dec_rights(fs_rights_inheriting);
-    let fs_flags = dec_fdflags(fs_flags);
-
-    // which open mode do we need?
-    let read = fs_rights_base
-        & ((host::__WASI_RIGHT_FD_READ | host::__WASI_RIGHT_FD_READDIR) as host::__wasi_rights_t)
-        != 0;
-    let write = fs_rights_base
-        & ((host::__WASI_RIGHT_FD_DATASYNC
-            | host::__WASI_RIGHT_FD_WRITE
-            | host::__WASI_RIGHT_FD_ALLOCATE
-            | host::__WASI_RIGHT_PATH_FILESTAT_SET_SIZE) as host::__wasi_rights_t)
-        != 0;
-
-    let mut nix_all_oflags = if read && write {
-        OFlag::O_RDWR
-    } else if read {
-        OFlag::O_RDONLY
-    } else {
-        OFlag::O_WRONLY
-    };
+        let ctx: &mut WasiCtx = vmctx.get_embed_ctx_mut();
+        let fe = match ctx.get_fd_entry(fd, host::__WASI_RIGHT_FD_WRITE.into(), 0) {
+            Ok(fe) => fe,
+            Err(e) => return enc_errno(e),
+        };
 
-    // on non-Capsicum systems, we always want nofollow
-    nix_all_oflags.insert(OFlag::O_NOFOLLOW);
+        let iovs: Vec<IoVec<&[u8]>> = iovs
+            .iter()
+            .map(|iov| unsafe { host::ciovec_to_nix(iov) })
+            .collect();
 
-    // which rights are needed on the dirfd?
-    let mut needed_base = host::__WASI_RIGHT_PATH_OPEN as host::__wasi_rights_t;
-    let mut needed_inheriting = fs_rights_base | fs_rights_inheriting;
+        let host_nwritten = match writev(fe.fd_object.rawfd, &iovs) {
+            Ok(len) => len,
+            Err(e) => return wasm32::errno_from_nix(e.as_errno().unwrap()),
+        };
 
-    // convert open flags
-    let nix_oflags = host::nix_from_oflags(oflags);
-    nix_all_oflags.insert(nix_oflags);
-    if nix_all_oflags.contains(OFlag::O_CREAT) {
-        needed_base |= host::__WASI_RIGHT_PATH_CREATE_FILE as host::__wasi_rights_t;
-    }
-    if nix_all_oflags.contains(OFlag::O_TRUNC) {
-        needed_inheriting |= host::__WASI_RIGHT_PATH_FILESTAT_SET_SIZE as host::__wasi_rights_t;
+        unsafe {
+            enc_usize_byref(&mut vmctx, nwritten, host_nwritten)
+                .map(|_| wasm32::__WASI_ESUCCESS)
+                .unwrap_or_else(|e| e)
+        }
     }
 
-    // convert file descriptor flags
-    nix_all_oflags.insert(host::nix_from_fdflags(fs_flags));
-    if nix_all_oflags.contains(OFlag::O_DSYNC) {
-        needed_inheriting |= host::__WASI_RIGHT_FD_DATASYNC as host::__wasi_rights_t;
-    }
-    if nix_all_oflags.intersects(O_RSYNC | OFlag::O_SYNC) {
-        needed_inheriting |= host::__WASI_RIGHT_FD_SYNC as host::__wasi_rights_t;
+    pub extern "C" fn __wasi_fd_readdir(
+        vmctx: *mut lucet_vmctx,
+        fd: wasm32::__wasi_fd_t,
+        buf: wasm32::uintptr_t,
+        buf_len: wasm32::size_t,
+        cookie: wasm32::__wasi_dircookie_t,
+    ) -> wasm32::__wasi_errno_t {
+        panic!("Stub!");
     }
 
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+    pub extern "C" fn __wasi_path_open(
+        vmctx: *mut lucet_vmctx,
+        dirfd: wasm32::__wasi_fd_t,
+        dirflags: wasm32::__wasi_lookupflags_t,
+        path_ptr: wasm32::uintptr_t,
+        path_len: wasm32::size_t,
+        oflags: wasm32::__wasi_oflags_t,
+        fs_rights_base: wasm32::__wasi_rights_t,
+        fs_rights_inheriting: wasm32::__wasi_rights_t,
+        fs_flags: wasm32::__wasi_fdflags_t,
+        fd_out_ptr: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        use nix::errno::Errno;
+        use nix::fcntl::{openat, AtFlags, OFlag};
+        use nix::sys::stat::{fstatat, Mode, SFlag};
+
+        let dirfd = dec_fd(dirfd);
+        let dirflags = dec_lookupflags(dirflags);
+        let oflags = dec_oflags(oflags);
+        let fs_rights_base = dec_rights(fs_rights_base);
+        let fs_rights_inheriting = dec_rights(fs_rights_inheriting);
+        let fs_flags = dec_fdflags(fs_flags);
+
+        // which open mode do we need?
+        let read = fs_rights_base
+            & ((host::__WASI_RIGHT_FD_READ | host::__WASI_RIGHT_FD_READDIR) as host::__wasi_rights_t)
+            != 0;
+        let write = fs_rights_base
+            & ((host::__WASI_RIGHT_FD_DATASYNC
+                | host::__WASI_RIGHT_FD_WRITE
+                | host::__WASI_RIGHT_FD_ALLOCATE
+                | host::__WASI_RIGHT_PATH_FILESTAT_SET_SIZE) as host::__wasi_rights_t)
+            != 0;
+
+        let mut nix_all_oflags = if read && write {
+            OFlag::O_RDWR
+        } else if read {
+            OFlag::O_RDONLY
+        } else {
+            OFlag::O_WRONLY
+        };
+
+        // on non-Capsicum systems, we always want nofollow
+        nix_all_oflags.insert(OFlag::O_NOFOLLOW);
+
+        // which rights are needed on the dirfd?
+        let mut needed_base = host::__WASI_RIGHT_PATH_OPEN as host::__wasi_rights_t;
+        let mut needed_inheriting = fs_rights_base | fs_rights_inheriting;
+
+        // convert open flags
+        let nix_oflags = host::nix_from_oflags(oflags);
+        nix_all_oflags.insert(nix_oflags);
+        if nix_all_oflags.contains(OFlag::O_CREAT) {
+            needed_base |= host::__WASI_RIGHT_PATH_CREATE_FILE as host::__wasi_rights_t;
+        }
+        if nix_all_oflags.contains(OFlag::O_TRUNC) {
+            needed_inheriting |= host::__WASI_RIGHT_PATH_FILESTAT_SET_SIZE as host::__wasi_rights_t;
+        }
+
+        // convert file descriptor flags
+        nix_all_oflags.insert(host::nix_from_fdflags(fs_flags));
+        if nix_all_oflags.contains(OFlag::O_DSYNC) {
+            needed_inheriting |= host::__WASI_RIGHT_FD_DATASYNC as host::__wasi_rights_t;
+        }
+        if nix_all_oflags.intersects(O_RSYNC | OFlag::O_SYNC) {
+            needed_inheriting |= host::__WASI_RIGHT_FD_SYNC as host::__wasi_rights_t;
+        }
+
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+
+        let path = match unsafe { dec_slice_of::<u8>(&mut vmctx, path_ptr, path_len) } {
+            Ok((ptr, len)) => OsStr::from_bytes(unsafe { std::slice::from_raw_parts(ptr, len) }),
+            Err(e) => return enc_errno(e),
+        };
+
+        let (dir, path) = match path_get(
+            &vmctx,
+            dirfd,
+            dirflags,
+            path,
+            needed_base,
+            needed_inheriting,
+            nix_oflags.contains(OFlag::O_CREAT),
+        ) {
+            Ok((dir, path)) => (dir, path),
+            Err(e) => return enc_errno(e),
+        };
 
-    let path = match unsafe { dec_slice_of::<u8>(&mut vmctx, path_ptr, path_len) } {
-        Ok((ptr, len)) => OsStr::from_bytes(unsafe { std::slice::from_raw_parts(ptr, len) }),
-        Err(e) => return enc_errno(e),
-    };
-
-    let (dir, path) = match path_get(
-        &vmctx,
-        dirfd,
-        dirflags,
-        path,
-        needed_base,
-        needed_inheriting,
-        nix_oflags.contains(OFlag::O_CREAT),
-    ) {
-        Ok((dir, path)) => (dir, path),
-        Err(e) => return enc_errno(e),
-    };
-
-    let new_fd = match openat(
-        dir,
-        path.as_os_str(),
-        nix_all_oflags,
-        Mode::from_bits_truncate(0o777),
-    ) {
-        Ok(fd) => fd,
-        Err(e) => {
-            match e.as_errno() {
-                // Linux returns ENXIO instead of EOPNOTSUPP when opening a socket
-                Some(Errno::ENXIO) => {
-                    if let Ok(stat) = fstatat(dir, path.as_os_str(), AtFlags::AT_SYMLINK_NOFOLLOW) {
-                        if SFlag::from_bits_truncate(stat.st_mode).contains(SFlag::S_IFSOCK) {
-                            return wasm32::__WASI_ENOTSUP;
+        let new_fd = match openat(
+            dir,
+            path.as_os_str(),
+            nix_all_oflags,
+            Mode::from_bits_truncate(0o777),
+        ) {
+            Ok(fd) => fd,
+            Err(e) => {
+                match e.as_errno() {
+                    // Linux returns ENXIO instead of EOPNOTSUPP when opening a socket
+                    Some(Errno::ENXIO) => {
+                        if let Ok(stat) = fstatat(dir, path.as_os_str(), AtFlags::AT_SYMLINK_NOFOLLOW) {
+                            if SFlag::from_bits_truncate(stat.st_mode).contains(SFlag::S_IFSOCK) {
+                                return wasm32::__WASI_ENOTSUP;
+                            } else {
+                                return wasm32::__WASI_ENXIO;
+                            }
                         } else {
                             return wasm32::__WASI_ENXIO;
                         }
-                    } else {
-                        return wasm32::__WASI_ENXIO;
                     }
+                    Some(e) => return wasm32::errno_from_nix(e),
+                    None => return wasm32::__WASI_ENOSYS,
                 }
-                Some(e) => return wasm32::errno_from_nix(e),
-                None => return wasm32::__WASI_ENOSYS,
             }
-        }
-    };
-
-    // Determine the type of the new file descriptor and which rights contradict with this type
-    let guest_fd = match unsafe { determine_type_rights(new_fd) } {
-        Err(e) => {
-            // if `close` fails, note it but do not override the underlying errno
-            nix::unistd::close(new_fd).unwrap_or_else(|e| {
-                dbg!(e);
-            });
-            return enc_errno(e);
-        }
-        Ok((_ty, max_base, max_inheriting)) => {
-            let mut fe = unsafe { FdEntry::from_raw_fd(new_fd) };
-            fe.rights_base &= max_base;
-            fe.rights_inheriting &= max_inheriting;
-            match vmctx.get_embed_ctx_mut::<WasiCtx>().insert_fd_entry(fe) {
-                Ok(fd) => fd,
-                Err(e) => return enc_errno(e),
+        };
+
+        // Determine the type of the new file descriptor and which rights contradict with this type
+        let guest_fd = match unsafe { determine_type_rights(new_fd) } {
+            Err(e) => {
+                // if `close` fails, note it but do not override the underlying errno
+                nix::unistd::close(new_fd).unwrap_or_else(|e| {
+                    dbg!(e);
+                });
+                return enc_errno(e);
             }
-        }
-    };
+            Ok((_ty, max_base, max_inheriting)) => {
+                let mut fe = unsafe { FdEntry::from_raw_fd(new_fd) };
+                fe.rights_base &= max_base;
+                fe.rights_inheriting &= max_inheriting;
+                match vmctx.get_embed_ctx_mut::<WasiCtx>().insert_fd_entry(fe) {
+                    Ok(fd) => fd,
+                    Err(e) => return enc_errno(e),
+                }
+            }
+        };
 
-    unsafe {
-        enc_fd_byref(&mut vmctx, fd_out_ptr, guest_fd)
-            .map(|_| wasm32::__WASI_ESUCCESS)
-            .unwrap_or_else(|e| e)
+        unsafe {
+            enc_fd_byref(&mut vmctx, fd_out_ptr, guest_fd)
+                .map(|_| wasm32::__WASI_ESUCCESS)
+                .unwrap_or_else(|e| e)
+        }
     }
 }
 
@@ -980,67 +969,60 @@ pub fn path_get<P: AsRef<OsStr>>(
     }
 }
 
-#[no_mangle]
-pub extern "C" fn __wasi_path_create_directory() {
-	panic!("Stub");
-}
+lucet_hostcalls! {
+    pub extern "C" fn __wasi_path_create_directory(vmctx: *mut lucet_vmctx) {
+        panic!("Stub");
+    }
 
-#[no_mangle]
-pub extern "C" fn __wasi_path_filestat_get() {
-	panic!("Stub");
-}
+    pub extern "C" fn __wasi_path_filestat_get(vmctx: *mut lucet_vmctx) {
+        panic!("Stub");
+    }
 
-#[no_mangle]
-pub extern "C" fn __wasi_path_link() {
-	panic!("Stub");
-}
+    pub extern "C" fn __wasi_path_link(vmctx: *mut lucet_vmctx) {
+        panic!("Stub");
+    }
 
-#[no_mangle]
-pub extern "C" fn __wasi_path_readlink() {
-	panic!("Stub");
-}
+    pub extern "C" fn __wasi_path_readlink(vmctx: *mut lucet_vmctx) {
+        panic!("Stub");
+    }
 
-#[no_mangle]
-pub extern "C" fn __wasi_path_remove_directory() {
-	panic!("Stub");
-}
+    pub extern "C" fn __wasi_path_remove_directory(vmctx: *mut lucet_vmctx) {
+        panic!("Stub");
+    }
 
-#[no_mangle]
-pub extern "C" fn __wasi_path_rename() {
-	panic!("Stub");
-}
+    pub extern "C" fn __wasi_path_rename(vmctx: *mut lucet_vmctx) {
+        panic!("Stub");
+    }
 
-#[no_mangle]
-pub extern "C" fn __wasi_path_symlink() {
-	panic!("Stub");
-}
+    pub extern "C" fn __wasi_path_symlink(vmctx: *mut lucet_vmctx) {
+        panic!("Stub");
+    }
 
-#[no_mangle]
-pub extern "C" fn __wasi_path_unlink_file() {
-	panic!("Stub");
-}
+    pub extern "C" fn __wasi_path_unlink_file(vmctx: *mut lucet_vmctx) {
+        panic!("Stub");
+    }
 
-#[no_mangle]
-pub extern "C" fn __wasi_random_get(
-    vmctx: *mut lucet_vmctx,
-    buf_ptr: wasm32::uintptr_t,
-    buf_len: wasm32::size_t,
-) -> wasm32::__wasi_errno_t {
-    use rand::{thread_rng, RngCore};
+    pub extern "C" fn __wasi_random_get(
+        vmctx: *mut lucet_vmctx,
+        buf_ptr: wasm32::uintptr_t,
+        buf_len: wasm32::size_t,
+    ) -> wasm32::__wasi_errno_t {
+        use rand::{thread_rng, RngCore};
 
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
 
-    let buf_len = dec_usize(buf_len);
-    let buf_ptr = match unsafe { dec_ptr(&mut vmctx, buf_ptr, buf_len) } {
-        Ok(ptr) => ptr,
-        Err(e) => return enc_errno(e),
-    };
+        let buf_len = dec_usize(buf_len);
+        let buf_ptr = match unsafe { dec_ptr(&mut vmctx, buf_ptr, buf_len) } {
+            Ok(ptr) => ptr,
+            Err(e) => return enc_errno(e),
+        };
 
-    let buf = unsafe { std::slice::from_raw_parts_mut(buf_ptr, buf_len) };
+        let buf = unsafe { std::slice::from_raw_parts_mut(buf_ptr, buf_len) };
 
-    thread_rng().fill_bytes(buf);
+        thread_rng().fill_bytes(buf);
 
-    return wasm32::__WASI_ESUCCESS;
+        return wasm32::__WASI_ESUCCESS;
+    }
 }
 
 // define the `fionread()` function, equivalent to `ioctl(fd, FIONREAD, *bytes)`
@@ -1186,101 +1168,106 @@ fn __wasi_poll_oneoff_handle_fd_event<'t>(
     wasm32::__WASI_ESUCCESS
 }
 
-#[no_mangle]
-pub extern "C" fn __wasi_poll_oneoff(
-    vmctx: *mut lucet_vmctx,
-    input: wasm32::uintptr_t,
-    output: wasm32::uintptr_t,
-    nsubscriptions: wasm32::size_t,
-    nevents: wasm32::uintptr_t,
-) -> wasm32::__wasi_errno_t {
-    if nsubscriptions as u64 > wasm32::__wasi_filesize_t::max_value() {
-        return wasm32::__WASI_EINVAL;
-    }
-    let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
-    unsafe { enc_pointee(&mut vmctx, nevents, 0) }.unwrap();
-    let input_slice_ =
-        unsafe { dec_slice_of::<wasm32::__wasi_subscription_t>(&mut vmctx, input, nsubscriptions) }
-            .unwrap();
-    let input_slice = unsafe { slice::from_raw_parts(input_slice_.0, input_slice_.1) };
-
-    let output_slice_ =
-        unsafe { dec_slice_of::<wasm32::__wasi_event_t>(&mut vmctx, output, nsubscriptions) }
-            .unwrap();
-    let output_slice = unsafe { slice::from_raw_parts_mut(output_slice_.0, output_slice_.1) };
-
-    let input: Vec<_> = input_slice.iter().map(|x| dec_subscription(x)).collect();
-
-    let timeout = input
-        .iter()
-        .filter_map(|event| match event {
-            Ok(event) if event.type_ == wasm32::__WASI_EVENTTYPE_CLOCK => Some(ClockEventData {
-                delay: wasi_clock_to_relative_ns_delay(unsafe { event.u.clock }) / 1_000_000,
-                userdata: event.userdata,
-            }),
-            _ => None,
-        })
-        .min_by_key(|event| event.delay);
-    let fd_events: Vec<_> = input
-        .iter()
-        .filter_map(|event| match event {
-            Ok(event)
-                if event.type_ == wasm32::__WASI_EVENTTYPE_FD_READ
-                    || event.type_ == wasm32::__WASI_EVENTTYPE_FD_WRITE =>
-            {
-                Some(FdEventData {
-                    fd: unsafe { event.u.fd_readwrite.fd } as c_int,
-                    type_: event.type_,
+lucet_hostcalls! {
+    pub extern "C" fn __wasi_poll_oneoff(
+        vmctx: *mut lucet_vmctx,
+        input: wasm32::uintptr_t,
+        output: wasm32::uintptr_t,
+        nsubscriptions: wasm32::size_t,
+        nevents: wasm32::uintptr_t,
+    ) -> wasm32::__wasi_errno_t {
+        if nsubscriptions as u64 > wasm32::__wasi_filesize_t::max_value() {
+            return wasm32::__WASI_EINVAL;
+        }
+        let mut vmctx = unsafe { Vmctx::from_raw(vmctx) };
+        unsafe { enc_pointee(&mut vmctx, nevents, 0) }.unwrap();
+        let input_slice_ =
+            unsafe { dec_slice_of::<wasm32::__wasi_subscription_t>(&mut vmctx, input, nsubscriptions) }
+                .unwrap();
+        let input_slice = unsafe { slice::from_raw_parts(input_slice_.0, input_slice_.1) };
+
+        let output_slice_ =
+            unsafe { dec_slice_of::<wasm32::__wasi_event_t>(&mut vmctx, output, nsubscriptions) }
+                .unwrap();
+        let output_slice = unsafe { slice::from_raw_parts_mut(output_slice_.0, output_slice_.1) };
+
+        let input: Vec<_> = input_slice.iter().map(|x| dec_subscription(x)).collect();
+
+        let timeout = input
+            .iter()
+            .filter_map(|event| match event {
+                Ok(event) if event.type_ == wasm32::__WASI_EVENTTYPE_CLOCK => Some(ClockEventData {
+                    delay: wasi_clock_to_relative_ns_delay(unsafe { event.u.clock }) / 1_000_000,
                     userdata: event.userdata,
-                })
-            }
-            _ => None,
-        })
-        .collect();
-    if fd_events.is_empty() && timeout.is_none() {
-        return wasm32::__WASI_ESUCCESS;
-    }
-    let mut poll_fds: Vec<_> = fd_events
-        .iter()
-        .map(|event| {
-            let mut flags = nix::poll::EventFlags::empty();
-            match event.type_ {
-                wasm32::__WASI_EVENTTYPE_FD_READ => flags.insert(nix::poll::EventFlags::POLLIN),
-                wasm32::__WASI_EVENTTYPE_FD_WRITE => flags.insert(nix::poll::EventFlags::POLLOUT),
-                // An event on a file descriptor can currently only be of type FD_READ or FD_WRITE
-                // Nothing else has been defined in the specification, and these are also the only two
-                // events we filtered before. If we get something else here, the code has a serious bug.
-                _ => unreachable!(),
-            };
-            nix::poll::PollFd::new(event.fd, flags)
-        })
-        .collect();
-    let timeout = timeout.map(|ClockEventData { delay, userdata }| ClockEventData {
-        delay: cmp::min(delay, c_int::max_value() as u128),
-        userdata,
-    });
-    let poll_timeout = timeout.map(|timeout| timeout.delay as c_int).unwrap_or(-1);
-    let ready = loop {
-        match nix::poll::poll(&mut poll_fds, poll_timeout) {
-            Err(_) => {
-                if nix::errno::Errno::last() == nix::errno::Errno::EINTR {
-                    continue;
+                }),
+                _ => None,
+            })
+            .min_by_key(|event| event.delay);
+        let fd_events: Vec<_> = input
+            .iter()
+            .filter_map(|event| match event {
+                Ok(event)
+                    if event.type_ == wasm32::__WASI_EVENTTYPE_FD_READ
+                        || event.type_ == wasm32::__WASI_EVENTTYPE_FD_WRITE =>
+                {
+                    Some(FdEventData {
+                        fd: unsafe { event.u.fd_readwrite.fd } as c_int,
+                        type_: event.type_,
+                        userdata: event.userdata,
+                    })
                 }
-                return wasm32::errno_from_nix(nix::errno::Errno::last());
+                _ => None,
+            })
+            .collect();
+        if fd_events.is_empty() && timeout.is_none() {
+            return wasm32::__WASI_ESUCCESS;
+        }
+        let mut poll_fds: Vec<_> = fd_events
+            .iter()
+            .map(|event| {
+                let mut flags = nix::poll::EventFlags::empty();
+                match event.type_ {
+                    wasm32::__WASI_EVENTTYPE_FD_READ => flags.insert(nix::poll::EventFlags::POLLIN),
+                    wasm32::__WASI_EVENTTYPE_FD_WRITE => flags.insert(nix::poll::EventFlags::POLLOUT),
+                    // An event on a file descriptor can currently only be of type FD_READ or FD_WRITE
+                    // Nothing else has been defined in the specification, and these are also the only two
+                    // events we filtered before. If we get something else here, the code has a serious bug.
+                    _ => unreachable!(),
+                };
+                nix::poll::PollFd::new(event.fd, flags)
+            })
+            .collect();
+        let timeout = timeout.map(|ClockEventData { delay, userdata }| ClockEventData {
+            delay: cmp::min(delay, c_int::max_value() as u128),
+            userdata,
+        });
+        let poll_timeout = timeout.map(|timeout| timeout.delay as c_int).unwrap_or(-1);
+        let ready = loop {
+            match nix::poll::poll(&mut poll_fds, poll_timeout) {
+                Err(_) => {
+                    if nix::errno::Errno::last() == nix::errno::Errno::EINTR {
+                        continue;
+                    }
+                    return wasm32::errno_from_nix(nix::errno::Errno::last());
+                }
+                Ok(ready) => break ready as usize,
             }
-            Ok(ready) => break ready as usize,
+        };
+        if ready == 0 {
+            return __wasi_poll_oneoff_handle_timeout_event(
+                &mut vmctx,
+                output_slice,
+                nevents,
+                timeout,
+            );
         }
-    };
-    if ready == 0 {
-        return __wasi_poll_oneoff_handle_timeout_event(&mut vmctx, output_slice, nevents, timeout);
+        let events = fd_events.iter().zip(poll_fds.iter()).take(ready);
+        __wasi_poll_oneoff_handle_fd_event(&mut vmctx, output_slice, nevents, events)
     }
-    let events = fd_events.iter().zip(poll_fds.iter()).take(ready);
-    __wasi_poll_oneoff_handle_fd_event(&mut vmctx, output_slice, nevents, events)
-}
 
-#[no_mangle]
-pub extern "C" fn __wasi_sched_yield() {
-	panic!("Stub");
+    pub extern "C" fn __wasi_sched_yield(vmctx: *mut lucet_vmctx) {
+        panic!("Stub");
+    }
 }
 
 #[doc(hidden)]
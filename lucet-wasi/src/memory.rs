@@ -13,6 +13,7 @@ use crate::{host, wasm32};
 use cast;
 use cast::From as _0;
 use lucet_runtime::vmctx::Vmctx;
+use lucet_runtime::{GuestMemoryError, GuestPtr, GuestSlice};
 use std::mem::{align_of, size_of};
 use std::slice;
 
@@ -22,25 +23,31 @@ macro_rules! bail_errno {
     };
 }
 
+pub fn enc_guest_memory_error(e: GuestMemoryError) -> host::__wasi_errno_t {
+    let errno = match e {
+        GuestMemoryError::OutOfBounds { .. } => host::__WASI_EFAULT,
+        GuestMemoryError::Misaligned { .. } => host::__WASI_EINVAL,
+        GuestMemoryError::Overflow => host::__WASI_EOVERFLOW,
+        GuestMemoryError::InvalidUtf8 => host::__WASI_EILSEQ,
+    };
+    errno as host::__wasi_errno_t
+}
+
 pub unsafe fn dec_ptr(
     vmctx: &mut Vmctx,
     ptr: wasm32::uintptr_t,
     len: usize,
 ) -> Result<*mut u8, host::__wasi_errno_t> {
-    let heap = vmctx.heap_mut();
-
     // check that `len` fits in the wasm32 address space
     if len > wasm32::UINTPTR_MAX as usize {
         bail_errno!(__WASI_EOVERFLOW);
     }
 
-    // check that `ptr` and `ptr + len` are both within the guest heap
-    if ptr as usize > heap.len() || ptr as usize + len > heap.len() {
-        bail_errno!(__WASI_EFAULT);
-    }
-
-    // translate the pointer
-    Ok(heap.as_mut_ptr().offset(ptr as isize))
+    // check that `ptr` and `ptr + len` are both within the guest heap, and translate the pointer
+    GuestSlice::new(GuestPtr::<u8>::new(ptr), len as u32)
+        .as_slice_mut(vmctx.heap_mut())
+        .map(|s| s.as_mut_ptr())
+        .map_err(enc_guest_memory_error)
 }
 
 pub unsafe fn dec_ptr_to<T>(
@@ -1,6 +1,6 @@
 use failure::{format_err, Error};
 use serde_json::{self, Map, Value};
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 
@@ -36,11 +36,15 @@ fn parse_methods(m: &Map<String, Value>) -> Result<HashMap<String, String>, Erro
 #[derive(Debug, Clone)]
 pub struct Bindings {
     bindings: HashMap<String, HashMap<String, String>>,
+    linked_modules: HashSet<String>,
 }
 
 impl Bindings {
     pub fn new(bindings: HashMap<String, HashMap<String, String>>) -> Bindings {
-        Self { bindings: bindings }
+        Self {
+            bindings: bindings,
+            linked_modules: HashSet::new(),
+        }
     }
 
     pub fn env(env: HashMap<String, String>) -> Bindings {
@@ -71,7 +75,19 @@ impl Bindings {
         Ok(Self::from_str(&contents)?)
     }
 
+    /// Satisfy the function imports from `module` with the exports of another Lucet module, linked
+    /// at runtime, rather than with hostcalls.
+    pub fn link_module(&mut self, module: &str) {
+        self.linked_modules.insert(module.to_owned());
+    }
+
+    pub fn is_linked(&self, module: &str) -> bool {
+        self.linked_modules.contains(module)
+    }
+
     pub fn extend(&mut self, other: &Bindings) -> Result<(), Error> {
+        self.linked_modules
+            .extend(other.linked_modules.iter().cloned());
         //self.bindings.extend(other.bindings);
         for (modname, othermodbindings) in other.bindings.iter() {
             match self.bindings.entry(modname.clone()) {
@@ -103,6 +119,25 @@ impl Bindings {
         Ok(())
     }
 
+    /// The bindings as `(module, symbol, binding)` triples, and the linked modules, both in sorted
+    /// order so that they do not depend on how the bindings were built.
+    pub fn canonical(&self) -> (Vec<(&str, &str, &str)>, Vec<&str>) {
+        let mut entries: Vec<(&str, &str, &str)> = self
+            .bindings
+            .iter()
+            .flat_map(|(module, symbols)| {
+                symbols.iter().map(move |(symbol, binding)| {
+                    (module.as_str(), symbol.as_str(), binding.as_str())
+                })
+            })
+            .collect();
+        entries.sort();
+        let mut linked_modules: Vec<&str> =
+            self.linked_modules.iter().map(String::as_str).collect();
+        linked_modules.sort();
+        (entries, linked_modules)
+    }
+
     pub fn translate(&self, module: &str, symbol: &str) -> Result<String, Error> {
         match self.bindings.get(module) {
             Some(m) => match m.get(symbol) {
@@ -146,6 +181,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn linked_module() {
+        let mut map = Bindings::empty();
+        map.link_module("other");
+        assert!(map.is_linked("other"));
+        assert!(!map.is_linked("env"));
+
+        let mut extended = Bindings::empty();
+        extended.extend(&map).expect("extend with linked module");
+        assert!(extended.is_linked("other"));
+    }
+
     #[test]
     fn explicit_from_nonexistent_file() {
         let fail_map = Bindings::from_file(&test_file("nonexistent_bindings.json"));
@@ -0,0 +1,167 @@
+//! A content-addressed cache of compiled shared objects.
+//!
+//! Each entry is named after a SHA-256 digest of everything that goes into compiling a module: the
+//! bytes of the input, the bindings, the heap settings, the target and its code generation
+//! settings, the builtins, the linked modules, the linker and its flags, the version of the cache
+//! format, and the version and build of `lucetc`. Compiling the same module in the same way again
+//! copies the cached shared object instead.
+//!
+//! The build of `lucetc` is identified by a digest of the running executable, since the package
+//! version stays the same across rebuilds that may generate different code, such as a local change
+//! to `lucetc` or an update of Cranelift.
+//!
+//! Entries are written to a temporary file in the cache directory and then renamed into place, so
+//! several `lucetc` processes can share a cache directory.
+
+use crate::bindings::Bindings;
+use crate::compiler::{isa_settings, OptLevel};
+use crate::program::memory::HeapSettings;
+use byteorder::{ByteOrder, LittleEndian};
+use failure::{Error, ResultExt};
+use log::info;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// The version of the code that `lucetc` generates and of the shared objects it links, as far as
+/// the cache is concerned. Bump it whenever either changes in a way the build of `lucetc` does not
+/// reflect, so that stale entries are no longer used.
+const CACHE_FORMAT_VERSION: u64 = 1;
+
+/// The key of a cache entry, which identifies a compilation by its inputs.
+pub struct CacheKey {
+    hasher: Sha256,
+}
+
+impl CacheKey {
+    /// Start a key for compiling at `opt_level`, covering the version and build of `lucetc`, the
+    /// version of the cache format, and the target and code generation settings for the host.
+    pub fn new(opt_level: OptLevel) -> Result<Self, Error> {
+        let mut key = CacheKey {
+            hasher: Sha256::new(),
+        };
+        key.add_str("lucetc");
+        key.add_str(env!("CARGO_PKG_VERSION"));
+        key.add_bytes(&build_id()?);
+        key.add_u64(CACHE_FORMAT_VERSION);
+        key.add_str(&isa_settings(opt_level));
+        Ok(key)
+    }
+
+    /// Add bytes to the key. Every item is prefixed with its length, so that the boundaries
+    /// between items are part of the key as well.
+    pub fn add_bytes(&mut self, bytes: &[u8]) {
+        self.add_u64(bytes.len() as u64);
+        self.hasher.input(bytes);
+    }
+
+    pub fn add_str(&mut self, s: &str) {
+        self.add_bytes(s.as_bytes());
+    }
+
+    pub fn add_u64(&mut self, n: u64) {
+        let mut buf = [0; 8];
+        LittleEndian::write_u64(&mut buf, n);
+        self.hasher.input(&buf);
+    }
+
+    /// Add the contents of a file to the key.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let contents = fs::read(path.as_ref())
+            .context(format!("reading {:?} for the cache key", path.as_ref()))?;
+        self.add_bytes(&contents);
+        Ok(())
+    }
+
+    pub fn add_bindings(&mut self, bindings: &Bindings) {
+        let (entries, linked_modules) = bindings.canonical();
+        self.add_u64(entries.len() as u64);
+        for (module, symbol, binding) in entries {
+            self.add_str(module);
+            self.add_str(symbol);
+            self.add_str(binding);
+        }
+        self.add_u64(linked_modules.len() as u64);
+        for module in linked_modules {
+            self.add_str(module);
+        }
+    }
+
+    pub fn add_heap(&mut self, heap: &HeapSettings) {
+        self.add_u64(heap.min_reserved_size);
+        self.add_u64(heap.max_reserved_size);
+        self.add_u64(heap.guard_size);
+    }
+
+    /// The name of the entry, as a hexadecimal digest.
+    pub fn digest(self) -> String {
+        self.hasher
+            .result()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// A digest of the running executable, which identifies the build of `lucetc` that is compiling.
+fn build_id() -> Result<Vec<u8>, Error> {
+    let exe = env::current_exe().context("finding the lucetc executable for the cache key")?;
+    let mut file = fs::File::open(&exe).context(format!("opening {:?} for the cache key", exe))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .context(format!("reading {:?} for the cache key", exe))?;
+        if n == 0 {
+            break;
+        }
+        hasher.input(&buf[..n]);
+    }
+    Ok(hasher.result().to_vec())
+}
+
+/// A directory of cached shared objects.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Use `dir` as the cache directory, creating it if it does not exist.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_owned();
+        fs::create_dir_all(&dir).context(format!("creating cache directory {:?}", dir))?;
+        Ok(Cache { dir })
+    }
+
+    fn entry_path(&self, digest: &str) -> PathBuf {
+        self.dir.join(format!("{}.so", digest))
+    }
+
+    /// Copy the shared object cached under `digest` to `output`, returning whether there was one.
+    pub fn fetch<P: AsRef<Path>>(&self, digest: &str, output: P) -> Result<bool, Error> {
+        let entry = self.entry_path(digest);
+        if !entry.exists() {
+            return Ok(false);
+        }
+        fs::copy(&entry, output.as_ref()).context(format!("copying cached {:?}", entry))?;
+        info!("using cached {:?} for {:?}", entry, output.as_ref());
+        Ok(true)
+    }
+
+    /// Cache the shared object at `output` under `digest`.
+    pub fn store<P: AsRef<Path>>(&self, digest: &str, output: P) -> Result<(), Error> {
+        let entry = self.entry_path(digest);
+        let mut tmp = NamedTempFile::new_in(&self.dir)?;
+        io::copy(&mut fs::File::open(output.as_ref())?, tmp.as_file_mut())
+            .context(format!("caching {:?}", output.as_ref()))?;
+        tmp.persist(&entry)
+            .map_err(|e| e.error)
+            .context(format!("creating cache entry {:?}", entry))?;
+        Ok(())
+    }
+}
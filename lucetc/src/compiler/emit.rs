@@ -0,0 +1,216 @@
+//! Compilation of function bodies to machine code, separately from the `cranelift_module::Module`.
+//!
+//! `Module::define_function` compiles a function and emits it into the artifact in one step, which
+//! ties compilation to the thread that owns the module. Instead, each function is compiled with
+//! its own Cranelift context into a `CompiledFunction`, holding its code along with the
+//! relocations and trap sites that emitting it produced, so that functions can be compiled in
+//! parallel. The compiled functions are then defined in the artifact one at a time, much like the
+//! faerie backend would have.
+
+use crate::compiler::Name;
+use cranelift_codegen::binemit::{Addend, CodeOffset, Reloc, RelocSink};
+use cranelift_codegen::{ir, isa, print_errors::pretty_error, CodegenError, Context};
+use cranelift_faerie::traps::FaerieTrapSink;
+use cranelift_faerie::FaerieProduct;
+use faerie::{Artifact, Decl, Link};
+use failure::{bail, format_err, Error, ResultExt};
+use goblin::elf::reloc::{
+    R_X86_64_32, R_X86_64_64, R_X86_64_GOTPCREL, R_X86_64_PC32, R_X86_64_PLT32,
+};
+use std::collections::HashMap;
+
+/// The symbols of the functions and data declared in the module, by the namespace and index of
+/// the `ExternalName` that refers to them in Cranelift IR.
+#[derive(Debug, Default)]
+pub struct Symbols {
+    names: HashMap<(u32, u32), String>,
+}
+
+impl Symbols {
+    pub fn insert(&mut self, name: &Name) {
+        if let ir::ExternalName::User { namespace, index } = ir::ExternalName::from(name.clone()) {
+            self.names
+                .insert((namespace, index), name.symbol().to_owned());
+        }
+    }
+
+    /// Return the symbol that a relocation refers to, declaring it in the artifact if it is a
+    /// libcall.
+    fn resolve(
+        &self,
+        name: &ir::ExternalName,
+        libcall_name: fn(ir::LibCall) -> String,
+        artifact: &mut Artifact,
+    ) -> Result<String, Error> {
+        match *name {
+            ir::ExternalName::User { namespace, index } => self
+                .names
+                .get(&(namespace, index))
+                .cloned()
+                .ok_or(format_err!("relocation against undeclared name {}", name)),
+            ir::ExternalName::LibCall(libcall) => {
+                let symbol = libcall_name(libcall);
+                artifact
+                    .declare(&symbol, Decl::function_import())
+                    .context(format!("declaring libcall {}", symbol))?;
+                Ok(symbol)
+            }
+            _ => bail!("relocation against invalid name {}", name),
+        }
+    }
+}
+
+/// A relocation in the code of a function, against a function or data symbol.
+#[derive(Debug)]
+struct Relocation {
+    offset: CodeOffset,
+    reloc: Reloc,
+    target: ir::ExternalName,
+    addend: Addend,
+}
+
+/// Records the relocations of a function as it is emitted.
+#[derive(Default)]
+struct Relocations {
+    relocs: Vec<Relocation>,
+    unsupported: Option<Reloc>,
+}
+
+impl RelocSink for Relocations {
+    fn reloc_ebb(&mut self, _offset: CodeOffset, reloc: Reloc, _ebb_offset: CodeOffset) {
+        self.unsupported = Some(reloc);
+    }
+
+    fn reloc_external(
+        &mut self,
+        offset: CodeOffset,
+        reloc: Reloc,
+        name: &ir::ExternalName,
+        addend: Addend,
+    ) {
+        self.relocs.push(Relocation {
+            offset,
+            reloc,
+            target: name.clone(),
+            addend,
+        });
+    }
+
+    fn reloc_jt(&mut self, _offset: CodeOffset, reloc: Reloc, _jt: ir::JumpTable) {
+        match reloc {
+            // jump tables are emitted right after the code, so these need no relocation
+            Reloc::X86PCRelRodata4 => (),
+            _ => self.unsupported = Some(reloc),
+        }
+    }
+}
+
+/// The machine code of a function, ready to be defined in the artifact.
+pub struct CompiledFunction {
+    name: Name,
+    code: Vec<u8>,
+    relocs: Vec<Relocation>,
+    traps: FaerieTrapSink,
+}
+
+impl CompiledFunction {
+    /// Compile a function, using a context of its own.
+    pub fn new(
+        isa: &isa::TargetIsa,
+        name: &Name,
+        func: &ir::Function,
+    ) -> Result<CompiledFunction, Error> {
+        let mut ctx = Context::for_function(func.clone());
+        let code_size = ctx.compile(isa).map_err(|e| match e {
+            // Verifier errors are never recoverable. This is the last time we'll have enough
+            // information still around to pretty-print
+            CodegenError::Verifier(_) => format_err!(
+                "code generation error in {}:\n{}",
+                name.symbol(),
+                pretty_error(func, Some(isa), e)
+            ),
+            _ => Error::from(e)
+                .context(format!("compiling {}", name.symbol()))
+                .into(),
+        })?;
+
+        let mut code = vec![0; code_size as usize];
+        let mut relocs = Relocations::default();
+        let mut traps = FaerieTrapSink::new(name.symbol(), code_size);
+        unsafe { ctx.emit_to_memory(isa, code.as_mut_ptr(), &mut relocs, &mut traps) };
+        if let Some(reloc) = relocs.unsupported {
+            bail!("unsupported relocation {} in {}", reloc, name.symbol());
+        }
+
+        Ok(CompiledFunction {
+            name: name.clone(),
+            code,
+            relocs: relocs.relocs,
+            traps,
+        })
+    }
+
+    /// Define the function in the artifact, along with its relocations and trap sites.
+    pub fn define(
+        self,
+        product: &mut FaerieProduct,
+        symbols: &Symbols,
+        libcall_name: fn(ir::LibCall) -> String,
+    ) -> Result<(), Error> {
+        let CompiledFunction {
+            name,
+            code,
+            relocs,
+            traps,
+        } = self;
+        let symbol = name.symbol();
+        product
+            .artifact
+            .define(symbol, code)
+            .context(format!("defining {}", symbol))?;
+
+        for reloc in relocs {
+            let to = symbols.resolve(&reloc.target, libcall_name, &mut product.artifact)?;
+            let raw_reloc = raw_relocation(reloc.reloc)?;
+            let addend = reloc.addend;
+            if addend as i32 as i64 != addend {
+                bail!("relocation addend {} out of range in {}", addend, symbol);
+            }
+            product
+                .artifact
+                .link_with(
+                    Link {
+                        from: symbol,
+                        to: &to,
+                        at: u64::from(reloc.offset),
+                    },
+                    faerie::Reloc::Raw {
+                        reloc: raw_reloc,
+                        addend: addend as i32,
+                    },
+                )
+                .context(format!("relocation in {} against {}", symbol, to))?;
+        }
+
+        product
+            .trap_manifest
+            .as_mut()
+            .expect("trap manifest is present")
+            .add_sink(traps);
+        Ok(())
+    }
+}
+
+/// The ELF relocation type for a Cranelift relocation, as the faerie backend translates them. x86-64
+/// is the only target that `lucetc` supports.
+fn raw_relocation(reloc: Reloc) -> Result<u32, Error> {
+    let raw = match reloc {
+        Reloc::Abs4 => R_X86_64_32,
+        Reloc::Abs8 => R_X86_64_64,
+        Reloc::X86PCRel4 | Reloc::X86CallPCRel4 => R_X86_64_PC32,
+        Reloc::X86CallPLTRel4 => R_X86_64_PLT32,
+        Reloc::X86GOTPCRel4 => R_X86_64_GOTPCREL,
+        _ => bail!("unsupported relocation {}", reloc),
+    };
+    Ok(raw)
+}
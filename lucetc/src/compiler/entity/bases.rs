@@ -5,6 +5,10 @@ use cranelift_codegen::ir::{self, types::I64, GlobalValueData};
 // VMContext points directly to the heap (offset 0).
 // Directly before the heap is a pointer to the globals (offset -POINTER_SIZE).
 const GLOBAL_BASE_OFFSET: i32 = -1 * POINTER_SIZE as i32;
+// Before the globals pointer is a pointer to the linked function table (see
+// `compiler::linked_import`), and before that is a pointer to the instance's descriptors of its
+// tables (offset -3 * POINTER_SIZE), each a pointer to the elements followed by their number.
+const TABLE_BASE_OFFSET: i32 = -3 * POINTER_SIZE as i32;
 
 pub struct GlobalBases {
     heap: Option<ir::GlobalValue>,
@@ -21,16 +25,16 @@ impl GlobalBases {
         }
     }
 
-    pub fn table(&mut self, func: &mut ir::Function, compiler: &Compiler) -> ir::GlobalValue {
+    pub fn table(&mut self, func: &mut ir::Function, _compiler: &Compiler) -> ir::GlobalValue {
+        // The table symbols in the module only hold the initial elements; each instance has its
+        // own copy of every table, which the guest may grow and the host may modify at runtime.
         self.table.unwrap_or_else(|| {
-            let table = &compiler.prog.tables()[0];
-            let gv = func.create_global_value(GlobalValueData::Symbol {
-                name: compiler
-                    .get_table(table)
-                    .expect("table must be declared")
-                    .into(),
-                offset: 0.into(), // Symbol points directly to table.
-                colocated: true,
+            let vmctx = func.create_global_value(GlobalValueData::VMContext);
+            let gv = func.create_global_value(GlobalValueData::Load {
+                base: vmctx,
+                offset: TABLE_BASE_OFFSET.into(),
+                global_type: I64,
+                readonly: false,
             });
             self.table = Some(gv);
             gv
@@ -6,7 +6,7 @@ use crate::program::types::cton_valuetype;
 use crate::program::FunctionDef;
 use cranelift_codegen::ir::{self, InstBuilder};
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
-use failure::{format_err, Error};
+use failure::{bail, format_err, Error};
 use parity_wasm::elements::{self, FuncBody, ValueType};
 
 pub fn compile_function<'p>(
@@ -42,7 +42,7 @@ pub fn compile_function<'p>(
             }
         }
         // local decls
-        declare_locals(&mut builder, &mut vargen, body.locals());
+        declare_locals(&mut builder, &mut vargen, body.locals())?;
 
         // Create exit block.
         let exit_block = builder.create_ebb();
@@ -91,7 +91,7 @@ fn declare_locals(
     builder: &mut FunctionBuilder,
     vargen: &mut VariableGen,
     locals: &[elements::Local],
-) {
+) -> Result<(), Error> {
     for local in locals {
         let localtype = local.value_type();
         let zeroval = match localtype {
@@ -99,7 +99,10 @@ fn declare_locals(
             ValueType::I64 => builder.ins().iconst(ir::types::I64, 0),
             ValueType::F32 => builder.ins().f32const(ir::immediates::Ieee32::with_bits(0)),
             ValueType::F64 => builder.ins().f64const(ir::immediates::Ieee64::with_bits(0)),
-            ValueType::V128 => unimplemented!(),
+            // Cranelift 0.29 has no vector encodings, not even to zero a register
+            ValueType::V128 => {
+                bail!("v128 locals are not supported: Cranelift 0.29 cannot compile SIMD code")
+            }
         };
         for _ in 0..local.count() {
             let lvar = vargen.mint();
@@ -107,6 +110,7 @@ fn declare_locals(
             builder.def_var(lvar, zeroval);
         }
     }
+    Ok(())
 }
 
 /// `VariableGen` is a source of fresh `Variable`s. It is never used directly by Cretonne.
@@ -0,0 +1,82 @@
+use crate::compiler::entity::{NATIVE_POINTER, POINTER_SIZE};
+use crate::compiler::Compiler;
+use crate::program::FunctionImport;
+use cranelift_codegen::ir::{self, InstBuilder};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use failure::{format_err, Error};
+
+// VMContext points directly to the heap (offset 0).
+// Directly before the heap is a pointer to the globals (offset -POINTER_SIZE), and before that is a
+// pointer to the linked function table (offset -2 * POINTER_SIZE). See `entity::bases`.
+const LINKED_FUNCS_OFFSET: i32 = -2 * POINTER_SIZE as i32;
+
+/// Size of an entry in the linked function table: a function pointer, followed by the `vmctx` of
+/// the instance that the function belongs to.
+const LINKED_FUNC_SIZE: i32 = 2 * POINTER_SIZE as i32;
+
+/// Define the trampoline for an import that is satisfied by another Lucet module.
+///
+/// The trampoline has the signature of the import, and is called like any other function. It
+/// loads the function pointer and `vmctx` from the import's entry in the linked function table of
+/// the calling instance, and calls the function with the callee's `vmctx` in place of its own.
+pub fn compile_linked_import<'p>(
+    compiler: &mut Compiler<'p>,
+    import: &FunctionImport,
+) -> Result<(), Error> {
+    let linked_index = import.linked_index().ok_or(format_err!(
+        "import {}::{} is not linked",
+        import.module(),
+        import.field()
+    ))?;
+    let sig = import.signature();
+
+    let name = compiler.get_function(import)?;
+    let mut func = ir::Function::with_name_signature(name.clone().into(), sig.clone());
+
+    {
+        let mut ctx: FunctionBuilderContext = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut func, &mut ctx);
+
+        let sig_ref = builder.import_signature(sig.clone());
+
+        let entry_block = builder.create_ebb();
+        builder.append_ebb_params_for_function_params(entry_block);
+        builder.switch_to_block(entry_block);
+        builder.seal_block(entry_block);
+
+        let mut args = builder.ebb_params(entry_block).to_vec();
+        let vmctx = builder
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .ok_or(format_err!("linked import trampoline has no vm context"))?;
+
+        let linked_funcs =
+            builder
+                .ins()
+                .load(NATIVE_POINTER, ir::MemFlags::new(), vmctx, LINKED_FUNCS_OFFSET);
+        let entry_offset = linked_index as i32 * LINKED_FUNC_SIZE;
+        let callee = builder.ins().load(
+            NATIVE_POINTER,
+            ir::MemFlags::new(),
+            linked_funcs,
+            entry_offset,
+        );
+        let callee_vmctx = builder.ins().load(
+            NATIVE_POINTER,
+            ir::MemFlags::new(),
+            linked_funcs,
+            entry_offset + POINTER_SIZE as i32,
+        );
+
+        // The vmctx is always the first parameter; see `CtonSignature`.
+        args[0] = callee_vmctx;
+        let call = builder.ins().call_indirect(sig_ref, callee, &args);
+        let results = builder.inst_results(call).to_vec();
+        builder.ins().return_(&results);
+
+        builder.finalize();
+    }
+
+    compiler.define_function(name, func)?;
+    Ok(())
+}
@@ -2,6 +2,7 @@ pub mod data;
 pub mod entity;
 pub mod function;
 pub mod globals;
+pub mod linked_import;
 pub mod memory;
 pub mod module_data;
 pub mod opcode;
@@ -9,22 +10,25 @@ pub mod state;
 pub mod table;
 pub mod traps;
 
+mod emit;
 mod name;
 mod stack_probe;
 
 pub use self::name::Name;
 
+use crate::compiler::emit::{CompiledFunction, Symbols};
 use crate::compiler::traps::write_trap_manifest;
 use crate::program::{Function, Program, TableDef};
 use byteorder::{LittleEndian, WriteBytesExt};
 use cranelift_codegen::settings::{self, Configurable};
-use cranelift_codegen::{ir, isa, print_errors::pretty_error, CodegenError};
+use cranelift_codegen::{ir, isa};
 use cranelift_faerie::{FaerieBackend, FaerieBuilder, FaerieProduct, FaerieTrapCollection};
-use cranelift_module::{DataContext, Linkage, Module, ModuleError};
+use cranelift_module::{DataContext, Linkage, Module};
 use cranelift_native;
 use faerie::Artifact;
 use failure::{format_err, Error, ResultExt};
-use std::collections::HashMap;
+use rayon::prelude::*;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -43,7 +47,7 @@ impl Default for OptLevel {
 }
 
 impl OptLevel {
-    fn to_flag(&self) -> &str {
+    pub(crate) fn to_flag(&self) -> &str {
         match self {
             OptLevel::Default => "default",
             OptLevel::Best => "best",
@@ -61,27 +65,41 @@ fn isa(opt_level: OptLevel) -> Box<isa::TargetIsa> {
     isa_builder.finish(settings::Flags::new(flags_builder))
 }
 
+/// The target triple and code generation settings of the ISA for `opt_level`, including the
+/// features of the host that it detected.
+pub(crate) fn isa_settings(opt_level: OptLevel) -> String {
+    let isa = isa(opt_level);
+    format!("{}\n{}", isa.triple(), isa)
+}
+
+fn libcall_name(libcall: ir::LibCall) -> String {
+    match libcall {
+        ir::LibCall::Probestack => stack_probe::STACK_PROBE_SYM.to_owned(),
+        _ => (FaerieBuilder::default_libcall_names())(libcall),
+    }
+}
+
 pub struct Compiler<'p> {
     pub prog: &'p Program,
-    funcs: HashMap<Name, ir::Function>,
+    /// The functions defined so far, in the order they were defined.
+    funcs: Vec<(Name, ir::Function)>,
+    defined: HashSet<Name>,
+    symbols: Symbols,
     module: Module<FaerieBackend>,
     opt_level: OptLevel,
 }
 
 impl<'p> Compiler<'p> {
     pub fn new(name: String, prog: &'p Program, opt_level: OptLevel) -> Result<Self, Error> {
-        let libcalls = Box::new(move |libcall| match libcall {
-            ir::LibCall::Probestack => stack_probe::STACK_PROBE_SYM.to_owned(),
-            _ => (FaerieBuilder::default_libcall_names())(libcall),
-        });
-
         let mut compiler = Self {
-            funcs: HashMap::new(),
+            funcs: Vec::new(),
+            defined: HashSet::new(),
+            symbols: Symbols::default(),
             module: Module::new(FaerieBuilder::new(
                 isa(opt_level),
                 name,
                 FaerieTrapCollection::Enabled,
-                libcalls,
+                Box::new(libcall_name),
             )?),
             prog: prog,
             opt_level: opt_level,
@@ -138,7 +156,9 @@ impl<'p> Compiler<'p> {
             .module
             .declare_function(&func.symbol(), func.linkage(), &func.signature())
             .context(format!("declaration of {}", func.symbol()))?;
-        Ok(Name::new_func(func.symbol().to_owned(), funcid))
+        let name = Name::new_func(func.symbol().to_owned(), funcid);
+        self.symbols.insert(&name);
+        Ok(name)
     }
 
     pub fn declare_table(&mut self, table: &TableDef) -> Result<Name, Error> {
@@ -156,7 +176,9 @@ impl<'p> Compiler<'p> {
         let dataid = self
             .module
             .declare_data(&table.symbol(), Linkage::Export, false)?;
-        Ok(Name::new_data(table.symbol(), dataid))
+        let name = Name::new_data(table.symbol(), dataid);
+        self.symbols.insert(&name);
+        Ok(name)
     }
 
     pub fn declare_data(
@@ -166,7 +188,9 @@ impl<'p> Compiler<'p> {
         mutable: bool,
     ) -> Result<Name, Error> {
         let dataid = self.module.declare_data(sym, linkage, mutable)?;
-        Ok(Name::new_data(sym.to_owned(), dataid))
+        let name = Name::new_data(sym.to_owned(), dataid);
+        self.symbols.insert(&name);
+        Ok(name)
     }
 
     pub fn get_function(&self, func: &Function) -> Result<Name, Error> {
@@ -194,18 +218,13 @@ impl<'p> Compiler<'p> {
     }
 
     pub fn define_function(&mut self, name: Name, func: ir::Function) -> Result<(), Error> {
-        use std::collections::hash_map::Entry;
-        match self.funcs.entry(name.clone()) {
-            Entry::Occupied(_entry) => {
-                return Err(format_err!(
-                    "function {} has duplicate definition",
-                    name.symbol()
-                ));
-            }
-            Entry::Vacant(entry) => {
-                entry.insert(func);
-            }
+        if !self.defined.insert(name.clone()) {
+            return Err(format_err!(
+                "function {} has duplicate definition",
+                name.symbol()
+            ));
         }
+        self.funcs.push((name, func));
         Ok(())
     }
 
@@ -226,41 +245,29 @@ impl<'p> Compiler<'p> {
     }
 
     pub fn codegen(self) -> Result<ObjectFile, Error> {
-        use cranelift_codegen::Context;
-
-        let isa = &*self.isa();
-        let mut ctx = Context::new();
-        let mut module = self.module;
-
-        for (name, func) in self.funcs.iter() {
-            ctx.func = func.clone();
-            let id = name
-                .into_funcid()
-                .ok_or(format_err!("function defined with invalid name {:?}", name,))?;
-            module.define_function(id, &mut ctx).map_err(|e| match e {
-                ModuleError::Compilation(ce) => match ce {
-                    CodegenError::Verifier(_) =>
-                    // Verifier errors are never recoverable. This is the last
-                    // time we'll have enough information still around to pretty-print
-                    {
-                        format_err!(
-                            "code generation error:\n{}",
-                            pretty_error(func, Some(isa), ce)
-                        )
-                    }
-                    _ => ModuleError::Compilation(ce).into(),
-                },
-                _ => e.into(),
-            })?;
-            ctx.clear();
+        // Functions are compiled to machine code in parallel, each with its own context and ISA,
+        // and then defined in the artifact in the order they were defined here, so that the
+        // object file does not depend on how the work was scheduled.
+        let opt_level = self.opt_level;
+        let compiled = self
+            .funcs
+            .par_iter()
+            .map_init(
+                || isa(opt_level),
+                |isa, (name, func)| CompiledFunction::new(&**isa, name, func),
+            )
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut product = self.module.finish();
+        for func in compiled {
+            func.define(&mut product, &self.symbols, libcall_name)?;
         }
-
-        ObjectFile::new(module.finish())
+        ObjectFile::new(product)
     }
 }
 
 pub struct CraneliftFuncs {
-    funcs: HashMap<Name, ir::Function>,
+    funcs: Vec<(Name, ir::Function)>,
     isa: Box<isa::TargetIsa>,
 }
 
@@ -3,7 +3,7 @@ use crate::program::data::sparse::OwnedSparseData;
 use byteorder::{LittleEndian, WriteBytesExt};
 use cranelift_module::{DataContext, Linkage};
 use failure::Error;
-use lucet_module_data::ModuleData;
+use lucet_module_data::{ModuleData, PassiveDataSegment, PassiveElemSegment};
 
 pub fn compile_module_data(compiler: &mut Compiler) -> Result<(), Error> {
     let module_data_serialized: Vec<u8> = {
@@ -17,7 +17,62 @@ pub fn compile_module_data(compiler: &mut Compiler) -> Result<(), Error> {
         let globals = compiler.prog.globals();
         let globals_spec = globals.iter().map(|g| g.to_spec()).collect();
 
-        let module_data = ModuleData::new(heap_spec, sparse_data, globals_spec);
+        let linked_imports = compiler
+            .prog
+            .linked_import_functions()
+            .into_iter()
+            .filter_map(|f| f.to_linked_import())
+            .collect();
+
+        let tables_spec = compiler.prog.tables().iter().map(|t| t.to_spec()).collect();
+
+        let function_exports = compiler
+            .prog
+            .defined_functions()
+            .iter()
+            .filter_map(|f| f.to_export())
+            .collect();
+
+        let memory_exports = compiler
+            .prog
+            .memory_exports()
+            .iter()
+            .map(|e| e.as_str())
+            .collect();
+
+        let passive_data = compiler
+            .prog
+            .passive_data()
+            .iter()
+            .map(|segment| PassiveDataSegment::new(segment.index, &segment.data))
+            .collect();
+
+        // The elements of each passive segment follow those of the previous one in
+        // `guest_passive_elements`
+        let mut start = 0;
+        let passive_elements = compiler
+            .prog
+            .passive_elements()
+            .iter()
+            .map(|segment| {
+                let len = segment.functions.len() as u32;
+                let spec = PassiveElemSegment::new(segment.index, start, len);
+                start += len;
+                spec
+            })
+            .collect();
+
+        let module_data = ModuleData::new(
+            heap_spec,
+            sparse_data,
+            globals_spec,
+            linked_imports,
+            tables_spec,
+            function_exports,
+            memory_exports,
+            passive_data,
+            passive_elements,
+        );
         module_data.serialize()?
     };
 
@@ -342,6 +342,43 @@ pub fn translate_opcode(
          * The call instructions pop off their arguments from the stack and append their
          * return values to it.
          ************************************ Calls ****************************************/
+        Instruction::Call(callee_index) if compiler.prog.runtime_call(callee_index).is_some() => {
+            // Bulk memory, table, and atomic operators were lowered into calls to helper functions,
+            // which are really implemented by the runtime. The segment index, if any, is already
+            // the last argument.
+            let returns_values = !compiler
+                .prog
+                .get_function(callee_index)?
+                .signature()
+                .returns
+                .is_empty();
+            let symbol = compiler
+                .prog
+                .runtime_call(callee_index)
+                .expect("runtime call was checked");
+            let &(ref callee_ref, ref callee_func) =
+                entity_creator.get_runtime_func(builder.func, symbol.into(), compiler)?;
+
+            let num_args = normal_args(&callee_func.signature());
+            let call_args = with_vmctx(builder.func, state.peekn(num_args))?;
+
+            let call = builder.cursor().ins().call(*callee_ref, &call_args);
+
+            state.dropn(num_args);
+            if returns_values {
+                // The atomic operators return their results, as the helpers they stand in for do
+                state.pushn(builder.inst_results(call));
+            } else if let Some(out_of_bounds) = builder.inst_results(call).first().cloned() {
+                // Everything else but the segment drops returns nonzero when out of bounds
+                let code = if symbol.starts_with("lucet_vmctx_table_") {
+                    ir::TrapCode::TableOutOfBounds
+                } else {
+                    ir::TrapCode::HeapOutOfBounds
+                };
+                builder.ins().trapnz(out_of_bounds, code);
+            }
+        }
+
         Instruction::Call(callee_index) => {
             let &(ref callee_ref, ref callee_func) =
                 entity_creator.get_direct_func(builder.func, callee_index, compiler)?;
@@ -357,8 +394,10 @@ pub fn translate_opcode(
         }
 
         Instruction::CallIndirect(type_index, _reserved) => {
-            let table_index = 0;
-            let (table, table_base) =
+            // Calls through tables other than table 0 were given a copy of their type by
+            // lowering, which tells them apart.
+            let (type_index, table_index) = compiler.prog.indirect_call(type_index);
+            let (_table, table_base) =
                 entity_creator.get_table(table_index, builder.func, compiler)?;
             let &(ref sig_ref, ref fnsig) =
                 entity_creator.get_indirect_sig(builder.func, type_index)?;
@@ -366,10 +405,12 @@ pub fn translate_opcode(
 
             let callee = state.pop1();
 
-            // Indirect calls are performed by looking up the callee function and type in a table that
-            // is present in the same object file.
-            // The table is an array of pairs of (type index, function pointer). Both elements in the
-            // pair are the size of a pointer.
+            // Indirect calls are performed by looking up the callee function and type in the
+            // instance's copy of the table.
+            // The instance describes each of its tables with a pair of (elements pointer, length),
+            // both the size of a pointer, as the length changes when the table grows.
+            // The elements are an array of pairs of (type index, function pointer). Both elements
+            // in the pair are the size of a pointer.
             // The array is indexed by the callee, as an integer. The callee passed in above is a
             // symbolic value because it is only known at run-time.
             // We bounds-check the callee, look up the type index, check that it is equal to the type
@@ -378,19 +419,31 @@ pub fn translate_opcode(
             let call: Result<ir::Inst, Error> = {
                 let mut pos = builder.cursor();
 
+                let descriptors = pos.ins().global_value(NATIVE_POINTER, table_base);
+                let descriptor_offs = table_index as i32 * 2 * POINTER_SIZE as i32;
+                let calleebound = pos.ins().load(
+                    ir::Type::int(64).unwrap(),
+                    ir::MemFlags::new(),
+                    descriptors,
+                    descriptor_offs + POINTER_SIZE as i32,
+                );
                 // `callee` is an integer value that may represent a valid offset into the
                 // icall table.
-                let calleebound = table.elements().len();
+                let callee_64 = pos.ins().uextend(ir::Type::int(64).unwrap(), callee);
                 // First see if the callee is even a valid index into the table.
-                let inbounds = pos.ins().icmp_imm(
+                let inbounds = pos.ins().icmp(
                     ir::condcodes::IntCC::UnsignedLessThan,
-                    callee,
-                    calleebound as i64,
+                    callee_64,
+                    calleebound,
                 );
                 pos.ins().trapz(inbounds, ir::TrapCode::IndirectCallToNull);
 
-                let table_addr = pos.ins().global_value(NATIVE_POINTER, table_base);
-                let callee_64 = pos.ins().uextend(ir::Type::int(64).unwrap(), callee);
+                let table_addr = pos.ins().load(
+                    NATIVE_POINTER,
+                    ir::MemFlags::new(),
+                    descriptors,
+                    descriptor_offs,
+                );
                 // Get the type index from memory:
                 let table_type_offs = pos.ins().imul_imm(callee_64, 2 * POINTER_SIZE as i64);
                 let table_type = pos.ins().iadd(table_addr, table_type_offs);
@@ -1,61 +1,27 @@
 use crate::compiler::Compiler;
 use crate::program::table::{TableDef, TableElem};
 use byteorder::{LittleEndian, WriteBytesExt};
-use cranelift_module::DataContext;
+use cranelift_module::{DataContext, Linkage};
 use failure::{format_err, Error, ResultExt};
 use std::io::Cursor;
 
 pub fn compile_table<'p>(compiler: &mut Compiler<'p>, table: &TableDef) -> Result<(), Error> {
-    // Indirect calls are performed by looking up the callee function and type in a table that
-    // is present in the same object file.
+    // Indirect calls are performed by looking up the callee function and type in a table.
     // The table is an array of pairs of (type index, function pointer). Both elements in the
     // pair are the size of a pointer.
-    // This function creates that table as a section in the object.
+    // This function creates the initial contents of that table as a section in the object. The
+    // runtime copies it into each instance, and guest code finds the copy through the vmctx.
 
     // For readability:
     let ptr_size = 8;
 
     let mut table_data = Cursor::new(Vec::with_capacity(table.elements().len() * 2 * ptr_size));
-    let putelem =
-        { |table: &mut Cursor<Vec<u8>>, elem: u64| table.write_u64::<LittleEndian>(elem).unwrap() };
     let mut table_ctx = DataContext::new();
 
     // table.elems is a vector that gives every entry of the table, either specifying the
     // wasm function index or that no element was given for that table entry.
     for table_elem in table.elements() {
-        match table_elem {
-            &TableElem::FunctionIx(ref func_index) => {
-                // Note: this is the only place we validate that the table entry points to a valid
-                // function. If this is ever removed, make sure this check happens elsewhere.
-                let func = compiler
-                    .prog
-                    .get_function(*func_index)
-                    .context("function index for table element")?;
-                let sig_ix = func
-                    .signature_index()
-                    .ok_or(format_err!("table function should have a signature index"))?;
-
-                // First element in row is the SignatureIndex for the function
-                putelem(&mut table_data, sig_ix as u64);
-
-                // Second element in row is the pointer to the function. The Reloc is doing the work
-                // here. We put a 0 in the table data itself to be overwritten at link time.
-                let funcname = compiler.get_function(func)?;
-                let funcref = table_ctx.import_function(funcname.into());
-                let position = table_data.position();
-                assert!(position < <u32>::max_value() as u64);
-                table_ctx.write_function_addr(position as u32, funcref);
-                putelem(&mut table_data, 0);
-            }
-            &TableElem::Empty => {
-                // First element is the signature index. These will always be 32 bits in wasm, so
-                // u64::max will always be out of bounds.
-                putelem(&mut table_data, <u64>::max_value());
-                // Second element is the function pointer. No relocation here, it will always be
-                // null.
-                putelem(&mut table_data, 0);
-            }
-        }
+        put_table_elem(compiler, &mut table_data, &mut table_ctx, table_elem)?;
     }
     table_ctx.define(table_data.into_inner().into_boxed_slice());
     let table_id = compiler
@@ -65,3 +31,106 @@ pub fn compile_table<'p>(compiler: &mut Compiler<'p>, table: &TableDef) -> Resul
     compiler.module.define_data(table_id, &table_ctx)?;
     Ok(())
 }
+
+pub fn compile_passive_elements<'p>(compiler: &mut Compiler<'p>) -> Result<(), Error> {
+    // Passive element segments are only copied into the table by `table.init`, so their contents
+    // are kept apart from the table, in an array with the same layout. The segments are laid out
+    // one after another, and the module data records where each one starts.
+    let prog = compiler.prog;
+    let elements = prog
+        .passive_elements()
+        .iter()
+        .flat_map(|segment| segment.functions.iter())
+        .map(|func_index| match func_index {
+            Some(func_index) => TableElem::FunctionIx(*func_index),
+            None => TableElem::Empty,
+        })
+        .collect::<Vec<_>>();
+    if elements.is_empty() {
+        return Ok(());
+    }
+
+    let mut elements_data = Cursor::new(Vec::with_capacity(elements.len() * 2 * 8));
+    let mut elements_ctx = DataContext::new();
+    for elem in elements.iter() {
+        put_table_elem(compiler, &mut elements_data, &mut elements_ctx, elem)?;
+    }
+    elements_ctx.define(elements_data.into_inner().into_boxed_slice());
+    let name = compiler.declare_data("guest_passive_elements", Linkage::Export, false)?;
+    compiler.define_data(name, &elements_ctx)
+}
+
+pub fn compile_ref_funcs<'p>(compiler: &mut Compiler<'p>) -> Result<(), Error> {
+    // `ref.func` passes the runtime the position of its function in this array, which has the
+    // layout of a table, so that the runtime can hand out the element as a `funcref`. Its length
+    // in bytes follows in a separate symbol, as for tables.
+    let prog = compiler.prog;
+    if prog.ref_funcs().is_empty() {
+        return Ok(());
+    }
+
+    let mut funcs_data = Cursor::new(Vec::with_capacity(prog.ref_funcs().len() * 2 * 8));
+    let mut funcs_ctx = DataContext::new();
+    for func_index in prog.ref_funcs() {
+        let elem = TableElem::FunctionIx(*func_index);
+        put_table_elem(compiler, &mut funcs_data, &mut funcs_ctx, &elem)?;
+    }
+    let funcs_data = funcs_data.into_inner();
+
+    let mut len_data = Cursor::new(Vec::with_capacity(8));
+    len_data
+        .write_u64::<LittleEndian>(funcs_data.len() as u64)
+        .unwrap();
+    let mut len_ctx = DataContext::new();
+    len_ctx.define(len_data.into_inner().into_boxed_slice());
+    let len_name = compiler.declare_data("guest_ref_funcs_len", Linkage::Export, false)?;
+    compiler.define_data(len_name, &len_ctx)?;
+
+    funcs_ctx.define(funcs_data.into_boxed_slice());
+    let name = compiler.declare_data("guest_ref_funcs", Linkage::Export, false)?;
+    compiler.define_data(name, &funcs_ctx)
+}
+
+fn put_table_elem<'p>(
+    compiler: &Compiler<'p>,
+    table_data: &mut Cursor<Vec<u8>>,
+    table_ctx: &mut DataContext,
+    table_elem: &TableElem,
+) -> Result<(), Error> {
+    let putelem =
+        { |table: &mut Cursor<Vec<u8>>, elem: u64| table.write_u64::<LittleEndian>(elem).unwrap() };
+    match table_elem {
+        &TableElem::FunctionIx(ref func_index) => {
+            // Note: this is the only place we validate that the table entry points to a valid
+            // function. If this is ever removed, make sure this check happens elsewhere.
+            let func = compiler
+                .prog
+                .get_function(*func_index)
+                .context("function index for table element")?;
+            let sig_ix = func
+                .signature_index()
+                .ok_or(format_err!("table function should have a signature index"))?;
+
+            // First element in row is the SignatureIndex for the function
+            putelem(table_data, sig_ix as u64);
+
+            // Second element in row is the pointer to the function. The Reloc is doing the work
+            // here. We put a 0 in the table data itself to be overwritten at link time.
+            let funcname = compiler.get_function(func)?;
+            let funcref = table_ctx.import_function(funcname.into());
+            let position = table_data.position();
+            assert!(position < <u32>::max_value() as u64);
+            table_ctx.write_function_addr(position as u32, funcref);
+            putelem(table_data, 0);
+        }
+        &TableElem::Empty => {
+            // First element is the signature index. These will always be 32 bits in wasm, so
+            // u64::max will always be out of bounds.
+            putelem(table_data, <u64>::max_value());
+            // Second element is the function pointer. No relocation here, it will always be
+            // null.
+            putelem(table_data, 0);
+        }
+    }
+    Ok(())
+}
@@ -44,7 +44,11 @@ impl Display for LucetcError {
 
 impl From<Error> for LucetcError {
     fn from(e: Error) -> LucetcError {
-        e.context(LucetcErrorKind::UnknownKind).into()
+        // keep the kind of errors that already have one, such as validation errors
+        match e.downcast::<Context<LucetcErrorKind>>() {
+            Ok(inner) => inner.into(),
+            Err(e) => e.context(LucetcErrorKind::UnknownKind).into(),
+        }
     }
 }
 
@@ -68,6 +72,8 @@ pub enum LucetcErrorKind {
     Function(String),
     #[fail(display = "Table {}", _0)]
     Table(String),
+    #[fail(display = "Linked import {}", _0)]
+    LinkedImport(String),
     #[fail(display = "Validation")]
     Validation,
 
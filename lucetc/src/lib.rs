@@ -1,23 +1,28 @@
 pub mod bindings;
+pub mod cache;
 pub mod compiler;
 pub mod error;
+pub mod link;
 pub mod load;
+pub mod lower;
 pub mod patch;
 pub mod program;
+pub mod validate;
 
+use crate::cache::{Cache, CacheKey};
 use crate::compiler::data::{compile_data_initializers, compile_sparse_page_data};
 use crate::compiler::function::compile_function;
 use crate::compiler::globals::compile_global_specs;
+use crate::compiler::linked_import::compile_linked_import;
 use crate::compiler::memory::compile_memory_specs;
 use crate::compiler::module_data::compile_module_data;
-use crate::compiler::table::compile_table;
+use crate::compiler::table::{compile_passive_elements, compile_ref_funcs, compile_table};
 use crate::error::{LucetcError, LucetcErrorKind};
 use crate::load::read_module;
 use crate::patch::patch_module;
 use crate::program::Program;
 use failure::{format_err, Error, ResultExt};
 use parity_wasm::elements::Module;
-use std::env;
 use std::path::{Path, PathBuf};
 use tempfile;
 
@@ -33,6 +38,9 @@ pub struct Lucetc {
     opt_level: OptLevel,
     heap: HeapSettings,
     builtins_paths: Vec<PathBuf>,
+    linked_modules: Vec<String>,
+    cache_dir: Option<PathBuf>,
+    external_linker: Option<ExternalLinker>,
 }
 
 pub trait AsLucetc {
@@ -55,6 +63,9 @@ pub trait LucetcOpts {
     fn builtins<P: AsRef<Path>>(&mut self, builtins_path: P);
     fn with_builtins<P: AsRef<Path>>(self, builtins_path: P) -> Self;
 
+    fn link_module(&mut self, module: &str);
+    fn with_link_module(self, module: &str) -> Self;
+
     fn min_reserved_size(&mut self, min_reserved_size: u64);
     fn with_min_reserved_size(self, min_reserved_size: u64) -> Self;
 
@@ -63,6 +74,12 @@ pub trait LucetcOpts {
 
     fn guard_size(&mut self, guard_size: u64);
     fn with_guard_size(self, guard_size: u64) -> Self;
+
+    fn cache_dir<P: AsRef<Path>>(&mut self, cache_dir: P);
+    fn with_cache_dir<P: AsRef<Path>>(self, cache_dir: P) -> Self;
+
+    fn external_linker(&mut self, linker: ExternalLinker);
+    fn with_external_linker(self, linker: ExternalLinker) -> Self;
 }
 
 impl<T: AsLucetc> LucetcOpts for T {
@@ -95,6 +112,15 @@ impl<T: AsLucetc> LucetcOpts for T {
         self
     }
 
+    fn link_module(&mut self, module: &str) {
+        self.as_lucetc().linked_modules.push(module.to_owned());
+    }
+
+    fn with_link_module(mut self, module: &str) -> Self {
+        self.link_module(module);
+        self
+    }
+
     fn min_reserved_size(&mut self, min_reserved_size: u64) {
         self.as_lucetc().heap.min_reserved_size = min_reserved_size;
     }
@@ -121,6 +147,24 @@ impl<T: AsLucetc> LucetcOpts for T {
         self.guard_size(guard_size);
         self
     }
+
+    fn cache_dir<P: AsRef<Path>>(&mut self, cache_dir: P) {
+        self.as_lucetc().cache_dir = Some(cache_dir.as_ref().to_owned());
+    }
+
+    fn with_cache_dir<P: AsRef<Path>>(mut self, cache_dir: P) -> Self {
+        self.cache_dir(cache_dir);
+        self
+    }
+
+    fn external_linker(&mut self, linker: ExternalLinker) {
+        self.as_lucetc().external_linker = Some(linker);
+    }
+
+    fn with_external_linker(mut self, linker: ExternalLinker) -> Self {
+        self.external_linker(linker);
+        self
+    }
 }
 
 impl Lucetc {
@@ -132,9 +176,44 @@ impl Lucetc {
             opt_level: OptLevel::default(),
             heap: HeapSettings::default(),
             builtins_paths: vec![],
+            linked_modules: vec![],
+            cache_dir: None,
+            // the built-in linker only produces ELF
+            external_linker: if cfg!(target_os = "macos") {
+                Some(ExternalLinker::default())
+            } else {
+                None
+            },
         }
     }
 
+    /// The key of the shared object this builder produces, covering everything that affects it.
+    fn cache_key(&self) -> Result<CacheKey, Error> {
+        let mut key = CacheKey::new(self.opt_level)?;
+        key.add_file(&self.input)?;
+        key.add_u64(self.bindings.len() as u64);
+        for bindings in self.bindings.iter() {
+            key.add_bindings(bindings);
+        }
+        key.add_heap(&self.heap);
+        key.add_u64(self.builtins_paths.len() as u64);
+        for builtins in self.builtins_paths.iter() {
+            key.add_file(builtins)?;
+        }
+        key.add_u64(self.linked_modules.len() as u64);
+        for module in self.linked_modules.iter() {
+            key.add_str(module);
+        }
+        match self.external_linker {
+            Some(ref linker) => {
+                key.add_str(&linker.ld);
+                key.add_str(&linker.ldflags);
+            }
+            None => key.add_str("builtin"),
+        }
+        Ok(key)
+    }
+
     fn build(&self) -> Result<(String, Module, Bindings), Error> {
         let name = String::from(
             self.input
@@ -161,6 +240,10 @@ impl Lucetc {
             bindings.extend(binding)?;
         }
 
+        for module in self.linked_modules.iter() {
+            bindings.link_module(module);
+        }
+
         Ok((name, module, bindings))
     }
 
@@ -189,11 +272,35 @@ impl Lucetc {
         Ok(())
     }
 
+    /// Compile and link a shared object.
+    ///
+    /// If a cache directory is set, a shared object compiled from the same inputs before is copied
+    /// from the cache instead, and a newly compiled one is added to it.
     pub fn shared_object_file<P: AsRef<Path>>(self, output: P) -> Result<(), Error> {
+        let cached = match self.cache_dir {
+            Some(ref dir) => {
+                let cache = Cache::new(dir)?;
+                let digest = self.cache_key()?.digest();
+                if cache.fetch(&digest, output.as_ref())? {
+                    return Ok(());
+                }
+                Some((cache, digest))
+            }
+            None => None,
+        };
+
+        let external_linker = self.external_linker.clone();
         let dir = tempfile::Builder::new().prefix("lucetc").tempdir()?;
         let objpath = dir.path().join("tmp.o");
         self.object_file(objpath.clone())?;
-        link_so(objpath, output)?;
+        match external_linker {
+            Some(linker) => linker.link_so(objpath, output.as_ref())?,
+            None => link::shared_object_file(objpath, output.as_ref())?,
+        }
+
+        if let Some((cache, digest)) = cached {
+            cache.store(&digest, output.as_ref())?;
+        }
         Ok(())
     }
 }
@@ -206,33 +313,55 @@ const LDFLAGS_DEFAULT: &str = "-shared";
 #[cfg(target_os = "macos")]
 const LDFLAGS_DEFAULT: &str = "-dylib -dead_strip -export_dynamic -undefined dynamic_lookup";
 
-fn link_so<P, Q>(objpath: P, sopath: Q) -> Result<(), Error>
-where
-    P: AsRef<Path>,
-    Q: AsRef<Path>,
-{
-    use std::process::Command;
-    let mut cmd_ld = Command::new(env::var("LD").unwrap_or(LD_DEFAULT.into()));
-    cmd_ld.arg(objpath.as_ref());
-    let env_ldflags = env::var("LDFLAGS").unwrap_or(LDFLAGS_DEFAULT.into());
-    for flag in env_ldflags.split_whitespace() {
-        cmd_ld.arg(flag);
+/// An external linker to link shared objects with, instead of the one built into `lucetc`.
+///
+/// The built-in linker is used by default, except on macOS, where it does not apply as it only
+/// produces ELF.
+#[derive(Debug, Clone)]
+pub struct ExternalLinker {
+    /// The linker to run.
+    pub ld: String,
+    /// The flags to pass it, separated by whitespace.
+    pub ldflags: String,
+}
+
+impl Default for ExternalLinker {
+    fn default() -> Self {
+        ExternalLinker {
+            ld: LD_DEFAULT.to_owned(),
+            ldflags: LDFLAGS_DEFAULT.to_owned(),
+        }
     }
-    cmd_ld.arg("-o");
-    cmd_ld.arg(sopath.as_ref());
-
-    let run_ld = cmd_ld
-        .output()
-        .context(format_err!("running ld on {:?}", objpath.as_ref()))?;
-
-    if !run_ld.status.success() {
-        Err(format_err!(
-            "ld of {} failed: {}",
-            objpath.as_ref().to_str().unwrap(),
-            String::from_utf8_lossy(&run_ld.stderr)
-        ))?;
+}
+
+impl ExternalLinker {
+    fn link_so<P, Q>(&self, objpath: P, sopath: Q) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        use std::process::Command;
+        let mut cmd_ld = Command::new(&self.ld);
+        cmd_ld.arg(objpath.as_ref());
+        for flag in self.ldflags.split_whitespace() {
+            cmd_ld.arg(flag);
+        }
+        cmd_ld.arg("-o");
+        cmd_ld.arg(sopath.as_ref());
+
+        let run_ld = cmd_ld
+            .output()
+            .context(format_err!("running ld on {:?}", objpath.as_ref()))?;
+
+        if !run_ld.status.success() {
+            Err(format_err!(
+                "ld of {} failed: {}",
+                objpath.as_ref().to_str().unwrap(),
+                String::from_utf8_lossy(&run_ld.stderr)
+            ))?;
+        }
+        Ok(())
     }
-    Ok(())
 }
 
 pub fn compile<'p>(
@@ -253,10 +382,19 @@ pub fn compile<'p>(
         compile_function(&mut compiler, &function, body)
             .context(LucetcErrorKind::Function(function.symbol().to_owned()))?;
     }
+    for import in program.linked_import_functions() {
+        compile_linked_import(&mut compiler, import).context(LucetcErrorKind::LinkedImport(
+            format!("{}::{}", import.module(), import.field()),
+        ))?;
+    }
     for table in program.tables() {
         compile_table(&mut compiler, &table)
             .context(LucetcErrorKind::Table(table.symbol().to_owned()))?;
     }
+    compile_passive_elements(&mut compiler)
+        .context(LucetcErrorKind::Table("guest_passive_elements".to_owned()))?;
+    compile_ref_funcs(&mut compiler)
+        .context(LucetcErrorKind::Table("guest_ref_funcs".to_owned()))?;
 
     Ok(compiler)
 }
@@ -0,0 +1,356 @@
+//! Reading relocatable ELF objects, and the ELF constants that the linker needs.
+//!
+//! Only what `faerie` produces for x86-64 is supported: 64-bit little-endian relocatable objects
+//! with `SHT_RELA` relocation sections.
+
+use byteorder::{ByteOrder, LittleEndian};
+use failure::{bail, format_err, Error};
+
+pub const EHDR_SIZE: u64 = 64;
+pub const PHDR_SIZE: u64 = 56;
+pub const SHDR_SIZE: u64 = 64;
+pub const SYM_SIZE: u64 = 24;
+pub const RELA_SIZE: u64 = 24;
+pub const DYN_SIZE: u64 = 16;
+
+pub const ET_REL: u16 = 1;
+pub const ET_DYN: u16 = 3;
+pub const EM_X86_64: u16 = 62;
+
+pub const SHT_NULL: u32 = 0;
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_RELA: u32 = 4;
+pub const SHT_HASH: u32 = 5;
+pub const SHT_DYNAMIC: u32 = 6;
+pub const SHT_NOBITS: u32 = 8;
+pub const SHT_DYNSYM: u32 = 11;
+pub const SHT_SYMTAB_SHNDX: u32 = 18;
+
+pub const SHF_WRITE: u64 = 0x1;
+pub const SHF_ALLOC: u64 = 0x2;
+pub const SHF_EXECINSTR: u64 = 0x4;
+pub const SHF_TLS: u64 = 0x400;
+
+pub const SHN_UNDEF: u16 = 0;
+pub const SHN_LORESERVE: u16 = 0xff00;
+pub const SHN_ABS: u16 = 0xfff1;
+pub const SHN_COMMON: u16 = 0xfff2;
+pub const SHN_XINDEX: u16 = 0xffff;
+
+pub const STB_GLOBAL: u8 = 1;
+pub const STB_WEAK: u8 = 2;
+pub const STT_SECTION: u8 = 3;
+pub const STT_FILE: u8 = 4;
+pub const STV_DEFAULT: u8 = 0;
+pub const STV_PROTECTED: u8 = 3;
+
+pub const PT_LOAD: u32 = 1;
+pub const PT_DYNAMIC: u32 = 2;
+pub const PT_GNU_STACK: u32 = 0x6474_e551;
+pub const PT_GNU_RELRO: u32 = 0x6474_e552;
+pub const PF_X: u32 = 0x1;
+pub const PF_W: u32 = 0x2;
+pub const PF_R: u32 = 0x4;
+
+pub const DT_NULL: u64 = 0;
+pub const DT_HASH: u64 = 4;
+pub const DT_STRTAB: u64 = 5;
+pub const DT_SYMTAB: u64 = 6;
+pub const DT_RELA: u64 = 7;
+pub const DT_RELASZ: u64 = 8;
+pub const DT_RELAENT: u64 = 9;
+pub const DT_STRSZ: u64 = 10;
+pub const DT_SYMENT: u64 = 11;
+pub const DT_RELACOUNT: u64 = 0x6fff_fff9;
+
+pub const R_X86_64_NONE: u32 = 0;
+pub const R_X86_64_64: u32 = 1;
+pub const R_X86_64_PC32: u32 = 2;
+pub const R_X86_64_PLT32: u32 = 4;
+pub const R_X86_64_GLOB_DAT: u32 = 6;
+pub const R_X86_64_RELATIVE: u32 = 8;
+pub const R_X86_64_GOTPCREL: u32 = 9;
+pub const R_X86_64_32: u32 = 10;
+pub const R_X86_64_32S: u32 = 11;
+pub const R_X86_64_PC64: u32 = 24;
+pub const R_X86_64_GOTPCRELX: u32 = 41;
+pub const R_X86_64_REX_GOTPCRELX: u32 = 42;
+
+/// A section of a relocatable object.
+#[derive(Debug)]
+pub struct Section<'a> {
+    pub name: String,
+    pub ty: u32,
+    pub flags: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+    pub addralign: u64,
+    /// The contents of the section, which are empty for `SHT_NOBITS`.
+    pub data: &'a [u8],
+}
+
+impl<'a> Section<'a> {
+    pub fn is_alloc(&self) -> bool {
+        self.flags & SHF_ALLOC != 0 && (self.ty == SHT_PROGBITS || self.ty == SHT_NOBITS)
+    }
+}
+
+/// Where a symbol is defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolSection {
+    Undefined,
+    Absolute,
+    Common,
+    /// Defined in the section with this index, resolving `SHN_XINDEX`.
+    Index(u32),
+}
+
+/// A symbol of a relocatable object.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub info: u8,
+    pub other: u8,
+    pub section: SymbolSection,
+    pub value: u64,
+    pub size: u64,
+}
+
+impl Symbol {
+    pub fn binding(&self) -> u8 {
+        self.info >> 4
+    }
+
+    pub fn ty(&self) -> u8 {
+        self.info & 0xf
+    }
+
+    pub fn visibility(&self) -> u8 {
+        self.other & 0x3
+    }
+
+    pub fn is_undefined(&self) -> bool {
+        self.section == SymbolSection::Undefined
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.binding() == STB_GLOBAL || self.binding() == STB_WEAK
+    }
+}
+
+/// A relocation of a relocatable object.
+#[derive(Debug, Clone, Copy)]
+pub struct Rela {
+    pub offset: u64,
+    pub sym: u32,
+    pub ty: u32,
+    pub addend: i64,
+}
+
+/// The sections, symbols, and relocations of a relocatable object.
+pub struct Object<'a> {
+    pub sections: Vec<Section<'a>>,
+    pub symbols: Vec<Symbol>,
+    /// The relocations of each section, by the index of the section they apply to.
+    pub relocs: Vec<Vec<Rela>>,
+}
+
+impl<'a> Object<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < EHDR_SIZE as usize || &bytes[0..4] != b"\x7fELF" {
+            bail!("not an ELF object");
+        }
+        if bytes[4] != 2 || bytes[5] != 1 {
+            bail!("not a 64-bit little-endian ELF object");
+        }
+        if LittleEndian::read_u16(&bytes[16..]) != ET_REL {
+            bail!("not a relocatable ELF object");
+        }
+        if LittleEndian::read_u16(&bytes[18..]) != EM_X86_64 {
+            bail!("not an x86-64 ELF object");
+        }
+        let shoff = LittleEndian::read_u64(&bytes[40..]);
+        let mut shnum = u64::from(LittleEndian::read_u16(&bytes[60..]));
+        let mut shstrndx = u32::from(LittleEndian::read_u16(&bytes[62..]));
+
+        let header = |idx: u64| -> Result<&'a [u8], Error> {
+            let start = shoff + idx * SHDR_SIZE;
+            slice(bytes, start, SHDR_SIZE)
+        };
+        // objects with many sections keep their number and the string table index in the first
+        // section header
+        if shoff != 0 {
+            let first = header(0)?;
+            if shnum == 0 {
+                shnum = LittleEndian::read_u64(&first[32..]);
+            }
+            if shstrndx == u32::from(SHN_XINDEX) {
+                shstrndx = LittleEndian::read_u32(&first[40..]);
+            }
+        }
+
+        let mut raw = vec![];
+        for idx in 0..shnum {
+            let h = header(idx)?;
+            let name = LittleEndian::read_u32(&h[0..]);
+            let ty = LittleEndian::read_u32(&h[4..]);
+            let flags = LittleEndian::read_u64(&h[8..]);
+            let offset = LittleEndian::read_u64(&h[24..]);
+            let size = LittleEndian::read_u64(&h[32..]);
+            let link = LittleEndian::read_u32(&h[40..]);
+            let info = LittleEndian::read_u32(&h[44..]);
+            let addralign = LittleEndian::read_u64(&h[48..]);
+            let data = if ty == SHT_NOBITS || ty == SHT_NULL {
+                &bytes[0..0]
+            } else {
+                slice(bytes, offset, size)?
+            };
+            raw.push((name, ty, flags, size, link, info, addralign, data));
+        }
+        let shstrtab = raw
+            .get(shstrndx as usize)
+            .map(|s| s.7)
+            .ok_or_else(|| format_err!("section name table out of bounds"))?;
+        let sections = raw
+            .into_iter()
+            .map(|(name, ty, flags, size, link, info, addralign, data)| {
+                Ok(Section {
+                    name: string_at(shstrtab, name)?,
+                    ty,
+                    flags,
+                    size,
+                    link,
+                    info,
+                    addralign: addralign.max(1),
+                    data,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let symbols = match sections.iter().position(|s| s.ty == SHT_SYMTAB) {
+            Some(symtab) => parse_symbols(&sections, symtab)?,
+            None => vec![],
+        };
+
+        let mut relocs = vec![vec![]; sections.len()];
+        for section in sections.iter().filter(|s| s.ty == SHT_RELA) {
+            let target = relocs
+                .get_mut(section.info as usize)
+                .ok_or_else(|| format_err!("relocation section {} out of bounds", section.name))?;
+            for entry in section.data.chunks(RELA_SIZE as usize) {
+                if entry.len() != RELA_SIZE as usize {
+                    bail!("truncated relocation section {}", section.name);
+                }
+                let info = LittleEndian::read_u64(&entry[8..]);
+                target.push(Rela {
+                    offset: LittleEndian::read_u64(&entry[0..]),
+                    sym: (info >> 32) as u32,
+                    ty: info as u32,
+                    addend: LittleEndian::read_i64(&entry[16..]),
+                });
+            }
+        }
+
+        Ok(Object {
+            sections,
+            symbols,
+            relocs,
+        })
+    }
+}
+
+fn parse_symbols(sections: &[Section], symtab: usize) -> Result<Vec<Symbol>, Error> {
+    let strtab = sections
+        .get(sections[symtab].link as usize)
+        .ok_or_else(|| format_err!("symbol name table out of bounds"))?
+        .data;
+    let shndx_table = sections
+        .iter()
+        .find(|s| s.ty == SHT_SYMTAB_SHNDX && s.link as usize == symtab)
+        .map(|s| s.data);
+    let mut symbols = vec![];
+    for (idx, entry) in sections[symtab].data.chunks(SYM_SIZE as usize).enumerate() {
+        if entry.len() != SYM_SIZE as usize {
+            bail!("truncated symbol table");
+        }
+        let raw_shndx = LittleEndian::read_u16(&entry[6..]);
+        let section = match (raw_shndx, shndx_table) {
+            (SHN_UNDEF, _) => SymbolSection::Undefined,
+            (SHN_ABS, _) => SymbolSection::Absolute,
+            (SHN_COMMON, _) => SymbolSection::Common,
+            (SHN_XINDEX, Some(table)) => {
+                SymbolSection::Index(LittleEndian::read_u32(slice(table, idx as u64 * 4, 4)?))
+            }
+            (SHN_XINDEX, None) => bail!("extended section index without a table"),
+            (shndx, _) if shndx >= SHN_LORESERVE => bail!("unsupported section index {}", shndx),
+            (shndx, _) => SymbolSection::Index(u32::from(shndx)),
+        };
+        symbols.push(Symbol {
+            name: string_at(strtab, LittleEndian::read_u32(&entry[0..]))?,
+            info: entry[4],
+            other: entry[5],
+            section,
+            value: LittleEndian::read_u64(&entry[8..]),
+            size: LittleEndian::read_u64(&entry[16..]),
+        });
+    }
+    Ok(symbols)
+}
+
+fn slice(bytes: &[u8], start: u64, len: u64) -> Result<&[u8], Error> {
+    let end = start
+        .checked_add(len)
+        .filter(|end| *end <= bytes.len() as u64)
+        .ok_or_else(|| format_err!("ELF object is truncated"))?;
+    Ok(&bytes[start as usize..end as usize])
+}
+
+fn string_at(table: &[u8], offset: u32) -> Result<String, Error> {
+    let bytes = table
+        .get(offset as usize..)
+        .ok_or_else(|| format_err!("string table offset out of bounds"))?;
+    let len = bytes
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or_else(|| format_err!("unterminated string"))?;
+    Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+}
+
+/// A string table under construction.
+#[derive(Default)]
+pub struct StringTable {
+    pub bytes: Vec<u8>,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        StringTable { bytes: vec![0] }
+    }
+
+    pub fn add(&mut self, s: &str) -> u32 {
+        if s.is_empty() {
+            return 0;
+        }
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(s.as_bytes());
+        self.bytes.push(0);
+        offset
+    }
+}
+
+/// The hash function of `DT_HASH` tables.
+pub fn sysv_hash(name: &str) -> u32 {
+    let mut h: u32 = 0;
+    for b in name.bytes() {
+        h = (h << 4).wrapping_add(u32::from(b));
+        let g = h & 0xf000_0000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+    }
+    h
+}
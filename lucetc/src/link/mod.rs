@@ -0,0 +1,873 @@
+//! A linker that turns the relocatable object written by `lucetc` into a shared object, so that
+//! producing one does not depend on an external `ld`.
+//!
+//! Only the x86-64 ELF objects that `faerie` writes are supported. The shared object has three
+//! loadable segments: one with the ELF headers, the dynamic linking tables and the read-only data,
+//! one with the code, and one with the writable data. Read-only data that the dynamic loader has to
+//! relocate, such as tables of function pointers, goes at the start of the writable segment, which
+//! the loader makes read-only again after relocating it.
+//!
+//! References between symbols of the object are resolved here, binding them to the definitions in
+//! the object as `-Bsymbolic` would. References to undefined symbols, such as hostcalls, go
+//! through a global offset table that the loader fills in when the shared object is loaded, and
+//! calls to them go through stubs in `.plt`. Every global symbol is exported in `.dynsym`.
+
+mod elf;
+
+use self::elf::*;
+use byteorder::{ByteOrder, LittleEndian};
+use failure::{bail, format_err, Error, ResultExt};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+const PAGE_SIZE: u64 = 0x1000;
+const PLT_ENTRY_SIZE: u64 = 16;
+
+/// The dynamic section has a fixed set of entries, ending with `DT_NULL`.
+const DYNAMIC_ENTRIES: u64 = 10;
+
+/// Link the relocatable object at `objpath` into a shared object at `sopath`.
+pub fn shared_object_file<P, Q>(objpath: P, sopath: Q) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let object = fs::read(objpath.as_ref()).context(format!("reading {:?}", objpath.as_ref()))?;
+    let so = shared_object(&object).context(format!("linking {:?}", objpath.as_ref()))?;
+    fs::write(sopath.as_ref(), so).context(format!("writing {:?}", sopath.as_ref()))?;
+    Ok(())
+}
+
+/// Link a relocatable object into a shared object.
+pub fn shared_object(object: &[u8]) -> Result<Vec<u8>, Error> {
+    let obj = Object::parse(object)?;
+    let inputs = classify(&obj)?;
+    let imports = Imports::collect(&obj)?;
+    let dynsyms = DynamicSymbols::collect(&obj, &imports);
+    Linker {
+        obj: &obj,
+        inputs,
+        imports,
+        dynsyms,
+    }
+    .link()
+}
+
+/// Where the contents of an allocated section of the object go in the shared object, in the order
+/// they are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Rodata,
+    Text,
+    RelRo,
+    Data,
+    Bss,
+}
+
+impl Kind {
+    fn section_name(self) -> &'static str {
+        match self {
+            Kind::Rodata => ".rodata",
+            Kind::Text => ".text",
+            Kind::RelRo => ".data.rel.ro",
+            Kind::Data => ".data",
+            Kind::Bss => ".bss",
+        }
+    }
+
+    fn section_flags(self) -> u64 {
+        match self {
+            Kind::Rodata => SHF_ALLOC,
+            Kind::Text => SHF_ALLOC | SHF_EXECINSTR,
+            Kind::RelRo | Kind::Data | Kind::Bss => SHF_ALLOC | SHF_WRITE,
+        }
+    }
+}
+
+/// Decide where each allocated section of the object goes.
+fn classify(obj: &Object) -> Result<Vec<Option<Kind>>, Error> {
+    let mut kinds = vec![];
+    for (idx, section) in obj.sections.iter().enumerate() {
+        if !section.is_alloc() {
+            kinds.push(None);
+            continue;
+        }
+        if section.flags & SHF_TLS != 0 {
+            bail!("thread-local section {} is not supported", section.name);
+        }
+        let absolute = obj.relocs[idx].iter().any(|r| r.ty == R_X86_64_64);
+        let kind = if section.flags & SHF_EXECINSTR != 0 {
+            if absolute {
+                bail!("absolute relocation in code section {}", section.name);
+            }
+            Kind::Text
+        } else if section.ty == SHT_NOBITS {
+            Kind::Bss
+        } else if section.flags & SHF_WRITE != 0 {
+            Kind::Data
+        } else if absolute {
+            Kind::RelRo
+        } else {
+            Kind::Rodata
+        };
+        kinds.push(Some(kind));
+    }
+    Ok(kinds)
+}
+
+/// The symbols that need an entry in the global offset table, the undefined functions that need a
+/// stub in `.plt`, and every undefined symbol that relocations refer to, by their index in the
+/// symbol table of the object.
+#[derive(Default)]
+struct Imports {
+    undefined: HashSet<u32>,
+    got: Vec<u32>,
+    got_index: HashMap<u32, u64>,
+    plt: Vec<u32>,
+    plt_index: HashMap<u32, u64>,
+}
+
+impl Imports {
+    fn collect(obj: &Object) -> Result<Self, Error> {
+        let mut imports = Imports::default();
+        for (idx, section) in obj.sections.iter().enumerate() {
+            if !section.is_alloc() {
+                continue;
+            }
+            for reloc in obj.relocs[idx].iter() {
+                let sym = obj.symbols.get(reloc.sym as usize).ok_or_else(|| {
+                    format_err!("relocation against missing symbol {}", reloc.sym)
+                })?;
+                let undefined = reloc.sym != 0 && sym.is_undefined();
+                if undefined {
+                    imports.undefined.insert(reloc.sym);
+                }
+                match reloc.ty {
+                    R_X86_64_NONE | R_X86_64_64 => (),
+                    R_X86_64_PC32 | R_X86_64_PLT32 | R_X86_64_PC64 if !undefined => (),
+                    R_X86_64_PC32 | R_X86_64_PLT32 => {
+                        imports.add_got(reloc.sym);
+                        if !imports.plt_index.contains_key(&reloc.sym) {
+                            imports
+                                .plt_index
+                                .insert(reloc.sym, imports.plt.len() as u64);
+                            imports.plt.push(reloc.sym);
+                        }
+                    }
+                    R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => {
+                        imports.add_got(reloc.sym)
+                    }
+                    R_X86_64_32 | R_X86_64_32S => bail!(
+                        "relocation against {} in {} is not position-independent",
+                        sym.name,
+                        section.name
+                    ),
+                    ty => bail!(
+                        "unsupported relocation type {} against {} in {}",
+                        ty,
+                        sym.name,
+                        section.name
+                    ),
+                }
+            }
+        }
+        Ok(imports)
+    }
+
+    fn add_got(&mut self, sym: u32) {
+        if !self.got_index.contains_key(&sym) {
+            self.got_index.insert(sym, self.got.len() as u64);
+            self.got.push(sym);
+        }
+    }
+}
+
+/// The symbols exported or imported through `.dynsym`: every global symbol of the object that is
+/// visible outside of it, and the undefined symbols that it refers to.
+struct DynamicSymbols {
+    /// The index in the symbol table of the object of each dynamic symbol, after the null symbol.
+    symbols: Vec<u32>,
+    index: HashMap<u32, u32>,
+}
+
+impl DynamicSymbols {
+    fn collect(obj: &Object, imports: &Imports) -> Self {
+        let mut dynsyms = DynamicSymbols {
+            symbols: vec![],
+            index: HashMap::new(),
+        };
+        for (idx, sym) in obj.symbols.iter().enumerate().skip(1) {
+            let visible = sym.visibility() == STV_DEFAULT || sym.visibility() == STV_PROTECTED;
+            let named = sym.ty() != STT_SECTION && sym.ty() != STT_FILE && !sym.name.is_empty();
+            let exported = if sym.is_undefined() {
+                imports.undefined.contains(&(idx as u32))
+            } else {
+                visible
+            };
+            if sym.is_global() && named && exported {
+                dynsyms
+                    .index
+                    .insert(idx as u32, dynsyms.symbols.len() as u32 + 1);
+                dynsyms.symbols.push(idx as u32);
+            }
+        }
+        dynsyms
+    }
+
+    /// The number of entries in `.dynsym`, including the null symbol.
+    fn len(&self) -> u64 {
+        self.symbols.len() as u64 + 1
+    }
+
+    fn nbucket(&self) -> u64 {
+        (self.len() / 2).max(1)
+    }
+}
+
+/// A relocation for the dynamic loader to apply.
+struct DynamicReloc {
+    offset: u64,
+    ty: u32,
+    sym: u32,
+    addend: i64,
+}
+
+/// A section header of the shared object.
+struct SectionHeader {
+    name: &'static str,
+    ty: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: &'static str,
+    info: u32,
+    addralign: u64,
+    entsize: u64,
+}
+
+impl SectionHeader {
+    fn null() -> Self {
+        SectionHeader {
+            name: "",
+            ty: SHT_NULL,
+            flags: 0,
+            addr: 0,
+            offset: 0,
+            size: 0,
+            link: "",
+            info: 0,
+            addralign: 0,
+            entsize: 0,
+        }
+    }
+
+    /// A header for a loaded section, which is at the same offset in the file as its address.
+    fn alloc(
+        name: &'static str,
+        ty: u32,
+        flags: u64,
+        addr: u64,
+        size: u64,
+        addralign: u64,
+    ) -> Self {
+        SectionHeader {
+            name,
+            ty,
+            flags,
+            addr,
+            offset: addr,
+            size,
+            addralign,
+            ..SectionHeader::null()
+        }
+    }
+
+    /// A header for a section that is not loaded, such as the full symbol table.
+    fn unloaded(name: &'static str, ty: u32, offset: u64, size: u64) -> Self {
+        SectionHeader {
+            name,
+            ty,
+            offset,
+            size,
+            addralign: 8,
+            ..SectionHeader::null()
+        }
+    }
+
+    /// Link a table to the section it refers to, such as a symbol table to its string table.
+    fn with_link(self, link: &'static str, info: u32, entsize: u64) -> Self {
+        SectionHeader {
+            link,
+            info,
+            entsize,
+            ..self
+        }
+    }
+}
+
+/// Addresses in the shared object are the same as offsets in the file, as every segment starts
+/// on a page boundary of both.
+#[derive(Default)]
+struct Layout {
+    cursor: u64,
+}
+
+impl Layout {
+    fn place(&mut self, size: u64, align: u64) -> u64 {
+        self.cursor = align_to(self.cursor, align);
+        let at = self.cursor;
+        self.cursor += size;
+        at
+    }
+
+    fn page(&mut self) {
+        self.cursor = align_to(self.cursor, PAGE_SIZE);
+    }
+}
+
+fn align_to(n: u64, align: u64) -> u64 {
+    match n % align {
+        0 => n,
+        rem => n + align - rem,
+    }
+}
+
+struct Linker<'o, 'a> {
+    obj: &'o Object<'a>,
+    inputs: Vec<Option<Kind>>,
+    imports: Imports,
+    dynsyms: DynamicSymbols,
+}
+
+impl<'o, 'a> Linker<'o, 'a> {
+    fn link(self) -> Result<Vec<u8>, Error> {
+        let obj = self.obj;
+
+        // the dynamic symbol names and the number of dynamic relocations are known up front, so
+        // that the tables can be laid out before the contents of the object
+        let mut dynstr = StringTable::new();
+        let dynsym_names = self
+            .dynsyms
+            .symbols
+            .iter()
+            .map(|idx| dynstr.add(&obj.symbols[*idx as usize].name))
+            .collect::<Vec<_>>();
+        let absolute_relocs = obj
+            .sections
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| self.inputs[*idx].is_some())
+            .flat_map(|(idx, _)| obj.relocs[idx].iter())
+            .filter(|r| r.ty == R_X86_64_64)
+            .count() as u64;
+        let dynamic_relocs = absolute_relocs + self.imports.got.len() as u64;
+        let has_text = !self.imports.plt.is_empty() || self.inputs.contains(&Some(Kind::Text));
+        let phnum = if has_text { 6 } else { 5 };
+
+        let mut layout = Layout::default();
+        layout.place(EHDR_SIZE + phnum * PHDR_SIZE, 8);
+        let hash_size = 4 * (2 + self.dynsyms.nbucket() + self.dynsyms.len());
+        let hash_addr = layout.place(hash_size, 8);
+        let dynsym_addr = layout.place(self.dynsyms.len() * SYM_SIZE, 8);
+        let dynstr_addr = layout.place(dynstr.bytes.len() as u64, 1);
+        let rela_addr = layout.place(dynamic_relocs * RELA_SIZE, 8);
+        let mut addrs = vec![None; obj.sections.len()];
+        let mut ranges = vec![];
+        let rodata = self.place(&mut layout, &mut addrs, Kind::Rodata);
+        ranges.push((Kind::Rodata, rodata));
+        let r_end = layout.cursor;
+
+        layout.page();
+        let text = self.place(&mut layout, &mut addrs, Kind::Text);
+        ranges.push((Kind::Text, text));
+        let plt_size = self.imports.plt.len() as u64 * PLT_ENTRY_SIZE;
+        let plt_addr = layout.place(plt_size, 16);
+        let rx = (text.0, layout.cursor);
+
+        layout.page();
+        let rw_start = layout.cursor;
+        let relro = self.place(&mut layout, &mut addrs, Kind::RelRo);
+        ranges.push((Kind::RelRo, relro));
+        let got_size = self.imports.got.len() as u64 * 8;
+        let got_addr = layout.place(got_size, 8);
+        let dynamic_size = DYNAMIC_ENTRIES * DYN_SIZE;
+        let dynamic_addr = layout.place(dynamic_size, 8);
+        let relro_end = layout.cursor;
+        let data = self.place(&mut layout, &mut addrs, Kind::Data);
+        ranges.push((Kind::Data, data));
+        let file_end = layout.cursor;
+        let bss = self.place(&mut layout, &mut addrs, Kind::Bss);
+        ranges.push((Kind::Bss, bss));
+        let rw_end = layout.cursor;
+
+        let mut image = vec![0; file_end as usize];
+        for (idx, section) in obj.sections.iter().enumerate() {
+            if let (Some(addr), true) = (addrs[idx], section.ty == SHT_PROGBITS) {
+                let at = addr as usize;
+                image[at..at + section.data.len()].copy_from_slice(section.data);
+            }
+        }
+
+        let address = |sym_idx: u32| -> Result<Option<u64>, Error> {
+            let sym = &obj.symbols[sym_idx as usize];
+            symbol_address(sym, &addrs)
+        };
+
+        // relocations
+        let mut relative = vec![];
+        let mut symbolic = vec![];
+        for (idx, section) in obj.sections.iter().enumerate() {
+            let base = match addrs[idx] {
+                Some(base) if !obj.relocs[idx].is_empty() => base,
+                _ => continue,
+            };
+            for reloc in obj.relocs[idx].iter() {
+                let sym = &obj.symbols[reloc.sym as usize];
+                let width = match reloc.ty {
+                    R_X86_64_NONE => continue,
+                    R_X86_64_64 | R_X86_64_PC64 => 8,
+                    _ => 4,
+                };
+                if reloc.offset + width > section.size {
+                    bail!(
+                        "relocation against {} out of bounds in {}",
+                        sym.name,
+                        section.name
+                    );
+                }
+                let place = base + reloc.offset;
+                let at = place as usize;
+                let target = address(reloc.sym)?;
+                match reloc.ty {
+                    R_X86_64_64 => {
+                        let value = match target {
+                            Some(s) => {
+                                let value = (s as i64).wrapping_add(reloc.addend);
+                                relative.push(DynamicReloc {
+                                    offset: place,
+                                    ty: R_X86_64_RELATIVE,
+                                    sym: 0,
+                                    addend: value,
+                                });
+                                value
+                            }
+                            None => {
+                                symbolic.push(DynamicReloc {
+                                    offset: place,
+                                    ty: R_X86_64_64,
+                                    sym: self.dynamic_index(reloc.sym)?,
+                                    addend: reloc.addend,
+                                });
+                                reloc.addend
+                            }
+                        };
+                        LittleEndian::write_i64(&mut image[at..], value);
+                    }
+                    R_X86_64_PC64 => {
+                        let s = target.expect("PC64 relocations are against defined symbols");
+                        let value = (s as i64)
+                            .wrapping_add(reloc.addend)
+                            .wrapping_sub(place as i64);
+                        LittleEndian::write_i64(&mut image[at..], value);
+                    }
+                    ty => {
+                        let s = match (ty, target) {
+                            (R_X86_64_PC32, Some(s)) | (R_X86_64_PLT32, Some(s)) => s,
+                            (R_X86_64_PC32, None) | (R_X86_64_PLT32, None) => {
+                                plt_addr + self.imports.plt_index[&reloc.sym] * PLT_ENTRY_SIZE
+                            }
+                            _ => got_addr + self.imports.got_index[&reloc.sym] * 8,
+                        };
+                        let value = (s as i64)
+                            .wrapping_add(reloc.addend)
+                            .wrapping_sub(place as i64);
+                        if value as i32 as i64 != value {
+                            bail!(
+                                "relocation against {} out of range in {}",
+                                sym.name,
+                                section.name
+                            );
+                        }
+                        LittleEndian::write_i32(&mut image[at..], value as i32);
+                    }
+                }
+            }
+        }
+
+        // global offset table, which the loader fills in eagerly
+        for (slot, sym_idx) in self.imports.got.iter().enumerate() {
+            let entry = got_addr + slot as u64 * 8;
+            match address(*sym_idx)? {
+                Some(s) => {
+                    LittleEndian::write_u64(&mut image[entry as usize..], s);
+                    relative.push(DynamicReloc {
+                        offset: entry,
+                        ty: R_X86_64_RELATIVE,
+                        sym: 0,
+                        addend: s as i64,
+                    });
+                }
+                None => symbolic.push(DynamicReloc {
+                    offset: entry,
+                    ty: R_X86_64_GLOB_DAT,
+                    sym: self.dynamic_index(*sym_idx)?,
+                    addend: 0,
+                }),
+            }
+        }
+
+        // each stub jumps through the global offset table entry of its function
+        for (slot, sym_idx) in self.imports.plt.iter().enumerate() {
+            let entry = plt_addr + slot as u64 * PLT_ENTRY_SIZE;
+            let got_entry = got_addr + self.imports.got_index[sym_idx] * 8;
+            let at = entry as usize;
+            image[at..at + PLT_ENTRY_SIZE as usize].copy_from_slice(&[0xcc; 16]);
+            image[at..at + 2].copy_from_slice(&[0xff, 0x25]);
+            LittleEndian::write_i32(&mut image[at + 2..], (got_entry - (entry + 6)) as i32);
+        }
+
+        let relative_count = relative.len() as u64;
+        let mut rela_at = rela_addr as usize;
+        for reloc in relative.iter().chain(symbolic.iter()) {
+            LittleEndian::write_u64(&mut image[rela_at..], reloc.offset);
+            let info = (u64::from(reloc.sym) << 32) | u64::from(reloc.ty);
+            LittleEndian::write_u64(&mut image[rela_at + 8..], info);
+            LittleEndian::write_i64(&mut image[rela_at + 16..], reloc.addend);
+            rela_at += RELA_SIZE as usize;
+        }
+
+        // section headers, other than the ones for the symbol table, which come last
+        let mut headers = vec![SectionHeader::null()];
+        let hash = SectionHeader::alloc(".hash", SHT_HASH, SHF_ALLOC, hash_addr, hash_size, 8);
+        headers.push(hash.with_link(".dynsym", 0, 4));
+        let dynsym_size = self.dynsyms.len() * SYM_SIZE;
+        let dynsym = SectionHeader::alloc(
+            ".dynsym",
+            SHT_DYNSYM,
+            SHF_ALLOC,
+            dynsym_addr,
+            dynsym_size,
+            8,
+        );
+        headers.push(dynsym.with_link(".dynstr", 1, SYM_SIZE));
+        let dynstr_size = dynstr.bytes.len() as u64;
+        headers.push(SectionHeader::alloc(
+            ".dynstr",
+            SHT_STRTAB,
+            SHF_ALLOC,
+            dynstr_addr,
+            dynstr_size,
+            1,
+        ));
+        let rela_size = dynamic_relocs * RELA_SIZE;
+        let rela = SectionHeader::alloc(".rela.dyn", SHT_RELA, SHF_ALLOC, rela_addr, rela_size, 8);
+        headers.push(rela.with_link(".dynsym", 0, RELA_SIZE));
+        for (kind, (start, end)) in ranges.iter().cloned() {
+            if end > start {
+                let ty = if kind == Kind::Bss {
+                    SHT_NOBITS
+                } else {
+                    SHT_PROGBITS
+                };
+                headers.push(SectionHeader::alloc(
+                    kind.section_name(),
+                    ty,
+                    kind.section_flags(),
+                    start,
+                    end - start,
+                    16,
+                ));
+            }
+            if kind == Kind::Text && plt_size > 0 {
+                let flags = SHF_ALLOC | SHF_EXECINSTR;
+                headers.push(SectionHeader::alloc(
+                    ".plt",
+                    SHT_PROGBITS,
+                    flags,
+                    plt_addr,
+                    plt_size,
+                    16,
+                ));
+            }
+            if kind == Kind::RelRo {
+                let flags = SHF_ALLOC | SHF_WRITE;
+                if got_size > 0 {
+                    headers.push(SectionHeader::alloc(
+                        ".got",
+                        SHT_PROGBITS,
+                        flags,
+                        got_addr,
+                        got_size,
+                        8,
+                    ));
+                }
+                let dynamic = SectionHeader::alloc(
+                    ".dynamic",
+                    SHT_DYNAMIC,
+                    flags,
+                    dynamic_addr,
+                    dynamic_size,
+                    8,
+                );
+                headers.push(dynamic.with_link(".dynstr", 0, DYN_SIZE));
+            }
+        }
+        let section_index = |headers: &[SectionHeader], kind: Kind| -> u16 {
+            headers
+                .iter()
+                .position(|h| h.name == kind.section_name())
+                .expect("section with symbols has a header") as u16
+        };
+        let output_shndx = |sym: &Symbol, headers: &[SectionHeader]| -> u16 {
+            match sym.section {
+                SymbolSection::Index(idx) => {
+                    let kind = self.inputs[idx as usize].expect("symbol is allocated");
+                    section_index(headers, kind)
+                }
+                SymbolSection::Absolute => SHN_ABS,
+                _ => SHN_UNDEF,
+            }
+        };
+
+        // dynamic symbols, and the hash table to look them up by
+        let mut dynsym_at = (dynsym_addr + SYM_SIZE) as usize;
+        for (sym_idx, name) in self.dynsyms.symbols.iter().zip(dynsym_names.iter()) {
+            let sym = &obj.symbols[*sym_idx as usize];
+            let value = address(*sym_idx)?.unwrap_or(0);
+            let shndx = output_shndx(sym, &headers);
+            write_symbol(&mut image[dynsym_at..], *name, sym, shndx, value);
+            dynsym_at += SYM_SIZE as usize;
+        }
+        let nbucket = self.dynsyms.nbucket();
+        let mut buckets = vec![0u32; nbucket as usize];
+        let mut chains = vec![0u32; self.dynsyms.len() as usize];
+        for (i, sym_idx) in self.dynsyms.symbols.iter().enumerate() {
+            let dynidx = i + 1;
+            let bucket = sysv_hash(&obj.symbols[*sym_idx as usize].name) as u64 % nbucket;
+            chains[dynidx] = buckets[bucket as usize];
+            buckets[bucket as usize] = dynidx as u32;
+        }
+        let mut hash_at = hash_addr as usize;
+        for word in [nbucket as u32, self.dynsyms.len() as u32]
+            .iter()
+            .chain(buckets.iter())
+            .chain(chains.iter())
+        {
+            LittleEndian::write_u32(&mut image[hash_at..], *word);
+            hash_at += 4;
+        }
+        let dynstr_at = dynstr_addr as usize;
+        image[dynstr_at..dynstr_at + dynstr.bytes.len()].copy_from_slice(&dynstr.bytes);
+
+        let dynamic = [
+            (DT_HASH, hash_addr),
+            (DT_STRTAB, dynstr_addr),
+            (DT_SYMTAB, dynsym_addr),
+            (DT_STRSZ, dynstr_size),
+            (DT_SYMENT, SYM_SIZE),
+            (DT_RELA, rela_addr),
+            (DT_RELASZ, rela_size),
+            (DT_RELAENT, RELA_SIZE),
+            (DT_RELACOUNT, relative_count),
+            (DT_NULL, 0),
+        ];
+        let mut dynamic_at = dynamic_addr as usize;
+        for (tag, value) in dynamic.iter() {
+            LittleEndian::write_u64(&mut image[dynamic_at..], *tag);
+            LittleEndian::write_u64(&mut image[dynamic_at + 8..], *value);
+            dynamic_at += DYN_SIZE as usize;
+        }
+
+        // the full symbol table, with locals first, for debuggers and profilers
+        let mut strtab = StringTable::new();
+        let mut symtab = vec![0; SYM_SIZE as usize];
+        let mut first_global = 1;
+        for globals in [false, true].iter() {
+            for (idx, sym) in obj.symbols.iter().enumerate().skip(1) {
+                let named = sym.ty() != STT_SECTION && sym.ty() != STT_FILE && !sym.name.is_empty();
+                let allocated = symbol_address(sym, &addrs).is_ok();
+                if sym.is_global() != *globals || !named || !allocated {
+                    continue;
+                }
+                let name = strtab.add(&sym.name);
+                let value = address(idx as u32)?.unwrap_or(0);
+                let mut entry = [0; SYM_SIZE as usize];
+                write_symbol(&mut entry, name, sym, output_shndx(sym, &headers), value);
+                symtab.extend_from_slice(&entry);
+                if !*globals {
+                    first_global += 1;
+                }
+            }
+        }
+
+        let symtab_offset = append(&mut image, &symtab);
+        let symtab_size = symtab.len() as u64;
+        let symtab = SectionHeader::unloaded(".symtab", SHT_SYMTAB, symtab_offset, symtab_size);
+        headers.push(symtab.with_link(".strtab", first_global, SYM_SIZE));
+        let strtab_offset = append(&mut image, &strtab.bytes);
+        let strtab_size = strtab.bytes.len() as u64;
+        headers.push(SectionHeader::unloaded(
+            ".strtab",
+            SHT_STRTAB,
+            strtab_offset,
+            strtab_size,
+        ));
+        let mut shstrtab = StringTable::new();
+        let mut header_names = headers
+            .iter()
+            .map(|h| shstrtab.add(h.name))
+            .collect::<Vec<_>>();
+        header_names.push(shstrtab.add(".shstrtab"));
+        let shstrtab_offset = append(&mut image, &shstrtab.bytes);
+        let shstrtab_size = shstrtab.bytes.len() as u64;
+        headers.push(SectionHeader::unloaded(
+            ".shstrtab",
+            SHT_STRTAB,
+            shstrtab_offset,
+            shstrtab_size,
+        ));
+
+        let shoff = align_to(image.len() as u64, 8);
+        image.resize(shoff as usize, 0);
+        for (i, header) in headers.iter().enumerate() {
+            let mut entry = [0; SHDR_SIZE as usize];
+            let name = header_names[i];
+            let link = if header.link.is_empty() {
+                0
+            } else {
+                headers
+                    .iter()
+                    .position(|h| h.name == header.link)
+                    .expect("linked section exists") as u32
+            };
+            LittleEndian::write_u32(&mut entry[0..], name);
+            LittleEndian::write_u32(&mut entry[4..], header.ty);
+            LittleEndian::write_u64(&mut entry[8..], header.flags);
+            LittleEndian::write_u64(&mut entry[16..], header.addr);
+            LittleEndian::write_u64(&mut entry[24..], header.offset);
+            LittleEndian::write_u64(&mut entry[32..], header.size);
+            LittleEndian::write_u32(&mut entry[40..], link);
+            LittleEndian::write_u32(&mut entry[44..], header.info);
+            LittleEndian::write_u64(&mut entry[48..], header.addralign);
+            LittleEndian::write_u64(&mut entry[56..], header.entsize);
+            image.extend_from_slice(&entry);
+        }
+
+        // ELF and program headers
+        let mut segments = vec![(PT_LOAD, PF_R, 0, r_end, r_end, PAGE_SIZE)];
+        if has_text {
+            segments.push((
+                PT_LOAD,
+                PF_R | PF_X,
+                rx.0,
+                rx.1 - rx.0,
+                rx.1 - rx.0,
+                PAGE_SIZE,
+            ));
+        }
+        segments.push((
+            PT_LOAD,
+            PF_R | PF_W,
+            rw_start,
+            file_end - rw_start,
+            rw_end - rw_start,
+            PAGE_SIZE,
+        ));
+        segments.push((
+            PT_DYNAMIC,
+            PF_R | PF_W,
+            dynamic_addr,
+            dynamic_size,
+            dynamic_size,
+            8,
+        ));
+        let relro_size = relro_end - rw_start;
+        segments.push((PT_GNU_RELRO, PF_R, rw_start, relro_size, relro_size, 1));
+        segments.push((PT_GNU_STACK, PF_R | PF_W, 0, 0, 0, 16));
+        debug_assert_eq!(segments.len() as u64, phnum);
+
+        image[0..16].copy_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+        LittleEndian::write_u16(&mut image[16..], ET_DYN);
+        LittleEndian::write_u16(&mut image[18..], EM_X86_64);
+        LittleEndian::write_u32(&mut image[20..], 1);
+        LittleEndian::write_u64(&mut image[24..], 0);
+        LittleEndian::write_u64(&mut image[32..], EHDR_SIZE);
+        LittleEndian::write_u64(&mut image[40..], shoff);
+        LittleEndian::write_u32(&mut image[48..], 0);
+        LittleEndian::write_u16(&mut image[52..], EHDR_SIZE as u16);
+        LittleEndian::write_u16(&mut image[54..], PHDR_SIZE as u16);
+        LittleEndian::write_u16(&mut image[56..], phnum as u16);
+        LittleEndian::write_u16(&mut image[58..], SHDR_SIZE as u16);
+        LittleEndian::write_u16(&mut image[60..], headers.len() as u16);
+        LittleEndian::write_u16(&mut image[62..], headers.len() as u16 - 1);
+        let mut phdr_at = EHDR_SIZE as usize;
+        for (ty, flags, addr, filesz, memsz, align) in segments {
+            let phdr = &mut image[phdr_at..];
+            LittleEndian::write_u32(&mut phdr[0..], ty);
+            LittleEndian::write_u32(&mut phdr[4..], flags);
+            LittleEndian::write_u64(&mut phdr[8..], addr);
+            LittleEndian::write_u64(&mut phdr[16..], addr);
+            LittleEndian::write_u64(&mut phdr[24..], addr);
+            LittleEndian::write_u64(&mut phdr[32..], filesz);
+            LittleEndian::write_u64(&mut phdr[40..], memsz);
+            LittleEndian::write_u64(&mut phdr[48..], align);
+            phdr_at += PHDR_SIZE as usize;
+        }
+
+        Ok(image)
+    }
+
+    /// Lay out the sections of the object that go in `kind`, returning where they start and end.
+    fn place(&self, layout: &mut Layout, addrs: &mut [Option<u64>], kind: Kind) -> (u64, u64) {
+        layout.place(0, 16);
+        let start = layout.cursor;
+        for (idx, section) in self.obj.sections.iter().enumerate() {
+            if self.inputs[idx] == Some(kind) {
+                addrs[idx] = Some(layout.place(section.size, section.addralign));
+            }
+        }
+        (start, layout.cursor)
+    }
+
+    fn dynamic_index(&self, sym_idx: u32) -> Result<u32, Error> {
+        self.dynsyms.index.get(&sym_idx).cloned().ok_or_else(|| {
+            format_err!(
+                "relocation against {} needs it to be a dynamic symbol",
+                self.obj.symbols[sym_idx as usize].name
+            )
+        })
+    }
+}
+
+/// Append the contents of a section that is not loaded to the end of the file, returning its
+/// offset.
+fn append(image: &mut Vec<u8>, data: &[u8]) -> u64 {
+    let offset = align_to(image.len() as u64, 8);
+    image.resize(offset as usize, 0);
+    image.extend_from_slice(data);
+    offset
+}
+
+/// The address of a symbol in the shared object, or `None` if it is undefined.
+fn symbol_address(sym: &Symbol, addrs: &[Option<u64>]) -> Result<Option<u64>, Error> {
+    match sym.section {
+        SymbolSection::Undefined => Ok(None),
+        SymbolSection::Absolute => Ok(Some(sym.value)),
+        SymbolSection::Common => bail!("common symbol {} is not supported", sym.name),
+        SymbolSection::Index(idx) => match addrs.get(idx as usize) {
+            Some(Some(base)) => Ok(Some(base + sym.value)),
+            _ => bail!("symbol {} is not in an allocated section", sym.name),
+        },
+    }
+}
+
+fn write_symbol(entry: &mut [u8], name: u32, sym: &Symbol, shndx: u16, value: u64) {
+    LittleEndian::write_u32(&mut entry[0..], name);
+    entry[4] = sym.info;
+    entry[5] = sym.other;
+    LittleEndian::write_u16(&mut entry[6..], shndx);
+    LittleEndian::write_u64(&mut entry[8..], value);
+    LittleEndian::write_u64(&mut entry[16..], sym.size);
+}
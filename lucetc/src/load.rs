@@ -1,18 +1,38 @@
+use crate::lower::lower_post_mvp;
+use crate::validate::validate_code;
 use failure::*;
 use parity_wasm::deserialize_buffer;
 pub use parity_wasm::elements::Module;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
-use wabt::wat2wasm;
+use wabt::{wat2wasm_with_features, Features};
 
 pub fn read_module<P: AsRef<Path>>(path: P) -> Result<Module, Error> {
     let contents = read_to_u8s(path)?;
     let wasm = if wasm_preamble(&contents) {
         contents
     } else {
-        wat2wasm(contents)?
+        let mut features = Features::new();
+        features.enable_sign_extension();
+        features.enable_sat_float_to_int();
+        features.enable_bulk_memory();
+        features.enable_multi_value();
+        features.enable_simd();
+        features.enable_threads();
+        features.enable_reference_types();
+        wat2wasm_with_features(contents, features)?
     };
+    read_module_buffer(&wasm)
+}
+
+/// Read a binary module, validating its function bodies and lowering the operators beyond the MVP
+/// that `lucetc` supports.
+pub fn read_module_buffer(wasm: &[u8]) -> Result<Module, Error> {
+    // type-check the function bodies before anything relies on them being valid
+    validate_code(wasm)?;
+    // `parity_wasm` only understands MVP operators, so rewrite the ones we support beyond that
+    let wasm = lower_post_mvp(wasm)?;
     let module_res = deserialize_buffer(&wasm);
     module_res.map_err(|e| format_err!("deserializing wasm module: {}", e))
 }
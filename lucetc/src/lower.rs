@@ -0,0 +1,2448 @@
+//! Lowering of post-MVP operators that `parity_wasm` cannot represent.
+//!
+//! This runs over the binary encoding of a module before it is deserialized, and rewrites the
+//! operators of the sign-extension, non-trapping float-to-int conversion, and bulk memory
+//! proposals into MVP code with the same semantics:
+//!
+//! - `i32.extend8_s` and friends become a pair of shifts, or a wrap and extend for
+//!   `i64.extend32_s`.
+//!
+//! - The `0xfc`-prefixed operators become calls to helper functions appended to the module.
+//!   Appending defined functions does not change the index of any existing function, so no other
+//!   section needs to be renumbered.
+//!
+//!   The helpers for `*.trunc_sat_*` check for NaN and out-of-range inputs before using the
+//!   trapping conversion. The helpers for the bulk memory and table operators take their segment
+//!   index as an extra parameter, and are never run: the compiler turns calls to them into calls
+//!   to the runtime, as listed in the `lucet_runtime_calls` custom section.
+//!
+//! - Passive data and element segments are removed from their sections, and recorded in the
+//!   `lucet_passive_data` and `lucet_passive_elements` custom sections instead. Active segments
+//!   using the explicit index encodings are rewritten to the MVP encoding, declarative element
+//!   segments are dropped, and so is the data count section.
+//!
+//! - Multiple values are passed through mutable globals appended to the module, one for each
+//!   value type and position in a sequence of values. Function types with several results are
+//!   rewritten to have none: the function stores its results into the globals before returning,
+//!   and its callers load them back right after the call. Blocks with a function type index as
+//!   their block type pass their parameters in the same way, as well as their results when there
+//!   are several. The result globals of each function are recorded in the `lucet_multi_value`
+//!   custom section, so the embedder can read them after calling an export.
+//!
+//! - The `0xfe`-prefixed atomic operators become calls to helpers as well. Each helper checks that
+//!   the effective address of the access is in bounds, with a plain load of the same width, and
+//!   that it is naturally aligned, and then calls one of the atomic runtime functions, which stand
+//!   behind helpers of their own. Shared memories are rewritten to unshared ones, and the
+//!   `lucet_shared_memory` custom section records that the memory was shared.
+//!
+//! - Reference types become `i64` wherever a value type appears: in function types, locals, block
+//!   types, globals, and the typed `select`. A `funcref` value is a handle that the runtime hands
+//!   out for a function, and an `externref` value is whatever the host passed in; zero is the null
+//!   reference of both. `ref.null` and `ref.is_null` become `i64.const 0` and `i64.eqz`, while
+//!   `ref.func` and the table operators become calls to helpers, with the table index passed as an
+//!   extra parameter, as the bulk table operators are.
+//!
+//! - Only table 0 is left in the module, as a `funcref` table. The element type of every table and
+//!   the declarations of the others are recorded in the `lucet_tables` custom section, along with
+//!   the active element segments that the MVP encoding cannot express, which are removed from the
+//!   element section. An indirect call through another table uses a copy of its type appended to
+//!   the type section, which the `lucet_indirect_calls` custom section maps back to the original
+//!   type and the table. The functions that `ref.func` refers to are listed in the
+//!   `lucet_ref_funcs` custom section.
+//!
+//! The `v128` type and the `0xfd`-prefixed SIMD operators are rejected here with an error, wherever
+//! they appear. Cranelift 0.29, which lucetc uses, has vector types in its IR but cannot compile
+//! them for x86-64: it has no encodings or legalizations for vector instructions, and its calling
+//! convention splits vector arguments into scalars. SIMD support needs a newer Cranelift.
+//!
+//! Modules that use none of these features are returned unchanged.
+
+use failure::{bail, format_err, Error};
+use lucet_module_data::RefType;
+use parity_wasm::elements::{External, Module, Section, ValueType};
+use std::collections::HashMap;
+
+pub(crate) const SECTION_CUSTOM: u8 = 0;
+pub(crate) const SECTION_TYPE: u8 = 1;
+pub(crate) const SECTION_IMPORT: u8 = 2;
+pub(crate) const SECTION_FUNCTION: u8 = 3;
+pub(crate) const SECTION_TABLE: u8 = 4;
+pub(crate) const SECTION_MEMORY: u8 = 5;
+pub(crate) const SECTION_GLOBAL: u8 = 6;
+const SECTION_EXPORT: u8 = 7;
+pub(crate) const SECTION_ELEMENT: u8 = 9;
+pub(crate) const SECTION_CODE: u8 = 10;
+pub(crate) const SECTION_DATA: u8 = 11;
+pub(crate) const SECTION_DATA_COUNT: u8 = 12;
+
+const RUNTIME_CALLS_SECTION: &str = "lucet_runtime_calls";
+const PASSIVE_DATA_SECTION: &str = "lucet_passive_data";
+const PASSIVE_ELEMENTS_SECTION: &str = "lucet_passive_elements";
+const MULTI_VALUE_SECTION: &str = "lucet_multi_value";
+const SHARED_MEMORY_SECTION: &str = "lucet_shared_memory";
+const TABLES_SECTION: &str = "lucet_tables";
+const REF_FUNCS_SECTION: &str = "lucet_ref_funcs";
+const INDIRECT_CALLS_SECTION: &str = "lucet_indirect_calls";
+
+pub(crate) const I32: u8 = 0x7f;
+pub(crate) const I64: u8 = 0x7e;
+pub(crate) const F32: u8 = 0x7d;
+pub(crate) const F64: u8 = 0x7c;
+pub(crate) const V128: u8 = 0x7b;
+pub(crate) const FUNCREF: u8 = 0x70;
+pub(crate) const EXTERNREF: u8 = 0x6f;
+
+/// Rewrite the post-MVP operators in a binary module into MVP code.
+pub fn lower_post_mvp(wasm: &[u8]) -> Result<Vec<u8>, Error> {
+    if wasm.len() < 8 {
+        bail!("module is too short to contain a header");
+    }
+    let mut sections = vec![];
+    let mut reader = Reader::new(&wasm[8..]);
+    while !reader.is_empty() {
+        let id = reader.byte()?;
+        let len = reader.uleb()? as usize;
+        sections.push((id, reader.bytes(len)?));
+    }
+
+    let lowered_imports = match section(&sections, SECTION_IMPORT) {
+        Some(imports) => lower_import_section(imports)?,
+        None => LoweredImports::default(),
+    };
+    let memory = match section(&sections, SECTION_MEMORY) {
+        Some(memory) => lower_memory_section(memory)?,
+        None => None,
+    };
+    let shared_memory = lowered_imports.shared_memory || memory.is_some();
+    let mut tables = lowered_imports.tables;
+    let table_section = match section(&sections, SECTION_TABLE) {
+        Some(table_section) => lower_table_section(table_section, &mut tables)?,
+        None => None,
+    };
+    let exports = match section(&sections, SECTION_EXPORT) {
+        Some(exports) => lower_export_section(exports, &mut tables)?,
+        None => None,
+    };
+    let lowered_globals = match section(&sections, SECTION_GLOBAL) {
+        Some(globals) => lower_global_section(globals)?,
+        None => None,
+    };
+
+    let imports = module_imports(&sections)?;
+    let imported = imports.functions.len() as u32;
+    let (types, ref_types) = func_types(&sections)?;
+    let type_count = types.len();
+    let mut func_type_indices = imports.functions;
+    if let Some(funcs) = section(&sections, SECTION_FUNCTION) {
+        func_type_indices.extend(Reader::new(funcs).indices()?);
+    }
+    let defined_globals = match section(&sections, SECTION_GLOBAL) {
+        Some(globals) => Reader::new(globals).uleb()?,
+        None => 0,
+    };
+    let mut ctx = CodeContext {
+        helper_base: func_type_indices.len() as u32,
+        imported,
+        types,
+        func_type_indices,
+        global_base: imports.globals + defined_globals,
+        result_globals: vec![],
+        result_global_indices: HashMap::new(),
+        shared_memory,
+        uses_helpers: false,
+        uses_atomics: false,
+        ref_funcs: vec![],
+        ref_func_indices: HashMap::new(),
+        indirect_types: vec![],
+    };
+    for func in 0..imported {
+        if ctx.func_type(func)?.results.len() > 1 {
+            bail!(
+                "imported function {} has multiple results, which is not supported",
+                func
+            );
+        }
+    }
+
+    let code = match section(&sections, SECTION_CODE) {
+        Some(code) => lower_code_section(code, &mut ctx)?,
+        None => None,
+    };
+    let mut multi_value = vec![];
+    for func in imported..ctx.helper_base {
+        let results = ctx.func_type(func)?.results.clone();
+        if results.len() > 1 {
+            let globals = (0..results.len())
+                .map(|position| ctx.result_global(position, results[position]))
+                .collect::<Vec<_>>();
+            multi_value.push((func, results, globals));
+        }
+    }
+    let lower_types = ref_types
+        || ctx.types.len() > type_count
+        || ctx.types.iter().any(|ty| ty.results.len() > 1);
+    let data = match section(&sections, SECTION_DATA) {
+        Some(data) => lower_data_section(data)?,
+        None => None,
+    };
+    let elements = match section(&sections, SECTION_ELEMENT) {
+        Some(elements) => lower_element_section(elements)?,
+        None => None,
+    };
+    let segments = match elements {
+        Some((_, _, ref segments)) => segments.as_slice(),
+        None => &[],
+    };
+    let lower_tables = tables.len() > 1
+        || tables
+            .iter()
+            .any(|table| table.elem_type != RefType::FuncRef)
+        || !segments.is_empty();
+    let has_data_count = section(&sections, SECTION_DATA_COUNT).is_some();
+    if code.is_none()
+        && data.is_none()
+        && elements.is_none()
+        && !has_data_count
+        && !lower_types
+        && lowered_imports.payload.is_none()
+        && memory.is_none()
+        && table_section.is_none()
+        && exports.is_none()
+        && lowered_globals.is_none()
+    {
+        return Ok(wasm.to_vec());
+    }
+    let uses_helpers = ctx.uses_helpers;
+    let helpers = ctx.helpers();
+    let mut globals = if ctx.result_globals.is_empty() {
+        lowered_globals
+    } else {
+        let payload = match lowered_globals {
+            Some(ref globals) => Some(globals.as_slice()),
+            None => section(&sections, SECTION_GLOBAL),
+        };
+        Some(append_result_globals(payload, &ctx.result_globals)?)
+    };
+
+    let mut out = wasm[..8].to_vec();
+    for (id, payload) in sections.iter() {
+        // the global section goes before all the known sections with a greater id, which is only
+        // a concern when it has to be added
+        if *id > SECTION_GLOBAL {
+            if let Some(globals) = globals.take() {
+                write_section(&mut out, SECTION_GLOBAL, &globals);
+            }
+        }
+        let payload = match (*id, &code, &data, &elements) {
+            (SECTION_TYPE, _, _, _) if uses_helpers || lower_types => {
+                lower_type_section(&ctx.types, &helpers)
+            }
+            (SECTION_IMPORT, _, _, _) => match lowered_imports.payload {
+                Some(ref imports) => imports.clone(),
+                None => payload.to_vec(),
+            },
+            (SECTION_FUNCTION, _, _, _) if uses_helpers => {
+                append_helper_funcs(payload, ctx.types.len(), helpers.len())?
+            }
+            (SECTION_TABLE, _, _, _) => match table_section {
+                Some(ref table_section) => table_section.clone(),
+                None => payload.to_vec(),
+            },
+            (SECTION_MEMORY, _, _, _) => match memory {
+                Some(ref memory) => memory.clone(),
+                None => payload.to_vec(),
+            },
+            (SECTION_GLOBAL, _, _, _) => match globals.take() {
+                Some(globals) => globals,
+                None => payload.to_vec(),
+            },
+            (SECTION_EXPORT, _, _, _) => match exports {
+                Some(ref exports) => exports.clone(),
+                None => payload.to_vec(),
+            },
+            (SECTION_CODE, Some(code), _, _) => code.clone(),
+            (SECTION_DATA, _, Some((data, _)), _) => data.clone(),
+            (SECTION_ELEMENT, _, _, Some((elements, _, _))) => elements.clone(),
+            (SECTION_DATA_COUNT, _, _, _) => continue,
+            _ => payload.to_vec(),
+        };
+        write_section(&mut out, *id, &payload);
+    }
+    if let Some(globals) = globals {
+        write_section(&mut out, SECTION_GLOBAL, &globals);
+    }
+
+    if uses_helpers {
+        let mut calls = vec![];
+        write_uleb(&mut calls, u64::from(imported));
+        let runtime_calls = helpers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, helper)| helper.runtime_call().map(|symbol| (i, symbol)))
+            .collect::<Vec<_>>();
+        write_uleb(&mut calls, runtime_calls.len() as u64);
+        for (i, symbol) in runtime_calls {
+            write_uleb(&mut calls, u64::from(ctx.helper_base) + i as u64);
+            write_name(&mut calls, symbol);
+        }
+        write_custom_section(&mut out, RUNTIME_CALLS_SECTION, &calls);
+    }
+    if let Some((_, ref passive)) = data {
+        if !passive.is_empty() {
+            let mut payload = vec![];
+            write_uleb(&mut payload, passive.len() as u64);
+            for segment in passive {
+                write_uleb(&mut payload, u64::from(segment.index));
+                write_uleb(&mut payload, segment.data.len() as u64);
+                payload.extend_from_slice(&segment.data);
+            }
+            write_custom_section(&mut out, PASSIVE_DATA_SECTION, &payload);
+        }
+    }
+    if let Some((_, ref passive, _)) = elements {
+        if !passive.is_empty() {
+            let mut payload = vec![];
+            write_uleb(&mut payload, u64::from(imported));
+            write_uleb(&mut payload, passive.len() as u64);
+            for segment in passive {
+                write_uleb(&mut payload, u64::from(segment.index));
+                write_elements(&mut payload, &segment.functions);
+            }
+            write_custom_section(&mut out, PASSIVE_ELEMENTS_SECTION, &payload);
+        }
+    }
+    if !multi_value.is_empty() {
+        let mut payload = vec![];
+        write_uleb(&mut payload, u64::from(imported));
+        write_uleb(&mut payload, multi_value.len() as u64);
+        for (func, results, globals) in multi_value {
+            write_uleb(&mut payload, u64::from(func));
+            write_uleb(&mut payload, results.len() as u64);
+            for (ty, global) in results.iter().zip(globals) {
+                payload.push(*ty);
+                write_uleb(&mut payload, u64::from(global));
+            }
+        }
+        write_custom_section(&mut out, MULTI_VALUE_SECTION, &payload);
+    }
+    if shared_memory {
+        write_custom_section(&mut out, SHARED_MEMORY_SECTION, &[]);
+    }
+    if lower_tables {
+        let mut payload = vec![];
+        write_uleb(&mut payload, u64::from(imported));
+        write_uleb(&mut payload, tables.len() as u64);
+        for table in tables.iter() {
+            payload.push(match table.elem_type {
+                RefType::FuncRef => FUNCREF,
+                RefType::ExternRef => EXTERNREF,
+            });
+            write_limits(&mut payload, table.min_size, table.max_size);
+            match table.import {
+                Some((ref module, ref field)) => {
+                    payload.push(0x01);
+                    write_name(&mut payload, module);
+                    write_name(&mut payload, field);
+                }
+                None => payload.push(0x00),
+            }
+            match table.export {
+                Some(ref name) => {
+                    payload.push(0x01);
+                    write_name(&mut payload, name);
+                }
+                None => payload.push(0x00),
+            }
+        }
+        write_uleb(&mut payload, segments.len() as u64);
+        for segment in segments {
+            write_uleb(&mut payload, u64::from(segment.table));
+            write_sleb(&mut payload, i64::from(segment.offset));
+            write_elements(&mut payload, &segment.functions);
+        }
+        write_custom_section(&mut out, TABLES_SECTION, &payload);
+    }
+    if !ctx.ref_funcs.is_empty() {
+        let mut payload = vec![];
+        write_uleb(&mut payload, u64::from(imported));
+        write_uleb(&mut payload, ctx.ref_funcs.len() as u64);
+        for func in ctx.ref_funcs.iter() {
+            write_uleb(&mut payload, u64::from(*func));
+        }
+        write_custom_section(&mut out, REF_FUNCS_SECTION, &payload);
+    }
+    if !ctx.indirect_types.is_empty() {
+        let mut payload = vec![];
+        write_uleb(&mut payload, ctx.indirect_types.len() as u64);
+        for (index, original, table) in ctx.indirect_types.iter() {
+            write_uleb(&mut payload, u64::from(*index));
+            write_uleb(&mut payload, u64::from(*original));
+            write_uleb(&mut payload, u64::from(*table));
+        }
+        write_custom_section(&mut out, INDIRECT_CALLS_SECTION, &payload);
+    }
+    Ok(out)
+}
+
+/// A passive data segment, removed from the data section by lowering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassiveData {
+    /// The index of the segment in the original data section.
+    pub index: u32,
+    pub data: Vec<u8>,
+}
+
+/// A passive element segment, removed from the element section by lowering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassiveElements {
+    /// The index of the segment in the original element section.
+    pub index: u32,
+    /// The function index of each element, or `None` for null elements.
+    pub functions: Vec<Option<u32>>,
+}
+
+/// A table of the original module, whether imported or defined.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoweredTable {
+    pub elem_type: RefType,
+    pub min_size: u32,
+    pub max_size: Option<u32>,
+    /// The module and field names of an imported table.
+    pub import: Option<(String, String)>,
+    pub export: Option<String>,
+}
+
+/// An active element segment that the MVP encoding cannot express, removed from the element
+/// section by lowering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableSegment {
+    pub table: u32,
+    pub offset: i32,
+    /// The function index of each element, or `None` for null elements.
+    pub functions: Vec<Option<u32>>,
+}
+
+/// What lowering recorded in the custom sections of a module.
+#[derive(Clone, Debug, Default)]
+pub struct Lowered {
+    /// Helper functions whose calls must be compiled as calls to the named runtime function.
+    pub runtime_calls: HashMap<u32, String>,
+    pub passive_data: Vec<PassiveData>,
+    pub passive_elements: Vec<PassiveElements>,
+    /// The original result types of the functions with several results, and the index of the
+    /// global each result is passed through.
+    pub multi_value: HashMap<u32, Vec<(ValueType, u32)>>,
+    /// Whether the memory of the module, defined or imported, was declared shared.
+    pub shared_memory: bool,
+    /// Every table of the module, imported tables first. Empty if table 0 is the only table and
+    /// holds `funcref` values, in which case the module describes it completely.
+    pub tables: Vec<LoweredTable>,
+    pub table_segments: Vec<TableSegment>,
+    /// The functions that `ref.func` refers to, in the order of the handles passed to
+    /// `lucet_vmctx_ref_func`.
+    pub ref_funcs: Vec<u32>,
+    /// The type and table of each indirect call through a table other than table 0, keyed by the
+    /// type index that the lowered call uses.
+    pub indirect_calls: HashMap<u32, (u32, u32)>,
+}
+
+impl Lowered {
+    /// Read back the custom sections written by `lower_post_mvp`.
+    ///
+    /// The sections that refer to functions also record how many functions the module imported
+    /// when it was lowered. If imports were prepended since then, as the builtins patcher does,
+    /// the function indices are shifted to match.
+    pub fn from_module(module: &Module) -> Result<Self, Error> {
+        let imported = module
+            .import_section()
+            .map(|imports| {
+                imports
+                    .entries()
+                    .iter()
+                    .filter(|entry| match entry.external() {
+                        External::Function(_) => true,
+                        _ => false,
+                    })
+                    .count() as u32
+            })
+            .unwrap_or(0);
+        let mut lowered = Lowered::default();
+        for section in module.sections() {
+            let custom = match section {
+                Section::Custom(custom) => custom,
+                _ => continue,
+            };
+            let mut reader = Reader::new(custom.payload());
+            match custom.name() {
+                RUNTIME_CALLS_SECTION => {
+                    let shift = import_shift(&mut reader, imported)?;
+                    for _ in 0..reader.uleb()? {
+                        let func = reader.uleb()? + shift;
+                        let symbol = reader.name()?;
+                        lowered.runtime_calls.insert(func, symbol);
+                    }
+                }
+                PASSIVE_DATA_SECTION => {
+                    for _ in 0..reader.uleb()? {
+                        let index = reader.uleb()?;
+                        let len = reader.uleb()? as usize;
+                        let data = reader.bytes(len)?.to_vec();
+                        lowered.passive_data.push(PassiveData { index, data });
+                    }
+                }
+                PASSIVE_ELEMENTS_SECTION => {
+                    let shift = import_shift(&mut reader, imported)?;
+                    for _ in 0..reader.uleb()? {
+                        let index = reader.uleb()?;
+                        let functions = reader.elements(shift)?;
+                        lowered
+                            .passive_elements
+                            .push(PassiveElements { index, functions });
+                    }
+                }
+                MULTI_VALUE_SECTION => {
+                    let shift = import_shift(&mut reader, imported)?;
+                    for _ in 0..reader.uleb()? {
+                        let func = reader.uleb()? + shift;
+                        let results = (0..reader.uleb()?)
+                            .map(|_| {
+                                let ty = reader.value_type()?;
+                                let global = reader.uleb()?;
+                                Ok((ty, global))
+                            })
+                            .collect::<Result<_, Error>>()?;
+                        lowered.multi_value.insert(func, results);
+                    }
+                }
+                SHARED_MEMORY_SECTION => lowered.shared_memory = true,
+                TABLES_SECTION => {
+                    let shift = import_shift(&mut reader, imported)?;
+                    for _ in 0..reader.uleb()? {
+                        let (elem_type, min_size, max_size) = reader.table_type()?;
+                        let import = match reader.byte()? {
+                            0x00 => None,
+                            _ => Some((reader.name()?, reader.name()?)),
+                        };
+                        let export = match reader.byte()? {
+                            0x00 => None,
+                            _ => Some(reader.name()?),
+                        };
+                        lowered.tables.push(LoweredTable {
+                            elem_type,
+                            min_size,
+                            max_size,
+                            import,
+                            export,
+                        });
+                    }
+                    for _ in 0..reader.uleb()? {
+                        let table = reader.uleb()?;
+                        let offset = reader.sleb()?;
+                        let functions = reader.elements(shift)?;
+                        lowered.table_segments.push(TableSegment {
+                            table,
+                            offset,
+                            functions,
+                        });
+                    }
+                }
+                REF_FUNCS_SECTION => {
+                    let shift = import_shift(&mut reader, imported)?;
+                    for _ in 0..reader.uleb()? {
+                        let func = reader.uleb()? + shift;
+                        lowered.ref_funcs.push(func);
+                    }
+                }
+                INDIRECT_CALLS_SECTION => {
+                    for _ in 0..reader.uleb()? {
+                        let index = reader.uleb()?;
+                        let original = reader.uleb()?;
+                        let table = reader.uleb()?;
+                        lowered.indirect_calls.insert(index, (original, table));
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(lowered)
+    }
+}
+
+/// How many function imports were prepended to the module since it was lowered.
+fn import_shift(reader: &mut Reader, imported: u32) -> Result<u32, Error> {
+    let lowered_imports = reader.uleb()?;
+    imported.checked_sub(lowered_imports).ok_or_else(|| {
+        format_err!(
+            "module has {} function imports, but had {} when lowered",
+            imported,
+            lowered_imports
+        )
+    })
+}
+
+/// The helpers appended to a module that uses any `0xfc`-prefixed operator or any of the other
+/// table operators: first those of the `0xfc`-prefixed operators, in the order of their
+/// sub-opcodes, then those of `table.get`, `table.set`, and `ref.func`, and the runtime functions
+/// that `table.get` uses. All of them are appended together so that each keeps a fixed index.
+///
+/// The table operators take the table index after their operands.
+const HELPERS: [Helper; 23] = [
+    Helper::TruncSat(TruncSat::new(F32, I32, 0xa8, true)),
+    Helper::TruncSat(TruncSat::new(F32, I32, 0xa9, false)),
+    Helper::TruncSat(TruncSat::new(F64, I32, 0xaa, true)),
+    Helper::TruncSat(TruncSat::new(F64, I32, 0xab, false)),
+    Helper::TruncSat(TruncSat::new(F32, I64, 0xae, true)),
+    Helper::TruncSat(TruncSat::new(F32, I64, 0xaf, false)),
+    Helper::TruncSat(TruncSat::new(F64, I64, 0xb0, true)),
+    Helper::TruncSat(TruncSat::new(F64, I64, 0xb1, false)),
+    Helper::RuntimeCall("lucet_vmctx_memory_init", &[I32; 4], &[]),
+    Helper::RuntimeCall("lucet_vmctx_data_drop", &[I32], &[]),
+    Helper::RuntimeCall("lucet_vmctx_memory_copy", &[I32; 3], &[]),
+    Helper::RuntimeCall("lucet_vmctx_memory_fill", &[I32; 3], &[]),
+    Helper::RuntimeCall("lucet_vmctx_table_init", &[I32; 5], &[]),
+    Helper::RuntimeCall("lucet_vmctx_elem_drop", &[I32], &[]),
+    // the source table follows the destination table
+    Helper::RuntimeCall("lucet_vmctx_table_copy", &[I32; 5], &[]),
+    Helper::RuntimeCall("lucet_vmctx_table_grow", &[I64, I32, I32], &[I32]),
+    Helper::RuntimeCall("lucet_vmctx_table_size", &[I32], &[I32]),
+    Helper::RuntimeCall("lucet_vmctx_table_fill", &[I32, I64, I32, I32], &[]),
+    Helper::TableGet,
+    Helper::RuntimeCall("lucet_vmctx_table_set", &[I32, I64, I32], &[]),
+    Helper::RuntimeCall("lucet_vmctx_ref_func", &[I32], &[I64]),
+    Helper::RuntimeCall("lucet_vmctx_table_get", &[I32, I32], &[I64]),
+    // the index is checked against the length of the access, which follows it
+    Helper::RuntimeCall("lucet_vmctx_table_check", &[I32; 3], &[]),
+];
+
+/// The offsets in `HELPERS` of the helpers of the table operators without a `0xfc` prefix, and of
+/// the runtime functions that `table.get` uses.
+const TABLE_GET: u32 = 18;
+const TABLE_SET: u32 = 19;
+const REF_FUNC: u32 = 20;
+const TABLE_GET_STUB: u32 = 21;
+const TABLE_CHECK: u32 = 22;
+
+/// The runtime functions behind the atomic operators, appended after `HELPERS` if the module uses
+/// any `0xfe`-prefixed operator. They take the checked effective address first, and the width of
+/// the access in bytes after the operands.
+const ATOMIC_STUBS: [Helper; 12] = [
+    Helper::RuntimeCall("lucet_vmctx_atomic_notify", &[I32, I32], &[I32]),
+    Helper::RuntimeCall("lucet_vmctx_atomic_wait32", &[I32, I32, I64], &[I32]),
+    Helper::RuntimeCall("lucet_vmctx_atomic_wait64", &[I32, I64, I64], &[I32]),
+    Helper::RuntimeCall("lucet_vmctx_atomic_fence", &[], &[]),
+    Helper::RuntimeCall("lucet_vmctx_atomic_load_i32", &[I32, I32], &[I32]),
+    Helper::RuntimeCall("lucet_vmctx_atomic_load_i64", &[I32, I32], &[I64]),
+    Helper::RuntimeCall("lucet_vmctx_atomic_store_i32", &[I32, I32, I32], &[]),
+    Helper::RuntimeCall("lucet_vmctx_atomic_store_i64", &[I32, I64, I32], &[]),
+    // the operation follows the width
+    Helper::RuntimeCall("lucet_vmctx_atomic_rmw_i32", &[I32, I32, I32, I32], &[I32]),
+    Helper::RuntimeCall("lucet_vmctx_atomic_rmw_i64", &[I32, I64, I32, I32], &[I64]),
+    Helper::RuntimeCall(
+        "lucet_vmctx_atomic_cmpxchg_i32",
+        &[I32, I32, I32, I32],
+        &[I32],
+    ),
+    Helper::RuntimeCall(
+        "lucet_vmctx_atomic_cmpxchg_i64",
+        &[I32, I64, I64, I32],
+        &[I64],
+    ),
+];
+
+/// The index of `lucet_vmctx_atomic_fence` in `ATOMIC_STUBS`.
+const ATOMIC_FENCE: usize = 3;
+
+/// The sub-opcodes of the atomic operators with a memory argument, in the order of their helpers,
+/// which follow `ATOMIC_STUBS`.
+fn atomic_subopcodes() -> impl Iterator<Item = u32> {
+    (0..3).chain(0x10..=0x4e)
+}
+
+/// The offset among the appended helpers of the helper for the atomic operator `sub`.
+fn atomic_helper(sub: u32) -> u32 {
+    let position = if sub < 3 { sub } else { sub - 0x10 + 3 };
+    (HELPERS.len() + ATOMIC_STUBS.len()) as u32 + position
+}
+
+#[derive(Clone, Copy)]
+enum Helper {
+    /// A saturating conversion, implemented in WebAssembly.
+    TruncSat(TruncSat),
+    /// An operator implemented by the named runtime function, with the given parameter and result
+    /// types.
+    RuntimeCall(&'static str, &'static [u8], &'static [u8]),
+    /// An atomic operator, which checks its memory access before calling one of `ATOMIC_STUBS`.
+    Atomic(AtomicOp),
+    /// `table.get`, which checks its index before calling `lucet_vmctx_table_get`, so that the
+    /// runtime function can return the element.
+    TableGet,
+}
+
+impl Helper {
+    fn write_type(&self, out: &mut Vec<u8>) {
+        match self {
+            Helper::TruncSat(helper) => {
+                out.extend_from_slice(&[0x60, 0x01, helper.from, 0x01, helper.to])
+            }
+            Helper::RuntimeCall(_, params, results) => write_func_type(out, params, results),
+            Helper::Atomic(op) => {
+                let mut params = vec![I32];
+                params.extend(op.operands());
+                params.push(I32);
+                write_func_type(out, &params, &op.results());
+            }
+            Helper::TableGet => write_func_type(out, &[I32, I32], &[I64]),
+        }
+    }
+
+    /// Encode the body of the helper function, including its local declarations.
+    fn body(&self, ctx: &CodeContext) -> Vec<u8> {
+        match self {
+            Helper::TruncSat(helper) => helper.helper_body(),
+            // never run, so just `unreachable`
+            Helper::RuntimeCall(..) => vec![0x00, 0x00, 0x0b],
+            Helper::Atomic(op) => op.helper_body(ctx),
+            Helper::TableGet => {
+                let mut body = vec![0x00];
+                // check an access of one element at the index
+                body.extend_from_slice(&[0x20, 0x00, 0x41, 0x01, 0x20, 0x01, 0x10]);
+                write_uleb(&mut body, u64::from(ctx.helper_base + TABLE_CHECK));
+                body.extend_from_slice(&[0x20, 0x00, 0x20, 0x01, 0x10]);
+                write_uleb(&mut body, u64::from(ctx.helper_base + TABLE_GET_STUB));
+                body.push(0x0b);
+                body
+            }
+        }
+    }
+
+    fn runtime_call(&self) -> Option<&'static str> {
+        match self {
+            Helper::RuntimeCall(symbol, _, _) => Some(*symbol),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AtomicKind {
+    Notify,
+    Wait,
+    Load,
+    Store,
+    /// A read-modify-write operation: add, sub, and, or, xor, or xchg, in the order of their
+    /// sub-opcodes.
+    Rmw(u8),
+    Cmpxchg,
+}
+
+/// The value type and width in bytes of the accesses of each group of atomic loads, stores, and
+/// read-modify-write operators, in the order of their sub-opcodes.
+const ATOMIC_ACCESSES: [(u8, u32); 7] = [
+    (I32, 4),
+    (I64, 8),
+    (I32, 1),
+    (I32, 2),
+    (I64, 1),
+    (I64, 2),
+    (I64, 4),
+];
+
+#[derive(Clone, Copy)]
+pub(crate) struct AtomicOp {
+    kind: AtomicKind,
+    /// The type of the values that are read from or written to memory.
+    ty: u8,
+    pub(crate) width: u32,
+}
+
+impl AtomicOp {
+    pub(crate) fn from_subopcode(sub: u32) -> Option<Self> {
+        let (kind, ty, width) = match sub {
+            0 => (AtomicKind::Notify, I32, 4),
+            1 => (AtomicKind::Wait, I32, 4),
+            2 => (AtomicKind::Wait, I64, 8),
+            0x10..=0x4e => {
+                let (ty, width) = ATOMIC_ACCESSES[(sub - 0x10) as usize % 7];
+                let kind = match (sub - 0x10) / 7 {
+                    0 => AtomicKind::Load,
+                    1 => AtomicKind::Store,
+                    8 => AtomicKind::Cmpxchg,
+                    group => AtomicKind::Rmw(group as u8 - 2),
+                };
+                (kind, ty, width)
+            }
+            _ => return None,
+        };
+        Some(AtomicOp { kind, ty, width })
+    }
+
+    /// The types of the operands after the address.
+    pub(crate) fn operands(&self) -> Vec<u8> {
+        match self.kind {
+            AtomicKind::Notify => vec![I32],
+            AtomicKind::Wait => vec![self.ty, I64],
+            AtomicKind::Load => vec![],
+            AtomicKind::Store | AtomicKind::Rmw(_) => vec![self.ty],
+            AtomicKind::Cmpxchg => vec![self.ty, self.ty],
+        }
+    }
+
+    pub(crate) fn results(&self) -> Vec<u8> {
+        match self.kind {
+            AtomicKind::Notify | AtomicKind::Wait => vec![I32],
+            AtomicKind::Store => vec![],
+            _ => vec![self.ty],
+        }
+    }
+
+    /// The index in `ATOMIC_STUBS` of the runtime function that performs the operation.
+    fn stub(&self) -> usize {
+        let is_i64 = (self.ty == I64) as usize;
+        match self.kind {
+            AtomicKind::Notify => 0,
+            AtomicKind::Wait => 1 + is_i64,
+            AtomicKind::Load => 4 + is_i64,
+            AtomicKind::Store => 6 + is_i64,
+            AtomicKind::Rmw(_) => 8 + is_i64,
+            AtomicKind::Cmpxchg => 10 + is_i64,
+        }
+    }
+
+    /// Encode the body of the helper function, whose parameters are the address, the operands,
+    /// and the offset of the memory argument:
+    ///
+    /// ```text
+    /// (local $ea i32)
+    /// (if (i32.wrap_i64 (i64.shr_u (i64.add (i64.extend_i32_u (local.get $addr))
+    ///                                        (i64.extend_i32_u (local.get $offset)))
+    ///                               (i64.const 32)))
+    ///   (then (drop (i32.load8_u offset=1 (i32.const -1))) (unreachable)))
+    /// (drop (iN.load (local.tee $ea (i32.add (local.get $addr) (local.get $offset)))))
+    /// (if (i32.and (local.get $ea) (i32.const width-1))
+    ///   (then (unreachable)))
+    /// (call $stub (local.get $ea) (local.get $operand)... (i32.const width) (i32.const op)?)
+    /// ```
+    ///
+    /// The plain loads trap in the same way as the access would if it is out of bounds. A `wait`
+    /// on a memory that is not shared traps instead of calling the runtime.
+    fn helper_body(&self, ctx: &CodeContext) -> Vec<u8> {
+        let operands = self.operands().len() as u64;
+        let offset = operands + 1;
+        let ea = operands + 2;
+        let mut body = vec![0x01, 0x01, I32];
+
+        body.extend_from_slice(&[0x20, 0x00, 0xad, 0x20]);
+        write_uleb(&mut body, offset);
+        body.extend_from_slice(&[0xad, 0x7c, 0x42, 0x20, 0x88, 0xa7, 0x04, 0x40]);
+        body.extend_from_slice(&[0x41, 0x7f, 0x2d, 0x00, 0x01, 0x1a, 0x00, 0x0b]);
+
+        body.extend_from_slice(&[0x20, 0x00, 0x20]);
+        write_uleb(&mut body, offset);
+        body.extend_from_slice(&[0x6a, 0x22]);
+        write_uleb(&mut body, ea);
+        let load = match self.width {
+            1 => 0x2d,
+            2 => 0x2f,
+            4 => 0x28,
+            _ => 0x29,
+        };
+        body.extend_from_slice(&[load, 0x00, 0x00, 0x1a]);
+
+        if self.width > 1 {
+            body.push(0x20);
+            write_uleb(&mut body, ea);
+            write_i32_const(&mut body, self.width - 1);
+            body.extend_from_slice(&[0x71, 0x04, 0x40, 0x00, 0x0b]);
+        }
+
+        if self.kind == AtomicKind::Wait && !ctx.shared_memory {
+            body.extend_from_slice(&[0x00, 0x0b]);
+            return body;
+        }
+        body.push(0x20);
+        write_uleb(&mut body, ea);
+        for operand in 1..=operands {
+            body.push(0x20);
+            write_uleb(&mut body, operand);
+        }
+        match self.kind {
+            AtomicKind::Load | AtomicKind::Store | AtomicKind::Cmpxchg => {
+                write_i32_const(&mut body, self.width)
+            }
+            AtomicKind::Rmw(op) => {
+                write_i32_const(&mut body, self.width);
+                write_i32_const(&mut body, u32::from(op));
+            }
+            AtomicKind::Notify | AtomicKind::Wait => (),
+        }
+        body.push(0x10);
+        write_uleb(
+            &mut body,
+            u64::from(ctx.helper_base) + (HELPERS.len() + self.stub()) as u64,
+        );
+        body.push(0x0b);
+        body
+    }
+}
+
+#[derive(Clone, Copy)]
+struct TruncSat {
+    from: u8,
+    to: u8,
+    trapping_op: u8,
+    signed: bool,
+}
+
+impl TruncSat {
+    const fn new(from: u8, to: u8, trapping_op: u8, signed: bool) -> Self {
+        TruncSat {
+            from,
+            to,
+            trapping_op,
+            signed,
+        }
+    }
+
+    fn bits(&self) -> i32 {
+        if self.to == I32 {
+            32
+        } else {
+            64
+        }
+    }
+
+    /// The smallest input that converts without saturating.
+    fn lower_bound(&self) -> f64 {
+        if self.signed {
+            -(2f64.powi(self.bits() - 1))
+        } else {
+            0.0
+        }
+    }
+
+    /// The smallest input that saturates to the maximum value.
+    fn upper_bound(&self) -> f64 {
+        if self.signed {
+            2f64.powi(self.bits() - 1)
+        } else {
+            2f64.powi(self.bits())
+        }
+    }
+
+    /// The saturated results, as the signed immediates of `i32.const` or `i64.const`.
+    fn min_max(&self) -> (i64, i64) {
+        match (self.to, self.signed) {
+            (I32, true) => (i64::from(std::i32::MIN), i64::from(std::i32::MAX)),
+            (I32, false) => (0, i64::from(std::u32::MAX as i32)),
+            (_, true) => (std::i64::MIN, std::i64::MAX),
+            (_, false) => (0, std::u64::MAX as i64),
+        }
+    }
+
+    fn write_float_const(&self, out: &mut Vec<u8>, value: f64) {
+        if self.from == F32 {
+            out.push(0x43);
+            out.extend_from_slice(&(value as f32).to_bits().to_le_bytes());
+        } else {
+            out.push(0x44);
+            out.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+    }
+
+    fn write_int_const(&self, out: &mut Vec<u8>, value: i64) {
+        out.push(if self.to == I32 { 0x41 } else { 0x42 });
+        write_sleb(out, value);
+    }
+
+    /// Encode the body of the helper function, including its (empty) local declarations:
+    ///
+    /// ```text
+    /// (if (result iN) (fX.ne (local.get 0) (local.get 0))
+    ///   (then (iN.const 0))
+    ///   (else (if (result iN) (fX.lt (local.get 0) (fX.const lower))
+    ///     (then (iN.const min))
+    ///     (else (if (result iN) (fX.ge (local.get 0) (fX.const upper))
+    ///       (then (iN.const max))
+    ///       (else (iN.trunc_fX (local.get 0)))))))))
+    /// ```
+    fn helper_body(&self) -> Vec<u8> {
+        let (ne, lt, ge) = if self.from == F32 {
+            (0x5c, 0x5d, 0x60)
+        } else {
+            (0x62, 0x63, 0x66)
+        };
+        let (min, max) = self.min_max();
+        let mut body = vec![0x00];
+        body.extend_from_slice(&[0x20, 0x00, 0x20, 0x00, ne, 0x04, self.to]);
+        self.write_int_const(&mut body, 0);
+        body.extend_from_slice(&[0x05, 0x20, 0x00]);
+        self.write_float_const(&mut body, self.lower_bound());
+        body.extend_from_slice(&[lt, 0x04, self.to]);
+        self.write_int_const(&mut body, min);
+        body.extend_from_slice(&[0x05, 0x20, 0x00]);
+        self.write_float_const(&mut body, self.upper_bound());
+        body.extend_from_slice(&[ge, 0x04, self.to]);
+        self.write_int_const(&mut body, max);
+        body.extend_from_slice(&[0x05, 0x20, 0x00, self.trapping_op]);
+        body.extend_from_slice(&[0x0b, 0x0b, 0x0b, 0x0b]);
+        body
+    }
+}
+
+/// The type index of each imported function, and the number of imported globals.
+struct Imports {
+    functions: Vec<u32>,
+    globals: u32,
+}
+
+fn module_imports(sections: &[(u8, &[u8])]) -> Result<Imports, Error> {
+    let mut imports = Imports {
+        functions: vec![],
+        globals: 0,
+    };
+    if let Some(payload) = section(sections, SECTION_IMPORT) {
+        let mut reader = Reader::new(payload);
+        for _ in 0..reader.uleb()? {
+            let module_len = reader.uleb()? as usize;
+            reader.bytes(module_len)?;
+            let field_len = reader.uleb()? as usize;
+            reader.bytes(field_len)?;
+            match reader.byte()? {
+                // function: type index
+                0x00 => imports.functions.push(reader.uleb()?),
+                // table: element type and limits
+                0x01 => {
+                    reader.byte()?;
+                    reader.limits()?;
+                }
+                // memory: limits
+                0x02 => reader.limits()?,
+                // global: value type and mutability
+                0x03 => {
+                    reader.byte()?;
+                    reader.byte()?;
+                    imports.globals += 1;
+                }
+                kind => bail!("unknown import kind {:#x}", kind),
+            }
+        }
+    }
+    Ok(imports)
+}
+
+/// A function type, with the encoded value types of its parameters and results.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FuncType {
+    pub(crate) params: Vec<u8>,
+    pub(crate) results: Vec<u8>,
+}
+
+/// The function types of the module, with reference types lowered to `i64`, and whether any
+/// were.
+fn func_types(sections: &[(u8, &[u8])]) -> Result<(Vec<FuncType>, bool), Error> {
+    let mut types = vec![];
+    let mut ref_types = false;
+    if let Some(payload) = section(sections, SECTION_TYPE) {
+        let mut reader = Reader::new(payload);
+        for _ in 0..reader.uleb()? {
+            let pos = reader.pos;
+            let form = reader.byte()?;
+            if form != 0x60 {
+                bail!("unsupported type form {:#x} at offset {}", form, pos);
+            }
+            let mut params = reader.value_types()?;
+            let mut results = reader.value_types()?;
+            for ty in params.iter_mut().chain(results.iter_mut()) {
+                let lowered = lower_value_type(*ty)?;
+                ref_types |= *ty != lowered;
+                *ty = lowered;
+            }
+            types.push(FuncType { params, results });
+        }
+    }
+    Ok((types, ref_types))
+}
+
+/// The value type that stands for the given one in lowered code: `i64` for the reference types,
+/// and the type itself otherwise. `v128` has no counterpart, so it is an error.
+fn lower_value_type(ty: u8) -> Result<u8, Error> {
+    match ty {
+        FUNCREF | EXTERNREF => Ok(I64),
+        V128 => bail!("v128 values are not supported: Cranelift 0.29 cannot compile SIMD code"),
+        ty => Ok(ty),
+    }
+}
+
+/// Encode the type section, with the results of function types that have several removed, and
+/// the types of the given helpers appended. The given types include those added for indirect
+/// calls through tables other than table 0.
+fn lower_type_section(types: &[FuncType], helpers: &[Helper]) -> Vec<u8> {
+    let mut out = vec![];
+    write_uleb(&mut out, (types.len() + helpers.len()) as u64);
+    for ty in types {
+        out.push(0x60);
+        write_uleb(&mut out, ty.params.len() as u64);
+        out.extend_from_slice(&ty.params);
+        if ty.results.len() > 1 {
+            out.push(0x00);
+        } else {
+            write_uleb(&mut out, ty.results.len() as u64);
+            out.extend_from_slice(&ty.results);
+        }
+    }
+    for helper in helpers {
+        helper.write_type(&mut out);
+    }
+    out
+}
+
+/// Append a mutable, zero-initialized global of each of the given types to the global section,
+/// which is created if the module has none.
+fn append_result_globals(payload: Option<&[u8]>, result_globals: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = vec![];
+    let (count, rest) = match payload {
+        Some(payload) => {
+            let mut reader = Reader::new(payload);
+            (reader.uleb()?, reader.rest())
+        }
+        None => (0, &[][..]),
+    };
+    write_uleb(&mut out, u64::from(count) + result_globals.len() as u64);
+    out.extend_from_slice(rest);
+    for ty in result_globals {
+        out.extend_from_slice(&[*ty, 0x01]);
+        match *ty {
+            I32 => out.extend_from_slice(&[0x41, 0x00]),
+            I64 => out.extend_from_slice(&[0x42, 0x00]),
+            F32 => {
+                out.push(0x43);
+                out.extend_from_slice(&[0; 4]);
+            }
+            F64 => {
+                out.push(0x44);
+                out.extend_from_slice(&[0; 8]);
+            }
+            ty => bail!("unsupported value type {:#x} in a sequence of values", ty),
+        }
+        out.push(0x0b);
+    }
+    Ok(out)
+}
+
+/// Append the given number of helpers to the function section, with their types following the
+/// given number of function types.
+fn append_helper_funcs(payload: &[u8], types: usize, helpers: usize) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader::new(payload);
+    let count = reader.uleb()?;
+    let mut out = vec![];
+    write_uleb(&mut out, u64::from(count) + helpers as u64);
+    out.extend_from_slice(reader.rest());
+    for i in 0..helpers {
+        write_uleb(&mut out, (types + i) as u64);
+    }
+    Ok(out)
+}
+
+/// The module-wide state that function bodies are lowered with.
+struct CodeContext {
+    /// The function index of the first helper, which is the number of functions already in the
+    /// module.
+    helper_base: u32,
+    imported: u32,
+    types: Vec<FuncType>,
+    /// The type index of each function, imported functions first.
+    func_type_indices: Vec<u32>,
+    /// The index of the first result global, which is the number of globals already in the
+    /// module.
+    global_base: u32,
+    /// The value type of each result global used so far.
+    result_globals: Vec<u8>,
+    /// The result global for each position in a sequence of values and value type.
+    result_global_indices: HashMap<(usize, u8), u32>,
+    shared_memory: bool,
+    uses_helpers: bool,
+    /// Whether the atomic helpers must be appended after the others.
+    uses_atomics: bool,
+    /// The functions that `ref.func` refers to, each passed to the runtime as its position here.
+    ref_funcs: Vec<u32>,
+    ref_func_indices: HashMap<u32, u32>,
+    /// The type index added for each pair of a type and a table other than table 0 that an
+    /// indirect call goes through, with that type and table.
+    indirect_types: Vec<(u32, u32, u32)>,
+}
+
+impl CodeContext {
+    /// The helpers to append to the module, which keep the same indices whether or not the atomic
+    /// helpers follow them.
+    fn helpers(&self) -> Vec<Helper> {
+        let mut helpers = vec![];
+        if self.uses_helpers {
+            helpers.extend_from_slice(&HELPERS);
+        }
+        if self.uses_atomics {
+            helpers.extend_from_slice(&ATOMIC_STUBS);
+            helpers.extend(atomic_subopcodes().map(|sub| {
+                Helper::Atomic(AtomicOp::from_subopcode(sub).expect("atomic sub-opcode"))
+            }));
+        }
+        helpers
+    }
+
+    fn type_at(&self, index: u32) -> Result<&FuncType, Error> {
+        self.types
+            .get(index as usize)
+            .ok_or_else(|| format_err!("type index {} is out of bounds", index))
+    }
+
+    fn func_type(&self, func: u32) -> Result<&FuncType, Error> {
+        let index = self
+            .func_type_indices
+            .get(func as usize)
+            .ok_or_else(|| format_err!("function index {} is out of bounds", func))?;
+        self.type_at(*index)
+    }
+
+    /// The handle that `ref.func` passes to the runtime for the given function.
+    fn ref_func(&mut self, func: u32) -> u32 {
+        let next = self.ref_funcs.len() as u32;
+        let handle = *self.ref_func_indices.entry(func).or_insert(next);
+        if handle == next {
+            self.ref_funcs.push(func);
+        }
+        handle
+    }
+
+    /// The type index for an indirect call of the given type through the given table, which is
+    /// a copy of the type appended to the others so that the call can be told apart.
+    fn indirect_type(&mut self, index: u32, table: u32) -> Result<u32, Error> {
+        let existing = self
+            .indirect_types
+            .iter()
+            .find(|(_, original, t)| (*original, *t) == (index, table));
+        if let Some((added, _, _)) = existing {
+            return Ok(*added);
+        }
+        let ty = self.type_at(index)?.clone();
+        let added = self.types.len() as u32;
+        self.types.push(ty);
+        self.indirect_types.push((added, index, table));
+        Ok(added)
+    }
+
+    /// The index of the global that passes a value of the given type at the given position in a
+    /// sequence of values.
+    fn result_global(&mut self, position: usize, ty: u8) -> u32 {
+        let next = self.global_base + self.result_globals.len() as u32;
+        let global = *self
+            .result_global_indices
+            .entry((position, ty))
+            .or_insert(next);
+        if global == next {
+            self.result_globals.push(ty);
+        }
+        global
+    }
+
+    /// Pop a sequence of values of the given types off the stack into the result globals.
+    fn store_values(&mut self, out: &mut Vec<u8>, types: &[u8]) {
+        for (position, ty) in types.iter().enumerate().rev() {
+            out.push(0x24);
+            write_uleb(out, u64::from(self.result_global(position, *ty)));
+        }
+    }
+
+    /// Push a sequence of values of the given types from the result globals.
+    fn load_values(&mut self, out: &mut Vec<u8>, types: &[u8]) {
+        for (position, ty) in types.iter().enumerate() {
+            out.push(0x23);
+            write_uleb(out, u64::from(self.result_global(position, *ty)));
+        }
+    }
+}
+
+/// Lower the operators in every function body of the code section.
+///
+/// Returns the new section payload if anything was lowered. Whether the helpers need to be
+/// appended to the module is recorded in the context.
+fn lower_code_section(payload: &[u8], ctx: &mut CodeContext) -> Result<Option<Vec<u8>>, Error> {
+    let mut reader = Reader::new(payload);
+    let count = reader.uleb()?;
+    let mut bodies = vec![];
+    let mut lowered_any = false;
+    for func in 0..count {
+        let len = reader.uleb()? as usize;
+        let body = reader.bytes(len)?;
+        let lowered = lower_body(ctx.imported + func, body, ctx)
+            .map_err(|e| format_err!("in function body {}: {}", func, e))?;
+        lowered_any |= lowered.as_slice() != body;
+        bodies.push(lowered);
+    }
+    if !lowered_any {
+        return Ok(None);
+    }
+    for helper in ctx.helpers() {
+        bodies.push(helper.body(ctx));
+    }
+    let mut out = vec![];
+    write_uleb(&mut out, bodies.len() as u64);
+    for body in bodies {
+        write_uleb(&mut out, body.len() as u64);
+        out.extend_from_slice(&body);
+    }
+    Ok(Some(out))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FrameKind {
+    Function,
+    Block,
+    Loop,
+    If,
+}
+
+/// A function body or structured instruction whose label can be branched to.
+struct Frame {
+    kind: FrameKind,
+    params: Vec<u8>,
+    results: Vec<u8>,
+    has_else: bool,
+}
+
+impl Frame {
+    /// The types of the values that a branch to the label of this frame carries.
+    fn label_types(&self) -> &[u8] {
+        match self.kind {
+            FrameKind::Loop => &self.params,
+            _ => &self.results,
+        }
+    }
+
+    /// Whether the lowered label carries its values itself, rather than through the result
+    /// globals.
+    fn native_label(&self) -> bool {
+        match self.kind {
+            FrameKind::Loop => self.params.is_empty(),
+            _ => self.results.len() <= 1,
+        }
+    }
+}
+
+fn label(frames: &[Frame], depth: u32) -> Result<&Frame, Error> {
+    frames
+        .len()
+        .checked_sub(depth as usize + 1)
+        .map(|i| &frames[i])
+        .ok_or_else(|| format_err!("branch depth {} is out of bounds", depth))
+}
+
+/// Lower the operators of a single function body, returning the new body.
+fn lower_body(func: u32, body: &[u8], ctx: &mut CodeContext) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader::new(body);
+    let mut out = Vec::with_capacity(body.len());
+
+    // a scratch `i32` local is appended to the local declarations if any operator needs to set
+    // its condition aside while values are moved through the result globals
+    let func_type = ctx.func_type(func)?.clone();
+    let mut local_count = func_type.params.len() as u64;
+    let groups = reader.uleb()?;
+    let groups_len = reader.pos;
+    let mut decls = vec![];
+    for _ in 0..groups {
+        let start = reader.pos;
+        local_count += u64::from(reader.uleb()?);
+        decls.extend_from_slice(&body[start..reader.pos]);
+        decls.push(lower_value_type(reader.byte()?)?);
+    }
+    let scratch = local_count;
+    let mut uses_scratch = false;
+
+    let mut frames = vec![Frame {
+        kind: FrameKind::Function,
+        params: func_type.params,
+        results: func_type.results,
+        has_else: false,
+    }];
+    while !reader.is_empty() {
+        let start = reader.pos;
+        let opcode = reader.byte()?;
+        match opcode {
+            // block, loop, if
+            0x02..=0x04 => {
+                let ty = match reader.block_type()? {
+                    BlockType::Value(0x40) => FuncType::default(),
+                    BlockType::Value(ty) => FuncType {
+                        params: vec![],
+                        results: vec![lower_value_type(ty)?],
+                    },
+                    BlockType::Index(index) => ctx.type_at(index)?.clone(),
+                };
+                if !ty.params.is_empty() {
+                    // the parameters are below the condition of an `if`
+                    if opcode == 0x04 {
+                        out.push(0x21);
+                        write_uleb(&mut out, scratch);
+                        uses_scratch = true;
+                    }
+                    ctx.store_values(&mut out, &ty.params);
+                    if opcode == 0x04 {
+                        out.push(0x20);
+                        write_uleb(&mut out, scratch);
+                    }
+                }
+                out.push(opcode);
+                out.push(match ty.results.as_slice() {
+                    [result] => *result,
+                    _ => 0x40,
+                });
+                ctx.load_values(&mut out, &ty.params);
+                let kind = match opcode {
+                    0x02 => FrameKind::Block,
+                    0x03 => FrameKind::Loop,
+                    _ => FrameKind::If,
+                };
+                frames.push(Frame {
+                    kind,
+                    params: ty.params,
+                    results: ty.results,
+                    has_else: false,
+                });
+            }
+            // else
+            0x05 => {
+                let frame = match frames.last_mut() {
+                    Some(frame) if frame.kind == FrameKind::If => frame,
+                    _ => bail!("else outside of an if at offset {}", start),
+                };
+                if frame.results.len() > 1 {
+                    ctx.store_values(&mut out, &frame.results);
+                }
+                out.push(0x05);
+                ctx.load_values(&mut out, &frame.params);
+                frame.has_else = true;
+            }
+            // end
+            0x0b => {
+                let frame = frames
+                    .pop()
+                    .ok_or_else(|| format_err!("unbalanced end at offset {}", start))?;
+                if frame.kind == FrameKind::If && !frame.has_else && !frame.params.is_empty() {
+                    // without an `else`, the parameters are passed through as the results, which
+                    // takes an explicit `else` once they are passed through the result globals
+                    if frame.results.len() > 1 {
+                        ctx.store_values(&mut out, &frame.results);
+                    }
+                    out.push(0x05);
+                    ctx.load_values(&mut out, &frame.params);
+                }
+                if frame.results.len() > 1 {
+                    ctx.store_values(&mut out, &frame.results);
+                }
+                out.push(0x0b);
+                if frame.kind != FrameKind::Function && frame.results.len() > 1 {
+                    ctx.load_values(&mut out, &frame.results);
+                }
+            }
+            // br
+            0x0c => {
+                let frame = label(&frames, reader.uleb()?)?;
+                if !frame.native_label() {
+                    ctx.store_values(&mut out, frame.label_types());
+                }
+                out.extend_from_slice(&body[start..reader.pos]);
+            }
+            // br_if: the values stay on the stack if the branch is not taken
+            0x0d => {
+                let frame = label(&frames, reader.uleb()?)?;
+                if !frame.native_label() {
+                    out.push(0x21);
+                    write_uleb(&mut out, scratch);
+                    ctx.store_values(&mut out, frame.label_types());
+                    ctx.load_values(&mut out, frame.label_types());
+                    out.push(0x20);
+                    write_uleb(&mut out, scratch);
+                    uses_scratch = true;
+                }
+                out.extend_from_slice(&body[start..reader.pos]);
+            }
+            // br_table: all of the labels carry values of the same types
+            0x0e => {
+                let depths = reader.indices()?;
+                let frame = label(&frames, reader.uleb()?)?;
+                for depth in depths {
+                    if label(&frames, depth)?.native_label() != frame.native_label() {
+                        bail!(
+                            "br_table at offset {} has labels that pass values differently \
+                             once lowered",
+                            start
+                        );
+                    }
+                }
+                if !frame.native_label() {
+                    out.push(0x21);
+                    write_uleb(&mut out, scratch);
+                    ctx.store_values(&mut out, frame.label_types());
+                    out.push(0x20);
+                    write_uleb(&mut out, scratch);
+                    uses_scratch = true;
+                }
+                out.extend_from_slice(&body[start..reader.pos]);
+            }
+            // return
+            0x0f => {
+                if frames[0].results.len() > 1 {
+                    ctx.store_values(&mut out, &frames[0].results);
+                }
+                out.push(0x0f);
+            }
+            // call, call_indirect
+            0x10 | 0x11 => {
+                let ty = if opcode == 0x10 {
+                    let ty = ctx.func_type(reader.uleb()?)?.clone();
+                    out.extend_from_slice(&body[start..reader.pos]);
+                    ty
+                } else {
+                    let index = reader.uleb()?;
+                    let ty = ctx.type_at(index)?.clone();
+                    match reader.uleb()? {
+                        0 => out.extend_from_slice(&body[start..reader.pos]),
+                        table => {
+                            out.push(0x11);
+                            write_uleb(&mut out, u64::from(ctx.indirect_type(index, table)?));
+                            out.push(0x00);
+                        }
+                    }
+                    ty
+                };
+                if ty.results.len() > 1 {
+                    ctx.load_values(&mut out, &ty.results);
+                }
+            }
+            // sign extension
+            0xc0 => lower_sign_ext(&mut out, 0x41, 0x74, 0x75, 24),
+            0xc1 => lower_sign_ext(&mut out, 0x41, 0x74, 0x75, 16),
+            0xc2 => lower_sign_ext(&mut out, 0x42, 0x86, 0x87, 56),
+            0xc3 => lower_sign_ext(&mut out, 0x42, 0x86, 0x87, 48),
+            // i64.extend32_s: i32.wrap_i64, i64.extend_i32_s
+            0xc4 => out.extend_from_slice(&[0xa7, 0xac]),
+            // select with value types, which only reference types need
+            0x1c => {
+                for ty in reader.value_types()? {
+                    lower_value_type(ty)?;
+                }
+                out.push(0x1b);
+            }
+            // table.get, table.set: table index
+            0x25 | 0x26 => {
+                write_i32_const(&mut out, reader.uleb()?);
+                let helper = if opcode == 0x25 { TABLE_GET } else { TABLE_SET };
+                out.push(0x10);
+                write_uleb(&mut out, u64::from(ctx.helper_base + helper));
+                ctx.uses_helpers = true;
+            }
+            // ref.null: a reference type, which older drafts of the proposal leave out
+            0xd0 => {
+                if let Some(&FUNCREF) | Some(&EXTERNREF) = reader.rest().first() {
+                    reader.byte()?;
+                }
+                out.extend_from_slice(&[0x42, 0x00]);
+            }
+            // ref.is_null: i64.eqz
+            0xd1 => out.push(0x50),
+            // ref.func: function index
+            0xd2 => {
+                let handle = ctx.ref_func(reader.uleb()?);
+                write_i32_const(&mut out, handle);
+                out.push(0x10);
+                write_uleb(&mut out, u64::from(ctx.helper_base + REF_FUNC));
+                ctx.uses_helpers = true;
+            }
+            0xfc => {
+                let sub = reader.uleb()?;
+                match sub {
+                    // saturating conversions
+                    0..=7 => (),
+                    // memory.init: segment index, then memory index
+                    8 => {
+                        let segment = reader.uleb()?;
+                        reader.zero_index()?;
+                        write_i32_const(&mut out, segment);
+                    }
+                    // table.init: segment index, then table index
+                    12 => {
+                        let segment = reader.uleb()?;
+                        let table = reader.uleb()?;
+                        write_i32_const(&mut out, segment);
+                        write_i32_const(&mut out, table);
+                    }
+                    // data.drop, elem.drop: segment index
+                    9 | 13 => {
+                        let segment = reader.uleb()?;
+                        write_i32_const(&mut out, segment);
+                    }
+                    // memory.copy: destination and source memory index
+                    10 => {
+                        reader.zero_index()?;
+                        reader.zero_index()?;
+                    }
+                    // memory.fill: memory index
+                    11 => reader.zero_index()?,
+                    // table.copy: destination and source table index
+                    14 => {
+                        let dst = reader.uleb()?;
+                        let src = reader.uleb()?;
+                        write_i32_const(&mut out, dst);
+                        write_i32_const(&mut out, src);
+                    }
+                    // table.grow, table.size, table.fill: table index
+                    15..=17 => write_i32_const(&mut out, reader.uleb()?),
+                    _ => bail!(
+                        "unsupported operator {:#x} {:#x} at offset {}",
+                        opcode,
+                        sub,
+                        start
+                    ),
+                }
+                out.push(0x10);
+                write_uleb(&mut out, u64::from(ctx.helper_base + sub));
+                ctx.uses_helpers = true;
+            }
+            0xfe => {
+                let sub = reader.uleb()?;
+                let helper = match AtomicOp::from_subopcode(sub) {
+                    // the offset of the memory argument is passed after the operands
+                    Some(_) => {
+                        reader.uleb()?;
+                        let offset = reader.uleb()?;
+                        write_i32_const(&mut out, offset);
+                        atomic_helper(sub)
+                    }
+                    // atomic.fence: a reserved zero byte
+                    None if sub == 3 => {
+                        reader.zero_index()?;
+                        (HELPERS.len() + ATOMIC_FENCE) as u32
+                    }
+                    None => bail!(
+                        "unsupported operator {:#x} {:#x} at offset {}",
+                        opcode,
+                        sub,
+                        start
+                    ),
+                };
+                out.push(0x10);
+                write_uleb(&mut out, u64::from(ctx.helper_base + helper));
+                ctx.uses_helpers = true;
+                ctx.uses_atomics = true;
+            }
+            0xfd => bail!(
+                "SIMD operator {:#x} {:#x} at offset {} is not supported: Cranelift 0.29 cannot \
+                 compile SIMD code",
+                opcode,
+                reader.uleb()?,
+                start
+            ),
+            _ => {
+                reader.skip_immediates(opcode)?;
+                out.extend_from_slice(&body[start..reader.pos]);
+            }
+        }
+    }
+    if !frames.is_empty() {
+        bail!("function body ends inside a block");
+    }
+
+    let mut lowered = vec![];
+    if uses_scratch {
+        write_uleb(&mut lowered, u64::from(groups) + 1);
+        lowered.extend_from_slice(&decls);
+        lowered.extend_from_slice(&[0x01, I32]);
+    } else {
+        lowered.extend_from_slice(&body[..groups_len]);
+        lowered.extend_from_slice(&decls);
+    }
+    lowered.extend_from_slice(&out);
+    Ok(lowered)
+}
+
+/// Remove the passive segments from the data section, and rewrite active segments with an
+/// explicit memory index to the MVP encoding.
+///
+/// Returns the new section payload and the passive segments if anything changed.
+fn lower_data_section(payload: &[u8]) -> Result<Option<(Vec<u8>, Vec<PassiveData>)>, Error> {
+    let mut reader = Reader::new(payload);
+    let count = reader.uleb()?;
+    let mut active = vec![];
+    let mut active_count = 0;
+    let mut passive = vec![];
+    let mut lowered = false;
+    for index in 0..count {
+        let start = reader.pos;
+        match reader.uleb()? {
+            0 => {
+                reader.skip_init_expr()?;
+                let len = reader.uleb()? as usize;
+                reader.bytes(len)?;
+                active.extend_from_slice(&payload[start..reader.pos]);
+                active_count += 1;
+            }
+            1 => {
+                let len = reader.uleb()? as usize;
+                let data = reader.bytes(len)?.to_vec();
+                passive.push(PassiveData { index, data });
+                lowered = true;
+            }
+            2 => {
+                reader.zero_index()?;
+                let offset = reader.pos;
+                reader.skip_init_expr()?;
+                let len = reader.uleb()? as usize;
+                reader.bytes(len)?;
+                active.push(0x00);
+                active.extend_from_slice(&payload[offset..reader.pos]);
+                active_count += 1;
+                lowered = true;
+            }
+            flags => bail!("unsupported data segment flags {:#x}", flags),
+        }
+    }
+    if !lowered {
+        return Ok(None);
+    }
+    let mut out = vec![];
+    write_uleb(&mut out, active_count);
+    out.extend_from_slice(&active);
+    Ok(Some((out, passive)))
+}
+
+/// Remove the passive and declarative segments from the element section, and rewrite active
+/// segments with an explicit table index or element expressions to the MVP encoding. Active
+/// segments that the MVP encoding cannot express, because they initialize a table other than table
+/// 0 or hold null elements, are removed as well.
+///
+/// Returns the new section payload, the passive segments, and the removed active segments if
+/// anything changed.
+fn lower_element_section(
+    payload: &[u8],
+) -> Result<Option<(Vec<u8>, Vec<PassiveElements>, Vec<TableSegment>)>, Error> {
+    let mut reader = Reader::new(payload);
+    let count = reader.uleb()?;
+    let mut active = vec![];
+    let mut active_count = 0;
+    let mut passive = vec![];
+    let mut segments = vec![];
+    let mut lowered = false;
+    for index in 0..count {
+        let start = reader.pos;
+        let flags = reader.uleb()?;
+        match flags {
+            0 => {
+                reader.skip_init_expr()?;
+                reader.indices()?;
+                active.extend_from_slice(&payload[start..reader.pos]);
+                active_count += 1;
+            }
+            1 => {
+                let functions = match reader.byte()? {
+                    // element kind `funcref`, with function indices
+                    0x00 => reader.indices()?.into_iter().map(Some).collect(),
+                    // older drafts of the proposal gave the element type and expressions here
+                    FUNCREF => reader.elem_exprs()?,
+                    kind => bail!("unsupported element kind {:#x}", kind),
+                };
+                passive.push(PassiveElements { index, functions });
+                lowered = true;
+            }
+            // active segments with a table index, element expressions, or both
+            2 | 4 | 6 => {
+                let table = if flags == 4 { 0 } else { reader.uleb()? };
+                let offset = reader.pos;
+                reader.skip_init_expr()?;
+                let offset_expr = &payload[offset..reader.pos];
+                let functions = match flags {
+                    2 => {
+                        reader.elem_kind()?;
+                        reader.indices()?.into_iter().map(Some).collect()
+                    }
+                    4 => reader.elem_exprs()?,
+                    _ => {
+                        reader.ref_type()?;
+                        reader.elem_exprs()?
+                    }
+                };
+                if table == 0 && functions.iter().all(Option::is_some) {
+                    active.push(0x00);
+                    active.extend_from_slice(offset_expr);
+                    write_uleb(&mut active, functions.len() as u64);
+                    for func in functions.into_iter().flatten() {
+                        write_uleb(&mut active, u64::from(func));
+                    }
+                    active_count += 1;
+                } else {
+                    let offset = Reader::new(offset_expr)
+                        .const_offset()
+                        .map_err(|e| format_err!("in element segment {}: {}", index, e))?;
+                    segments.push(TableSegment {
+                        table,
+                        offset,
+                        functions,
+                    });
+                }
+                lowered = true;
+            }
+            // declarative segments only matter to validation
+            3 => {
+                reader.elem_kind()?;
+                reader.indices()?;
+                lowered = true;
+            }
+            5 => {
+                reader.ref_type()?;
+                let functions = reader.elem_exprs()?;
+                passive.push(PassiveElements { index, functions });
+                lowered = true;
+            }
+            7 => {
+                reader.ref_type()?;
+                reader.elem_exprs()?;
+                lowered = true;
+            }
+            flags => bail!("unsupported element segment flags {:#x}", flags),
+        }
+    }
+    if !lowered {
+        return Ok(None);
+    }
+    let mut out = vec![];
+    write_uleb(&mut out, active_count);
+    out.extend_from_slice(&active);
+    Ok(Some((out, passive, segments)))
+}
+
+/// What lowering changed in the import section.
+#[derive(Default)]
+struct LoweredImports {
+    /// The new section payload, if anything changed.
+    payload: Option<Vec<u8>>,
+    /// The imported tables, in order.
+    tables: Vec<LoweredTable>,
+    /// Whether the imported memory is shared.
+    shared_memory: bool,
+}
+
+/// Rewrite the import section to the MVP encoding: the limits of shared memories, the type of
+/// globals holding references, and the tables, of which only the first is kept, as a `funcref`
+/// table.
+fn lower_import_section(payload: &[u8]) -> Result<LoweredImports, Error> {
+    let mut reader = Reader::new(payload);
+    let count = reader.uleb()?;
+    let mut imports = LoweredImports::default();
+    let mut entries = vec![];
+    let mut kept = 0;
+    let mut changed = false;
+    for _ in 0..count {
+        let start = reader.pos;
+        let module = reader.name()?;
+        let field = reader.name()?;
+        let kind = reader.byte()?;
+        let mut entry = payload[start..reader.pos].to_vec();
+        let start = reader.pos;
+        match kind {
+            0x00 => reader.skip_leb()?,
+            0x01 => {
+                let (elem_type, min_size, max_size) = reader.table_type()?;
+                let first = imports.tables.is_empty();
+                imports.tables.push(LoweredTable {
+                    elem_type,
+                    min_size,
+                    max_size,
+                    import: Some((module, field)),
+                    export: None,
+                });
+                if !first {
+                    changed = true;
+                    continue;
+                }
+                if elem_type != RefType::FuncRef {
+                    changed = true;
+                    entry.push(FUNCREF);
+                    write_limits(&mut entry, min_size, max_size);
+                    entries.push(entry);
+                    kept += 1;
+                    continue;
+                }
+            }
+            0x02 => {
+                imports.shared_memory |= lower_limits(&mut reader, &mut entry)?;
+                entries.push(entry);
+                kept += 1;
+                continue;
+            }
+            0x03 => {
+                let ty = reader.byte()?;
+                let lowered = lower_value_type(ty)?;
+                changed |= ty != lowered;
+                entry.push(lowered);
+                entry.push(reader.byte()?);
+                entries.push(entry);
+                kept += 1;
+                continue;
+            }
+            kind => bail!("unknown import kind {:#x}", kind),
+        }
+        entry.extend_from_slice(&payload[start..reader.pos]);
+        entries.push(entry);
+        kept += 1;
+    }
+    if changed || imports.shared_memory {
+        let mut out = vec![];
+        write_uleb(&mut out, kept);
+        for entry in entries {
+            out.extend_from_slice(&entry);
+        }
+        imports.payload = Some(out);
+    }
+    Ok(imports)
+}
+
+/// Record the tables defined by the table section after the imported ones, and rewrite the
+/// section to define table 0 as a `funcref` table if it is not imported, and nothing else.
+///
+/// Returns the new section payload if anything changed.
+fn lower_table_section(
+    payload: &[u8],
+    tables: &mut Vec<LoweredTable>,
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut reader = Reader::new(payload);
+    let imported = tables.len();
+    for _ in 0..reader.uleb()? {
+        let (elem_type, min_size, max_size) = reader.table_type()?;
+        tables.push(LoweredTable {
+            elem_type,
+            min_size,
+            max_size,
+            import: None,
+            export: None,
+        });
+    }
+    if imported > 0 || tables.len() > 1 || tables[0].elem_type != RefType::FuncRef {
+        let mut out = vec![];
+        if imported == 0 {
+            write_uleb(&mut out, 1);
+            out.push(FUNCREF);
+            write_limits(&mut out, tables[0].min_size, tables[0].max_size);
+        } else {
+            write_uleb(&mut out, 0);
+        }
+        return Ok(Some(out));
+    }
+    Ok(None)
+}
+
+/// Record the names the tables are exported under, and remove the exports of tables other than
+/// table 0 from the export section.
+///
+/// Returns the new section payload if anything changed.
+fn lower_export_section(
+    payload: &[u8],
+    tables: &mut [LoweredTable],
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut reader = Reader::new(payload);
+    let mut entries = vec![];
+    let mut kept = 0;
+    let mut changed = false;
+    for _ in 0..reader.uleb()? {
+        let start = reader.pos;
+        let name = reader.name()?;
+        let kind = reader.byte()?;
+        let index = reader.uleb()?;
+        // table
+        if kind == 0x01 {
+            if let Some(table) = tables.get_mut(index as usize) {
+                table.export = Some(name);
+            }
+            if index != 0 {
+                changed = true;
+                continue;
+            }
+        }
+        entries.extend_from_slice(&payload[start..reader.pos]);
+        kept += 1;
+    }
+    if !changed {
+        return Ok(None);
+    }
+    let mut out = vec![];
+    write_uleb(&mut out, kept);
+    out.extend_from_slice(&entries);
+    Ok(Some(out))
+}
+
+/// Rewrite the globals holding references to `i64` globals, with `ref.null` initializers replaced
+/// by zero.
+///
+/// Returns the new section payload if anything changed.
+fn lower_global_section(payload: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let mut reader = Reader::new(payload);
+    let count = reader.uleb()?;
+    let mut out = vec![];
+    write_uleb(&mut out, u64::from(count));
+    let mut changed = false;
+    for index in 0..count {
+        let ty = reader.byte()?;
+        let lowered = lower_value_type(ty)?;
+        changed |= ty != lowered;
+        out.push(lowered);
+        out.push(reader.byte()?);
+        match reader.rest().first() {
+            // ref.null
+            Some(0xd0) => {
+                reader.byte()?;
+                if let Some(&FUNCREF) | Some(&EXTERNREF) = reader.rest().first() {
+                    reader.byte()?;
+                }
+                if reader.byte()? != 0x0b {
+                    bail!("global {} has an unsupported initializer", index);
+                }
+                out.extend_from_slice(&[0x42, 0x00, 0x0b]);
+                changed = true;
+            }
+            Some(0xd2) => bail!(
+                "global {} is initialized with ref.func, which is not supported",
+                index
+            ),
+            _ => {
+                let start = reader.pos;
+                reader.skip_init_expr()?;
+                out.extend_from_slice(&payload[start..reader.pos]);
+            }
+        }
+    }
+    Ok(if changed { Some(out) } else { None })
+}
+
+/// Rewrite the limits of shared memories in the memory section to the MVP encoding.
+///
+/// Returns the new section payload if any memory was shared.
+fn lower_memory_section(payload: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let mut reader = Reader::new(payload);
+    let count = reader.uleb()?;
+    let mut out = vec![];
+    write_uleb(&mut out, u64::from(count));
+    let mut shared = false;
+    for _ in 0..count {
+        shared |= lower_limits(&mut reader, &mut out)?;
+    }
+    Ok(if shared { Some(out) } else { None })
+}
+
+/// Copy the limits of a memory in the MVP encoding, returning whether the memory is shared.
+fn lower_limits(reader: &mut Reader, out: &mut Vec<u8>) -> Result<bool, Error> {
+    let pos = reader.pos;
+    let flags = reader.uleb()?;
+    let shared = match flags {
+        0 | 1 => false,
+        3 => true,
+        2 => bail!("shared memory at offset {} has no maximum size", pos),
+        flags => bail!("unsupported limits flags {:#x} at offset {}", flags, pos),
+    };
+    write_uleb(out, u64::from(flags & 1));
+    write_uleb(out, u64::from(reader.uleb()?));
+    if flags & 1 != 0 {
+        write_uleb(out, u64::from(reader.uleb()?));
+    }
+    Ok(shared)
+}
+
+fn section<'a>(sections: &[(u8, &'a [u8])], id: u8) -> Option<&'a [u8]> {
+    sections
+        .iter()
+        .find(|(section_id, _)| *section_id == id)
+        .map(|(_, payload)| *payload)
+}
+
+/// Sign-extend the low bits of the value on top of the stack by shifting them to the top and back.
+fn lower_sign_ext(out: &mut Vec<u8>, const_op: u8, shl: u8, shr_s: u8, shift: i64) {
+    out.push(const_op);
+    write_sleb(out, shift);
+    out.push(shl);
+    out.push(const_op);
+    write_sleb(out, shift);
+    out.push(shr_s);
+}
+
+pub(crate) enum BlockType {
+    /// The empty block type `0x40`, or the single result value type.
+    Value(u8),
+    /// The index of a function type.
+    Index(u32),
+}
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    /// Read `bytes` starting at `pos`, so that offsets in errors are relative to the start of
+    /// `bytes` rather than to `pos`.
+    pub(crate) fn at(bytes: &'a [u8], pos: usize) -> Self {
+        Reader { bytes, pos }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    pub(crate) fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, Error> {
+        let b = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| format_err!("unexpected end of module at offset {}", self.pos))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() - self.pos < len {
+            bail!("unexpected end of module at offset {}", self.pos);
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub(crate) fn uleb(&mut self) -> Result<u32, Error> {
+        let mut result: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            result |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift > 28 {
+                bail!("integer too long at offset {}", self.pos);
+            }
+        }
+        if result > u64::from(std::u32::MAX) {
+            bail!("integer too large at offset {}", self.pos);
+        }
+        Ok(result as u32)
+    }
+
+    pub(crate) fn name(&mut self) -> Result<String, Error> {
+        let len = self.uleb()? as usize;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| format_err!("invalid name: {}", e))
+    }
+
+    /// Read a memory or table index, which must be 0 since only one of each is supported.
+    fn zero_index(&mut self) -> Result<(), Error> {
+        let pos = self.pos;
+        if self.uleb()? != 0 {
+            bail!(
+                "only memory and table index 0 are supported, at offset {}",
+                pos
+            );
+        }
+        Ok(())
+    }
+
+    /// Read the element kind of a segment, which must be `funcref`.
+    pub(crate) fn elem_kind(&mut self) -> Result<(), Error> {
+        let pos = self.pos;
+        let kind = self.byte()?;
+        if kind != 0x00 {
+            bail!("unsupported element kind {:#x} at offset {}", kind, pos);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn ref_type(&mut self) -> Result<RefType, Error> {
+        let pos = self.pos;
+        match self.byte()? {
+            FUNCREF => Ok(RefType::FuncRef),
+            EXTERNREF => Ok(RefType::ExternRef),
+            ty => bail!("unsupported reference type {:#x} at offset {}", ty, pos),
+        }
+    }
+
+    /// Read the element type and limits of a table.
+    pub(crate) fn table_type(&mut self) -> Result<(RefType, u32, Option<u32>), Error> {
+        let elem_type = self.ref_type()?;
+        let pos = self.pos;
+        let has_max = match self.uleb()? {
+            0 => false,
+            1 => true,
+            flags => bail!("unsupported limits flags {:#x} at offset {}", flags, pos),
+        };
+        let min_size = self.uleb()?;
+        let max_size = if has_max { Some(self.uleb()?) } else { None };
+        Ok((elem_type, min_size, max_size))
+    }
+
+    /// Read a vector of element expressions, each either `ref.func` or `ref.null`.
+    pub(crate) fn elem_exprs(&mut self) -> Result<Vec<Option<u32>>, Error> {
+        let mut exprs = vec![];
+        for _ in 0..self.uleb()? {
+            let pos = self.pos;
+            let expr = match self.byte()? {
+                0xd2 => Some(self.uleb()?),
+                0xd0 => {
+                    // older drafts of the proposal had no type immediate for `ref.null`
+                    if let Some(&FUNCREF) | Some(&EXTERNREF) = self.rest().first() {
+                        self.byte()?;
+                    }
+                    None
+                }
+                op => bail!("unsupported element expression {:#x} at offset {}", op, pos),
+            };
+            if self.byte()? != 0x0b {
+                bail!("element expression at offset {} is too long", pos);
+            }
+            exprs.push(expr);
+        }
+        Ok(exprs)
+    }
+
+    pub(crate) fn indices(&mut self) -> Result<Vec<u32>, Error> {
+        (0..self.uleb()?).map(|_| self.uleb()).collect()
+    }
+
+    pub(crate) fn value_types(&mut self) -> Result<Vec<u8>, Error> {
+        let len = self.uleb()? as usize;
+        Ok(self.bytes(len)?.to_vec())
+    }
+
+    fn value_type(&mut self) -> Result<ValueType, Error> {
+        let pos = self.pos;
+        match self.byte()? {
+            I32 => Ok(ValueType::I32),
+            I64 => Ok(ValueType::I64),
+            F32 => Ok(ValueType::F32),
+            F64 => Ok(ValueType::F64),
+            ty => bail!("unsupported value type {:#x} at offset {}", ty, pos),
+        }
+    }
+
+    pub(crate) fn block_type(&mut self) -> Result<BlockType, Error> {
+        match self.rest().first() {
+            // the empty block type and value types are encoded as negative single-byte integers
+            Some(&b) if b & 0xc0 == 0x40 => {
+                self.byte()?;
+                Ok(BlockType::Value(b))
+            }
+            _ => Ok(BlockType::Index(self.uleb()?)),
+        }
+    }
+
+    /// Skip a constant expression, including its `end`.
+    /// Read the elements of a segment as written by `write_elements`, shifting the function
+    /// indices by the given number of imports.
+    fn elements(&mut self, shift: u32) -> Result<Vec<Option<u32>>, Error> {
+        (0..self.uleb()?)
+            .map(|_| match self.uleb()? {
+                0 => Ok(None),
+                func => Ok(Some(func - 1 + shift)),
+            })
+            .collect()
+    }
+
+    /// Read the constant offset expression of a segment, which must be an `i32.const`.
+    fn const_offset(&mut self) -> Result<i32, Error> {
+        let pos = self.pos;
+        if self.byte()? != 0x41 {
+            bail!("unsupported offset expression at offset {}", pos);
+        }
+        let offset = self.sleb()?;
+        if self.byte()? != 0x0b {
+            bail!("offset expression at offset {} is too long", pos);
+        }
+        Ok(offset)
+    }
+
+    pub(crate) fn sleb(&mut self) -> Result<i32, Error> {
+        let mut result: i64 = 0;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            result |= i64::from(b & 0x7f) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                if b & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                break;
+            }
+            if shift > 28 {
+                bail!("integer too long at offset {}", self.pos);
+            }
+        }
+        if result < i64::from(std::i32::MIN) || result > i64::from(std::i32::MAX) {
+            bail!("integer too large at offset {}", self.pos);
+        }
+        Ok(result as i32)
+    }
+
+    fn skip_init_expr(&mut self) -> Result<(), Error> {
+        loop {
+            let opcode = self.byte()?;
+            if opcode == 0x0b {
+                return Ok(());
+            }
+            self.skip_immediates(opcode)?;
+        }
+    }
+
+    /// Skip a signed or unsigned LEB128 integer of any width.
+    pub(crate) fn skip_leb(&mut self) -> Result<(), Error> {
+        while self.byte()? & 0x80 != 0 {}
+        Ok(())
+    }
+
+    pub(crate) fn limits(&mut self) -> Result<(), Error> {
+        let has_max = self.uleb()? & 1 != 0;
+        self.uleb()?;
+        if has_max {
+            self.uleb()?;
+        }
+        Ok(())
+    }
+
+    /// Skip the immediates of an MVP operator.
+    pub(crate) fn skip_immediates(&mut self, opcode: u8) -> Result<(), Error> {
+        match opcode {
+            // unreachable, nop, else, end, return, drop, select, and the numeric operators
+            0x00 | 0x01 | 0x05 | 0x0b | 0x0f | 0x1a | 0x1b | 0x45..=0xbf => (),
+            // block, loop, if: block type
+            0x02..=0x04 => self.skip_leb()?,
+            // br, br_if, call, local and global accesses
+            0x0c | 0x0d | 0x10 | 0x20..=0x24 => self.skip_leb()?,
+            // br_table: label vector and default label
+            0x0e => {
+                for _ in 0..self.uleb()? {
+                    self.skip_leb()?;
+                }
+                self.skip_leb()?;
+            }
+            // call_indirect: type index and table index
+            0x11 => {
+                self.skip_leb()?;
+                self.skip_leb()?;
+            }
+            // loads and stores: alignment and offset
+            0x28..=0x3e => {
+                self.skip_leb()?;
+                self.skip_leb()?;
+            }
+            // memory.size, memory.grow: memory index
+            0x3f | 0x40 => self.skip_leb()?,
+            // i32.const, i64.const
+            0x41 | 0x42 => self.skip_leb()?,
+            // f32.const, f64.const
+            0x43 => {
+                self.bytes(4)?;
+            }
+            0x44 => {
+                self.bytes(8)?;
+            }
+            _ => bail!(
+                "unsupported operator {:#x} at offset {}",
+                opcode,
+                self.pos - 1
+            ),
+        }
+        Ok(())
+    }
+}
+
+fn write_uleb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let b = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(b);
+            return;
+        }
+        out.push(b | 0x80);
+    }
+}
+
+fn write_i32_const(out: &mut Vec<u8>, value: u32) {
+    out.push(0x41);
+    write_sleb(out, i64::from(value as i32));
+}
+
+fn write_func_type(out: &mut Vec<u8>, params: &[u8], results: &[u8]) {
+    out.push(0x60);
+    write_uleb(out, params.len() as u64);
+    out.extend_from_slice(params);
+    write_uleb(out, results.len() as u64);
+    out.extend_from_slice(results);
+}
+
+/// Encode the limits of a table.
+fn write_limits(out: &mut Vec<u8>, min_size: u32, max_size: Option<u32>) {
+    match max_size {
+        Some(max_size) => {
+            out.push(0x01);
+            write_uleb(out, u64::from(min_size));
+            write_uleb(out, u64::from(max_size));
+        }
+        None => {
+            out.push(0x00);
+            write_uleb(out, u64::from(min_size));
+        }
+    }
+}
+
+/// Encode the elements of a segment.
+fn write_elements(out: &mut Vec<u8>, functions: &[Option<u32>]) {
+    write_uleb(out, functions.len() as u64);
+    for func in functions {
+        // null elements are written as 0, and functions as their index plus one
+        write_uleb(out, func.map(|f| u64::from(f) + 1).unwrap_or(0));
+    }
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_uleb(out, name.len() as u64);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, payload: &[u8]) {
+    out.push(id);
+    write_uleb(out, payload.len() as u64);
+    out.extend_from_slice(payload);
+}
+
+fn write_custom_section(out: &mut Vec<u8>, name: &str, payload: &[u8]) {
+    let mut section = vec![];
+    write_name(&mut section, name);
+    section.extend_from_slice(payload);
+    write_section(out, SECTION_CUSTOM, &section);
+}
+
+fn write_sleb(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let b = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && b & 0x40 == 0) || (value == -1 && b & 0x40 != 0) {
+            out.push(b);
+            return;
+        }
+        out.push(b | 0x80);
+    }
+}
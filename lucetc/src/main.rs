@@ -3,7 +3,7 @@ mod options;
 use crate::options::{CodegenOutput, Options};
 use failure::{format_err, Error, ResultExt};
 use log::info;
-use lucetc::{Bindings, Lucetc, LucetcOpts};
+use lucetc::{Bindings, ExternalLinker, Lucetc, LucetcOpts};
 
 use std::io::{self, Write};
 use std::path::PathBuf;
@@ -46,6 +46,10 @@ pub fn run(opts: &Options) -> Result<(), Error> {
         .with_bindings(bindings)
         .with_opt_level(opts.opt_level);
 
+    for module in opts.linked_modules.iter() {
+        c.link_module(module);
+    }
+
     if let Some(ref builtins) = opts.builtins_path {
         c.builtins(builtins);
     }
@@ -62,6 +66,18 @@ pub fn run(opts: &Options) -> Result<(), Error> {
         c.guard_size(guard_size);
     }
 
+    if let Some(ref cache_dir) = opts.cache_dir {
+        c.cache_dir(cache_dir);
+    }
+
+    if opts.ld.is_some() || opts.ldflags.is_some() {
+        let default = ExternalLinker::default();
+        c.external_linker(ExternalLinker {
+            ld: opts.ld.clone().unwrap_or(default.ld),
+            ldflags: opts.ldflags.clone().unwrap_or(default.ldflags),
+        });
+    }
+
     match opts.codegen {
         CodegenOutput::Obj => c.object_file(&opts.output)?,
         CodegenOutput::SharedObj => c.shared_object_file(&opts.output)?,
@@ -1,6 +1,7 @@
 use clap::{App, Arg, ArgMatches};
 use failure::Error;
 use lucetc::{HeapSettings, OptLevel};
+use std::env;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -35,11 +36,15 @@ pub struct Options {
     pub input: Vec<PathBuf>,
     pub codegen: CodegenOutput,
     pub binding_files: Vec<PathBuf>,
+    pub linked_modules: Vec<String>,
     pub builtins_path: Option<PathBuf>,
     pub min_reserved_size: Option<u64>,
     pub max_reserved_size: Option<u64>,
     pub guard_size: Option<u64>,
     pub opt_level: OptLevel,
+    pub cache_dir: Option<PathBuf>,
+    pub ld: Option<String>,
+    pub ldflags: Option<String>,
 }
 
 impl Options {
@@ -58,6 +63,12 @@ impl Options {
             .map(PathBuf::from)
             .collect();
 
+        let linked_modules: Vec<String> = m
+            .values_of("link_module")
+            .unwrap_or_default()
+            .map(String::from)
+            .collect();
+
         let codegen = match m.value_of("emit") {
             None => CodegenOutput::SharedObj,
             Some("clif") => CodegenOutput::Clif,
@@ -94,16 +105,37 @@ impl Options {
             Some(_) => panic!("unknown value for opt-level"),
         };
 
+        let cache_dir = m
+            .value_of("cache_dir")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("LUCETC_CACHE_DIR").map(PathBuf::from));
+
+        // before `lucetc` had a linker of its own, it always ran `$LD $LDFLAGS`, so those still
+        // select an external linker when the options are not given
+        let from_env = |var: &str| env::var(var).ok().filter(|value| !value.is_empty());
+        let ld = m
+            .value_of("ld")
+            .map(String::from)
+            .or_else(|| from_env("LD"));
+        let ldflags = m
+            .value_of("ldflags")
+            .map(String::from)
+            .or_else(|| from_env("LDFLAGS"));
+
         Ok(Options {
             output,
             input,
             codegen,
             binding_files,
+            linked_modules,
             builtins_path,
             min_reserved_size,
             max_reserved_size,
             guard_size,
             opt_level,
+            cache_dir,
+            ld,
+            ldflags,
         })
     }
     pub fn get() -> Result<Self, Error> {
@@ -137,6 +169,14 @@ impl Options {
                     .number_of_values(1)
                     .help("path to bindings json file"),
             )
+            .arg(
+                Arg::with_name("link_module")
+                    .long("--link-module")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("name of an import module satisfied by another lucet module at runtime"),
+            )
             .arg(
                 Arg::with_name("min_reserved_size")
                     .long("--min-reserved-size")
@@ -183,6 +223,31 @@ impl Options {
                     .possible_values(&["default", "fastest", "best"])
                     .help("optimization level (default: 'default')"),
             )
+            .arg(
+                Arg::with_name("cache_dir")
+                    .long("--cache-dir")
+                    .takes_value(true)
+                    .help("directory to cache shared objects in (default: $LUCETC_CACHE_DIR, if set)"),
+            )
+            .arg(
+                Arg::with_name("ld")
+                    .long("--ld")
+                    .takes_value(true)
+                    .help(
+                        "link shared objects with this linker instead of the built-in one \
+                         (default: $LD, if set)",
+                    ),
+            )
+            .arg(
+                Arg::with_name("ldflags")
+                    .long("--ldflags")
+                    .takes_value(true)
+                    .allow_hyphen_values(true)
+                    .help(
+                        "flags for the external linker, which is ld if only these are given \
+                         (default: $LDFLAGS, if set, or -shared)",
+                    ),
+            )
             .get_matches();
 
         Self::from_args(&m)
@@ -1,9 +1,10 @@
 use crate::bindings::Bindings;
-use crate::program::types::{CtonSignature, FunctionSig};
+use crate::program::types::{data_valuetype, CtonSignature, FunctionSig};
 use cranelift_codegen::ir;
 use cranelift_module::Linkage;
 use failure::Error;
-use parity_wasm::elements::{FunctionType, ImportEntry};
+use lucet_module_data as data;
+use parity_wasm::elements::{FunctionType, ImportEntry, ValueType};
 
 pub trait Function {
     fn signature(&self) -> ir::Signature;
@@ -63,6 +64,7 @@ pub struct FunctionDef {
     sig: FunctionSig,
     exported: bool,
     symbol: String,
+    result_globals: Vec<(ValueType, u32)>,
 }
 
 impl FunctionDef {
@@ -72,9 +74,17 @@ impl FunctionDef {
             sig,
             exported,
             symbol,
+            result_globals: vec![],
         }
     }
 
+    /// Set the globals that the results of the function are passed through, when lowering
+    /// removed its several results from its signature.
+    pub fn with_result_globals(mut self, result_globals: Vec<(ValueType, u32)>) -> Self {
+        self.result_globals = result_globals;
+        self
+    }
+
     pub fn signature(&self) -> ir::Signature {
         self.sig.cton_signature()
     }
@@ -87,6 +97,24 @@ impl FunctionDef {
         &self.symbol
     }
 
+    /// The signature and export name of the function, if it is exported.
+    pub fn to_export(&self) -> Option<data::FunctionExport> {
+        if self.exported {
+            let name = self.symbol.trim_start_matches("guest_func_");
+            let result_globals = self
+                .result_globals
+                .iter()
+                .map(|(ty, global)| (data_valuetype(ty), *global))
+                .collect();
+            Some(
+                data::FunctionExport::new(name, self.sig.to_signature())
+                    .with_result_globals(result_globals),
+            )
+        } else {
+            None
+        }
+    }
+
     pub fn linkage(&self) -> Linkage {
         if self.exported {
             Linkage::Export
@@ -103,27 +131,50 @@ pub struct FunctionImport {
     field: String,
     sig: FunctionSig,
     symbol: String,
+    linked_index: Option<u32>,
 }
 
 impl FunctionImport {
+    /// Create a function import.
+    ///
+    /// Imports from modules marked as linked in `bindings` are given the next index from
+    /// `linked_count` in the instance's linked function table, and a local trampoline symbol that
+    /// calls through that table. All other imports are bound to hostcall symbols.
     pub fn new(
         wasmidx: u32,
         importentry: &ImportEntry,
         sig: FunctionSig,
         bindings: &Bindings,
+        linked_count: &mut u32,
     ) -> Result<Self, Error> {
         let module = String::from(importentry.module());
         let field = String::from(importentry.field());
-        let symbol = bindings.translate(&module, &field)?;
+        let (symbol, linked_index) = if bindings.is_linked(&module) {
+            let linked_index = *linked_count;
+            *linked_count += 1;
+            (
+                format!("guest_linked_import_{}", linked_index),
+                Some(linked_index),
+            )
+        } else {
+            (bindings.translate(&module, &field)?, None)
+        };
         Ok(Self {
             wasmidx,
             module,
             field,
             sig,
             symbol,
+            linked_index,
         })
     }
 
+    /// The index of this import in the linked function table, if it is satisfied by another Lucet
+    /// module rather than a hostcall.
+    pub fn linked_index(&self) -> Option<u32> {
+        self.linked_index
+    }
+
     pub fn module(&self) -> &str {
         self.module.as_str()
     }
@@ -144,8 +195,19 @@ impl FunctionImport {
         &self.symbol
     }
 
+    /// The module, field, and signature of the import, if it is linked to another module.
+    pub fn to_linked_import(&self) -> Option<data::LinkedImport> {
+        self.linked_index
+            .map(|_| data::LinkedImport::new(&self.module, &self.field, self.sig.to_signature()))
+    }
+
     pub fn linkage(&self) -> Linkage {
-        Linkage::Import
+        if self.linked_index.is_some() {
+            // the trampoline for a linked import is defined in this module
+            Linkage::Local
+        } else {
+            Linkage::Import
+        }
     }
 }
 
@@ -1,8 +1,9 @@
 use super::init_expr::const_init_expr;
-use super::types::cton_valuetype;
-use crate::error::LucetcError;
+use super::types::{cton_valuetype, data_valuetype};
+use crate::error::{LucetcError, LucetcErrorKind};
 use cranelift_codegen::ir;
-use parity_wasm::elements::{GlobalType, ImportEntry, InitExpr};
+use lucet_module_data as data;
+use parity_wasm::elements::{GlobalType, ImportEntry, InitExpr, ValueType};
 
 #[derive(Debug, Clone)]
 pub struct GlobalImport {
@@ -13,19 +14,28 @@ pub struct GlobalImport {
 }
 
 impl GlobalImport {
-    pub fn new(importentry: &ImportEntry, global_type: GlobalType, export: Option<String>) -> Self {
-        Self {
+    pub fn new(
+        importentry: &ImportEntry,
+        global_type: GlobalType,
+        export: Option<String>,
+    ) -> Result<Self, LucetcError> {
+        check_global_type(&global_type)?;
+        Ok(Self {
             module: String::from(importentry.module()),
             field: String::from(importentry.field()),
             global_type,
             export,
-        }
+        })
     }
 
     pub fn cton_type(&self) -> ir::Type {
         cton_valuetype(&self.global_type.content_type())
     }
 
+    pub fn data_type(&self) -> data::ValueType {
+        data_valuetype(&self.global_type.content_type())
+    }
+
     pub fn module(&self) -> &str {
         self.module.as_str()
     }
@@ -55,6 +65,7 @@ impl GlobalDef {
         init_expr: &InitExpr,
         export: Option<String>,
     ) -> Result<Self, LucetcError> {
+        check_global_type(&global_type)?;
         let value = const_init_expr(init_expr.code())?;
         Ok(Self {
             global_type: global_type,
@@ -65,6 +76,9 @@ impl GlobalDef {
     pub fn cton_type(&self) -> ir::Type {
         cton_valuetype(&self.global_type.content_type())
     }
+    pub fn data_type(&self) -> data::ValueType {
+        data_valuetype(&self.global_type.content_type())
+    }
     pub fn value(&self) -> i64 {
         self.value
     }
@@ -76,6 +90,14 @@ impl GlobalDef {
     }
 }
 
+/// Globals live in 64-bit slots at runtime, which cannot hold a `v128`.
+fn check_global_type(global_type: &GlobalType) -> Result<(), LucetcError> {
+    if global_type.content_type() == ValueType::V128 {
+        Err(LucetcErrorKind::Unsupported("v128 globals".to_owned()))?
+    }
+    Ok(())
+}
+
 pub enum Global {
     Import(GlobalImport),
     Def(GlobalDef),
@@ -89,6 +111,13 @@ impl Global {
         }
     }
 
+    pub fn data_type(&self) -> data::ValueType {
+        match self {
+            Global::Import(i) => i.data_type(),
+            Global::Def(d) => d.data_type(),
+        }
+    }
+
     pub fn as_import(&self) -> Option<&GlobalImport> {
         match self {
             Global::Import(i) => Some(i),
@@ -111,8 +140,6 @@ impl Global {
     }
 }
 
-use lucet_module_data as data;
-
 impl Global {
     pub fn to_spec(&self) -> data::GlobalSpec {
         let global = match self {
@@ -125,6 +152,6 @@ impl Global {
             },
         };
         let export = self.export();
-        data::GlobalSpec::new(global, export)
+        data::GlobalSpec::new(global, self.data_type(), export)
     }
 }
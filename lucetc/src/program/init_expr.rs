@@ -24,10 +24,15 @@ pub fn const_init_expr(opcodes: &[Instruction]) -> Result<i64, LucetcError> {
     match opcodes[0] {
         Instruction::I32Const(i32_const) => Ok(i32_const as i64),
         Instruction::I64Const(i64_const) => Ok(i64_const),
+        // floating point globals are stored as their bit patterns in the low bits of the slot
+        Instruction::F32Const(f32_bits) => Ok(f32_bits as i64),
+        Instruction::F64Const(f64_bits) => Ok(f64_bits as i64),
         _ => Err(format_err!(
-            "init expr is not a const integer expr, got {:?}",
+            "init expr is not a const scalar expr, got {:?}",
             opcodes
         ))
-        .context(LucetcErrorKind::Unsupported("non-int init expr".to_owned()))?,
+        .context(LucetcErrorKind::Unsupported(
+            "non-scalar init expr".to_owned(),
+        ))?,
     }
 }
@@ -4,6 +4,7 @@ use failure::{bail, Error};
 pub struct MemorySpec {
     pub initial_pages: u32,
     pub max_pages: Option<u32>,
+    pub shared: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -46,6 +47,7 @@ pub fn create_heap_spec(mem: &MemorySpec, heap: &HeapSettings) -> Result<HeapSpe
         guard_size: heap.guard_size,
         initial_size: initial_size,
         max_size: max_size,
+        shared: mem.shared,
     })
 }
 pub fn empty_heap_spec() -> HeapSpec {
@@ -54,5 +56,6 @@ pub fn empty_heap_spec() -> HeapSpec {
         guard_size: 0,
         initial_size: 0,
         max_size: None,
+        shared: false,
     }
 }
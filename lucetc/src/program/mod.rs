@@ -14,11 +14,12 @@ pub use self::globals::{Global, GlobalDef, GlobalImport};
 pub use self::memory::{create_heap_spec, empty_heap_spec, HeapSettings, HeapSpec, MemorySpec};
 pub use self::names::{module_names, ModuleNames};
 pub use self::runtime::Runtime;
-pub use self::table::{TableBuilder, TableDef};
+pub use self::table::{TableBuilder, TableDef, TableElem};
 pub use self::types::{CtonSignature, FunctionSig};
 
 use crate::bindings::Bindings;
 use crate::error::{LucetcError, LucetcErrorKind};
+use crate::lower::{Lowered, PassiveData, PassiveElements};
 use crate::program::init_expr::const_init_expr;
 use failure::{format_err, ResultExt};
 use parity_wasm::elements::{External, FuncBody, MemoryType, Module, TableElementType, Type};
@@ -38,6 +39,10 @@ pub struct Program {
 
     import_functions: Vec<FunctionImport>,
     import_memory: Option<MemorySpec>,
+
+    memory_exports: Vec<String>,
+
+    lowered: Lowered,
 }
 
 impl Program {
@@ -52,9 +57,10 @@ impl Program {
 
         let module = validate_module(module)?.unwrap();
         let names = module_names(&module)?;
-        let imports = module_imports(&module, bindings, &names)?;
-        let defs = module_definitions(&module, &imports, &names)?;
-        let tables = module_tables(&module, imports.tables)?;
+        let lowered = Lowered::from_module(&module)?;
+        let imports = module_imports(&module, bindings, &names, &lowered)?;
+        let defs = module_definitions(&module, &imports, &names, &lowered)?;
+        let tables = module_tables(&module, imports.tables, &names, &lowered)?;
         let globals = module_globals(imports.globals, defs.globals);
         let runtime = Runtime::liblucet_runtime_c();
         Ok(Self {
@@ -68,6 +74,10 @@ impl Program {
 
             import_functions: imports.functions,
             import_memory: imports.memory,
+
+            memory_exports: names.memory_exports().to_vec(),
+
+            lowered,
         })
     }
 
@@ -118,6 +128,15 @@ impl Program {
         self.runtime.get_symbol(name)
     }
 
+    /// The runtime function that calls to the given function must be compiled as, if it is one of
+    /// the helpers appended by `lower_post_mvp`.
+    pub fn runtime_call(&self, func_index: u32) -> Option<&str> {
+        self.lowered
+            .runtime_calls
+            .get(&func_index)
+            .map(|s| s.as_str())
+    }
+
     pub fn heap_spec(&self) -> Result<HeapSpec, LucetcError> {
         if let Some(ref mem_spec) = self.import_memory {
             Ok(create_heap_spec(mem_spec, &self.heap_settings)
@@ -135,6 +154,30 @@ impl Program {
         Ok(v)
     }
 
+    pub fn passive_data(&self) -> &[PassiveData] {
+        &self.lowered.passive_data
+    }
+
+    pub fn passive_elements(&self) -> &[PassiveElements] {
+        &self.lowered.passive_elements
+    }
+
+    /// The functions that `ref.func` refers to, in the order of the handles it passes to the
+    /// runtime.
+    pub fn ref_funcs(&self) -> &[u32] {
+        &self.lowered.ref_funcs
+    }
+
+    /// The type index and table index of an indirect call with the given type index. Calls through
+    /// tables other than table 0 use a copy of their type, added by `lower_post_mvp`.
+    pub fn indirect_call(&self, type_index: u32) -> (u32, u32) {
+        self.lowered
+            .indirect_calls
+            .get(&type_index)
+            .cloned()
+            .unwrap_or((type_index, 0))
+    }
+
     pub fn function_body(&self, def: &FunctionDef) -> &FuncBody {
         let bodies = self
             .module
@@ -154,6 +197,19 @@ impl Program {
     pub fn import_functions(&self) -> &[FunctionImport] {
         self.import_functions.as_ref()
     }
+
+    /// The function imports satisfied by other Lucet modules, in linked function table order.
+    pub fn linked_import_functions(&self) -> Vec<&FunctionImport> {
+        self.import_functions
+            .iter()
+            .filter(|f| f.linked_index().is_some())
+            .collect()
+    }
+
+    /// The names under which the module exports its linear memory.
+    pub fn memory_exports(&self) -> &[String] {
+        &self.memory_exports
+    }
 }
 
 pub struct ModuleTables {
@@ -161,15 +217,17 @@ pub struct ModuleTables {
 }
 
 enum TableDecl {
-    Import(TableDef),
+    Import(TableBuilder),
     Def(TableBuilder),
 }
 
-fn memory_spec(mem: &MemoryType) -> MemorySpec {
+/// Lowering rewrites shared memories to unshared ones, and records whether the memory was shared.
+fn memory_spec(mem: &MemoryType, lowered: &Lowered) -> MemorySpec {
     let limits = mem.limits();
     MemorySpec {
         initial_pages: limits.initial(),
         max_pages: limits.maximum(),
+        shared: lowered.shared_memory,
     }
 }
 
@@ -189,7 +247,7 @@ struct ModuleImports {
     memory: Option<MemorySpec>,
     functions: Vec<FunctionImport>,
     globals: Vec<GlobalImport>,
-    tables: Vec<TableDef>,
+    tables: Vec<TableBuilder>,
 }
 
 impl ModuleImports {
@@ -205,18 +263,26 @@ fn module_imports(
     module: &Module,
     bindings: Bindings,
     names: &ModuleNames,
+    lowered: &Lowered,
 ) -> Result<ModuleImports, LucetcError> {
     let mut memory = None;
     let mut functions = Vec::new();
     let mut globals = Vec::new();
     let mut tables = Vec::new();
+    let mut linked_count = 0;
     if let Some(import_section) = module.import_section() {
         for entry in import_section.entries().iter() {
             match entry.external() {
                 &External::Function(typeix) => {
                     let functionix = functions.len() as u32;
                     let ftype = module_get_signature(&module, typeix)?;
-                    functions.push(FunctionImport::new(functionix, entry, ftype, &bindings)?)
+                    functions.push(FunctionImport::new(
+                        functionix,
+                        entry,
+                        ftype,
+                        &bindings,
+                        &mut linked_count,
+                    )?)
                 }
                 &External::Global(ref gty) => {
                     let globalix = globals.len() as u32;
@@ -224,16 +290,17 @@ fn module_imports(
                         entry,
                         gty.clone(),
                         names.global_symbol(globalix),
-                    ))
+                    )?)
                 }
 
                 &External::Table(ref tty) => {
                     let tableix = tables.len() as u32;
-                    let builder =
+                    let mut builder =
                         TableBuilder::new(tableix, tty.limits().initial(), tty.limits().maximum())?;
-                    tables.push(builder.finalize());
+                    builder.import(entry.module(), entry.field());
+                    tables.push(builder);
                 }
-                &External::Memory(ref mem) => memory = Some(memory_spec(mem)),
+                &External::Memory(ref mem) => memory = Some(memory_spec(mem, lowered)),
             }
         }
     }
@@ -255,6 +322,7 @@ fn module_definitions(
     module: &Module,
     imports: &ModuleImports,
     names: &ModuleNames,
+    lowered: &Lowered,
 ) -> Result<ModuleDefs, LucetcError> {
     let mut memory = None;
 
@@ -266,12 +334,16 @@ fn module_definitions(
     for (ix, decl) in decls.iter().enumerate() {
         let funcindex = ix as u32 + imports.function_index_base();
 
-        funcs.push(FunctionDef::new(
+        let mut func = FunctionDef::new(
             funcindex,
             module_get_signature(&module, decl.type_ref()).expect("signature for func must exist"),
             names.function_exported(funcindex),
             names.function_symbol(funcindex),
-        ))
+        );
+        if let Some(results) = lowered.multi_value.get(&funcindex) {
+            func = func.with_result_globals(results.clone());
+        }
+        funcs.push(func)
     }
 
     if let Some(memory_types) = module.memory_section().map(|s| s.entries()) {
@@ -279,7 +351,7 @@ fn module_definitions(
             Err(format_err!("multiple memories are not supported"))?
         }
         for memory_type in memory_types.iter() {
-            memory = Some(memory_spec(memory_type))
+            memory = Some(memory_spec(memory_type, lowered))
         }
     }
 
@@ -303,11 +375,13 @@ fn module_definitions(
 
 fn module_tables(
     module: &Module,
-    import_tables: Vec<TableDef>,
+    import_tables: Vec<TableBuilder>,
+    names: &ModuleNames,
+    lowered: &Lowered,
 ) -> Result<Vec<TableDef>, LucetcError> {
     let mut tables = HashMap::new();
-    for tbl in import_tables.iter() {
-        tables.insert(tbl.index(), TableDecl::Import(tbl.clone()));
+    for tbl in import_tables.into_iter() {
+        tables.insert(tbl.index(), TableDecl::Import(tbl));
     }
     if let Some(table_section) = module.table_section() {
         for table_type in table_section.entries().iter() {
@@ -336,48 +410,109 @@ fn module_tables(
                 }
             }
         }
-        if let Some(element_section) = module.elements_section() {
-            for (segment_ix, element_segment) in element_section.entries().iter().enumerate() {
-                let table_ix = element_segment.index();
-                let offs: i64 = const_init_expr(
-                    element_segment
-                        .offset()
-                        .as_ref()
-                        .ok_or(format_err!("Offset not found"))?
-                        .code(),
-                )
-                .context(LucetcErrorKind::Other(format!(
-                    "in element segment offset for table {}, segment {}",
-                    table_ix, segment_ix
-                )))?;
-                // Ensure its safe to make into an i32:
-                assert!(offs >= <i32>::min_value() as i64 && offs <= <i32>::max_value() as i64);
-                match tables.get_mut(&table_ix) {
-                    Some(TableDecl::Def(ref mut builder)) => builder
-                        .push_elements(offs as i32, element_segment.members().to_vec())
-                        .context(LucetcErrorKind::Other(format!(
-                            "in elements for table {}, segment {}",
-                            table_ix, segment_ix
-                        )))?,
-                    Some(TableDecl::Import(_)) => Err(format_err!(
-                        "Cannot define element for imported table {}",
-                        table_ix
-                    ))?,
-                    None => Err(format_err!(
-                        "Cannot define element for undeclared table {}",
-                        table_ix
-                    ))?,
+    }
+    if let Some(element_section) = module.elements_section() {
+        for (segment_ix, element_segment) in element_section.entries().iter().enumerate() {
+            let table_ix = element_segment.index();
+            let offs: i64 = const_init_expr(
+                element_segment
+                    .offset()
+                    .as_ref()
+                    .ok_or(format_err!("Offset not found"))?
+                    .code(),
+            )
+            .context(LucetcErrorKind::Other(format!(
+                "in element segment offset for table {}, segment {}",
+                table_ix, segment_ix
+            )))?;
+            // Ensure its safe to make into an i32:
+            assert!(offs >= <i32>::min_value() as i64 && offs <= <i32>::max_value() as i64);
+            match tables.get_mut(&table_ix) {
+                // Elements for an imported table initialize this module's copy of it, just like
+                // elements for a defined table.
+                Some(TableDecl::Import(ref mut builder))
+                | Some(TableDecl::Def(ref mut builder)) => builder
+                    .push_elements(
+                        offs as i32,
+                        element_segment
+                            .members()
+                            .iter()
+                            .map(|func| TableElem::FunctionIx(*func))
+                            .collect(),
+                    )
+                    .context(LucetcErrorKind::Other(format!(
+                        "in elements for table {}, segment {}",
+                        table_ix, segment_ix
+                    )))?,
+                None => Err(format_err!(
+                    "Cannot define element for undeclared table {}",
+                    table_ix
+                ))?,
+            }
+        }
+    }
+    // The module only declares table 0; the other tables, and the segments that initialize them,
+    // are described by `lower_post_mvp`.
+    for (table_ix, table) in lowered.tables.iter().enumerate() {
+        let table_ix = table_ix as u32;
+        if table_ix > 0 {
+            let mut builder = TableBuilder::new(table_ix, table.min_size, table.max_size)?;
+            if let Some(ref export) = table.export {
+                builder.export(export);
+            }
+            let decl = match table.import {
+                Some((ref module, ref field)) => {
+                    builder.import(module, field);
+                    TableDecl::Import(builder)
                 }
+                None => TableDecl::Def(builder),
+            };
+            tables.insert(table_ix, decl);
+        }
+        match tables.get_mut(&table_ix) {
+            Some(TableDecl::Import(ref mut builder)) | Some(TableDecl::Def(ref mut builder)) => {
+                builder.set_elem_type(table.elem_type)
             }
+            None => Err(format_err!("Cannot declare undeclared table {}", table_ix))?,
         }
     }
-    let tables = tables
-        .values()
-        .map(|decl| match decl {
-            TableDecl::Import(def) => def.clone(),
-            TableDecl::Def(builder) => builder.finalize(),
+    for (segment_ix, segment) in lowered.table_segments.iter().enumerate() {
+        let elems = segment
+            .functions
+            .iter()
+            .map(|func| match func {
+                Some(func) => TableElem::FunctionIx(*func),
+                None => TableElem::Empty,
+            })
+            .collect();
+        match tables.get_mut(&segment.table) {
+            Some(TableDecl::Import(ref mut builder)) | Some(TableDecl::Def(ref mut builder)) => {
+                builder
+                    .push_elements(segment.offset, elems)
+                    .context(LucetcErrorKind::Other(format!(
+                        "in elements for table {}, lowered segment {}",
+                        segment.table, segment_ix
+                    )))?
+            }
+            None => Err(format_err!(
+                "Cannot define element for undeclared table {}",
+                segment.table
+            ))?,
+        }
+    }
+    let mut tables: Vec<TableDef> = tables
+        .values_mut()
+        .map(|decl| {
+            let builder = match decl {
+                TableDecl::Import(builder) | TableDecl::Def(builder) => builder,
+            };
+            if let Some(export) = names.table_export(builder.index()) {
+                builder.export(&export);
+            }
+            builder.finalize()
         })
         .collect();
+    tables.sort_by_key(|t| t.index());
     Ok(tables)
 }
 
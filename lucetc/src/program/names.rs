@@ -7,6 +7,8 @@ pub struct ModuleNames {
     func_exports: Vec<u32>,
     func_names: BiMap<u32, String>,
     glob_exports: HashMap<usize, String>,
+    table_exports: HashMap<u32, String>,
+    mem_exports: Vec<String>,
 }
 
 impl ModuleNames {
@@ -28,6 +30,12 @@ impl ModuleNames {
     pub fn global_symbol(&self, ix: u32) -> Option<String> {
         self.glob_exports.get(&(ix as usize)).cloned()
     }
+    pub fn table_export(&self, ix: u32) -> Option<String> {
+        self.table_exports.get(&ix).cloned()
+    }
+    pub fn memory_exports(&self) -> &[String] {
+        &self.mem_exports
+    }
 }
 
 fn define_unique_name(func_names: &mut BiMap<u32, String>, ix: u32, n: String) {
@@ -52,6 +60,8 @@ pub fn module_names(module: &Module) -> Result<ModuleNames, LucetcError> {
     let mut func_exports = Vec::new();
     let mut func_names = BiMap::new();
     let mut glob_exports = HashMap::new();
+    let mut table_exports = HashMap::new();
+    let mut mem_exports = Vec::new();
 
     if let Some(export_entries) = module.export_section().map(|s| s.entries()) {
         for entry in export_entries.iter() {
@@ -63,8 +73,12 @@ pub fn module_names(module: &Module) -> Result<ModuleNames, LucetcError> {
                 Internal::Global(idx) => {
                     glob_exports.insert(idx as usize, String::from(entry.field()));
                 }
-                Internal::Table(_) => {} // We do not do anything with exported tables
-                Internal::Memory(_) => {} // We do not do anything with exported memories
+                Internal::Table(idx) => {
+                    table_exports.insert(idx, String::from(entry.field()));
+                }
+                Internal::Memory(_) => {
+                    mem_exports.push(String::from(entry.field()));
+                }
             }
         }
     }
@@ -89,6 +103,8 @@ pub fn module_names(module: &Module) -> Result<ModuleNames, LucetcError> {
     Ok(ModuleNames {
         func_exports,
         glob_exports,
+        table_exports,
+        mem_exports,
         func_names,
     })
 }
@@ -12,10 +12,109 @@ impl Runtime {
     pub fn liblucet_runtime_c() -> Self {
         let current_memory_type = FunctionType::new(Vec::new(), Some(ValueType::I32));
         let grow_memory_type = FunctionType::new(vec![ValueType::I32], Some(ValueType::I32));
+        // the bulk memory and table operators return nonzero if they are out of bounds
+        let bulk_type =
+            |params| FunctionType::new(vec![ValueType::I32; params], Some(ValueType::I32));
+        let drop_type = FunctionType::new(vec![ValueType::I32], None);
+        // the atomic operators take their checked effective address first
+        use ValueType::{I32, I64};
+        let atomic_type = |params: &[ValueType], ret| FunctionType::new(params.to_vec(), ret);
+        // the table operators take the table index last, and pass references as `i64`
+        let table_type = |params: &[ValueType], ret| FunctionType::new(params.to_vec(), ret);
         Self {
             funcs: vec![
                 FunctionRuntime::new(0, "lucet_vmctx_current_memory", current_memory_type),
                 FunctionRuntime::new(1, "lucet_vmctx_grow_memory", grow_memory_type),
+                FunctionRuntime::new(2, "lucet_vmctx_memory_init", bulk_type(4)),
+                FunctionRuntime::new(3, "lucet_vmctx_data_drop", drop_type.clone()),
+                FunctionRuntime::new(4, "lucet_vmctx_memory_copy", bulk_type(3)),
+                FunctionRuntime::new(5, "lucet_vmctx_memory_fill", bulk_type(3)),
+                FunctionRuntime::new(6, "lucet_vmctx_table_init", bulk_type(5)),
+                FunctionRuntime::new(7, "lucet_vmctx_elem_drop", drop_type),
+                FunctionRuntime::new(8, "lucet_vmctx_table_copy", bulk_type(5)),
+                FunctionRuntime::new(
+                    9,
+                    "lucet_vmctx_atomic_notify",
+                    atomic_type(&[I32, I32], Some(I32)),
+                ),
+                FunctionRuntime::new(
+                    10,
+                    "lucet_vmctx_atomic_wait32",
+                    atomic_type(&[I32, I32, I64], Some(I32)),
+                ),
+                FunctionRuntime::new(
+                    11,
+                    "lucet_vmctx_atomic_wait64",
+                    atomic_type(&[I32, I64, I64], Some(I32)),
+                ),
+                FunctionRuntime::new(12, "lucet_vmctx_atomic_fence", atomic_type(&[], None)),
+                FunctionRuntime::new(
+                    13,
+                    "lucet_vmctx_atomic_load_i32",
+                    atomic_type(&[I32, I32], Some(I32)),
+                ),
+                FunctionRuntime::new(
+                    14,
+                    "lucet_vmctx_atomic_load_i64",
+                    atomic_type(&[I32, I32], Some(I64)),
+                ),
+                FunctionRuntime::new(
+                    15,
+                    "lucet_vmctx_atomic_store_i32",
+                    atomic_type(&[I32, I32, I32], None),
+                ),
+                FunctionRuntime::new(
+                    16,
+                    "lucet_vmctx_atomic_store_i64",
+                    atomic_type(&[I32, I64, I32], None),
+                ),
+                FunctionRuntime::new(
+                    17,
+                    "lucet_vmctx_atomic_rmw_i32",
+                    atomic_type(&[I32, I32, I32, I32], Some(I32)),
+                ),
+                FunctionRuntime::new(
+                    18,
+                    "lucet_vmctx_atomic_rmw_i64",
+                    atomic_type(&[I32, I64, I32, I32], Some(I64)),
+                ),
+                FunctionRuntime::new(
+                    19,
+                    "lucet_vmctx_atomic_cmpxchg_i32",
+                    atomic_type(&[I32, I32, I32, I32], Some(I32)),
+                ),
+                FunctionRuntime::new(
+                    20,
+                    "lucet_vmctx_atomic_cmpxchg_i64",
+                    atomic_type(&[I32, I64, I64, I32], Some(I64)),
+                ),
+                FunctionRuntime::new(
+                    21,
+                    "lucet_vmctx_table_grow",
+                    table_type(&[I64, I32, I32], Some(I32)),
+                ),
+                FunctionRuntime::new(22, "lucet_vmctx_table_size", table_type(&[I32], Some(I32))),
+                FunctionRuntime::new(
+                    23,
+                    "lucet_vmctx_table_fill",
+                    table_type(&[I32, I64, I32, I32], Some(I32)),
+                ),
+                FunctionRuntime::new(
+                    24,
+                    "lucet_vmctx_table_get",
+                    table_type(&[I32, I32], Some(I64)),
+                ),
+                FunctionRuntime::new(
+                    25,
+                    "lucet_vmctx_table_set",
+                    table_type(&[I32, I64, I32], Some(I32)),
+                ),
+                FunctionRuntime::new(
+                    26,
+                    "lucet_vmctx_table_check",
+                    table_type(&[I32, I32, I32], Some(I32)),
+                ),
+                FunctionRuntime::new(27, "lucet_vmctx_ref_func", table_type(&[I32], Some(I64))),
             ],
         }
     }
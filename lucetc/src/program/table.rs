@@ -1,14 +1,18 @@
 use failure::{format_err, Error};
+use lucet_module_data::{RefType, TableSpec};
 use std::cmp;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct TableBuilder {
     index: u32,
+    elem_type: RefType,
     min_size: u32,
     max_size: Option<u32>,
     /// Map from icall index to function
-    elems: HashMap<usize, u32>,
+    elems: HashMap<usize, TableElem>,
+    import: Option<(String, String)>,
+    export: Option<String>,
 }
 
 impl TableBuilder {
@@ -24,13 +28,36 @@ impl TableBuilder {
         }
         Ok(Self {
             index: index,
+            elem_type: RefType::FuncRef,
             min_size: min_size,
             max_size: max_size,
             elems: HashMap::new(),
+            import: None,
+            export: None,
         })
     }
 
-    pub fn push_elements(&mut self, offset: i32, elems: Vec<u32>) -> Result<(), Error> {
+    /// Mark the table as imported from `field` of the WebAssembly module `module`.
+    pub fn import(&mut self, module: &str, field: &str) {
+        self.import = Some((module.to_owned(), field.to_owned()));
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Set the type of the references the table holds, which is `funcref` unless the reference
+    /// types proposal is in use.
+    pub fn set_elem_type(&mut self, elem_type: RefType) {
+        self.elem_type = elem_type;
+    }
+
+    /// Export the table under `name`.
+    pub fn export(&mut self, name: &str) {
+        self.export = Some(name.to_owned());
+    }
+
+    pub fn push_elements(&mut self, offset: i32, elems: Vec<TableElem>) -> Result<(), Error> {
         if offset < 0 {
             return Err(format_err!(
                 "table elements given at negative offset {}",
@@ -53,7 +80,7 @@ impl TableBuilder {
                     ));
                 }
             }
-            self.elems.insert(offset as usize + i, *e);
+            self.elems.insert(offset as usize + i, e.clone());
         }
         Ok(())
     }
@@ -70,14 +97,19 @@ impl TableBuilder {
         let mut elems = Vec::with_capacity(capacity);
         for index in 0..capacity {
             match self.elems.get(&index) {
-                Some(value) => elems.push(TableElem::FunctionIx(*value)),
+                Some(value) => elems.push(value.clone()),
                 None => elems.push(TableElem::Empty),
             }
         }
 
         TableDef {
             index: self.index,
+            elem_type: self.elem_type,
             elems: elems,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            import: self.import.clone(),
+            export: self.export.clone(),
         }
     }
 }
@@ -91,13 +123,21 @@ pub enum TableElem {
 #[derive(Debug, Clone)]
 pub struct TableDef {
     index: u32,
+    elem_type: RefType,
     elems: Vec<TableElem>,
+    min_size: u32,
+    max_size: Option<u32>,
+    import: Option<(String, String)>,
+    export: Option<String>,
 }
 
 impl TableDef {
     pub fn index(&self) -> u32 {
         self.index
     }
+    pub fn elem_type(&self) -> RefType {
+        self.elem_type
+    }
     pub fn elements(&self) -> &[TableElem] {
         &self.elems
     }
@@ -111,4 +151,15 @@ impl TableDef {
     pub fn len_symbol(&self) -> String {
         format!("{}_len", self.symbol())
     }
+    pub fn to_spec(&self) -> TableSpec {
+        TableSpec::new(
+            self.elem_type,
+            self.min_size,
+            self.max_size,
+            self.import
+                .as_ref()
+                .map(|(module, field)| (module.as_str(), field.as_str())),
+            self.export.as_ref().map(|e| e.as_str()),
+        )
+    }
 }
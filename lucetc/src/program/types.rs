@@ -1,4 +1,5 @@
 use cranelift_codegen::{ir, isa};
+use lucet_module_data as data;
 use parity_wasm::elements::{FunctionType, ValueType};
 
 #[derive(Debug, Clone)]
@@ -14,6 +15,13 @@ impl FunctionSig {
             ftype: ftype.clone(),
         }
     }
+
+    pub fn to_signature(&self) -> data::Signature {
+        data::Signature::new(
+            self.ftype.params().iter().map(data_valuetype).collect(),
+            self.ftype.return_type().as_ref().map(data_valuetype),
+        )
+    }
 }
 
 pub fn cton_valuetype(t: &ValueType) -> ir::Type {
@@ -22,7 +30,17 @@ pub fn cton_valuetype(t: &ValueType) -> ir::Type {
         &ValueType::I64 => ir::types::I64,
         &ValueType::F32 => ir::types::F32,
         &ValueType::F64 => ir::types::F64,
-        &ValueType::V128 => unimplemented!(),
+        &ValueType::V128 => ir::types::I8X16,
+    }
+}
+
+pub fn data_valuetype(t: &ValueType) -> data::ValueType {
+    match t {
+        &ValueType::I32 => data::ValueType::I32,
+        &ValueType::I64 => data::ValueType::I64,
+        &ValueType::F32 => data::ValueType::F32,
+        &ValueType::F64 => data::ValueType::F64,
+        &ValueType::V128 => data::ValueType::V128,
     }
 }
 
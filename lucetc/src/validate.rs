@@ -0,0 +1,1042 @@
+//! Validation of function bodies, before a module is lowered and compiled.
+//!
+//! The code generator assumes that every function body is well-typed, and `pwasm_validation` only
+//! sees a module after `lower_post_mvp` has rewritten it, so an invalid body could reach the code
+//! generator and make it panic, or be rejected without any indication of where it went wrong.
+//!
+//! This pass type-checks each function body of the binary module the way the WebAssembly
+//! specification does, including the post-MVP operators that `lower_post_mvp` supports. The first
+//! error is reported as a [`ValidationError`](struct.ValidationError.html) with the index and name
+//! of the function and the offset of the operator in the module, in the context of
+//! `LucetcErrorKind::Validation`.
+//!
+//! The rest of the module is still validated by `pwasm_validation` once it is lowered, and SIMD
+//! operators are left for `lower_post_mvp` to reject.
+
+use crate::error::LucetcErrorKind;
+use crate::lower::{
+    AtomicOp, BlockType, FuncType, Reader, EXTERNREF, F32, F64, FUNCREF, I32, I64, SECTION_CODE,
+    SECTION_CUSTOM, SECTION_DATA, SECTION_DATA_COUNT, SECTION_ELEMENT, SECTION_FUNCTION,
+    SECTION_GLOBAL, SECTION_IMPORT, SECTION_MEMORY, SECTION_TABLE, SECTION_TYPE,
+};
+use failure::{bail, format_err, Error, Fail};
+use lucet_module_data::RefType;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+const V128: u8 = 0x7b;
+
+/// The type of a `ref.null` without a type immediate, as older drafts of the reference types
+/// proposal encode it, which can be used as either reference type.
+const NULLREF: u8 = 0x6e;
+
+/// The most locals a function may have, as in the JavaScript embedding.
+const MAX_LOCALS: u64 = 50_000;
+
+/// The natural alignment, as a power of two, and the value type of the loads and stores from
+/// `i32.load` (`0x28`) to `i64.store32` (`0x3e`). The stores start at `0x36`.
+const MEMORY_ACCESSES: [(u32, u8); 23] = [
+    (2, I32),
+    (3, I64),
+    (2, F32),
+    (3, F64),
+    (0, I32),
+    (0, I32),
+    (1, I32),
+    (1, I32),
+    (0, I64),
+    (0, I64),
+    (1, I64),
+    (1, I64),
+    (2, I64),
+    (2, I64),
+    (2, I32),
+    (3, I64),
+    (2, F32),
+    (3, F64),
+    (0, I32),
+    (1, I32),
+    (0, I64),
+    (1, I64),
+    (2, I64),
+];
+
+/// A function body that failed to validate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The index of the function, counting imported functions.
+    pub func_index: u32,
+    /// The name of the function in the name section, if it has one.
+    pub func_name: Option<String>,
+    /// The offset in the module of the operator that is invalid.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl Fail for ValidationError {}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "function {}", self.func_index)?;
+        if let Some(ref name) = self.func_name {
+            write!(f, " `{}`", name)?;
+        }
+        write!(f, " at offset {}: {}", self.offset, self.message)
+    }
+}
+
+/// Check that every function body of a binary module is valid.
+///
+/// An invalid body is reported as a [`ValidationError`](struct.ValidationError.html) in the
+/// context of `LucetcErrorKind::Validation`. A module too malformed to find its function bodies in
+/// is reported as it is found.
+pub fn validate_code(wasm: &[u8]) -> Result<(), Error> {
+    if wasm.len() < 8 {
+        bail!("module is too short to contain a header");
+    }
+    let mut sections = vec![];
+    let mut reader = Reader::new(wasm);
+    reader.bytes(8)?;
+    while !reader.is_empty() {
+        let id = reader.byte()?;
+        let len = reader.uleb()? as usize;
+        let payload = reader.bytes(len)?;
+        sections.push((id, reader.position() - len, payload));
+    }
+
+    let ctx = ModuleContext::new(&sections)?;
+    for &(_, start, payload) in sections.iter().filter(|(id, _, _)| *id == SECTION_CODE) {
+        // read the section in place, so that positions are offsets in the module
+        let mut reader = Reader::at(&wasm[..start + payload.len()], start);
+        let count = reader.uleb()?;
+        if count as usize != ctx.funcs.len() - ctx.imported_funcs as usize {
+            bail!("the function and code sections have different lengths");
+        }
+        for defined in 0..count {
+            let size = reader.uleb()? as usize;
+            let body_start = reader.position();
+            reader.bytes(size)?;
+            let func_index = ctx.imported_funcs + defined;
+            let body = Reader::at(&wasm[..body_start + size], body_start);
+            FuncValidator::new(&ctx, func_index)?
+                .validate(body)
+                .map_err(|(offset, message)| {
+                    let err = ValidationError {
+                        func_index,
+                        func_name: ctx.names.get(&func_index).cloned(),
+                        offset,
+                        message: message.to_string(),
+                    };
+                    Error::from(err).context(LucetcErrorKind::Validation)
+                })?;
+        }
+    }
+    Ok(())
+}
+
+/// What function bodies may refer to in the rest of the module.
+#[derive(Default)]
+struct ModuleContext {
+    types: Vec<FuncType>,
+    /// The type index of every function, imported ones first.
+    funcs: Vec<u32>,
+    imported_funcs: u32,
+    /// The element type of every table.
+    tables: Vec<u8>,
+    memories: u32,
+    /// The value type and mutability of every global.
+    globals: Vec<(u8, bool)>,
+    /// The element type of every element segment.
+    elems: Vec<u8>,
+    data_segments: u32,
+    names: HashMap<u32, String>,
+}
+
+impl ModuleContext {
+    fn new(sections: &[(u8, usize, &[u8])]) -> Result<Self, Error> {
+        let mut ctx = ModuleContext::default();
+        for &(id, _, payload) in sections {
+            let mut reader = Reader::new(payload);
+            match id {
+                SECTION_TYPE => {
+                    for _ in 0..reader.uleb()? {
+                        let form = reader.byte()?;
+                        if form != 0x60 {
+                            bail!("unsupported type form {:#x}", form);
+                        }
+                        let params = reader.value_types()?;
+                        let results = reader.value_types()?;
+                        for ty in params.iter().chain(results.iter()) {
+                            check_value_type(*ty)?;
+                        }
+                        ctx.types.push(FuncType { params, results });
+                    }
+                }
+                SECTION_IMPORT => {
+                    for _ in 0..reader.uleb()? {
+                        reader.name()?;
+                        reader.name()?;
+                        match reader.byte()? {
+                            0x00 => {
+                                ctx.funcs.push(reader.uleb()?);
+                                ctx.imported_funcs += 1;
+                            }
+                            0x01 => ctx.tables.push(ref_type_code(reader.table_type()?.0)),
+                            0x02 => {
+                                reader.limits()?;
+                                ctx.memories += 1;
+                            }
+                            0x03 => {
+                                let ty = reader.byte()?;
+                                ctx.globals.push((ty, reader.byte()? == 1));
+                            }
+                            kind => bail!("unknown import kind {:#x}", kind),
+                        }
+                    }
+                }
+                SECTION_FUNCTION => ctx.funcs.extend(reader.indices()?),
+                SECTION_TABLE => {
+                    for _ in 0..reader.uleb()? {
+                        ctx.tables.push(ref_type_code(reader.table_type()?.0));
+                    }
+                }
+                SECTION_MEMORY => ctx.memories += reader.uleb()?,
+                SECTION_GLOBAL => {
+                    for _ in 0..reader.uleb()? {
+                        let ty = reader.byte()?;
+                        ctx.globals.push((ty, reader.byte()? == 1));
+                        skip_const_expr(&mut reader)?;
+                    }
+                }
+                SECTION_ELEMENT => {
+                    for _ in 0..reader.uleb()? {
+                        ctx.elems.push(elem_segment_type(&mut reader)?);
+                    }
+                }
+                SECTION_DATA_COUNT => ctx.data_segments = reader.uleb()?,
+                SECTION_DATA => ctx.data_segments = reader.uleb()?,
+                SECTION_CUSTOM => {
+                    if reader.name()? == "name" {
+                        // the name section is only used to describe errors, so ignore it if it is
+                        // malformed
+                        ctx.names = function_names(&mut reader).unwrap_or_default();
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(ctx)
+    }
+
+    fn type_at(&self, index: u32) -> Result<&FuncType, Error> {
+        self.types
+            .get(index as usize)
+            .ok_or_else(|| format_err!("type index {} out of bounds", index))
+    }
+
+    fn func_type(&self, func: u32) -> Result<&FuncType, Error> {
+        let index = self
+            .funcs
+            .get(func as usize)
+            .ok_or_else(|| format_err!("function index {} out of bounds", func))?;
+        self.type_at(*index)
+    }
+
+    fn table(&self, table: u32) -> Result<u8, Error> {
+        self.tables
+            .get(table as usize)
+            .cloned()
+            .ok_or_else(|| format_err!("table index {} out of bounds", table))
+    }
+
+    fn global(&self, global: u32) -> Result<(u8, bool), Error> {
+        self.globals
+            .get(global as usize)
+            .cloned()
+            .ok_or_else(|| format_err!("global index {} out of bounds", global))
+    }
+
+    fn elem(&self, segment: u32) -> Result<u8, Error> {
+        self.elems
+            .get(segment as usize)
+            .cloned()
+            .ok_or_else(|| format_err!("element segment index {} out of bounds", segment))
+    }
+
+    fn data(&self, segment: u32) -> Result<(), Error> {
+        if segment >= self.data_segments {
+            bail!("data segment index {} out of bounds", segment);
+        }
+        Ok(())
+    }
+}
+
+/// Read the element type of an element segment, and skip the rest of it.
+fn elem_segment_type(reader: &mut Reader) -> Result<u8, Error> {
+    let ty = match reader.uleb()? {
+        0 => {
+            skip_const_expr(reader)?;
+            reader.indices()?;
+            FUNCREF
+        }
+        1 => {
+            match reader.byte()? {
+                0x00 => reader.indices()?,
+                // older drafts of the bulk memory proposal gave the element type and expressions
+                FUNCREF => {
+                    reader.elem_exprs()?;
+                    vec![]
+                }
+                kind => bail!("unsupported element kind {:#x}", kind),
+            };
+            FUNCREF
+        }
+        2 => {
+            reader.uleb()?;
+            skip_const_expr(reader)?;
+            reader.elem_kind()?;
+            reader.indices()?;
+            FUNCREF
+        }
+        3 => {
+            reader.elem_kind()?;
+            reader.indices()?;
+            FUNCREF
+        }
+        4 => {
+            skip_const_expr(reader)?;
+            reader.elem_exprs()?;
+            FUNCREF
+        }
+        flags @ 5..=7 => {
+            if flags == 6 {
+                reader.uleb()?;
+                skip_const_expr(reader)?;
+            }
+            let ty = ref_type_code(reader.ref_type()?);
+            reader.elem_exprs()?;
+            ty
+        }
+        flags => bail!("unsupported element segment flags {:#x}", flags),
+    };
+    Ok(ty)
+}
+
+/// Skip a constant expression, including its `end`.
+fn skip_const_expr(reader: &mut Reader) -> Result<(), Error> {
+    loop {
+        match reader.byte()? {
+            0x0b => return Ok(()),
+            0xd0 => {
+                if let Some(&FUNCREF) | Some(&EXTERNREF) = reader.rest().first() {
+                    reader.byte()?;
+                }
+            }
+            0xd2 => {
+                reader.uleb()?;
+            }
+            // v128.const
+            0xfd => {
+                reader.uleb()?;
+                reader.bytes(16)?;
+            }
+            opcode => reader.skip_immediates(opcode)?,
+        }
+    }
+}
+
+/// Read the function names subsection of the name section, following the section name.
+fn function_names(reader: &mut Reader) -> Result<HashMap<u32, String>, Error> {
+    let mut names = HashMap::new();
+    while !reader.is_empty() {
+        let id = reader.byte()?;
+        let len = reader.uleb()? as usize;
+        let payload = reader.bytes(len)?;
+        if id == 1 {
+            let mut reader = Reader::new(payload);
+            for _ in 0..reader.uleb()? {
+                let func = reader.uleb()?;
+                names.insert(func, reader.name()?);
+            }
+        }
+    }
+    Ok(names)
+}
+
+fn ref_type_code(ty: RefType) -> u8 {
+    match ty {
+        RefType::FuncRef => FUNCREF,
+        RefType::ExternRef => EXTERNREF,
+    }
+}
+
+fn is_ref(ty: u8) -> bool {
+    ty == FUNCREF || ty == EXTERNREF || ty == NULLREF
+}
+
+fn check_value_type(ty: u8) -> Result<(), Error> {
+    match ty {
+        I32 | I64 | F32 | F64 | V128 | FUNCREF | EXTERNREF => Ok(()),
+        ty => bail!("unknown value type {:#x}", ty),
+    }
+}
+
+fn type_name(ty: u8) -> &'static str {
+    match ty {
+        I32 => "i32",
+        I64 => "i64",
+        F32 => "f32",
+        F64 => "f64",
+        V128 => "v128",
+        FUNCREF => "funcref",
+        EXTERNREF => "externref",
+        _ => "nullref",
+    }
+}
+
+/// Whether a value of type `actual` can be used where one of type `expected` is.
+fn matches(actual: u8, expected: u8) -> bool {
+    actual == expected || (actual == NULLREF && is_ref(expected))
+}
+
+/// The operand types and result type of a numeric operator, from `i32.eqz` (`0x45`) to
+/// `i64.extend32_s` (`0xc4`).
+fn numeric_signature(opcode: u8) -> Option<(&'static [u8], u8)> {
+    let signature: (&'static [u8], u8) = match opcode {
+        0x45 => (&[I32], I32),
+        0x46..=0x4f => (&[I32, I32], I32),
+        0x50 => (&[I64], I32),
+        0x51..=0x5a => (&[I64, I64], I32),
+        0x5b..=0x60 => (&[F32, F32], I32),
+        0x61..=0x66 => (&[F64, F64], I32),
+        0x67..=0x69 => (&[I32], I32),
+        0x6a..=0x78 => (&[I32, I32], I32),
+        0x79..=0x7b => (&[I64], I64),
+        0x7c..=0x8a => (&[I64, I64], I64),
+        0x8b..=0x91 => (&[F32], F32),
+        0x92..=0x98 => (&[F32, F32], F32),
+        0x99..=0x9f => (&[F64], F64),
+        0xa0..=0xa6 => (&[F64, F64], F64),
+        0xa7 => (&[I64], I32),
+        0xa8 | 0xa9 => (&[F32], I32),
+        0xaa | 0xab => (&[F64], I32),
+        0xac | 0xad => (&[I32], I64),
+        0xae | 0xaf => (&[F32], I64),
+        0xb0 | 0xb1 => (&[F64], I64),
+        0xb2 | 0xb3 => (&[I32], F32),
+        0xb4 | 0xb5 => (&[I64], F32),
+        0xb6 => (&[F64], F32),
+        0xb7 | 0xb8 => (&[I32], F64),
+        0xb9 | 0xba => (&[I64], F64),
+        0xbb => (&[F32], F64),
+        0xbc => (&[F32], I32),
+        0xbd => (&[F64], I64),
+        0xbe => (&[I32], F32),
+        0xbf => (&[I64], F64),
+        0xc0 | 0xc1 => (&[I32], I32),
+        0xc2..=0xc4 => (&[I64], I64),
+        _ => return None,
+    };
+    Some(signature)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Function,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+/// A block, loop, or conditional being validated, or the function body itself.
+struct Frame {
+    kind: FrameKind,
+    params: Vec<u8>,
+    results: Vec<u8>,
+    /// The height of the operand stack when the frame was entered.
+    height: usize,
+    /// Whether the rest of the frame is unreachable, so that its operand stack is polymorphic.
+    unreachable: bool,
+}
+
+/// The type checker of one function body, following the validation algorithm in the appendix of
+/// the WebAssembly specification.
+struct FuncValidator<'a> {
+    ctx: &'a ModuleContext,
+    locals: Vec<u8>,
+    /// The types of the operands, where `None` is an operand of unknown type that was popped
+    /// from the polymorphic stack of unreachable code.
+    stack: Vec<Option<u8>>,
+    frames: Vec<Frame>,
+}
+
+impl<'a> FuncValidator<'a> {
+    fn new(ctx: &'a ModuleContext, func_index: u32) -> Result<Self, Error> {
+        let ty = ctx.func_type(func_index)?;
+        Ok(FuncValidator {
+            ctx,
+            locals: ty.params.clone(),
+            stack: vec![],
+            frames: vec![Frame {
+                kind: FrameKind::Function,
+                params: vec![],
+                results: ty.results.clone(),
+                height: 0,
+                unreachable: false,
+            }],
+        })
+    }
+
+    /// Validate the body read by `reader`, returning the offset of the operator and the error if
+    /// it is invalid.
+    fn validate(mut self, mut reader: Reader) -> Result<(), (usize, Error)> {
+        let start = reader.position();
+        self.read_locals(&mut reader).map_err(|e| (start, e))?;
+        while !self.frames.is_empty() {
+            let offset = reader.position();
+            match reader.rest().first() {
+                None => {
+                    return Err((offset, format_err!("function body does not end with `end`")));
+                }
+                // the types of SIMD operators are not checked; `lower_post_mvp` rejects them
+                Some(0xfd) => return Ok(()),
+                Some(_) => self.operator(&mut reader).map_err(|e| (offset, e))?,
+            }
+        }
+        if !reader.is_empty() {
+            return Err((
+                reader.position(),
+                format_err!("operators after the end of the function body"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn read_locals(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        let mut count = self.locals.len() as u64;
+        for _ in 0..reader.uleb()? {
+            let n = reader.uleb()?;
+            let ty = reader.byte()?;
+            check_value_type(ty)?;
+            count += u64::from(n);
+            if count > MAX_LOCALS {
+                bail!("too many locals");
+            }
+            self.locals.extend((0..n).map(|_| ty));
+        }
+        Ok(())
+    }
+
+    fn operator(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        let opcode = reader.byte()?;
+        match opcode {
+            // unreachable
+            0x00 => self.set_unreachable(),
+            // nop
+            0x01 => (),
+            // block, loop
+            0x02 | 0x03 => {
+                let (params, results) = self.block_type(reader)?;
+                self.pop_values(&params)?;
+                let kind = if opcode == 0x02 {
+                    FrameKind::Block
+                } else {
+                    FrameKind::Loop
+                };
+                self.push_frame(kind, params, results);
+            }
+            // if
+            0x04 => {
+                let (params, results) = self.block_type(reader)?;
+                self.pop_expect(I32)?;
+                self.pop_values(&params)?;
+                self.push_frame(FrameKind::If, params, results);
+            }
+            // else
+            0x05 => {
+                if self.frame().kind != FrameKind::If {
+                    bail!("`else` without a matching `if`");
+                }
+                let frame = self.pop_frame()?;
+                self.push_frame(FrameKind::Else, frame.params, frame.results);
+            }
+            // end
+            0x0b => {
+                let frame = self.pop_frame()?;
+                if frame.kind == FrameKind::If && frame.params != frame.results {
+                    bail!("type mismatch: `if` without `else` changes the stack");
+                }
+                self.push_values(&frame.results);
+            }
+            // br
+            0x0c => {
+                let types = self.label_types(reader.uleb()?)?;
+                self.pop_values(&types)?;
+                self.set_unreachable();
+            }
+            // br_if
+            0x0d => {
+                let types = self.label_types(reader.uleb()?)?;
+                self.pop_expect(I32)?;
+                self.pop_values(&types)?;
+                self.push_values(&types);
+            }
+            // br_table
+            0x0e => {
+                let targets = reader.indices()?;
+                let default = self.label_types(reader.uleb()?)?;
+                self.pop_expect(I32)?;
+                for target in targets {
+                    let types = self.label_types(target)?;
+                    if types.len() != default.len() {
+                        bail!("type mismatch: `br_table` targets have different arities");
+                    }
+                    self.peek_values(&types)?;
+                }
+                self.pop_values(&default)?;
+                self.set_unreachable();
+            }
+            // return
+            0x0f => {
+                let results = self.frames[0].results.clone();
+                self.pop_values(&results)?;
+                self.set_unreachable();
+            }
+            // call
+            0x10 => {
+                let ty = self.ctx.func_type(reader.uleb()?)?;
+                self.pop_values(&ty.params)?;
+                self.push_values(&ty.results);
+            }
+            // call_indirect
+            0x11 => {
+                let ty = self.ctx.type_at(reader.uleb()?)?;
+                let table = reader.uleb()?;
+                if self.ctx.table(table)? != FUNCREF {
+                    bail!(
+                        "type mismatch: `call_indirect` through table {} of externref",
+                        table
+                    );
+                }
+                self.pop_expect(I32)?;
+                self.pop_values(&ty.params)?;
+                self.push_values(&ty.results);
+            }
+            // drop
+            0x1a => {
+                self.pop()?;
+            }
+            // select
+            0x1b => {
+                self.pop_expect(I32)?;
+                let first = self.pop()?;
+                let second = self.pop()?;
+                for ty in first.iter().chain(second.iter()) {
+                    if is_ref(*ty) {
+                        bail!("type mismatch: `select` of references must have a type");
+                    }
+                }
+                match (first, second) {
+                    (Some(first), Some(second)) if first != second => bail!(
+                        "type mismatch: `select` between {} and {}",
+                        type_name(second),
+                        type_name(first)
+                    ),
+                    (ty, None) | (None, ty) => self.stack.push(ty),
+                    (ty, _) => self.stack.push(ty),
+                }
+            }
+            // select with value types
+            0x1c => {
+                let types = reader.value_types()?;
+                if types.len() != 1 {
+                    bail!("`select` must have exactly one value type");
+                }
+                let ty = types[0];
+                check_value_type(ty)?;
+                self.pop_expect(I32)?;
+                self.pop_expect(ty)?;
+                self.pop_expect(ty)?;
+                self.push(ty);
+            }
+            // local.get, local.set, local.tee
+            0x20..=0x22 => {
+                let index = reader.uleb()?;
+                let ty = *self
+                    .locals
+                    .get(index as usize)
+                    .ok_or_else(|| format_err!("local index {} out of bounds", index))?;
+                if opcode != 0x20 {
+                    self.pop_expect(ty)?;
+                }
+                if opcode != 0x21 {
+                    self.push(ty);
+                }
+            }
+            // global.get
+            0x23 => {
+                let (ty, _) = self.ctx.global(reader.uleb()?)?;
+                self.push(ty);
+            }
+            // global.set
+            0x24 => {
+                let index = reader.uleb()?;
+                let (ty, mutable) = self.ctx.global(index)?;
+                if !mutable {
+                    bail!("global {} is immutable", index);
+                }
+                self.pop_expect(ty)?;
+            }
+            // table.get
+            0x25 => {
+                let ty = self.ctx.table(reader.uleb()?)?;
+                self.pop_expect(I32)?;
+                self.push(ty);
+            }
+            // table.set
+            0x26 => {
+                let ty = self.ctx.table(reader.uleb()?)?;
+                self.pop_expect(ty)?;
+                self.pop_expect(I32)?;
+            }
+            // loads and stores
+            0x28..=0x3e => {
+                let (align, ty) = MEMORY_ACCESSES[(opcode - 0x28) as usize];
+                self.memarg(reader, align, false)?;
+                if opcode < 0x36 {
+                    self.pop_expect(I32)?;
+                    self.push(ty);
+                } else {
+                    self.pop_expect(ty)?;
+                    self.pop_expect(I32)?;
+                }
+            }
+            // memory.size
+            0x3f => {
+                self.memory_index(reader)?;
+                self.push(I32);
+            }
+            // memory.grow
+            0x40 => {
+                self.memory_index(reader)?;
+                self.pop_expect(I32)?;
+                self.push(I32);
+            }
+            // i32.const
+            0x41 => {
+                reader.sleb()?;
+                self.push(I32);
+            }
+            // i64.const
+            0x42 => {
+                reader.skip_leb()?;
+                self.push(I64);
+            }
+            // f32.const
+            0x43 => {
+                reader.bytes(4)?;
+                self.push(F32);
+            }
+            // f64.const
+            0x44 => {
+                reader.bytes(8)?;
+                self.push(F64);
+            }
+            // ref.null: a reference type, which older drafts of the proposal leave out
+            0xd0 => {
+                let ty = match reader.rest().first() {
+                    Some(&FUNCREF) | Some(&EXTERNREF) => reader.byte()?,
+                    _ => NULLREF,
+                };
+                self.push(ty);
+            }
+            // ref.is_null
+            0xd1 => {
+                if let Some(ty) = self.pop()? {
+                    if !is_ref(ty) {
+                        bail!(
+                            "type mismatch: expected a reference, found {}",
+                            type_name(ty)
+                        );
+                    }
+                }
+                self.push(I32);
+            }
+            // ref.func
+            0xd2 => {
+                self.ctx.func_type(reader.uleb()?)?;
+                self.push(FUNCREF);
+            }
+            0xfc => self.misc_operator(reader)?,
+            0xfe => self.atomic_operator(reader)?,
+            _ => match numeric_signature(opcode) {
+                Some((operands, result)) => {
+                    self.pop_values(operands)?;
+                    self.push(result);
+                }
+                None => bail!("unknown operator {:#x}", opcode),
+            },
+        }
+        Ok(())
+    }
+
+    /// Validate an `0xfc`-prefixed operator, after the prefix.
+    fn misc_operator(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        let sub = reader.uleb()?;
+        match sub {
+            // the saturating conversions, in the order of the trapping ones from `0xa8` to `0xb1`
+            0..=7 => {
+                let operand = if sub & 2 == 0 { F32 } else { F64 };
+                let result = if sub < 4 { I32 } else { I64 };
+                self.pop_expect(operand)?;
+                self.push(result);
+            }
+            // memory.init
+            8 => {
+                self.ctx.data(reader.uleb()?)?;
+                self.memory_index(reader)?;
+                self.pop_values(&[I32, I32, I32])?;
+            }
+            // data.drop
+            9 => self.ctx.data(reader.uleb()?)?,
+            // memory.copy
+            10 => {
+                self.memory_index(reader)?;
+                self.memory_index(reader)?;
+                self.pop_values(&[I32, I32, I32])?;
+            }
+            // memory.fill
+            11 => {
+                self.memory_index(reader)?;
+                self.pop_values(&[I32, I32, I32])?;
+            }
+            // table.init
+            12 => {
+                let segment = reader.uleb()?;
+                let table = reader.uleb()?;
+                let elem_type = self.ctx.elem(segment)?;
+                let table_type = self.ctx.table(table)?;
+                if elem_type != table_type {
+                    bail!(
+                        "type mismatch: element segment {} holds {}, but table {} holds {}",
+                        segment,
+                        type_name(elem_type),
+                        table,
+                        type_name(table_type)
+                    );
+                }
+                self.pop_values(&[I32, I32, I32])?;
+            }
+            // elem.drop
+            13 => {
+                self.ctx.elem(reader.uleb()?)?;
+            }
+            // table.copy
+            14 => {
+                let dst = reader.uleb()?;
+                let src = reader.uleb()?;
+                let dst_type = self.ctx.table(dst)?;
+                let src_type = self.ctx.table(src)?;
+                if dst_type != src_type {
+                    bail!(
+                        "type mismatch: table {} holds {}, but table {} holds {}",
+                        src,
+                        type_name(src_type),
+                        dst,
+                        type_name(dst_type)
+                    );
+                }
+                self.pop_values(&[I32, I32, I32])?;
+            }
+            // table.grow
+            15 => {
+                let ty = self.ctx.table(reader.uleb()?)?;
+                self.pop_values(&[ty, I32])?;
+                self.push(I32);
+            }
+            // table.size
+            16 => {
+                self.ctx.table(reader.uleb()?)?;
+                self.push(I32);
+            }
+            // table.fill
+            17 => {
+                let ty = self.ctx.table(reader.uleb()?)?;
+                self.pop_values(&[I32, ty, I32])?;
+            }
+            _ => bail!("unknown operator {:#x} {:#x}", 0xfc, sub),
+        }
+        Ok(())
+    }
+
+    /// Validate an `0xfe`-prefixed atomic operator, after the prefix.
+    fn atomic_operator(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        let sub = reader.uleb()?;
+        // atomic.fence
+        if sub == 3 {
+            if reader.byte()? != 0 {
+                bail!("`atomic.fence` must have a zero immediate");
+            }
+            return Ok(());
+        }
+        let op = AtomicOp::from_subopcode(sub)
+            .ok_or_else(|| format_err!("unknown operator {:#x} {:#x}", 0xfe, sub))?;
+        self.memarg(reader, op.width.trailing_zeros(), true)?;
+        self.pop_values(&op.operands())?;
+        self.pop_expect(I32)?;
+        self.push_values(&op.results());
+        Ok(())
+    }
+
+    /// Read the memory argument of an access whose natural alignment is `2^natural`, which atomic
+    /// accesses must have exactly.
+    fn memarg(&self, reader: &mut Reader, natural: u32, atomic: bool) -> Result<(), Error> {
+        self.check_memory()?;
+        let align = reader.uleb()?;
+        reader.uleb()?;
+        if align > natural || (atomic && align != natural) {
+            bail!(
+                "alignment 2^{} does not match the natural alignment 2^{} of the access",
+                align,
+                natural
+            );
+        }
+        Ok(())
+    }
+
+    fn memory_index(&self, reader: &mut Reader) -> Result<(), Error> {
+        self.check_memory()?;
+        if reader.uleb()? != 0 {
+            bail!("memory index must be 0");
+        }
+        Ok(())
+    }
+
+    fn check_memory(&self) -> Result<(), Error> {
+        if self.ctx.memories == 0 {
+            bail!("memory access in a module without a memory");
+        }
+        Ok(())
+    }
+
+    fn block_type(&self, reader: &mut Reader) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        match reader.block_type()? {
+            BlockType::Value(0x40) => Ok((vec![], vec![])),
+            BlockType::Value(ty) => {
+                check_value_type(ty)?;
+                Ok((vec![], vec![ty]))
+            }
+            BlockType::Index(index) => {
+                let ty = self.ctx.type_at(index)?;
+                Ok((ty.params.clone(), ty.results.clone()))
+            }
+        }
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames
+            .last()
+            .expect("frames are not empty while validating")
+    }
+
+    /// The types that a branch to the label at `depth` passes.
+    fn label_types(&self, depth: u32) -> Result<Vec<u8>, Error> {
+        let frame = self
+            .frames
+            .iter()
+            .rev()
+            .nth(depth as usize)
+            .ok_or_else(|| format_err!("branch depth {} out of bounds", depth))?;
+        Ok(if frame.kind == FrameKind::Loop {
+            frame.params.clone()
+        } else {
+            frame.results.clone()
+        })
+    }
+
+    fn push(&mut self, ty: u8) {
+        self.stack.push(Some(ty));
+    }
+
+    fn push_values(&mut self, types: &[u8]) {
+        for ty in types {
+            self.push(*ty);
+        }
+    }
+
+    fn pop(&mut self) -> Result<Option<u8>, Error> {
+        let frame = self.frame();
+        if self.stack.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+            bail!("type mismatch: expected a value, but the stack is empty");
+        }
+        Ok(self.stack.pop().expect("stack is above the frame height"))
+    }
+
+    fn pop_expect(&mut self, expected: u8) -> Result<Option<u8>, Error> {
+        let actual = self.pop()?;
+        match actual {
+            Some(actual) if !matches(actual, expected) => bail!(
+                "type mismatch: expected {}, found {}",
+                type_name(expected),
+                type_name(actual)
+            ),
+            _ => Ok(actual),
+        }
+    }
+
+    fn pop_values(&mut self, types: &[u8]) -> Result<(), Error> {
+        for ty in types.iter().rev() {
+            self.pop_expect(*ty)?;
+        }
+        Ok(())
+    }
+
+    /// Check that the operands on top of the stack have the given types, and leave them there.
+    fn peek_values(&mut self, types: &[u8]) -> Result<(), Error> {
+        let mut popped = vec![];
+        for ty in types.iter().rev() {
+            popped.push(self.pop_expect(*ty)?);
+        }
+        self.stack.extend(popped.into_iter().rev());
+        Ok(())
+    }
+
+    fn push_frame(&mut self, kind: FrameKind, params: Vec<u8>, results: Vec<u8>) {
+        let height = self.stack.len();
+        self.push_values(&params);
+        self.frames.push(Frame {
+            kind,
+            params,
+            results,
+            height,
+            unreachable: false,
+        });
+    }
+
+    fn pop_frame(&mut self) -> Result<Frame, Error> {
+        let results = self.frame().results.clone();
+        self.pop_values(&results)?;
+        if self.stack.len() != self.frame().height {
+            bail!("type mismatch: values remain on the stack at the end of a block");
+        }
+        Ok(self
+            .frames
+            .pop()
+            .expect("frames are not empty while validating"))
+    }
+
+    /// Mark the rest of the current frame as unreachable, so that its operand stack becomes
+    /// polymorphic.
+    fn set_unreachable(&mut self) {
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("frames are not empty while validating");
+        self.stack.truncate(frame.height);
+        frame.unreachable = true;
+    }
+}
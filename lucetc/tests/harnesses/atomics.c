@@ -0,0 +1,37 @@
+#include "vm.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+uint32_t guest_func_add(struct vmctx *, uint32_t, uint32_t);
+uint64_t guest_func_load(struct vmctx *, uint32_t);
+void     guest_func_store(struct vmctx *, uint32_t, uint32_t);
+uint32_t guest_func_cmpxchg(struct vmctx *, uint32_t, uint32_t, uint32_t);
+uint32_t guest_func_wait(struct vmctx *, uint32_t, uint32_t);
+uint32_t guest_func_notify(struct vmctx *, uint32_t);
+
+int main()
+{
+    struct VM *   vm    = make_vm();
+    struct vmctx *vmctx = get_vmctx(vm);
+
+    // the read-modify-write operators return the old value
+    assert(guest_func_add(vmctx, 16, 5) == 0);
+    assert(guest_func_add(vmctx, 16, 7) == 5);
+    // the offset of the memory argument is added to the address
+    assert(guest_func_load(vmctx, 8) == 12);
+
+    // narrow stores and compare-exchanges only touch their own bytes
+    guest_func_store(vmctx, 17, 0x1ff);
+    assert(guest_func_load(vmctx, 8) == 0xff0c);
+    assert(guest_func_cmpxchg(vmctx, 16, 0, 1) == 0xff0c);
+    assert(guest_func_cmpxchg(vmctx, 16, 0x1ff0c, 1) == 0xff0c);
+    assert(guest_func_load(vmctx, 8) == 1);
+
+    // a wait returns "not-equal" if the value differs, and "timed-out" otherwise
+    assert(guest_func_wait(vmctx, 16, 0) == 1);
+    assert(guest_func_wait(vmctx, 16, 1) == 2);
+    assert(guest_func_notify(vmctx, 16) == 0);
+
+    return 0;
+}
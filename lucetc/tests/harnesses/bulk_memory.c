@@ -0,0 +1,29 @@
+#include "vm.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+void guest_func_copy(struct vmctx *, uint32_t, uint32_t, uint32_t);
+void guest_func_fill(struct vmctx *, uint32_t, uint32_t, uint32_t);
+
+int main()
+{
+    struct VM *   vm    = make_vm();
+    struct vmctx *vmctx = get_vmctx(vm);
+
+    memcpy(vm->heap, "abcdef", 6);
+
+    // overlapping ranges are copied as if through a temporary buffer
+    guest_func_copy(vmctx, 2, 0, 4);
+    assert(memcmp(vm->heap, "ababcd", 6) == 0);
+    guest_func_copy(vmctx, 0, 1, 4);
+    assert(memcmp(vm->heap, "babccd", 6) == 0);
+
+    guest_func_fill(vmctx, 1, 'z', 3);
+    assert(memcmp(vm->heap, "bzzzcd", 6) == 0);
+    // only the low byte of the value is used
+    guest_func_fill(vmctx, 0, 0x100 + 'y', 1);
+    assert(memcmp(vm->heap, "yzzzcd", 6) == 0);
+
+    return 0;
+}
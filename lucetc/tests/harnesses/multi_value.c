@@ -0,0 +1,19 @@
+#include "vm.h"
+#include <assert.h>
+#include <stdint.h>
+
+uint32_t guest_func_divmod_combined(struct vmctx *, uint32_t, uint32_t);
+int32_t  guest_func_block_sub(struct vmctx *, int32_t, int32_t);
+uint32_t guest_func_sum(struct vmctx *, uint32_t);
+
+int main()
+{
+    struct VM *   vm    = make_vm();
+    struct vmctx *vmctx = get_vmctx(vm);
+
+    assert(guest_func_divmod_combined(vmctx, 17, 5) == 302);
+    assert(guest_func_block_sub(vmctx, 10, 3) == -7);
+    assert(guest_func_sum(vmctx, 10) == 55);
+
+    return 0;
+}
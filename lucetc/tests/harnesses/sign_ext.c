@@ -0,0 +1,28 @@
+#include "vm.h"
+#include <assert.h>
+#include <stdint.h>
+
+int32_t guest_func_i32_extend8_s(struct vmctx *, int32_t);
+int32_t guest_func_i32_extend16_s(struct vmctx *, int32_t);
+int64_t guest_func_i64_extend8_s(struct vmctx *, int64_t);
+int64_t guest_func_i64_extend16_s(struct vmctx *, int64_t);
+int64_t guest_func_i64_extend32_s(struct vmctx *, int64_t);
+
+int main()
+{
+    struct vmctx *vmctx = get_vmctx(make_vm());
+
+    assert(guest_func_i32_extend8_s(vmctx, 0x7f) == 0x7f);
+    assert(guest_func_i32_extend8_s(vmctx, 0x80) == -0x80);
+    assert(guest_func_i32_extend8_s(vmctx, 0x12345678) == 0x78);
+    assert(guest_func_i32_extend16_s(vmctx, 0x7fff) == 0x7fff);
+    assert(guest_func_i32_extend16_s(vmctx, 0x12348000) == -0x8000);
+
+    assert(guest_func_i64_extend8_s(vmctx, 0xff) == -1);
+    assert(guest_func_i64_extend8_s(vmctx, 0x0123456789abcd01) == 1);
+    assert(guest_func_i64_extend16_s(vmctx, 0x8000) == -0x8000);
+    assert(guest_func_i64_extend32_s(vmctx, 0x80000000) == -0x80000000LL);
+    assert(guest_func_i64_extend32_s(vmctx, 0x0123456700000001) == 1);
+
+    return 0;
+}
@@ -0,0 +1,53 @@
+#include "vm.h"
+#include <assert.h>
+#include <math.h>
+#include <stdint.h>
+
+int32_t  guest_func_i32_trunc_s_f32(struct vmctx *, float);
+uint32_t guest_func_i32_trunc_u_f32(struct vmctx *, float);
+int32_t  guest_func_i32_trunc_s_f64(struct vmctx *, double);
+uint32_t guest_func_i32_trunc_u_f64(struct vmctx *, double);
+int64_t  guest_func_i64_trunc_s_f32(struct vmctx *, float);
+uint64_t guest_func_i64_trunc_u_f32(struct vmctx *, float);
+int64_t  guest_func_i64_trunc_s_f64(struct vmctx *, double);
+uint64_t guest_func_i64_trunc_u_f64(struct vmctx *, double);
+uint32_t guest_func_call_inc(struct vmctx *);
+
+uint32_t inc(void)
+{
+    return 1;
+}
+
+int main()
+{
+    struct vmctx *vmctx = get_vmctx(make_vm());
+
+    assert(guest_func_i32_trunc_s_f32(vmctx, -3.7f) == -3);
+    assert(guest_func_i32_trunc_s_f32(vmctx, NAN) == 0);
+    assert(guest_func_i32_trunc_s_f32(vmctx, 1e10f) == INT32_MAX);
+    assert(guest_func_i32_trunc_s_f32(vmctx, -1e10f) == INT32_MIN);
+    assert(guest_func_i32_trunc_u_f32(vmctx, -1.5f) == 0);
+    assert(guest_func_i32_trunc_u_f32(vmctx, INFINITY) == UINT32_MAX);
+
+    assert(guest_func_i32_trunc_s_f64(vmctx, 2147483647.9) == INT32_MAX);
+    assert(guest_func_i32_trunc_s_f64(vmctx, -2147483648.9) == INT32_MIN);
+    assert(guest_func_i32_trunc_s_f64(vmctx, -2147483649.0) == INT32_MIN);
+    assert(guest_func_i32_trunc_u_f64(vmctx, 4294967295.9) == UINT32_MAX);
+    assert(guest_func_i32_trunc_u_f64(vmctx, 5e9) == UINT32_MAX);
+    assert(guest_func_i32_trunc_u_f64(vmctx, -0.9) == 0);
+
+    assert(guest_func_i64_trunc_s_f32(vmctx, 1e30f) == INT64_MAX);
+    assert(guest_func_i64_trunc_s_f32(vmctx, -INFINITY) == INT64_MIN);
+    assert(guest_func_i64_trunc_u_f32(vmctx, 42.5f) == 42);
+    assert(guest_func_i64_trunc_u_f32(vmctx, 1e30f) == UINT64_MAX);
+
+    assert(guest_func_i64_trunc_s_f64(vmctx, -1e30) == INT64_MIN);
+    assert(guest_func_i64_trunc_s_f64(vmctx, -NAN) == 0);
+    assert(guest_func_i64_trunc_u_f64(vmctx, 1e30) == UINT64_MAX);
+    assert(guest_func_i64_trunc_u_f64(vmctx, 12345.678) == 12345);
+
+    // the helpers appended to the module must not disturb the existing imports
+    assert(guest_func_call_inc(vmctx) == 1);
+
+    return 0;
+}
@@ -38,3 +38,353 @@ uint32_t lucet_vmctx_current_memory(struct vmctx *ctx)
     assert(get_vm(ctx) == &vm);
     return 1;
 }
+
+uint32_t lucet_vmctx_memory_copy(struct vmctx *ctx, uint32_t dst, uint32_t src, uint32_t len)
+    __attribute__((weak));
+uint32_t lucet_vmctx_memory_copy(struct vmctx *ctx, uint32_t dst, uint32_t src, uint32_t len)
+{
+    struct VM *the_vm = get_vm(ctx);
+    if ((uint64_t) dst + len > HEAP_SIZE || (uint64_t) src + len > HEAP_SIZE) {
+        return 1;
+    }
+    memmove(&the_vm->heap[dst], &the_vm->heap[src], len);
+    return 0;
+}
+
+uint32_t lucet_vmctx_memory_fill(struct vmctx *ctx, uint32_t dst, uint32_t val, uint32_t len)
+    __attribute__((weak));
+uint32_t lucet_vmctx_memory_fill(struct vmctx *ctx, uint32_t dst, uint32_t val, uint32_t len)
+{
+    struct VM *the_vm = get_vm(ctx);
+    if ((uint64_t) dst + len > HEAP_SIZE) {
+        return 1;
+    }
+    memset(&the_vm->heap[dst], (int) (val & 0xff), len);
+    return 0;
+}
+
+// Passive segments live in the module data, which these tests do not load, so the operations on
+// them are only stubbed out for linking.
+
+uint32_t lucet_vmctx_memory_init(struct vmctx *ctx, uint32_t dst, uint32_t src, uint32_t len,
+                                 uint32_t segment) __attribute__((weak));
+uint32_t lucet_vmctx_memory_init(struct vmctx *ctx, uint32_t dst, uint32_t src, uint32_t len,
+                                 uint32_t segment)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) dst;
+    (void) src;
+    (void) len;
+    (void) segment;
+    assert(0 && "memory.init is not supported by the test harness");
+    return 1;
+}
+
+void lucet_vmctx_data_drop(struct vmctx *ctx, uint32_t segment) __attribute__((weak));
+void lucet_vmctx_data_drop(struct vmctx *ctx, uint32_t segment)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) segment;
+}
+
+uint32_t lucet_vmctx_table_init(struct vmctx *ctx, uint32_t dst, uint32_t src, uint32_t len,
+                                uint32_t segment, uint32_t table) __attribute__((weak));
+uint32_t lucet_vmctx_table_init(struct vmctx *ctx, uint32_t dst, uint32_t src, uint32_t len,
+                                uint32_t segment, uint32_t table)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) dst;
+    (void) src;
+    (void) len;
+    (void) segment;
+    (void) table;
+    assert(0 && "table.init is not supported by the test harness");
+    return 1;
+}
+
+void lucet_vmctx_elem_drop(struct vmctx *ctx, uint32_t segment) __attribute__((weak));
+void lucet_vmctx_elem_drop(struct vmctx *ctx, uint32_t segment)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) segment;
+}
+
+uint32_t lucet_vmctx_table_copy(struct vmctx *ctx, uint32_t dst, uint32_t src, uint32_t len,
+                                uint32_t dst_table, uint32_t src_table) __attribute__((weak));
+uint32_t lucet_vmctx_table_copy(struct vmctx *ctx, uint32_t dst, uint32_t src, uint32_t len,
+                                uint32_t dst_table, uint32_t src_table)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) dst;
+    (void) src;
+    (void) len;
+    (void) dst_table;
+    (void) src_table;
+    assert(0 && "table.copy is not supported by the test harness");
+    return 1;
+}
+
+// The test harness has no tables of its own, so the reference types operators on tables are not
+// supported, but `ref.func` hands out the position of the function plus one, which is never null.
+uint32_t lucet_vmctx_table_grow(struct vmctx *ctx, uint64_t init, uint32_t delta, uint32_t table)
+    __attribute__((weak));
+uint32_t lucet_vmctx_table_grow(struct vmctx *ctx, uint64_t init, uint32_t delta, uint32_t table)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) init;
+    (void) delta;
+    (void) table;
+    return UINT32_MAX;
+}
+
+uint32_t lucet_vmctx_table_size(struct vmctx *ctx, uint32_t table) __attribute__((weak));
+uint32_t lucet_vmctx_table_size(struct vmctx *ctx, uint32_t table)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) table;
+    return 0;
+}
+
+uint32_t lucet_vmctx_table_fill(struct vmctx *ctx, uint32_t dst, uint64_t val, uint32_t len,
+                                uint32_t table) __attribute__((weak));
+uint32_t lucet_vmctx_table_fill(struct vmctx *ctx, uint32_t dst, uint64_t val, uint32_t len,
+                                uint32_t table)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) dst;
+    (void) val;
+    (void) table;
+    return len != 0 || dst != 0;
+}
+
+uint32_t lucet_vmctx_table_check(struct vmctx *ctx, uint32_t index, uint32_t len, uint32_t table)
+    __attribute__((weak));
+uint32_t lucet_vmctx_table_check(struct vmctx *ctx, uint32_t index, uint32_t len, uint32_t table)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) table;
+    return len != 0 || index != 0;
+}
+
+uint64_t lucet_vmctx_table_get(struct vmctx *ctx, uint32_t index, uint32_t table)
+    __attribute__((weak));
+uint64_t lucet_vmctx_table_get(struct vmctx *ctx, uint32_t index, uint32_t table)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) index;
+    (void) table;
+    assert(0 && "table.get is not supported by the test harness");
+    return 0;
+}
+
+uint32_t lucet_vmctx_table_set(struct vmctx *ctx, uint32_t index, uint64_t val, uint32_t table)
+    __attribute__((weak));
+uint32_t lucet_vmctx_table_set(struct vmctx *ctx, uint32_t index, uint64_t val, uint32_t table)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) index;
+    (void) val;
+    (void) table;
+    return 1;
+}
+
+uint64_t lucet_vmctx_ref_func(struct vmctx *ctx, uint32_t func) __attribute__((weak));
+uint64_t lucet_vmctx_ref_func(struct vmctx *ctx, uint32_t func)
+{
+    assert(get_vm(ctx) == &vm);
+    return (uint64_t) func + 1;
+}
+
+// The helpers that atomic operators are lowered to check the bounds and alignment of the access
+// before calling the runtime, so these only perform it. Nothing else runs concurrently with the
+// tests, so waits never block.
+
+#define DEFINE_ATOMIC_OPS(bits)                                                                  \
+    static uint##bits##_t atomic_rmw##bits(uint##bits##_t *p, uint##bits##_t value, uint32_t op) \
+    {                                                                                            \
+        switch (op) {                                                                            \
+        case 0:                                                                                  \
+            return __atomic_fetch_add(p, value, __ATOMIC_SEQ_CST);                               \
+        case 1:                                                                                  \
+            return __atomic_fetch_sub(p, value, __ATOMIC_SEQ_CST);                               \
+        case 2:                                                                                  \
+            return __atomic_fetch_and(p, value, __ATOMIC_SEQ_CST);                               \
+        case 3:                                                                                  \
+            return __atomic_fetch_or(p, value, __ATOMIC_SEQ_CST);                                \
+        case 4:                                                                                  \
+            return __atomic_fetch_xor(p, value, __ATOMIC_SEQ_CST);                               \
+        default:                                                                                 \
+            return __atomic_exchange_n(p, value, __ATOMIC_SEQ_CST);                              \
+        }                                                                                        \
+    }                                                                                            \
+    static uint##bits##_t atomic_cmpxchg##bits(uint##bits##_t *p, uint##bits##_t expected,       \
+                                               uint##bits##_t replacement)                       \
+    {                                                                                            \
+        __atomic_compare_exchange_n(p, &expected, replacement, 0, __ATOMIC_SEQ_CST,              \
+                                    __ATOMIC_SEQ_CST);                                           \
+        return expected;                                                                         \
+    }
+
+DEFINE_ATOMIC_OPS(8)
+DEFINE_ATOMIC_OPS(16)
+DEFINE_ATOMIC_OPS(32)
+DEFINE_ATOMIC_OPS(64)
+
+static uint64_t atomic_load(char *p, uint32_t width)
+{
+    switch (width) {
+    case 1:
+        return __atomic_load_n((uint8_t *) p, __ATOMIC_SEQ_CST);
+    case 2:
+        return __atomic_load_n((uint16_t *) p, __ATOMIC_SEQ_CST);
+    case 4:
+        return __atomic_load_n((uint32_t *) p, __ATOMIC_SEQ_CST);
+    default:
+        return __atomic_load_n((uint64_t *) p, __ATOMIC_SEQ_CST);
+    }
+}
+
+static void atomic_store(char *p, uint64_t value, uint32_t width)
+{
+    switch (width) {
+    case 1:
+        __atomic_store_n((uint8_t *) p, (uint8_t) value, __ATOMIC_SEQ_CST);
+        break;
+    case 2:
+        __atomic_store_n((uint16_t *) p, (uint16_t) value, __ATOMIC_SEQ_CST);
+        break;
+    case 4:
+        __atomic_store_n((uint32_t *) p, (uint32_t) value, __ATOMIC_SEQ_CST);
+        break;
+    default:
+        __atomic_store_n((uint64_t *) p, value, __ATOMIC_SEQ_CST);
+        break;
+    }
+}
+
+static uint64_t atomic_rmw(char *p, uint64_t value, uint32_t width, uint32_t op)
+{
+    switch (width) {
+    case 1:
+        return atomic_rmw8((uint8_t *) p, (uint8_t) value, op);
+    case 2:
+        return atomic_rmw16((uint16_t *) p, (uint16_t) value, op);
+    case 4:
+        return atomic_rmw32((uint32_t *) p, (uint32_t) value, op);
+    default:
+        return atomic_rmw64((uint64_t *) p, value, op);
+    }
+}
+
+static uint64_t atomic_cmpxchg(char *p, uint64_t expected, uint64_t replacement, uint32_t width)
+{
+    switch (width) {
+    case 1:
+        return atomic_cmpxchg8((uint8_t *) p, (uint8_t) expected, (uint8_t) replacement);
+    case 2:
+        return atomic_cmpxchg16((uint16_t *) p, (uint16_t) expected, (uint16_t) replacement);
+    case 4:
+        return atomic_cmpxchg32((uint32_t *) p, (uint32_t) expected, (uint32_t) replacement);
+    default:
+        return atomic_cmpxchg64((uint64_t *) p, expected, replacement);
+    }
+}
+
+uint32_t lucet_vmctx_atomic_notify(struct vmctx *ctx, uint32_t addr, uint32_t count)
+    __attribute__((weak));
+uint32_t lucet_vmctx_atomic_notify(struct vmctx *ctx, uint32_t addr, uint32_t count)
+{
+    assert(get_vm(ctx) == &vm);
+    (void) addr;
+    (void) count;
+    return 0;
+}
+
+uint32_t lucet_vmctx_atomic_wait32(struct vmctx *ctx, uint32_t addr, uint32_t expected,
+                                   int64_t timeout) __attribute__((weak));
+uint32_t lucet_vmctx_atomic_wait32(struct vmctx *ctx, uint32_t addr, uint32_t expected,
+                                   int64_t timeout)
+{
+    (void) timeout;
+    return atomic_load(&get_vm(ctx)->heap[addr], 4) == expected ? 2 : 1;
+}
+
+uint32_t lucet_vmctx_atomic_wait64(struct vmctx *ctx, uint32_t addr, uint64_t expected,
+                                   int64_t timeout) __attribute__((weak));
+uint32_t lucet_vmctx_atomic_wait64(struct vmctx *ctx, uint32_t addr, uint64_t expected,
+                                   int64_t timeout)
+{
+    (void) timeout;
+    return atomic_load(&get_vm(ctx)->heap[addr], 8) == expected ? 2 : 1;
+}
+
+void lucet_vmctx_atomic_fence(struct vmctx *ctx) __attribute__((weak));
+void lucet_vmctx_atomic_fence(struct vmctx *ctx)
+{
+    assert(get_vm(ctx) == &vm);
+    __atomic_thread_fence(__ATOMIC_SEQ_CST);
+}
+
+uint32_t lucet_vmctx_atomic_load_i32(struct vmctx *ctx, uint32_t addr, uint32_t width)
+    __attribute__((weak));
+uint32_t lucet_vmctx_atomic_load_i32(struct vmctx *ctx, uint32_t addr, uint32_t width)
+{
+    return (uint32_t) atomic_load(&get_vm(ctx)->heap[addr], width);
+}
+
+uint64_t lucet_vmctx_atomic_load_i64(struct vmctx *ctx, uint32_t addr, uint32_t width)
+    __attribute__((weak));
+uint64_t lucet_vmctx_atomic_load_i64(struct vmctx *ctx, uint32_t addr, uint32_t width)
+{
+    return atomic_load(&get_vm(ctx)->heap[addr], width);
+}
+
+void lucet_vmctx_atomic_store_i32(struct vmctx *ctx, uint32_t addr, uint32_t value,
+                                  uint32_t width) __attribute__((weak));
+void lucet_vmctx_atomic_store_i32(struct vmctx *ctx, uint32_t addr, uint32_t value,
+                                  uint32_t width)
+{
+    atomic_store(&get_vm(ctx)->heap[addr], value, width);
+}
+
+void lucet_vmctx_atomic_store_i64(struct vmctx *ctx, uint32_t addr, uint64_t value,
+                                  uint32_t width) __attribute__((weak));
+void lucet_vmctx_atomic_store_i64(struct vmctx *ctx, uint32_t addr, uint64_t value,
+                                  uint32_t width)
+{
+    atomic_store(&get_vm(ctx)->heap[addr], value, width);
+}
+
+uint32_t lucet_vmctx_atomic_rmw_i32(struct vmctx *ctx, uint32_t addr, uint32_t value,
+                                    uint32_t width, uint32_t op) __attribute__((weak));
+uint32_t lucet_vmctx_atomic_rmw_i32(struct vmctx *ctx, uint32_t addr, uint32_t value,
+                                    uint32_t width, uint32_t op)
+{
+    return (uint32_t) atomic_rmw(&get_vm(ctx)->heap[addr], value, width, op);
+}
+
+uint64_t lucet_vmctx_atomic_rmw_i64(struct vmctx *ctx, uint32_t addr, uint64_t value,
+                                    uint32_t width, uint32_t op) __attribute__((weak));
+uint64_t lucet_vmctx_atomic_rmw_i64(struct vmctx *ctx, uint32_t addr, uint64_t value,
+                                    uint32_t width, uint32_t op)
+{
+    return atomic_rmw(&get_vm(ctx)->heap[addr], value, width, op);
+}
+
+uint32_t lucet_vmctx_atomic_cmpxchg_i32(struct vmctx *ctx, uint32_t addr, uint32_t expected,
+                                        uint32_t replacement, uint32_t width)
+    __attribute__((weak));
+uint32_t lucet_vmctx_atomic_cmpxchg_i32(struct vmctx *ctx, uint32_t addr, uint32_t expected,
+                                        uint32_t replacement, uint32_t width)
+{
+    return (uint32_t) atomic_cmpxchg(&get_vm(ctx)->heap[addr], expected, replacement, width);
+}
+
+uint64_t lucet_vmctx_atomic_cmpxchg_i64(struct vmctx *ctx, uint32_t addr, uint64_t expected,
+                                        uint64_t replacement, uint32_t width)
+    __attribute__((weak));
+uint64_t lucet_vmctx_atomic_cmpxchg_i64(struct vmctx *ctx, uint32_t addr, uint64_t expected,
+                                        uint64_t replacement, uint32_t width)
+{
+    return atomic_cmpxchg(&get_vm(ctx)->heap[addr], expected, replacement, width);
+}
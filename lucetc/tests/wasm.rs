@@ -189,6 +189,7 @@ mod programs {
                 initial_size: 6 * 64 * 1024,
                 // max size of import is specified as 10 wasm pages
                 max_size: Some(10 * 64 * 1024),
+                shared: false,
             }
         );
     }
@@ -210,6 +211,7 @@ mod programs {
                 initial_size: 5 * 64 * 1024,
                 // no max size defined
                 max_size: None,
+                shared: false,
             }
         );
     }
@@ -228,6 +230,7 @@ mod programs {
                 guard_size: 0,
                 initial_size: 0,
                 max_size: None,
+                shared: false,
             }
         );
     }
@@ -244,20 +247,194 @@ mod programs {
         );
     }
 
+    #[test]
+    fn trunc_sat_helpers() {
+        let m = load("trunc_sat");
+        let b = super::test_bindings();
+        let h = HeapSettings::default();
+        let p = Program::new(m, b, h).expect(&format!("instantiating program"));
+
+        // the saturating conversions are lowered to calls to helpers appended after the module's
+        // own functions, along with the helpers for the bulk memory operators
+        assert_eq!(p.import_functions().len(), 1);
+        assert_eq!(p.defined_functions().len(), 9 + 23);
+        assert_eq!(
+            p.defined_functions().get(0).unwrap().symbol(),
+            "guest_func_i32_trunc_s_f32"
+        );
+    }
+
+    #[test]
+    fn bulk_memory_segments() {
+        use lucetc::lower::{PassiveData, PassiveElements};
+        let m = load("bulk_memory");
+        let b = super::test_bindings();
+        let h = HeapSettings::default();
+        let p = Program::new(m, b, h).expect(&format!("instantiating program"));
+
+        // passive segments are kept out of the data and element sections, under their original
+        // segment indices
+        assert_eq!(p.data_initializers().unwrap().len(), 1);
+        assert_eq!(
+            p.passive_data(),
+            &[PassiveData {
+                index: 1,
+                data: b"hello".to_vec(),
+            }]
+        );
+        assert_eq!(
+            p.passive_elements(),
+            &[PassiveElements {
+                index: 0,
+                functions: vec![Some(0), Some(1)],
+            }]
+        );
+
+        // the operators are calls to helpers that stand in for runtime functions
+        assert_eq!(p.defined_functions().len(), 7 + 23);
+        assert_eq!(p.runtime_call(0), None);
+        assert_eq!(p.runtime_call(7 + 8), Some("lucet_vmctx_memory_init"));
+        assert_eq!(p.runtime_call(7 + 10), Some("lucet_vmctx_memory_copy"));
+        assert_eq!(p.runtime_call(7 + 14), Some("lucet_vmctx_table_copy"));
+    }
+
+    #[test]
+    fn multi_value_results() {
+        let m = load("multi_value");
+        let b = super::test_bindings();
+        let h = HeapSettings::default();
+        let p = Program::new(m, b, h).expect(&format!("instantiating program"));
+
+        // functions with several results return nothing, and pass their results through globals
+        let divmod = p.defined_functions()[0]
+            .to_export()
+            .expect("divmod is exported");
+        assert_eq!(divmod.name(), "divmod");
+        assert_eq!(divmod.signature().ret_ty, None);
+        let results = divmod.result_globals();
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|(ty, _)| *ty == lucet_module_data::ValueType::I32));
+        assert_ne!(results[0].1, results[1].1);
+        assert!(results
+            .iter()
+            .all(|(_, global)| (*global as usize) < p.globals().len()));
+
+        // functions of the same result types share the globals
+        let minmax = p.defined_functions()[2]
+            .to_export()
+            .expect("minmax is exported");
+        assert_eq!(minmax.result_globals(), results);
+
+        let combined = p.defined_functions()[3]
+            .to_export()
+            .expect("divmod_combined is exported");
+        assert!(combined.result_globals().is_empty());
+    }
+
+    #[test]
+    fn atomics() {
+        let m = load("atomics");
+        let b = super::test_bindings();
+        let h = HeapSettings::default();
+        let p = Program::new(m, b, h).expect(&format!("instantiating program"));
+
+        assert!(p.heap_spec().unwrap().shared);
+
+        // the operators are calls to helpers that check the access, and then call the runtime
+        // functions that perform it
+        assert_eq!(p.defined_functions().len(), 6 + 23 + 12 + 66);
+        assert_eq!(p.runtime_call(6 + 23), Some("lucet_vmctx_atomic_notify"));
+        assert_eq!(
+            p.runtime_call(6 + 23 + 8),
+            Some("lucet_vmctx_atomic_rmw_i32")
+        );
+        assert_eq!(p.runtime_call(6 + 23 + 12), None);
+    }
+
+    #[test]
+    fn reference_types() {
+        use lucet_module_data::RefType;
+        let m = load("reference_types");
+        let b = super::test_bindings();
+        let h = HeapSettings::default();
+        let p = Program::new(m, b, h).expect(&format!("instantiating program"));
+
+        // every table is declared, though the module only kept table 0
+        assert_eq!(p.tables().len(), 3);
+        assert_eq!(p.get_table(1).unwrap().elem_type(), RefType::FuncRef);
+        assert_eq!(
+            p.get_table(1).unwrap().elements(),
+            &[TableElem::Empty, TableElem::FunctionIx(1)]
+        );
+        let refs = p.get_table(2).unwrap();
+        assert_eq!(refs.elem_type(), RefType::ExternRef);
+        assert_eq!(refs.to_spec().max_size(), Some(4));
+
+        // indirect calls through table 1 use a copy of their type
+        assert!((0..16).any(|ty| p.indirect_call(ty) == (0, 1)));
+        assert_eq!(p.ref_funcs(), &[1]);
+
+        // the table operators are calls to helpers that stand in for runtime functions
+        assert_eq!(p.defined_functions().len(), 9 + 23);
+        assert_eq!(p.runtime_call(9 + 15), Some("lucet_vmctx_table_grow"));
+        assert_eq!(p.runtime_call(9 + 18), None);
+        assert_eq!(p.runtime_call(9 + 20), Some("lucet_vmctx_ref_func"));
+    }
+
     // XXX adding more negative tests like the one above is valuable - lets do it
 
-    use lucetc::error::LucetcErrorKind;
+    use failure::Fail;
+    use lucetc::error::{LucetcError, LucetcErrorKind};
+    use lucetc::validate::ValidationError;
     #[test]
     fn invalid_module() {
         // I used the `wast2json` tool to produce the file invalid.wasm from an assert_invalid part
         // of a spectest (call.wast)
         let wasmfile = PathBuf::from("tests/wasm/invalid.wasm");
-        let m = load::read_module(&wasmfile).expect(&format!("loading module from {:?}", wasmfile));
-        let b = Bindings::empty();
-        let h = HeapSettings::default();
-        let p = Program::new(m, b, h);
-        assert!(p.is_err());
-        assert_eq!(*p.err().unwrap().get_context(), LucetcErrorKind::Validation);
+        let err = load::read_module(&wasmfile).expect_err("invalid module is rejected");
+        let err = LucetcError::from(err);
+        assert_eq!(*err.get_context(), LucetcErrorKind::Validation);
+        let cause = err.cause().expect("validation error has a cause");
+        assert_eq!(
+            cause.to_string(),
+            "function 0 at offset 30: type mismatch: expected i32, found i64"
+        );
+    }
+
+    #[test]
+    fn stray_else() {
+        // a function named `stray_else` in the name section, whose body has an `else` without an
+        // `if`, which `wat2wasm` would not produce
+        let wasmfile = PathBuf::from("tests/wasm/stray_else.wasm");
+        let err = load::read_module(&wasmfile).expect_err("invalid module is rejected");
+        let err = LucetcError::from(err);
+        assert_eq!(*err.get_context(), LucetcErrorKind::Validation);
+        let cause = err.cause().expect("validation error has a cause");
+        let validation = cause
+            .downcast_ref::<ValidationError>()
+            .expect("cause is a validation error");
+        assert_eq!(validation.func_index, 1);
+        assert_eq!(
+            validation.func_name.as_ref().map(String::as_str),
+            Some("stray_else")
+        );
+        assert_eq!(validation.offset, 33);
+    }
+
+    #[test]
+    fn simd_operators() {
+        let watfile = PathBuf::from("tests/wasm/simd.wat");
+        let err = load::read_module(&watfile).expect_err("SIMD operators are rejected");
+        assert!(err.to_string().contains("SIMD operator"));
+    }
+
+    #[test]
+    fn simd_values() {
+        let watfile = PathBuf::from("tests/wasm/simd_values.wat");
+        let err = load::read_module(&watfile).expect_err("v128 values are rejected");
+        assert!(err.to_string().contains("v128 values"));
     }
 
     #[test]
@@ -314,6 +491,45 @@ mod compile {
     compile_test!(grow_memory);
     compile_test!(unreachable_code);
     compile_test!(start_section);
+    compile_test!(sign_ext);
+    compile_test!(trunc_sat);
+    compile_test!(bulk_memory);
+    compile_test!(multi_value);
+    compile_test!(atomics);
+    compile_test!(reference_types);
+
+    #[test]
+    fn reproducible() {
+        // functions are compiled in parallel, which must not change the object file: compiling
+        // on one thread and on several must give the same bytes
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("reproducible_out")
+            .tempdir()
+            .expect("Failed to create temp dir");
+        let objects = [1, 4]
+            .iter()
+            .map(|&threads| {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .expect("build thread pool");
+                let obj_path = tmp_dir.path().join(format!("icall_{}.o", threads));
+                pool.install(|| {
+                    let m = load("icall");
+                    let p = Program::new(m, super::test_bindings(), HeapSettings::default())
+                        .expect("make program for icall");
+                    compile(&p, "icall", OptLevel::Best)
+                        .expect("compile icall")
+                        .codegen()
+                        .expect("generate code")
+                        .write(&obj_path)
+                        .expect("write object file")
+                });
+                std::fs::read(&obj_path).expect("read object file")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(objects[0], objects[1]);
+    }
 }
 
 mod execute {
@@ -325,7 +541,7 @@ mod execute {
     use std::process::Command;
     use std::str;
 
-    fn run_execute_test(file: &str) {
+    fn run_execute_test(file: &str, shared: bool) {
         // Compile the wasm file
         let m = load(file);
         let b = super::test_bindings();
@@ -370,17 +586,29 @@ mod execute {
         }
         assert!(objcopy.status.success(), "objcopy failed!");
 
+        // Link a shared object with the built-in linker, if asked to, which then takes the place of
+        // the object. The harness exports its symbols so that the guest can call them.
+        let guest_path = if shared {
+            let so_path = tmp_dir.path().join(format!("lib{}.so", file));
+            lucetc::link::shared_object_file(&obj_path, &so_path).expect("link shared object");
+            so_path
+        } else {
+            obj_path.clone()
+        };
+
         // Invoke GCC to compile and link the harness and the object.
         let harness_file = format!("tests/harnesses/{}.c", file);
-        let output = Command::new("gcc")
-            .arg("--std=c99")
+        let mut gcc = Command::new("gcc");
+        gcc.arg("--std=c99")
             .arg(harness_file)
             .arg("tests/harnesses/vm.c")
             .arg("tests/harnesses/globals.c")
-            .arg(obj_file)
-            .args(&["-o", exec_file])
-            .output()
-            .expect("failed to execute GCC");
+            .arg(&guest_path)
+            .args(&["-o", exec_file]);
+        if shared {
+            gcc.arg("-rdynamic");
+        }
+        let output = gcc.output().expect("failed to execute GCC");
 
         if !output.status.success() {
             let stdout = str::from_utf8(&output.stdout).unwrap();
@@ -416,7 +644,16 @@ mod execute {
         ($base_name:ident) => {
             #[test]
             pub fn $base_name() {
-                run_execute_test(stringify!($base_name));
+                run_execute_test(stringify!($base_name), false);
+            }
+        };
+    }
+
+    macro_rules! execute_shared_test {
+        ($base_name:ident, $test_name:ident) => {
+            #[test]
+            pub fn $test_name() {
+                run_execute_test(stringify!($base_name), true);
             }
         };
     }
@@ -435,4 +672,63 @@ mod execute {
     execute_test!(heap_spec_import);
     execute_test!(heap_spec_definition);
     execute_test!(globals_definition);
+    execute_test!(sign_ext);
+    execute_test!(trunc_sat);
+    execute_test!(bulk_memory);
+    execute_test!(multi_value);
+    execute_test!(atomics);
+
+    // harnesses that only call into the guest can run against a shared object, as they do not
+    // refer to its data
+    execute_shared_test!(fibonacci, fibonacci_shared);
+    execute_shared_test!(call, call_shared);
+    execute_shared_test!(icall_import, icall_import_shared);
+    execute_shared_test!(import_many, import_many_shared);
+}
+
+mod cache {
+    // Tests for the compilation cache
+    use lucetc::{Lucetc, LucetcOpts, OptLevel};
+    use std::fs;
+
+    #[test]
+    fn cached_shared_object() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("cache_test_out")
+            .tempdir()
+            .expect("Failed to create temp dir");
+        let cache_dir = tmp_dir.path().join("cache");
+        let build = || Lucetc::new("tests/wasm/fibonacci.wat").with_cache_dir(&cache_dir);
+        let entries = || {
+            fs::read_dir(&cache_dir)
+                .expect("read cache dir")
+                .map(|entry| entry.expect("cache entry").path())
+                .collect::<Vec<_>>()
+        };
+
+        let first = tmp_dir.path().join("first.so");
+        build()
+            .shared_object_file(&first)
+            .expect("compile fibonacci");
+        let cached = entries();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(fs::read(&cached[0]).unwrap(), fs::read(&first).unwrap());
+
+        // compiling the same module again copies the cache entry, whatever it holds
+        fs::write(&cached[0], b"cached").expect("overwrite cache entry");
+        let second = tmp_dir.path().join("second.so");
+        build()
+            .shared_object_file(&second)
+            .expect("compile fibonacci again");
+        assert_eq!(fs::read(&second).unwrap(), b"cached");
+
+        // a different optimization level is a different entry
+        let third = tmp_dir.path().join("third.so");
+        build()
+            .with_opt_level(OptLevel::Fastest)
+            .shared_object_file(&third)
+            .expect("compile fibonacci with another optimization level");
+        assert_ne!(fs::read(&third).unwrap(), b"cached");
+        assert_eq!(entries().len(), 2);
+    }
 }
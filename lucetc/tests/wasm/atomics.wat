@@ -0,0 +1,14 @@
+(module
+  (memory 1 1 shared)
+  (func $add (export "add") (param i32 i32) (result i32)
+    (i32.atomic.rmw.add (local.get 0) (local.get 1)))
+  (func $load (export "load") (param i32) (result i64)
+    (i64.atomic.load offset=8 (local.get 0)))
+  (func $store (export "store") (param i32 i32)
+    (i32.atomic.store8 (local.get 0) (local.get 1)))
+  (func $cmpxchg (export "cmpxchg") (param i32 i32 i32) (result i32)
+    (i32.atomic.rmw16.cmpxchg_u (local.get 0) (local.get 1) (local.get 2)))
+  (func $wait (export "wait") (param i32 i32) (result i32)
+    (i32.atomic.wait (local.get 0) (local.get 1) (i64.const 0)))
+  (func $notify (export "notify") (param i32) (result i32)
+    (atomic.notify (local.get 0) (i32.const 1))))
@@ -0,0 +1,28 @@
+(module
+  (memory 1)
+  (table 2 anyfunc)
+  (data (i32.const 0) "abc")
+  (data passive "hello")
+  (elem passive $copy $fill)
+  (func $copy (export "copy") (param i32 i32 i32)
+    (memory.copy (get_local 0) (get_local 1) (get_local 2))
+  )
+  (func $fill (export "fill") (param i32 i32 i32)
+    (memory.fill (get_local 0) (get_local 1) (get_local 2))
+  )
+  (func $init (export "init") (param i32 i32 i32)
+    (memory.init 1 (get_local 0) (get_local 1) (get_local 2))
+  )
+  (func $drop (export "drop")
+    (data.drop 1)
+  )
+  (func $table_init (export "table_init") (param i32 i32 i32)
+    (table.init 0 (get_local 0) (get_local 1) (get_local 2))
+  )
+  (func $elem_drop (export "elem_drop")
+    (elem.drop 0)
+  )
+  (func $table_copy (export "table_copy") (param i32 i32 i32)
+    (table.copy (get_local 0) (get_local 1) (get_local 2))
+  )
+)
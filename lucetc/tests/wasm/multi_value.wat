@@ -0,0 +1,64 @@
+(module
+  (func $divmod (export "divmod") (param i32 i32) (result i32 i32)
+    get_local 0
+    get_local 1
+    i32.div_u
+    get_local 0
+    get_local 1
+    i32.rem_u
+  )
+  (func $swap (param i32 i32) (result i32 i32)
+    get_local 1
+    get_local 0
+  )
+  (func $minmax (export "minmax") (param i32 i32) (result i32 i32)
+    get_local 0
+    get_local 1
+    get_local 0
+    get_local 1
+    i32.lt_s
+    if (param i32 i32) (result i32 i32)
+      return
+    else
+      call $swap
+    end
+  )
+  ;; quotient * 100 + remainder
+  (func (export "divmod_combined") (param i32 i32) (result i32)
+    (local i32)
+    get_local 0
+    get_local 1
+    call $divmod
+    set_local 2
+    i32.const 100
+    i32.mul
+    get_local 2
+    i32.add
+  )
+  (func (export "block_sub") (param i32 i32) (result i32)
+    get_local 0
+    get_local 1
+    block (param i32 i32) (result i32 i32)
+      call $swap
+    end
+    i32.sub
+  )
+  ;; the sum of 1 to n, with the loop taking the sum and the counter as parameters
+  (func (export "sum") (param i32) (result i32)
+    (local i32 i32)
+    i32.const 0
+    get_local 0
+    loop (param i32 i32) (result i32)
+      set_local 1
+      get_local 1
+      i32.add
+      get_local 1
+      i32.const 1
+      i32.sub
+      tee_local 2
+      get_local 2
+      br_if 0
+      drop
+    end
+  )
+)
@@ -0,0 +1,17 @@
+(module
+  (func $i32_extend8_s (export "i32_extend8_s") (param i32) (result i32)
+    (i32.extend8_s (get_local 0))
+  )
+  (func $i32_extend16_s (export "i32_extend16_s") (param i32) (result i32)
+    (i32.extend16_s (get_local 0))
+  )
+  (func $i64_extend8_s (export "i64_extend8_s") (param i64) (result i64)
+    (i64.extend8_s (get_local 0))
+  )
+  (func $i64_extend16_s (export "i64_extend16_s") (param i64) (result i64)
+    (i64.extend16_s (get_local 0))
+  )
+  (func $i64_extend32_s (export "i64_extend32_s") (param i64) (result i64)
+    (i64.extend32_s (get_local 0))
+  )
+)
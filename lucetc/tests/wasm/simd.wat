@@ -0,0 +1,3 @@
+(module
+  (func $splat (export "splat")
+    (drop (i32x4.splat (i32.const 1)))))
@@ -0,0 +1,3 @@
+(module
+  (func $id (export "id") (param v128) (result v128)
+    (local.get 0)))
@@ -0,0 +1,30 @@
+(module
+  (import "env" "inc" (func $inc (result i32)))
+  (func $i32_trunc_s_f32 (export "i32_trunc_s_f32") (param f32) (result i32)
+    (i32.trunc_s:sat/f32 (get_local 0))
+  )
+  (func $i32_trunc_u_f32 (export "i32_trunc_u_f32") (param f32) (result i32)
+    (i32.trunc_u:sat/f32 (get_local 0))
+  )
+  (func $i32_trunc_s_f64 (export "i32_trunc_s_f64") (param f64) (result i32)
+    (i32.trunc_s:sat/f64 (get_local 0))
+  )
+  (func $i32_trunc_u_f64 (export "i32_trunc_u_f64") (param f64) (result i32)
+    (i32.trunc_u:sat/f64 (get_local 0))
+  )
+  (func $i64_trunc_s_f32 (export "i64_trunc_s_f32") (param f32) (result i64)
+    (i64.trunc_s:sat/f32 (get_local 0))
+  )
+  (func $i64_trunc_u_f32 (export "i64_trunc_u_f32") (param f32) (result i64)
+    (i64.trunc_u:sat/f32 (get_local 0))
+  )
+  (func $i64_trunc_s_f64 (export "i64_trunc_s_f64") (param f64) (result i64)
+    (i64.trunc_s:sat/f64 (get_local 0))
+  )
+  (func $i64_trunc_u_f64 (export "i64_trunc_u_f64") (param f64) (result i64)
+    (i64.trunc_u:sat/f64 (get_local 0))
+  )
+  (func $call_inc (export "call_inc") (result i32)
+    (call $inc)
+  )
+)